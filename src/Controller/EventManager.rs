@@ -161,7 +161,7 @@ pub struct QuEvent<EventType>
 ///     {
 ///     };
 ///
-///     let audio_information = flac_reader.read_information(argument[0].2.clone());
+///     let audio_information = flac_reader.read_information(argument[0].2.clone()).unwrap();
 ///     let event_to_send = QuEvent::<QuEventType>
 ///     {
 ///         m_event_type: QuEventType::EMusicInformationRetrieved,
@@ -40,6 +40,18 @@ pub enum QuEventType
     /// Ask to retrieve a playlist or all the playlist
     EAskTryRetrievePlayList,
 
+    /// Ask to measure the loudness of files and directories and to write their gains, in the background
+    EAskScanLoudness,
+
+    /// Ask to stop the scan of the loudness after the current album
+    EAskCancelLoudnessScan,
+
+    /// Ask to change the way the gains of the played musics are applied
+    EAskChangeReplayGainSettings,
+
+    /// Ask the next samples of the music being played
+    EAskReadMusicBlock,
+
     //
     // All output possible
     /// result of the scan on the directory
@@ -48,14 +60,29 @@ pub enum QuEventType
     /// result of the read of metadata of the music
     EMusicInformationRetrieved,
 
+    /// the metadata of the music cannot be read, contains the path of the music and the error
+    EMusicInformationError,
+
     /// result of the operation on the music
     EReadMusicState,
 
+    /// the music cannot be opened or decoded, contains the path of the music and the error
+    EReadMusicError,
+
+    /// the next samples of the music being played, with its gain
+    EMusicBlockRead,
+
     /// result of the operation to apply to a playlist
     EOperationPlaylistState,
 
     /// result of the retrieving of the playlist
     EPlaylistRetrieved,
+
+    /// the loudness of a music is measured and its gains are written
+    ELoudnessScanned,
+
+    /// the loudness of a music cannot be measured or its gains cannot be written, contains the path of the music and the error
+    ELoudnessScanError,
 }
 
 pub(crate) mod EventManager;
\ No newline at end of file
@@ -57,6 +57,13 @@ pub fn register_event_listeners(gui_manager: Arc<GUIManager>, event_manager: Arc
     event_manager.lock().unwrap().register_listener(QuEventType::EMusicInformationRetrieved, move |event| {
         read_music_information_from_event(&tmp_gui_manager, event);
     });
+
+    //
+    // The error is shown in place of the information of the music
+    let tmp_gui_manager = gui_manager.clone();
+    event_manager.lock().unwrap().register_listener(QuEventType::EMusicInformationError, move |event| {
+        read_music_information_from_event(&tmp_gui_manager, event);
+    });
 }
 
 /// Create the gui manager with all the parameters set to default values
@@ -0,0 +1,594 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on the Audio Interchange File Format specification 1.3 of Apple, and its AIFF-C draft
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use crate::audio_reader::{AudioInformation, AudioLoop, AudioReader, AudioReaderError, CuePoint, LoopMode, TrackMetadata};
+use crate::audio_reader::id3_tag::Id3Tag;
+use crate::audio_reader::pcm_decoder::{get_default_channel_mask, PcmDecoder, PcmFormat, PcmSampleFormat};
+use crate::audio_reader::wav_reader::{decode_chunk_text, read_chunk_data};
+
+/// Size of the COMM chunk of AIFF, AIFF-C adds the compression after it
+const COMMON_CHUNK_SIZE: usize = 18;
+
+/// Size of the INST chunk
+const INSTRUMENT_CHUNK_SIZE: usize = 20;
+
+/// Exponent of the 80 bits extended precision value 1.0
+const EXTENDED_EXPONENT_BIAS: i32 = 16383;
+
+/// Content of the COMM chunk
+struct AiffCommon
+{
+    m_format: PcmFormat,
+
+    /// Number of samples per channel
+    m_sample_frame_count: u32,
+}
+
+/// Loop of the INST chunk, between two markers
+struct AiffLoop
+{
+    m_mode: Option<LoopMode>,
+    m_start_marker_id: u16,
+    m_end_marker_id: u16,
+}
+
+/// Position of the audio and tags of an AIFF stream, found by reading its chunks
+struct AiffStream
+{
+    m_common: AiffCommon,
+
+    /// Position of the first sample from the beginning of the stream
+    m_data_offset: u64,
+
+    /// Number of bytes of samples
+    m_data_length: u64,
+
+    m_name: String,
+    m_author: String,
+    m_annotations: Vec<String>,
+    m_markers: Vec<CuePoint>,
+
+    /// Sustain and release loops of the INST chunk
+    m_loops: Vec<(AiffLoop, bool)>,
+    m_id3_tag: Option<Id3Tag>,
+}
+
+pub struct AiffReader
+{
+}
+
+/// Convert the 80 bits extended precision float of the sample rate to an integer
+///
+/// # Return
+/// The rounded rate, None if it is not a positive value fitting on 32 bits
+fn convert_extended_to_rate(data: &[u8]) -> Option<u32>
+{
+    let sign_and_exponent = u16::from_be_bytes(data[0..2].try_into().unwrap());
+    let mantissa = u64::from_be_bytes(data[2..10].try_into().unwrap());
+    if sign_and_exponent & 0x8000 != 0 || mantissa == 0
+    {
+        return None;
+    }
+
+    //
+    // The mantissa has its integer bit explicitly stored, on the left of the 63 bits of the fraction
+    let exponent = (sign_and_exponent & 0x7FFF) as i32 - EXTENDED_EXPONENT_BIAS - 63;
+    let rate = (mantissa as f64 * 2f64.powi(exponent)).round();
+    if rate < 1.0 || rate > u32::MAX as f64
+    {
+        return None;
+    }
+    return Some(rate as u32);
+}
+
+/// Decode a Pascal string: its length on a byte followed by its characters, padded to an even size
+///
+/// # Return
+/// The string and the size it takes in the data
+fn parse_pascal_string(data: &[u8]) -> (String, usize)
+{
+    if data.is_empty()
+    {
+        return (String::new(), 0);
+    }
+    let length = data[0] as usize;
+    let end = (1 + length).min(data.len());
+    let size = 1 + length + (1 + length) % 2;
+    return (decode_chunk_text(&data[1..end]), size);
+}
+
+/// Decode the content of the COMM chunk
+///
+/// # Params
+/// data: the content of the chunk
+/// is_aiff_c: true if the chunk comes from an AIFF-C file, which adds the type of compression
+fn parse_common_chunk(data: &[u8], is_aiff_c: bool) -> Result<AiffCommon, AudioReaderError>
+{
+    if data.len() < COMMON_CHUNK_SIZE || (is_aiff_c && data.len() < COMMON_CHUNK_SIZE + 4)
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "COMM", m_length: data.len() as u32 });
+    }
+    let channel_count = u16::from_be_bytes(data[0..2].try_into().unwrap());
+    let sample_frame_count = u32::from_be_bytes(data[2..6].try_into().unwrap());
+    let sample_size = u16::from_be_bytes(data[6..8].try_into().unwrap());
+    let rate = convert_extended_to_rate(&data[8..18]).ok_or(AudioReaderError::InvalidValue("sample rate"))?;
+    let compression_type: [u8; 4] = if is_aiff_c { data[18..22].try_into().unwrap() } else { *b"NONE" };
+
+    //
+    // The sample format of each compression type: the type of the samples, their endianness and the size of their container
+    let integer_container_bits = (sample_size.min(64) + 7) / 8 * 8;
+    let (sample_format, is_big_endian, container_bits) = match &compression_type
+    {
+        b"NONE" | b"twos" => (PcmSampleFormat::SignedInteger, true, integer_container_bits),
+        b"sowt" => (PcmSampleFormat::SignedInteger, false, integer_container_bits),
+        b"raw " => (PcmSampleFormat::UnsignedInteger, true, integer_container_bits),
+        b"in24" => (PcmSampleFormat::SignedInteger, true, 24),
+        b"in32" => (PcmSampleFormat::SignedInteger, true, 32),
+        b"fl32" | b"FL32" => (PcmSampleFormat::Float, true, 32),
+        b"fl64" | b"FL64" => (PcmSampleFormat::Float, true, 64),
+        _ => return Err(AudioReaderError::UnsupportedFeature("aiff compression type")),
+    };
+    let valid_bits = if sample_format == PcmSampleFormat::Float || sample_size == 0 || sample_size > container_bits { container_bits } else { sample_size };
+    if container_bits == 0 || (sample_format != PcmSampleFormat::Float && container_bits > 32)
+    {
+        return Err(AudioReaderError::InvalidValue("bits per sample"));
+    }
+    if channel_count == 0 || channel_count > u8::MAX as u16
+    {
+        return Err(AudioReaderError::UnsupportedFeature("aiff channel count"));
+    }
+
+    return Ok(AiffCommon
+    {
+        m_format: PcmFormat
+        {
+            m_sample_format: sample_format,
+            m_is_big_endian: is_big_endian,
+            m_channel_count: channel_count as u8,
+            m_rate: rate,
+            m_container_bits: container_bits,
+            m_valid_bits: valid_bits,
+            m_channel_mask: get_default_channel_mask(channel_count as u8),
+        },
+        m_sample_frame_count: sample_frame_count,
+    });
+}
+
+/// Decode the markers of the MARK chunk
+fn parse_marker_chunk(data: &[u8]) -> Result<Vec<CuePoint>, AudioReaderError>
+{
+    if data.len() < 2
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "MARK", m_length: data.len() as u32 });
+    }
+    let marker_count = u16::from_be_bytes(data[0..2].try_into().unwrap());
+    let mut markers: Vec<CuePoint> = Vec::new();
+    let mut position = 2;
+    for _ in 0..marker_count
+    {
+        if position + 6 > data.len()
+        {
+            return Err(AudioReaderError::InvalidBlockLength { m_block_name: "MARK", m_length: data.len() as u32 });
+        }
+        let (label, label_size) = parse_pascal_string(&data[position + 6..]);
+        markers.push(CuePoint
+        {
+            m_id: u16::from_be_bytes(data[position..position + 2].try_into().unwrap()) as u32,
+            m_sample: u32::from_be_bytes(data[position + 2..position + 6].try_into().unwrap()) as u64,
+            m_label: label,
+        });
+        position += 6 + label_size;
+    }
+    markers.sort_by_key(|marker| marker.m_sample);
+    return Ok(markers);
+}
+
+/// Decode a loop of the INST chunk
+fn parse_instrument_loop(data: &[u8]) -> AiffLoop
+{
+    let mode = match u16::from_be_bytes(data[0..2].try_into().unwrap())
+    {
+        1 => Some(LoopMode::Forward),
+        2 => Some(LoopMode::ForwardBackward),
+        _ => None,
+    };
+    return AiffLoop
+    {
+        m_mode: mode,
+        m_start_marker_id: u16::from_be_bytes(data[2..4].try_into().unwrap()),
+        m_end_marker_id: u16::from_be_bytes(data[4..6].try_into().unwrap()),
+    };
+}
+
+/// Read the chunks of an AIFF stream to find its format, its samples and its tags
+///
+/// # Params
+/// source: the stream, placed at its first byte
+/// is_reading_tags: false to stop at the SSND chunk, when only the samples are needed
+fn read_aiff_stream<R: Read + Seek>(source: &mut R, is_reading_tags: bool) -> Result<AiffStream, AudioReaderError>
+{
+    let start_offset = source.seek(SeekFrom::Current(0))?;
+    let stream_length = source.seek(SeekFrom::End(0))? - start_offset;
+    source.seek(SeekFrom::Start(start_offset))?;
+
+    let mut header = [0u8; 12];
+    source.read_exact(&mut header).map_err(|_| AudioReaderError::InvalidMagicNumber)?;
+    let is_aiff_c = &header[8..12] == b"AIFC";
+    if &header[0..4] != b"FORM" || (&header[8..12] != b"AIFF" && !is_aiff_c)
+    {
+        return Err(AudioReaderError::InvalidMagicNumber);
+    }
+
+    let mut common: Option<AiffCommon> = None;
+    let mut data_position: Option<(u64, u64)> = None;
+    let mut name = String::new();
+    let mut author = String::new();
+    let mut annotations: Vec<String> = Vec::new();
+    let mut markers: Vec<CuePoint> = Vec::new();
+    let mut loops: Vec<(AiffLoop, bool)> = Vec::new();
+    let mut id3_tag: Option<Id3Tag> = None;
+    let mut chunk_offset: u64 = 12;
+    while chunk_offset + 8 <= stream_length
+    {
+        let mut chunk_header = [0u8; 8];
+        source.read_exact(&mut chunk_header)?;
+        let chunk_id: [u8; 4] = chunk_header[0..4].try_into().unwrap();
+        let chunk_size = u32::from_be_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+        let content_offset = chunk_offset + 8;
+
+        match &chunk_id
+        {
+            b"COMM" =>
+            {
+                common = Some(parse_common_chunk(&read_chunk_data(source, chunk_size, "COMM", chunk_offset)?, is_aiff_c)?);
+            }
+            b"SSND" =>
+            {
+                //
+                // The samples start after the offset and block size fields, and after the number of bytes given by the offset
+                let mut ssnd_header = [0u8; 8];
+                source.read_exact(&mut ssnd_header).map_err(|_| AudioReaderError::TruncatedBlock { m_block_name: "SSND", m_offset: chunk_offset })?;
+                let data_offset = content_offset + 8 + u32::from_be_bytes(ssnd_header[0..4].try_into().unwrap()) as u64;
+                let data_end = (content_offset + chunk_size).min(stream_length);
+                data_position = Some((data_offset, data_end.saturating_sub(data_offset)));
+                if !is_reading_tags && common.is_some()
+                {
+                    break;
+                }
+            }
+            b"NAME" if is_reading_tags =>
+            {
+                name = decode_chunk_text(&read_chunk_data(source, chunk_size, "NAME", chunk_offset)?);
+            }
+            b"AUTH" if is_reading_tags =>
+            {
+                author = decode_chunk_text(&read_chunk_data(source, chunk_size, "AUTH", chunk_offset)?);
+            }
+            b"ANNO" if is_reading_tags =>
+            {
+                annotations.push(decode_chunk_text(&read_chunk_data(source, chunk_size, "ANNO", chunk_offset)?));
+            }
+            b"MARK" if is_reading_tags =>
+            {
+                markers = parse_marker_chunk(&read_chunk_data(source, chunk_size, "MARK", chunk_offset)?)?;
+            }
+            b"INST" if is_reading_tags =>
+            {
+                let data = read_chunk_data(source, chunk_size, "INST", chunk_offset)?;
+                if data.len() < INSTRUMENT_CHUNK_SIZE
+                {
+                    return Err(AudioReaderError::InvalidBlockLength { m_block_name: "INST", m_length: data.len() as u32 });
+                }
+                loops = vec![(parse_instrument_loop(&data[8..14]), false), (parse_instrument_loop(&data[14..20]), true)];
+            }
+            b"ID3 " | b"id3 " if is_reading_tags =>
+            {
+                //
+                // A damaged ID3 chunk only loses the tags, the audio is still readable
+                id3_tag = Id3Tag::parse(&read_chunk_data(source, chunk_size, "ID3", chunk_offset)?).ok();
+            }
+            _ => {}
+        }
+
+        //
+        // The chunks are aligned on 2 bytes
+        chunk_offset = content_offset + chunk_size + chunk_size % 2;
+        source.seek(SeekFrom::Start(start_offset + chunk_offset))?;
+    }
+
+    let common = common.ok_or(AudioReaderError::MissingBlock("COMM"))?;
+    let (data_offset, data_length) = match data_position
+    {
+        Some(data_position) => data_position,
+
+        //
+        // A file without samples can omit the SSND chunk
+        None if common.m_sample_frame_count == 0 => (stream_length, 0),
+        None => return Err(AudioReaderError::MissingBlock("SSND")),
+    };
+
+    //
+    // The number of samples of the COMM chunk is the reference, the SSND chunk can be longer
+    let data_length = data_length.min(common.m_sample_frame_count as u64 * common.m_format.get_block_align());
+    return Ok(AiffStream
+    {
+        m_common: common,
+        m_data_offset: data_offset,
+        m_data_length: data_length,
+        m_name: name,
+        m_author: author,
+        m_annotations: annotations,
+        m_markers: markers,
+        m_loops: loops,
+        m_id3_tag: id3_tag,
+    });
+}
+
+/// Check if a file is an AIFF or AIFF-C file
+///
+/// # Params
+/// file: the file, placed at its first byte
+pub fn is_aiff_file(file: &File) -> bool
+{
+    let mut header = [0u8; 12];
+    if (&*file).read_exact(&mut header).is_err()
+    {
+        return false;
+    }
+    return &header[0..4] == b"FORM" && (&header[8..12] == b"AIFF" || &header[8..12] == b"AIFC");
+}
+
+impl AudioReader for AiffReader
+{
+    fn read_information(&self, str_path_to_music: String) -> Result<AudioInformation, AudioReaderError>
+    {
+        let aiff_stream = read_aiff_stream(&mut File::open(str_path_to_music)?, true)?;
+        let format = &aiff_stream.m_common.m_format;
+
+        //
+        // The loops of the INST chunk are placed between two markers, an unknown marker disables the loop
+        let markers = &aiff_stream.m_markers;
+        let find_marker_sample = |marker_id: u16| markers.iter().find(|marker| marker.m_id == marker_id as u32).map(|marker| marker.m_sample);
+        let loops: Vec<AudioLoop> = aiff_stream.m_loops.iter()
+            .filter_map(|(aiff_loop, is_release_loop)|
+            {
+                let start_sample = find_marker_sample(aiff_loop.m_start_marker_id)?;
+                let end_sample = find_marker_sample(aiff_loop.m_end_marker_id)?;
+                if start_sample >= end_sample
+                {
+                    return None;
+                }
+                return Some(AudioLoop
+                {
+                    m_start_sample: start_sample,
+                    m_end_sample: end_sample,
+                    m_mode: aiff_loop.m_mode?,
+                    m_is_release_loop: *is_release_loop,
+                });
+            })
+            .collect();
+
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = aiff_stream.m_name;
+        metadata.m_artists = Some(aiff_stream.m_author).filter(|author| !author.is_empty()).into_iter().collect();
+        let mut audio_information = AudioInformation
+        {
+            m_metadata: metadata,
+            m_rate: format.m_rate,
+            m_channel_count: format.m_channel_count,
+            m_bits_per_sample: format.m_valid_bits as u8,
+            m_total_samples: aiff_stream.m_common.m_sample_frame_count as u64,
+            m_bitrate: format.get_bitrate(),
+            m_codec: format.get_codec_name().to_string(),
+            m_is_lossless: true,
+            m_pictures: Vec::new(),
+            m_tracks: Vec::new(),
+            m_comments: aiff_stream.m_annotations,
+            m_cue_points: aiff_stream.m_markers,
+            m_loops: loops,
+        };
+
+        //
+        // The ID3 tag is more precise than the text chunks and replaces the fields it has
+        if let Some(id3_tag) = aiff_stream.m_id3_tag
+        {
+            id3_tag.apply_to_information(&mut audio_information);
+        }
+
+        return Ok(audio_information);
+    }
+}
+
+/// Open an AIFF or AIFF-C file and find its samples
+///
+/// # Params
+/// str_path_to_music: the path of the AIFF file
+pub fn open_aiff_decoder(str_path_to_music: String) -> Result<PcmDecoder<File>, AudioReaderError>
+{
+    return create_aiff_decoder(File::open(str_path_to_music)?);
+}
+
+/// Create a decoder reading an AIFF or AIFF-C stream from a source, ready to decode the first sample
+///
+/// # Params
+/// source: the source of the AIFF stream, placed at the beginning of the stream
+pub fn create_aiff_decoder<R: Read + Seek>(mut source: R) -> Result<PcmDecoder<R>, AudioReaderError>
+{
+    let start_offset = source.seek(SeekFrom::Current(0))?;
+    let aiff_stream = read_aiff_stream(&mut source, false)?;
+    return PcmDecoder::new(source, aiff_stream.m_common.m_format, start_offset + aiff_stream.m_data_offset, aiff_stream.m_data_length);
+}
+
+#[cfg(test)]
+mod test_aiff_reader
+{
+    use crate::audio_reader::{AudioLoop, AudioReader, CuePoint, LoopMode};
+    use crate::audio_reader::aiff_reader::{open_aiff_decoder, AiffReader};
+    use crate::audio_reader::pcm_decoder::PcmSampleFormat;
+    use crate::audio_reader::wav_reader::write_chunk;
+
+    /// Create an AIFF or AIFF-C file with a COMM chunk, followed by other chunks
+    ///
+    /// # Params
+    /// compression_type: the compression of an AIFF-C file, None for an AIFF file
+    /// rate: the 80 bits extended precision sample rate
+    fn create_aiff_file(compression_type: Option<&[u8; 4]>, channel_count: u16, frame_count: u32, sample_size: u16, rate: [u8; 10], chunks: &[u8]) -> Vec<u8>
+    {
+        let mut common: Vec<u8> = Vec::new();
+        common.extend_from_slice(&channel_count.to_be_bytes());
+        common.extend_from_slice(&frame_count.to_be_bytes());
+        common.extend_from_slice(&sample_size.to_be_bytes());
+        common.extend_from_slice(&rate);
+        if let Some(compression_type) = compression_type
+        {
+            common.extend_from_slice(compression_type);
+            common.extend_from_slice(b"\x0Bnot defined");
+        }
+
+        let mut form_content: Vec<u8> = if compression_type.is_some() { b"AIFC".to_vec() } else { b"AIFF".to_vec() };
+        write_chunk(&mut form_content, b"COMM", &common, true);
+        form_content.extend_from_slice(chunks);
+        let mut aiff_data: Vec<u8> = Vec::new();
+        write_chunk(&mut aiff_data, b"FORM", &form_content, true);
+        return aiff_data;
+    }
+
+    /// Create a SSND chunk, with an offset before the samples
+    fn create_sound_chunk(audio_data: &[u8]) -> Vec<u8>
+    {
+        let mut sound: Vec<u8> = vec![0, 0, 0, 4, 0, 0, 0, 0, 0xAA, 0xAA, 0xAA, 0xAA];
+        sound.extend_from_slice(audio_data);
+        return sound;
+    }
+
+    #[test]
+    fn decode_aiff_files()
+    {
+        let path = std::env::temp_dir().join("quadrium_decode.aiff");
+        let str_path = path.to_string_lossy().to_string();
+        let values: Vec<i32> = (0..600).map(|i| ((i as f64 * 0.07).sin() * 2000.0) as i32).collect();
+
+        //
+        // 12 bits samples on the left of 16 bits big endian, at 44100 Hz
+        let audio_data: Vec<u8> = values.iter().flat_map(|value| ((*value << 4) as i16).to_be_bytes().to_vec()).collect();
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"SSND", &create_sound_chunk(&audio_data), true);
+        let rate_44100 = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        std::fs::write(&path, create_aiff_file(None, 2, 300, 12, rate_44100, &chunks)).unwrap();
+        let mut decoder = open_aiff_decoder(str_path.clone()).unwrap();
+        assert_eq!(decoder.get_rate(), 44100);
+        assert_eq!(decoder.get_bits_per_sample(), 12);
+        assert_eq!(decoder.get_total_samples(), 300);
+        assert_eq!(decoder.read_all_samples().unwrap(), values);
+
+        //
+        // Little endian samples of AIFF-C, at 22050 Hz, with more bytes in SSND than frames in COMM
+        let mut audio_data: Vec<u8> = values.iter().flat_map(|value| (*value as i16).to_le_bytes().to_vec()).collect();
+        audio_data.extend_from_slice(&[1, 2, 3, 4]);
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"SSND", &create_sound_chunk(&audio_data), true);
+        let rate_22050 = [0x40, 0x0D, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        std::fs::write(&path, create_aiff_file(Some(b"sowt"), 1, 600, 16, rate_22050, &chunks)).unwrap();
+        let mut decoder = open_aiff_decoder(str_path.clone()).unwrap();
+        assert_eq!(decoder.get_rate(), 22050);
+        assert_eq!(decoder.read_all_samples().unwrap(), values);
+        decoder.seek(599).unwrap();
+        assert_eq!(decoder.read_next_block().unwrap().unwrap().m_samples, vec![values[599]]);
+
+        //
+        // Big endian floats of AIFF-C, at 48000 Hz
+        let audio_data: Vec<u8> = values.iter().flat_map(|value| (*value as f32 / 32768.0).to_be_bytes().to_vec()).collect();
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"SSND", &create_sound_chunk(&audio_data), true);
+        let rate_48000 = [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0];
+        std::fs::write(&path, create_aiff_file(Some(b"fl32"), 1, 600, 32, rate_48000, &chunks)).unwrap();
+        let mut decoder = open_aiff_decoder(str_path.clone()).unwrap();
+        assert_eq!(decoder.get_rate(), 48000);
+        assert_eq!(decoder.get_sample_format(), PcmSampleFormat::Float);
+        let expected_samples: Vec<i32> = values.iter().map(|value| *value << 16).collect();
+        assert_eq!(decoder.read_all_samples().unwrap(), expected_samples);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_aiff_markers()
+    {
+        let path = std::env::temp_dir().join("quadrium_markers.aiff");
+        let str_path = path.to_string_lossy().to_string();
+
+        let mut markers: Vec<u8> = vec![0, 3];
+        for (marker_id, position, name) in [(1u16, 100u32, "Start"), (3, 50, "Intro"), (2, 900, "End")].iter()
+        {
+            markers.extend_from_slice(&marker_id.to_be_bytes());
+            markers.extend_from_slice(&position.to_be_bytes());
+            markers.push(name.len() as u8);
+            markers.extend_from_slice(name.as_bytes());
+            if name.len() % 2 == 0
+            {
+                markers.push(0);
+            }
+        }
+
+        //
+        // Forward sustain loop between the markers 1 and 2, the release loop uses an unknown marker
+        let mut instrument: Vec<u8> = vec![60, 0, 0, 127, 0, 127, 0, 0];
+        instrument.extend_from_slice(&[0, 1, 0, 1, 0, 2]);
+        instrument.extend_from_slice(&[0, 2, 0, 1, 0, 9]);
+
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"NAME", b"Pluck", true);
+        write_chunk(&mut chunks, b"AUTH", b"Sound designer", true);
+        write_chunk(&mut chunks, b"ANNO", b"First note", true);
+        write_chunk(&mut chunks, b"ANNO", b"Second note", true);
+        write_chunk(&mut chunks, b"MARK", &markers, true);
+        write_chunk(&mut chunks, b"INST", &instrument, true);
+        write_chunk(&mut chunks, b"SSND", &create_sound_chunk(&vec![0; 1000]), true);
+        let rate_8000 = [0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0];
+        std::fs::write(&path, create_aiff_file(None, 1, 1000, 8, rate_8000, &chunks)).unwrap();
+
+        let audio_information = AiffReader {}.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_rate, 8000);
+        assert_eq!(audio_information.m_bits_per_sample, 8);
+        assert_eq!(audio_information.m_metadata.m_title, "Pluck");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Sound designer"]);
+        assert_eq!(audio_information.m_comments, vec!["First note".to_string(), "Second note".to_string()]);
+        assert_eq!(audio_information.m_cue_points, vec![
+            CuePoint { m_id: 3, m_sample: 50, m_label: "Intro".to_string() },
+            CuePoint { m_id: 1, m_sample: 100, m_label: "Start".to_string() },
+            CuePoint { m_id: 2, m_sample: 900, m_label: "End".to_string() },
+        ]);
+        assert_eq!(audio_information.m_loops, vec![AudioLoop { m_start_sample: 100, m_end_sample: 900, m_mode: LoopMode::Forward, m_is_release_loop: false }]);
+
+        //
+        // A truncated ID3 chunk is ignored, the text chunks are still read
+        write_chunk(&mut chunks, b"ID3 ", b"ID3\x04\x00\x00\x00\x00\x01\x00TIT2", true);
+        std::fs::write(&path, create_aiff_file(None, 1, 1000, 8, rate_8000, &chunks)).unwrap();
+        let audio_information = AiffReader {}.read_information(str_path).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Pluck");
+        assert_eq!(audio_information.m_total_samples, 1000);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
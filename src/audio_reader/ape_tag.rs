@@ -0,0 +1,771 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on https://wiki.hydrogenaud.io/index.php?title=APEv2_specification
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::audio_reader::{AudioInformation, AudioPicture, AudioReaderError, PictureType, TagDate, TrackMetadata};
+use crate::audio_reader::flac_writer::get_temporary_path;
+use crate::audio_reader::replay_gain::ReplayGainInfo;
+
+/// Size of the header and of the footer of a tag
+const APE_HEADER_SIZE: usize = 32;
+
+/// Size of an ID3v1 tag, which is written after the APE tag when both exist
+const ID3V1_TAG_SIZE: u64 = 128;
+
+/// Versions of the format, APEv1 tags have no header and only text items
+const APE_VERSION_1: u32 = 1000;
+const APE_VERSION_2: u32 = 2000;
+
+/// Flags of the header and of the footer
+const TAG_FLAG_HAS_HEADER: u32 = 1 << 31;
+const TAG_FLAG_HAS_NO_FOOTER: u32 = 1 << 30;
+const TAG_FLAG_IS_HEADER: u32 = 1 << 29;
+
+/// Flag of the items which should not be edited by the user
+const ITEM_FLAG_READ_ONLY: u32 = 1;
+
+/// Suffixes of the keys of the pictures, "Cover Art (Front)" for example, in the order of the ID3v2 picture types
+const COVER_ART_SUFFIXES: [&str; 21] = [
+    "Other", "Icon", "Other Icon", "Front", "Back", "Leaflet", "Media", "Lead Artist", "Artist", "Conductor", "Band",
+    "Composer", "Lyricist", "Recording Location", "During Recording", "During Performance", "Video Capture", "Fish",
+    "Illustration", "Band Logotype", "Publisher Logotype",
+];
+
+/// Keys which cannot be used by an item, because they are the magic numbers of other tags
+const RESERVED_KEYS: [&str; 4] = ["ID3", "TAG", "OggS", "MP+"];
+
+/// Keys of the items stored in the fields of their own of the metadata, or in the comments
+const APE_METADATA_KEYS: [&str; 11] = [
+    "Title", "Artist", "Album", "Album Artist", "AlbumArtist", "Composer", "Genre", "Track", "Disc", "Year", "Comment",
+];
+
+/// Type of the value of an item
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApeItemType
+{
+    /// UTF-8 text, with several values separated by null characters
+    Text,
+
+    /// Binary data, like a picture
+    Binary,
+
+    /// UTF-8 link to a resource outside of the file
+    ExternalLink,
+
+    /// Value reserved by the format
+    Reserved,
+}
+
+/// Item of an APE tag: a key and its value
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApeItem
+{
+    /// Key of the item, like "Title", compared without case
+    pub m_key: String,
+    pub m_item_type: ApeItemType,
+    pub m_is_read_only: bool,
+    pub m_value: Vec<u8>,
+}
+
+/// APEv1 or APEv2 tag, stored at the end of WavPack, Monkey's Audio and some MP3 files
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApeTag
+{
+    /// 1000 for APEv1, 2000 for APEv2
+    pub m_version: u32,
+    pub m_items: Vec<ApeItem>,
+}
+
+/// Header or footer of a tag
+struct ApeTagHeader
+{
+    m_version: u32,
+
+    /// Size of the items and of the footer, without the header
+    m_tag_size: u32,
+    m_item_count: u32,
+    m_flags: u32,
+}
+
+/// Check whether a key follows the specification: 2 to 255 printable ASCII characters, without the reserved keys
+fn is_valid_key(key: &str) -> bool
+{
+    return (2..=255).contains(&key.len())
+        && key.bytes().all(|byte| (0x20..=0x7E).contains(&byte))
+        && !RESERVED_KEYS.iter().any(|reserved_key| key.eq_ignore_ascii_case(reserved_key));
+}
+
+/// Read a header or a footer
+///
+/// # Return
+/// The header, or None if the data do not start with the preamble "APETAGEX"
+fn read_tag_header(data: &[u8]) -> Option<ApeTagHeader>
+{
+    if data.len() < APE_HEADER_SIZE || &data[0..8] != b"APETAGEX"
+    {
+        return None;
+    }
+    return Some(ApeTagHeader
+    {
+        m_version: u32::from_le_bytes(data[8..12].try_into().unwrap()),
+        m_tag_size: u32::from_le_bytes(data[12..16].try_into().unwrap()),
+        m_item_count: u32::from_le_bytes(data[16..20].try_into().unwrap()),
+        m_flags: u32::from_le_bytes(data[20..24].try_into().unwrap()),
+    });
+}
+
+/// Write a header or a footer
+fn write_tag_header(data: &mut Vec<u8>, tag_size: u32, item_count: u32, flags: u32)
+{
+    data.extend_from_slice(b"APETAGEX");
+    data.extend_from_slice(&APE_VERSION_2.to_le_bytes());
+    data.extend_from_slice(&tag_size.to_le_bytes());
+    data.extend_from_slice(&item_count.to_le_bytes());
+    data.extend_from_slice(&flags.to_le_bytes());
+    data.extend_from_slice(&[0; 8]);
+}
+
+/// Find the MIME type of a picture from its first bytes
+fn guess_picture_mime_type(data: &[u8]) -> String
+{
+    let mime_type = if data.starts_with(&[0x89, b'P', b'N', b'G'])
+    {
+        "image/png"
+    }
+    else if data.starts_with(&[0xFF, 0xD8])
+    {
+        "image/jpeg"
+    }
+    else if data.starts_with(b"GIF8")
+    {
+        "image/gif"
+    }
+    else if data.starts_with(b"BM")
+    {
+        "image/bmp"
+    }
+    else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP"
+    {
+        "image/webp"
+    }
+    else
+    {
+        "image/"
+    };
+    return mime_type.to_string();
+}
+
+/// Find the position of the tag of a file: after the audio, before an ID3v1 tag, or at the beginning of the file
+///
+/// # Return
+/// The position of the first byte of the tag and of the byte following it, or None if the file has no tag
+fn find_tag<R: Read + Seek>(source: &mut R) -> Result<Option<(u64, u64)>, AudioReaderError>
+{
+    let file_size = source.seek(SeekFrom::End(0))?;
+    let mut header_data = [0u8; APE_HEADER_SIZE];
+
+    //
+    // The tag ends with a footer, at the end of the file or before the ID3v1 tag
+    let mut tag_ends: Vec<u64> = vec![file_size];
+    if file_size >= ID3V1_TAG_SIZE
+    {
+        let mut id3v1_magic = [0u8; 3];
+        source.seek(SeekFrom::Start(file_size - ID3V1_TAG_SIZE))?;
+        source.read_exact(&mut id3v1_magic)?;
+        if &id3v1_magic == b"TAG"
+        {
+            tag_ends.insert(0, file_size - ID3V1_TAG_SIZE);
+        }
+    }
+    for tag_end in tag_ends
+    {
+        if tag_end < APE_HEADER_SIZE as u64
+        {
+            continue;
+        }
+        source.seek(SeekFrom::Start(tag_end - APE_HEADER_SIZE as u64))?;
+        source.read_exact(&mut header_data)?;
+        if let Some(footer) = read_tag_header(&header_data).filter(|footer| footer.m_flags & TAG_FLAG_IS_HEADER == 0)
+        {
+            let header_size = if footer.m_version >= APE_VERSION_2 && footer.m_flags & TAG_FLAG_HAS_HEADER != 0 { APE_HEADER_SIZE as u64 } else { 0 };
+            let tag_start = tag_end.checked_sub(footer.m_tag_size as u64 + header_size)
+                .ok_or(AudioReaderError::InvalidBlockLength { m_block_name: "APE", m_length: footer.m_tag_size })?;
+            return Ok(Some((tag_start, tag_end)));
+        }
+    }
+
+    //
+    // A tag without footer can only be at the beginning of the file
+    source.seek(SeekFrom::Start(0))?;
+    if source.read_exact(&mut header_data).is_ok()
+    {
+        if let Some(header) = read_tag_header(&header_data).filter(|header| header.m_flags & TAG_FLAG_IS_HEADER != 0)
+        {
+            return Ok(Some((0, (APE_HEADER_SIZE as u64 + header.m_tag_size as u64).min(file_size))));
+        }
+    }
+    return Ok(None);
+}
+
+impl ApeTag
+{
+    /// Create an APEv2 tag without any item
+    pub fn new() -> ApeTag
+    {
+        return ApeTag
+        {
+            m_version: APE_VERSION_2,
+            m_items: Vec::new(),
+        };
+    }
+
+    /// Decode a tag
+    ///
+    /// # Params
+    /// data: the whole tag, starting with its header or with its first item if it has no header
+    pub fn parse(data: &[u8]) -> Result<ApeTag, AudioReaderError>
+    {
+        //
+        // The header and the footer give the same information, one of them can be missing
+        let (header, items_start) = match read_tag_header(data)
+        {
+            Some(header) => (header, APE_HEADER_SIZE),
+            None => (read_tag_header(&data[data.len().saturating_sub(APE_HEADER_SIZE)..]).ok_or(AudioReaderError::InvalidMagicNumber)?, 0),
+        };
+        if header.m_version != APE_VERSION_1 && header.m_version != APE_VERSION_2
+        {
+            return Err(AudioReaderError::UnsupportedFeature("APE tag version"));
+        }
+        let has_footer = header.m_version == APE_VERSION_1 || header.m_flags & TAG_FLAG_HAS_NO_FOOTER == 0;
+        let items_size = (header.m_tag_size as usize).checked_sub(if has_footer { APE_HEADER_SIZE } else { 0 })
+            .filter(|items_size| items_start + items_size <= data.len())
+            .ok_or(AudioReaderError::InvalidBlockLength { m_block_name: "APE", m_length: header.m_tag_size })?;
+        let items_data = &data[items_start..items_start + items_size];
+
+        let mut items: Vec<ApeItem> = Vec::new();
+        let mut position = 0;
+        for _ in 0..header.m_item_count
+        {
+            if position + 8 > items_data.len()
+            {
+                return Err(AudioReaderError::TruncatedBlock { m_block_name: "APE item", m_offset: (items_start + position) as u64 });
+            }
+            let value_size = u32::from_le_bytes(items_data[position..position + 4].try_into().unwrap()) as usize;
+            let item_flags = u32::from_le_bytes(items_data[position + 4..position + 8].try_into().unwrap());
+
+            //
+            // The key is made of printable ASCII characters ended by a null character
+            let key_start = position + 8;
+            let key_end = items_data[key_start..].iter().position(|byte| *byte == 0).map(|key_size| key_start + key_size)
+                .ok_or(AudioReaderError::InvalidValue("APE item key"))?;
+            let value_start = key_end + 1;
+            if value_start + value_size > items_data.len()
+            {
+                return Err(AudioReaderError::InvalidBlockLength { m_block_name: "APE item", m_length: value_size as u32 });
+            }
+
+            //
+            // All the items of APEv1 are texts, their flags are not defined
+            let item_type = match (header.m_version, (item_flags >> 1) & 3)
+            {
+                (APE_VERSION_1, _) | (_, 0) => ApeItemType::Text,
+                (_, 1) => ApeItemType::Binary,
+                (_, 2) => ApeItemType::ExternalLink,
+                _ => ApeItemType::Reserved,
+            };
+            items.push(ApeItem
+            {
+                m_key: String::from_utf8_lossy(&items_data[key_start..key_end]).to_string(),
+                m_item_type: item_type,
+                m_is_read_only: header.m_version == APE_VERSION_2 && item_flags & ITEM_FLAG_READ_ONLY != 0,
+                m_value: items_data[value_start..value_start + value_size].to_vec(),
+            });
+            position = value_start + value_size;
+        }
+
+        return Ok(ApeTag
+        {
+            m_version: header.m_version,
+            m_items: items,
+        });
+    }
+
+    /// Read the tag of a file
+    ///
+    /// # Params
+    /// source: the file
+    ///
+    /// # Return
+    /// The tag, or None if the file has no tag
+    pub fn read<R: Read + Seek>(source: &mut R) -> Result<Option<ApeTag>, AudioReaderError>
+    {
+        return match find_tag(source)?
+        {
+            None => Ok(None),
+            Some((tag_start, tag_end)) =>
+            {
+                let mut tag_data = vec![0u8; (tag_end - tag_start) as usize];
+                source.seek(SeekFrom::Start(tag_start))?;
+                source.read_exact(&mut tag_data)?;
+                Ok(Some(ApeTag::parse(&tag_data)?))
+            }
+        };
+    }
+
+    /// Encode the tag as an APEv2 tag with a header and a footer, the read-only items included
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut items_data: Vec<u8> = Vec::new();
+        for item in self.m_items.iter()
+        {
+            let type_flags: u32 = match item.m_item_type
+            {
+                ApeItemType::Text => 0,
+                ApeItemType::Binary => 1,
+                ApeItemType::ExternalLink => 2,
+                ApeItemType::Reserved => 3,
+            };
+            let read_only_flag = if item.m_is_read_only { ITEM_FLAG_READ_ONLY } else { 0 };
+            items_data.extend_from_slice(&(item.m_value.len() as u32).to_le_bytes());
+            items_data.extend_from_slice(&((type_flags << 1) | read_only_flag).to_le_bytes());
+            items_data.extend_from_slice(item.m_key.as_bytes());
+            items_data.push(0);
+            items_data.extend_from_slice(&item.m_value);
+        }
+
+        let tag_size = (items_data.len() + APE_HEADER_SIZE) as u32;
+        let item_count = self.m_items.len() as u32;
+        let mut data: Vec<u8> = Vec::with_capacity(items_data.len() + 2 * APE_HEADER_SIZE);
+        write_tag_header(&mut data, tag_size, item_count, TAG_FLAG_HAS_HEADER | TAG_FLAG_IS_HEADER);
+        data.extend_from_slice(&items_data);
+        write_tag_header(&mut data, tag_size, item_count, TAG_FLAG_HAS_HEADER);
+        return data;
+    }
+
+    /// Write the tag in a file, replacing its previous APE tag.
+    /// The tag is written after the audio, before the ID3v1 tag if the file has one.
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the file
+    pub fn write_to_file(&self, str_path_to_music: &str) -> Result<(), AudioReaderError>
+    {
+        let mut file = File::open(str_path_to_music)?;
+        let file_size = file.seek(SeekFrom::End(0))?;
+
+        //
+        // Keep the ID3v1 tag, then remove everything from the old tag or from the ID3v1 tag
+        let mut id3v1_tag: Vec<u8> = Vec::new();
+        if file_size >= ID3V1_TAG_SIZE
+        {
+            id3v1_tag.resize(ID3V1_TAG_SIZE as usize, 0);
+            file.seek(SeekFrom::Start(file_size - ID3V1_TAG_SIZE))?;
+            file.read_exact(&mut id3v1_tag)?;
+            if &id3v1_tag[0..3] != b"TAG"
+            {
+                id3v1_tag.clear();
+            }
+        }
+        let audio_end = match find_tag(&mut file)?
+        {
+            Some((0, _)) => return Err(AudioReaderError::UnsupportedFeature("APE tag at the beginning of the file")),
+            Some((tag_start, _)) => tag_start,
+            None => file_size - id3v1_tag.len() as u64,
+        };
+
+        //
+        // The new end of the file is built in memory, the tags are small compared to the audio
+        let mut tail: Vec<u8> = Vec::new();
+        if !self.m_items.is_empty()
+        {
+            tail.extend_from_slice(&self.to_bytes());
+        }
+        tail.extend_from_slice(&id3v1_tag);
+
+        //
+        // Write the new file next to the original one, then replace the original one
+        let path = Path::new(str_path_to_music);
+        let temporary_path = get_temporary_path(path);
+        let result = (|| -> Result<(), AudioReaderError>
+        {
+            let mut output_file = File::create(&temporary_path)?;
+            file.seek(SeekFrom::Start(0))?;
+            std::io::copy(&mut (&mut file).take(audio_end), &mut output_file)?;
+            output_file.write_all(&tail)?;
+            output_file.set_permissions(file.metadata()?.permissions())?;
+            output_file.sync_all()?;
+            std::fs::rename(&temporary_path, path)?;
+            return Ok(());
+        })();
+        if result.is_err()
+        {
+            let _ = std::fs::remove_file(&temporary_path);
+        }
+
+        return result;
+    }
+
+    /// Remove the APE tag of a file, keeping its ID3v1 tag
+    pub fn remove_from_file(str_path_to_music: &str) -> Result<(), AudioReaderError>
+    {
+        return ApeTag::new().write_to_file(str_path_to_music);
+    }
+
+    /// Get the item with a key, compared without case
+    pub fn get_item(&self, key: &str) -> Option<&ApeItem>
+    {
+        return self.m_items.iter().find(|item| item.m_key.eq_ignore_ascii_case(key));
+    }
+
+    /// Get the values of a text item, separated by null characters, empty if there is no such item
+    pub fn get_text_values(&self, key: &str) -> Vec<String>
+    {
+        return match self.get_item(key).filter(|item| item.m_item_type != ApeItemType::Binary)
+        {
+            None => Vec::new(),
+            Some(item) => String::from_utf8_lossy(&item.m_value).split('\0').map(|value| value.to_string()).collect(),
+        };
+    }
+
+    /// Get the first value of a text item, empty if there is no such item
+    pub fn get_text(&self, key: &str) -> String
+    {
+        return self.get_text_values(key).into_iter().next().unwrap_or_default();
+    }
+
+    /// Replace an item by a text item, with one or several values
+    ///
+    /// # Return
+    /// The InvalidValue error if the key does not follow the specification
+    pub fn set_text_values(&mut self, key: &str, values: &[&str]) -> Result<(), AudioReaderError>
+    {
+        return self.set_item(key, ApeItemType::Text, values.join("\0").into_bytes());
+    }
+
+    /// Replace an item by a text item
+    ///
+    /// # Return
+    /// The InvalidValue error if the key does not follow the specification
+    pub fn set_text(&mut self, key: &str, value: &str) -> Result<(), AudioReaderError>
+    {
+        return self.set_text_values(key, &[value]);
+    }
+
+    /// Replace an item by a new one, added after the others if there was no such item
+    ///
+    /// # Return
+    /// The InvalidValue error if the key is not made of 2 to 255 printable ASCII characters, or is a reserved key
+    pub fn set_item(&mut self, key: &str, item_type: ApeItemType, value: Vec<u8>) -> Result<(), AudioReaderError>
+    {
+        if !is_valid_key(key)
+        {
+            return Err(AudioReaderError::InvalidValue("APE item key"));
+        }
+        self.replace_item(key, item_type, value);
+        return Ok(());
+    }
+
+    /// Replace an item by a new one without checking its key
+    fn replace_item(&mut self, key: &str, item_type: ApeItemType, value: Vec<u8>)
+    {
+        let new_item = ApeItem { m_key: key.to_string(), m_item_type: item_type, m_is_read_only: false, m_value: value };
+        match self.m_items.iter_mut().find(|item| item.m_key.eq_ignore_ascii_case(key))
+        {
+            Some(item) => *item = new_item,
+            None => self.m_items.push(new_item),
+        }
+    }
+
+    /// Remove the item with a key
+    pub fn remove_item(&mut self, key: &str)
+    {
+        self.m_items.retain(|item| !item.m_key.eq_ignore_ascii_case(key));
+    }
+
+    /// Get the pictures of the "Cover Art" items, whose value is the name of the picture file followed by the picture
+    pub fn get_pictures(&self) -> Vec<AudioPicture>
+    {
+        let mut pictures: Vec<AudioPicture> = Vec::new();
+        for item in self.m_items.iter().filter(|item| item.m_item_type == ApeItemType::Binary)
+        {
+            let picture_type = match COVER_ART_SUFFIXES.iter().position(|suffix| item.m_key.eq_ignore_ascii_case(&format!("Cover Art ({})", suffix)))
+            {
+                None => continue,
+                Some(type_index) => PictureType::from(type_index as u32),
+            };
+            let name_end = item.m_value.iter().position(|byte| *byte == 0).unwrap_or(0);
+            let picture_data = &item.m_value[(name_end + 1).min(item.m_value.len())..];
+            pictures.push(AudioPicture
+            {
+                m_picture_type: picture_type,
+                m_mime_type: guess_picture_mime_type(picture_data),
+                m_description: String::from_utf8_lossy(&item.m_value[..name_end]).to_string(),
+                m_width: 0,
+                m_height: 0,
+                m_color_depth: 0,
+                m_indexed_color_count: 0,
+                m_data: picture_data.to_vec(),
+            });
+        }
+        return pictures;
+    }
+
+    /// Add a picture as a "Cover Art" item, replacing the picture of the same type
+    ///
+    /// # Params
+    /// picture: the picture, its description is used as the name of the picture file
+    pub fn set_picture(&mut self, picture: &AudioPicture)
+    {
+        let type_index = (u32::from(picture.m_picture_type) as usize).min(COVER_ART_SUFFIXES.len() - 1);
+        let mut value = picture.m_description.as_bytes().to_vec();
+        value.push(0);
+        value.extend_from_slice(&picture.m_data);
+        self.replace_item(&format!("Cover Art ({})", COVER_ART_SUFFIXES[type_index]), ApeItemType::Binary, value);
+    }
+
+    /// Get the non empty values of a text item, from the first key of the item found
+    fn get_non_empty_values(&self, keys: &[&str]) -> Vec<String>
+    {
+        return keys.iter()
+            .map(|key| self.get_text_values(key).into_iter().filter(|value| !value.is_empty()).collect::<Vec<String>>())
+            .find(|values| !values.is_empty())
+            .unwrap_or_default();
+    }
+
+    /// Get the tags stored in the text items. The text items without a field of their own are the extra fields.
+    pub fn get_metadata(&self) -> TrackMetadata
+    {
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = self.get_text("Title");
+        metadata.m_artists = self.get_non_empty_values(&["Artist"]);
+        metadata.m_album = self.get_text("Album");
+        metadata.m_album_artists = self.get_non_empty_values(&["Album Artist", "AlbumArtist"]);
+        metadata.m_composers = self.get_non_empty_values(&["Composer"]);
+        metadata.m_genres = self.get_non_empty_values(&["Genre"]);
+        metadata.set_track_text(&self.get_text("Track"));
+        metadata.set_disc_text(&self.get_text("Disc"));
+        metadata.m_date = TagDate::parse(&self.get_text("Year"));
+        for item in self.m_items.iter().filter(|item| item.m_item_type == ApeItemType::Text)
+        {
+            if !APE_METADATA_KEYS.iter().any(|key| key.eq_ignore_ascii_case(&item.m_key))
+            {
+                for value in String::from_utf8_lossy(&item.m_value).split('\0')
+                {
+                    metadata.add_extra(&item.m_key, value.to_string());
+                }
+            }
+        }
+        metadata.m_replay_gain = ReplayGainInfo::take_from_extra(&mut metadata.m_extra);
+        return metadata;
+    }
+
+    /// Copy the tag to the information of a title, the fields not stored in the tag are kept
+    pub fn apply_to_information(&self, audio_information: &mut AudioInformation)
+    {
+        audio_information.m_metadata.apply(self.get_metadata());
+        audio_information.m_comments.extend(self.get_text_values("Comment").into_iter().filter(|comment| !comment.is_empty()));
+        audio_information.m_pictures.extend(self.get_pictures());
+    }
+}
+
+/// Read the APE tag of a file
+///
+/// # Params
+/// str_path_to_music: the path of the file
+///
+/// # Return
+/// The tag, or None if the file has no tag
+pub fn read_ape_tag(str_path_to_music: &str) -> Result<Option<ApeTag>, AudioReaderError>
+{
+    return ApeTag::read(&mut File::open(str_path_to_music)?);
+}
+
+#[cfg(test)]
+mod test_ape_tag
+{
+    use std::io::Cursor;
+    use crate::audio_reader::{AudioPicture, AudioReader, AudioReaderError, PictureType, TagFormat, DEFAULT_TAG_PRIORITY};
+    use crate::audio_reader::ape_tag::{read_ape_tag, ApeItemType, ApeTag};
+    use crate::audio_reader::symphonia_reader::SymphoniaReader;
+
+    /// Create an ID3v1 tag with a title
+    fn create_id3v1_tag(title: &str) -> Vec<u8>
+    {
+        let mut id3v1_tag = b"TAG".to_vec();
+        id3v1_tag.extend_from_slice(title.as_bytes());
+        id3v1_tag.resize(128, 0);
+        id3v1_tag[127] = 0xFF;
+        return id3v1_tag;
+    }
+
+    /// Create MPEG-1 layer III frames of silence at 128 kbit/s and 44100 Hz
+    fn create_mp3_frames(frame_count: usize) -> Vec<u8>
+    {
+        let mut frames: Vec<u8> = Vec::new();
+        for _ in 0..frame_count
+        {
+            frames.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            frames.extend_from_slice(&[0; 413]);
+        }
+        return frames;
+    }
+
+    #[test]
+    fn write_and_read_ape_tag()
+    {
+        let path = std::env::temp_dir().join("quadrium_ape_tag.mp3");
+        let str_path = path.to_string_lossy().to_string();
+        let audio_data = create_mp3_frames(4);
+        let id3v1_tag = create_id3v1_tag("Id3v1 title");
+        std::fs::write(&path, [audio_data.clone(), id3v1_tag.clone()].concat()).unwrap();
+
+        let mut tag = ApeTag::new();
+        tag.set_text("Title", "Ape title").unwrap();
+        tag.set_text_values("Artist", &["First", "Second"]).unwrap();
+        tag.set_text("Comment", "Ape comment").unwrap();
+        tag.set_picture(&AudioPicture
+        {
+            m_picture_type: PictureType::FrontCover,
+            m_mime_type: "image/png".to_string(),
+            m_description: "cover.png".to_string(),
+            m_width: 0,
+            m_height: 0,
+            m_color_depth: 0,
+            m_indexed_color_count: 0,
+            m_data: vec![0x89, b'P', b'N', b'G', 0, 1],
+        });
+        tag.write_to_file(&str_path).unwrap();
+
+        //
+        // The tag is written between the audio and the ID3v1 tag, which is kept
+        let file_data = std::fs::read(&path).unwrap();
+        assert_eq!(file_data[..audio_data.len()], audio_data[..]);
+        assert_eq!(file_data[file_data.len() - 128..], id3v1_tag[..]);
+        assert_eq!(&file_data[audio_data.len()..audio_data.len() + 8], b"APETAGEX");
+
+        let read_tag = read_ape_tag(&str_path).unwrap().unwrap();
+        assert_eq!(read_tag, tag);
+        assert_eq!(read_tag.get_text("TITLE"), "Ape title");
+        assert_eq!(read_tag.get_text_values("artist"), vec!["First", "Second"]);
+        assert_eq!(read_tag.get_item("Cover Art (Front)").unwrap().m_item_type, ApeItemType::Binary);
+        let pictures = read_tag.get_pictures();
+        assert_eq!(pictures[0].m_picture_type, PictureType::FrontCover);
+        assert_eq!(pictures[0].m_mime_type, "image/png");
+        assert_eq!(pictures[0].m_description, "cover.png");
+        assert_eq!(pictures[0].m_data, vec![0x89, b'P', b'N', b'G', 0, 1]);
+
+        //
+        // Rewriting a smaller tag replaces the old one, removing the tag leaves the file as it was
+        let mut smaller_tag = read_tag.clone();
+        smaller_tag.remove_item("Cover Art (Front)");
+        smaller_tag.write_to_file(&str_path).unwrap();
+        assert_eq!(read_ape_tag(&str_path).unwrap().unwrap(), smaller_tag);
+        assert_eq!(std::fs::metadata(&path).unwrap().len() as usize, audio_data.len() + smaller_tag.to_bytes().len() + 128);
+        ApeTag::remove_from_file(&str_path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), [audio_data, id3v1_tag].concat());
+        assert!(read_ape_tag(&str_path).unwrap().is_none());
+        assert!(!path.with_file_name(".quadrium_ape_tag.mp3.quadrium-tmp").exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_ape_keys()
+    {
+        //
+        // The keys are 2 to 255 printable ASCII characters, the magic numbers of the other tags are reserved
+        let mut tag = ApeTag::new();
+        let long_key = "K".repeat(256);
+        for key in ["", "T", "Titr\u{E9}", "Line\nfeed", "ID3", "tag", "OggS", "MP+", long_key.as_str()].iter()
+        {
+            assert!(matches!(tag.set_text(key, "Value"), Err(AudioReaderError::InvalidValue(_))), "{0:?}", key);
+            assert!(matches!(tag.set_item(key, ApeItemType::Binary, vec![1]), Err(AudioReaderError::InvalidValue(_))), "{0:?}", key);
+        }
+        assert_eq!(tag, ApeTag::new());
+
+        tag.set_text("ID", "Two characters").unwrap();
+        tag.set_text(&"K".repeat(255), "Longest key").unwrap();
+        tag.set_text("Key with ~ and spaces", "Value").unwrap();
+        assert_eq!(ApeTag::parse(&tag.to_bytes()).unwrap(), tag);
+    }
+
+    #[test]
+    fn read_ape_v1_tag()
+    {
+        //
+        // APEv1 tag: a footer without header, the flags of the items are not used
+        let mut items: Vec<u8> = Vec::new();
+        for (key, value) in [("Title", "Old title"), ("Year", "1998")].iter()
+        {
+            items.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            items.extend_from_slice(&2u32.to_le_bytes());
+            items.extend_from_slice(key.as_bytes());
+            items.push(0);
+            items.extend_from_slice(value.as_bytes());
+        }
+        let mut file_data = vec![0u8; 100];
+        file_data.extend_from_slice(&items);
+        file_data.extend_from_slice(b"APETAGEX");
+        for value in [1000u32, (items.len() + 32) as u32, 2, 0].iter()
+        {
+            file_data.extend_from_slice(&value.to_le_bytes());
+        }
+        file_data.extend_from_slice(&[0; 8]);
+
+        let tag = ApeTag::read(&mut Cursor::new(file_data)).unwrap().unwrap();
+        assert_eq!(tag.m_version, 1000);
+        assert_eq!(tag.get_item("Title").unwrap().m_item_type, ApeItemType::Text);
+        assert_eq!(tag.get_text("title"), "Old title");
+        assert_eq!(tag.get_text("Year"), "1998");
+    }
+
+    #[test]
+    fn choose_tags_by_priority()
+    {
+        //
+        // MP3 file with an ID3v2 tag, an APE tag and an ID3v1 tag giving different titles
+        let path = std::env::temp_dir().join("quadrium_tag_priority.mp3");
+        let str_path = path.to_string_lossy().to_string();
+        let mut id3v2_tag = b"ID3\x03\0\0\0\0\0\x14".to_vec();
+        id3v2_tag.extend_from_slice(b"TIT2\0\0\0\x0A\0\0\0Id3 title");
+        std::fs::write(&path, [id3v2_tag, create_mp3_frames(20), create_id3v1_tag("Id3v1 title")].concat()).unwrap();
+        let mut ape_tag = ApeTag::new();
+        ape_tag.set_text("Title", "Ape title").unwrap();
+        ape_tag.set_text("Album", "Ape album").unwrap();
+        ape_tag.write_to_file(&str_path).unwrap();
+
+        let audio_information = SymphoniaReader { m_tag_priority: DEFAULT_TAG_PRIORITY.to_vec() }.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_rate, 44100);
+        assert_eq!(audio_information.m_metadata.m_title, "Id3 title");
+        assert_eq!(audio_information.m_metadata.m_album, "Ape album");
+
+        let audio_information = SymphoniaReader { m_tag_priority: vec![TagFormat::Ape, TagFormat::Id3v2] }.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Ape title");
+
+        let audio_information = SymphoniaReader { m_tag_priority: vec![TagFormat::Id3v1] }.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Id3v1 title");
+        assert_eq!(audio_information.m_metadata.m_album, "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
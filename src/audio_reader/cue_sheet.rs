@@ -0,0 +1,848 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on the CDRWIN cue sheet format, as written by EAC, foobar2000 and the other rippers
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use crate::audio_reader::{AudioInformation, AudioReader, AudioReaderError, DecodedBlock, TagDate, TrackIndexPoint, TrackMetadata, VirtualTrack};
+use crate::audio_reader::reader_registry::{AudioReaderRegistry, FileDecoder};
+use crate::audio_reader::replay_gain::{ReplayGainInfo, ReplayGainProcessor};
+use crate::Controller;
+use crate::Controller::EventManager::QuInformationData;
+
+/// Number of CD frames per second, the unit of the positions of the INDEX commands
+const CD_FRAMES_PER_SECOND: u64 = 75;
+
+/// Audio file named by a FILE command
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheetFile
+{
+    /// Path of the file as written in the sheet, usually relative to the sheet
+    pub m_path: String,
+
+    /// Type of the file, like WAVE, MP3, AIFF or BINARY
+    pub m_file_type: String,
+}
+
+/// Index point given by an INDEX command
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheetIndex
+{
+    /// 0 for the pre-gap, 1 for the start of the track, the next ones for the subdivisions of the track
+    pub m_index_number: u8,
+
+    /// Position in the files of the sheet of the file containing the index point
+    pub m_file_index: usize,
+
+    /// Position from the beginning of the file, in CD frames of 1/75 s
+    pub m_frame: u64,
+}
+
+/// Track given by a TRACK command and the commands following it
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheetTrack
+{
+    pub m_track_number: u8,
+
+    /// Type of the data of the track, AUDIO for the audio tracks
+    pub m_data_type: String,
+
+    /// Title of the track, empty if unknown
+    pub m_title: String,
+    pub m_performer: String,
+    pub m_songwriter: String,
+
+    /// International Standard Recording Code, empty if unknown
+    pub m_isrc: String,
+
+    /// Flags of the FLAGS command, like PRE for the pre-emphasis
+    pub m_flags: Vec<String>,
+    pub m_indexes: Vec<CueSheetIndex>,
+
+    /// Fields of the REM commands of the track by key in upper case, like REPLAYGAIN_TRACK_GAIN
+    pub m_remarks: BTreeMap<String, Vec<String>>,
+}
+
+/// Cue sheet describing the tracks stored in one or several audio files, like the rip of a whole CD in a single file
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheet
+{
+    /// Title of the album, empty if unknown
+    pub m_title: String,
+    pub m_performer: String,
+    pub m_songwriter: String,
+
+    /// Media Catalog Number of the disc, empty if unknown
+    pub m_catalog: String,
+
+    /// Fields of the REM commands before the first track by key in upper case, like GENRE, DATE or REPLAYGAIN_ALBUM_GAIN
+    pub m_remarks: BTreeMap<String, Vec<String>>,
+    pub m_files: Vec<CueSheetFile>,
+    pub m_tracks: Vec<CueSheetTrack>,
+}
+
+/// Remove the quotes around a value, the values without space are often written without them
+fn unquote(text: &str) -> String
+{
+    return match text.strip_prefix('"')
+    {
+        None => text.to_string(),
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+    };
+}
+
+/// Split the arguments of a FILE command into the path and the type of the file, the path can contain spaces
+fn parse_file_arguments(arguments: &str) -> (String, String)
+{
+    if let Some(quoted) = arguments.strip_prefix('"')
+    {
+        if let Some((path, file_type)) = quoted.split_once('"')
+        {
+            return (path.to_string(), file_type.trim().to_string());
+        }
+    }
+    return match arguments.rsplit_once(char::is_whitespace)
+    {
+        None => (arguments.to_string(), String::new()),
+        Some((path, file_type)) => (path.trim().to_string(), file_type.to_string()),
+    };
+}
+
+/// Parse a position written "mm:ss:ff", minutes, seconds and CD frames
+///
+/// # Return
+/// The position in CD frames
+fn parse_time(text: &str) -> Result<u64, AudioReaderError>
+{
+    let fields: Vec<u64> = text.split(':').map(|field| field.trim().parse::<u64>()).collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| AudioReaderError::InvalidValue("cue sheet index position"))?;
+    return match fields[..]
+    {
+        [minutes, seconds, frames] if seconds < 60 && frames < CD_FRAMES_PER_SECOND => minutes.checked_mul(60)
+            .and_then(|minute_seconds| minute_seconds.checked_add(seconds))
+            .and_then(|total_seconds| total_seconds.checked_mul(CD_FRAMES_PER_SECOND))
+            .and_then(|second_frames| second_frames.checked_add(frames))
+            .ok_or(AudioReaderError::InvalidValue("cue sheet index position")),
+        _ => Err(AudioReaderError::InvalidValue("cue sheet index position")),
+    };
+}
+
+/// Decode the text of a cue sheet.
+/// The sheets are written in UTF-8 or UTF-16 with a BOM, in UTF-8 without BOM, or in the legacy charset of the system
+/// of the ripper, which is then guessed from the text.
+pub fn decode_cue_sheet_text(data: &[u8]) -> String
+{
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data)
+    {
+        return encoding.decode_without_bom_handling(&data[bom_length..]).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(data)
+    {
+        return text.to_string();
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    return detector.guess(None, false).decode_without_bom_handling(data).0.into_owned();
+}
+
+impl CueSheetTrack
+{
+    /// Check whether the track contains audio, the data tracks of the mixed mode CDs are not played
+    pub fn is_audio(&self) -> bool
+    {
+        return self.m_data_type.eq_ignore_ascii_case("AUDIO");
+    }
+
+    /// Get the index point where the track starts: its index 1, or its first index point if it has no index 1
+    pub fn get_start_index(&self) -> Option<&CueSheetIndex>
+    {
+        return self.m_indexes.iter().find(|index| index.m_index_number == 1).or(self.m_indexes.first());
+    }
+}
+
+impl CueSheet
+{
+    /// Create a sheet without any file or track
+    pub fn new() -> CueSheet
+    {
+        return CueSheet
+        {
+            m_title: String::new(),
+            m_performer: String::new(),
+            m_songwriter: String::new(),
+            m_catalog: String::new(),
+            m_remarks: BTreeMap::new(),
+            m_files: Vec::new(),
+            m_tracks: Vec::new(),
+        };
+    }
+
+    /// Parse the text of a cue sheet.
+    /// The commands are read without case. PREGAP, POSTGAP, CDTEXTFILE and the unknown commands are ignored,
+    /// the gaps they add are not stored in the files.
+    pub fn parse(text: &str) -> Result<CueSheet, AudioReaderError>
+    {
+        let mut cue_sheet = CueSheet::new();
+        for line in text.lines()
+        {
+            let line = line.trim();
+            let (command, arguments) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, arguments)| (command, arguments.trim()));
+            let track = cue_sheet.m_tracks.last_mut();
+            match command.to_ascii_uppercase().as_str()
+            {
+                "FILE" =>
+                {
+                    let (path, file_type) = parse_file_arguments(arguments);
+                    cue_sheet.m_files.push(CueSheetFile { m_path: path, m_file_type: file_type });
+                }
+                "TRACK" =>
+                {
+                    if cue_sheet.m_files.is_empty()
+                    {
+                        return Err(AudioReaderError::InvalidValue("cue sheet track before any file"));
+                    }
+                    let (number, data_type) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, "AUDIO"));
+                    let track_number = number.parse::<u8>().map_err(|_| AudioReaderError::InvalidValue("cue sheet track number"))?;
+                    cue_sheet.m_tracks.push(CueSheetTrack
+                    {
+                        m_track_number: track_number,
+                        m_data_type: data_type.trim().to_string(),
+                        m_title: String::new(),
+                        m_performer: String::new(),
+                        m_songwriter: String::new(),
+                        m_isrc: String::new(),
+                        m_flags: Vec::new(),
+                        m_indexes: Vec::new(),
+                        m_remarks: BTreeMap::new(),
+                    });
+                }
+                "INDEX" =>
+                {
+                    let track = track.ok_or(AudioReaderError::InvalidValue("cue sheet index outside of a track"))?;
+                    let (number, time) = arguments.split_once(char::is_whitespace).ok_or(AudioReaderError::InvalidValue("cue sheet index"))?;
+                    track.m_indexes.push(CueSheetIndex
+                    {
+                        m_index_number: number.parse::<u8>().map_err(|_| AudioReaderError::InvalidValue("cue sheet index number"))?,
+                        m_file_index: cue_sheet.m_files.len() - 1,
+                        m_frame: parse_time(time)?,
+                    });
+                }
+                "TITLE" => *track.map_or(&mut cue_sheet.m_title, |track| &mut track.m_title) = unquote(arguments),
+                "PERFORMER" => *track.map_or(&mut cue_sheet.m_performer, |track| &mut track.m_performer) = unquote(arguments),
+                "SONGWRITER" => *track.map_or(&mut cue_sheet.m_songwriter, |track| &mut track.m_songwriter) = unquote(arguments),
+                "CATALOG" => cue_sheet.m_catalog = unquote(arguments),
+                "ISRC" =>
+                {
+                    if let Some(track) = track
+                    {
+                        track.m_isrc = unquote(arguments);
+                    }
+                }
+                "FLAGS" =>
+                {
+                    if let Some(track) = track
+                    {
+                        track.m_flags = arguments.split_whitespace().map(|flag| flag.to_ascii_uppercase()).collect();
+                    }
+                }
+                "REM" =>
+                {
+                    let (key, value) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+                    if !key.is_empty()
+                    {
+                        let remarks = track.map_or(&mut cue_sheet.m_remarks, |track| &mut track.m_remarks);
+                        remarks.entry(key.to_ascii_uppercase()).or_default().push(unquote(value.trim()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        return Ok(cue_sheet);
+    }
+
+    /// Parse a cue sheet in any charset
+    pub fn parse_bytes(data: &[u8]) -> Result<CueSheet, AudioReaderError>
+    {
+        return CueSheet::parse(&decode_cue_sheet_text(data));
+    }
+
+    /// Get the tags of a track: the ones of the track, then the ones of the album for the fields the track does not give.
+    /// The REM fields without a field of their own are the extra fields.
+    pub fn get_track_metadata(&self, track: &CueSheetTrack) -> TrackMetadata
+    {
+        let non_empty = |values: &[&String]| values.iter().find(|value| !value.is_empty()).map(|value| vec![value.to_string()]).unwrap_or_default();
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = track.m_title.clone();
+        metadata.m_artists = non_empty(&[&track.m_performer, &self.m_performer]);
+        metadata.m_album = self.m_title.clone();
+        metadata.m_album_artists = non_empty(&[&self.m_performer]);
+        metadata.m_composers = non_empty(&[&track.m_songwriter, &self.m_songwriter]);
+        metadata.m_track_number = Some(track.m_track_number as u32);
+        metadata.m_track_total = Some(self.m_tracks.iter().filter(|track| track.is_audio()).count() as u32);
+
+        //
+        // The REM fields of the track replace the ones of the album with the same key
+        let mut remarks = self.m_remarks.clone();
+        remarks.extend(track.m_remarks.clone());
+        let mut take_first = |key: &str| remarks.remove(key).and_then(|values| values.into_iter().next()).unwrap_or_default();
+        metadata.m_genres = non_empty(&[&take_first("GENRE")]);
+        metadata.m_date = TagDate::parse(&take_first("DATE"));
+        metadata.m_disc_number = take_first("DISCNUMBER").trim().parse().ok().filter(|number| *number != 0);
+        metadata.m_disc_total = take_first("TOTALDISCS").trim().parse().ok().filter(|total| *total != 0);
+        metadata.m_extra = remarks;
+        metadata.m_replay_gain = ReplayGainInfo::take_from_extra(&mut metadata.m_extra);
+        return metadata;
+    }
+}
+
+/// Read a cue sheet file in any charset
+pub fn read_cue_sheet(str_path_to_cue_sheet: &str) -> Result<CueSheet, AudioReaderError>
+{
+    return CueSheet::parse_bytes(&fs::read(str_path_to_cue_sheet)?);
+}
+
+/// Find the audio file named by a FILE command.
+/// The sheets often name the file the disc was ripped to, like "album.wav", and the file was encoded to another format afterwards:
+/// a file with the same name and another extension read by the registry is used then.
+///
+/// # Params
+/// registry: the registry recognizing the audio files
+/// directory: the directory of the sheet, the paths of the sheet are relative to it
+/// file_path: the path written in the sheet
+fn find_audio_file(registry: &AudioReaderRegistry, directory: &Path, file_path: &str) -> Result<String, AudioReaderError>
+{
+    let path = directory.join(file_path);
+    if path.is_file()
+    {
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    let not_found = || AudioReaderError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, format!("file of the cue sheet not found: {0}", file_path)));
+    let (parent, stem) = match (path.parent(), path.file_stem())
+    {
+        (Some(parent), Some(stem)) => (parent, stem),
+        _ => return Err(not_found()),
+    };
+    let mut candidates: Vec<String> = fs::read_dir(parent).map_err(|_| not_found())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|candidate| candidate.file_stem() == Some(stem) && !candidate.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("cue")))
+        .map(|candidate| candidate.to_string_lossy().to_string())
+        .collect();
+    candidates.sort();
+    return candidates.into_iter().find(|candidate| registry.find_reader(candidate).is_ok()).ok_or_else(not_found);
+}
+
+/// Track of a cue sheet, listed and played like a file of its own
+pub struct CueTrack
+{
+    /// Path of the audio file containing the track
+    pub m_str_path_to_music: String,
+
+    /// Position of the track in the stream of the file
+    pub m_track: VirtualTrack,
+
+    /// The properties of the stream of the file with the length of the track, and the tags of the track
+    pub m_information: AudioInformation,
+}
+
+impl QuInformationData for CueTrack
+{
+    fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
+    {
+        let mut key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = self.m_information.convert_to_key_map();
+        key_map.push(("path_file".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_path_to_music.clone()));
+        key_map.push(("start_sample".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_track.m_start_sample.to_string()));
+        key_map.push(("end_sample".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_track.m_end_sample.to_string()));
+
+        return key_map;
+    }
+}
+
+/// Read a cue sheet and split its files into tracks.
+/// A track starts at its index point 1 and ends at the start of the next track of the same file, or at the end of the file,
+/// so the pre-gap of a track is played at the end of the previous one, like the cuesheets of the flac files.
+/// The positions are converted from CD frames to the samples of each file.
+///
+/// # Params
+/// registry: the registry reading the audio files
+/// str_path_to_cue_sheet: the path of the cue sheet
+///
+/// # Return
+/// The audio tracks in the order of the sheet
+pub fn read_cue_tracks(registry: &AudioReaderRegistry, str_path_to_cue_sheet: &str) -> Result<Vec<CueTrack>, AudioReaderError>
+{
+    let cue_sheet = read_cue_sheet(str_path_to_cue_sheet)?;
+    let directory = Path::new(str_path_to_cue_sheet).parent().unwrap_or_else(|| Path::new(""));
+
+    //
+    // The files are read once, when their first audio track is met, the data files are never read
+    let mut files: Vec<Option<(String, AudioInformation)>> = (0..cue_sheet.m_files.len()).map(|_| None).collect();
+    let mut cue_tracks: Vec<CueTrack> = Vec::new();
+    for (position, track) in cue_sheet.m_tracks.iter().enumerate()
+    {
+        if !track.is_audio()
+        {
+            continue;
+        }
+        let start_index = track.get_start_index().ok_or(AudioReaderError::InvalidValue("cue sheet track without index"))?;
+        let file_index = start_index.m_file_index;
+        if files[file_index].is_none()
+        {
+            let str_path_to_music = find_audio_file(registry, directory, &cue_sheet.m_files[file_index].m_path)?;
+            let file_information = registry.read_information(str_path_to_music.clone())?;
+            files[file_index] = Some((str_path_to_music, file_information));
+        }
+        let (str_path_to_music, file_information) = files[file_index].as_ref().unwrap();
+        let convert_frame_to_sample = |frame: u64| frame.saturating_mul(file_information.m_rate as u64) / CD_FRAMES_PER_SECOND;
+
+        let start_sample = convert_frame_to_sample(start_index.m_frame);
+        let end_of_file = match file_information.m_total_samples
+        {
+            0 => u64::MAX,
+            total_samples => total_samples,
+        };
+        let end_sample = cue_sheet.m_tracks.get(position + 1)
+            .and_then(|next_track| next_track.get_start_index())
+            .filter(|next_start_index| next_start_index.m_file_index == file_index)
+            .map_or(end_of_file, |next_start_index| convert_frame_to_sample(next_start_index.m_frame))
+            .min(end_of_file);
+        if start_sample >= end_sample
+        {
+            return Err(AudioReaderError::InvalidValue("cue sheet track position"));
+        }
+
+        let index_points: Vec<TrackIndexPoint> = track.m_indexes.iter()
+            .filter(|index| index.m_file_index == file_index)
+            .map(|index| TrackIndexPoint { m_index_number: index.m_index_number, m_sample: convert_frame_to_sample(index.m_frame) })
+            .collect();
+
+        //
+        // The tags of the file complete the ones of the sheet for the whole album, its own title and gain are the ones of the whole file
+        let mut file_metadata = file_information.m_metadata.clone();
+        file_metadata.m_title.clear();
+        file_metadata.m_track_number = None;
+        file_metadata.m_track_total = None;
+        file_metadata.m_replay_gain.m_track = None;
+        file_metadata.m_extra.clear();
+        let mut metadata = cue_sheet.get_track_metadata(track);
+        metadata.fill_missing(file_metadata);
+
+        let mut information = AudioInformation::new();
+        information.m_comments = metadata.m_extra.remove("COMMENT").unwrap_or_default();
+        information.m_metadata = metadata;
+        information.m_rate = file_information.m_rate;
+        information.m_channel_count = file_information.m_channel_count;
+        information.m_bits_per_sample = file_information.m_bits_per_sample;
+        information.m_total_samples = match end_sample
+        {
+            u64::MAX => 0,
+            _ => end_sample - start_sample,
+        };
+        information.m_bitrate = file_information.m_bitrate;
+        information.m_codec = file_information.m_codec.clone();
+        information.m_is_lossless = file_information.m_is_lossless;
+        information.m_pictures = file_information.m_pictures.clone();
+
+        cue_tracks.push(CueTrack
+        {
+            m_str_path_to_music: str_path_to_music.clone(),
+            m_track: VirtualTrack
+            {
+                m_track_number: track.m_track_number,
+                m_start_sample: start_sample,
+                m_end_sample: end_sample,
+                m_index_points: index_points,
+                m_isrc: track.m_isrc.clone(),
+                m_has_pre_emphasis: track.m_flags.iter().any(|flag| flag == "PRE"),
+            },
+            m_information: information,
+        });
+    }
+
+    return Ok(cue_tracks);
+}
+
+/// Split a file into the tracks of its embedded cuesheet, listed like the tracks of a cue sheet.
+/// The tags of the file are the ones of the album, its title and its track gain are the ones of the whole file.
+///
+/// # Params
+/// str_path_to_music: the path of the file
+/// file_information: the information of the file, with the tracks of its cuesheet
+///
+/// # Return
+/// The tracks of the file, empty if it has no cuesheet
+pub fn split_embedded_tracks(str_path_to_music: &str, file_information: &AudioInformation) -> Vec<CueTrack>
+{
+    let mut cue_tracks: Vec<CueTrack> = Vec::new();
+    for track in file_information.m_tracks.iter()
+    {
+        let mut metadata = file_information.m_metadata.clone();
+        metadata.m_title.clear();
+        metadata.m_track_number = Some(track.m_track_number as u32);
+        metadata.m_track_total = Some(file_information.m_tracks.len() as u32);
+        metadata.m_replay_gain.m_track = None;
+
+        let mut information = AudioInformation::new();
+        information.m_metadata = metadata;
+        information.m_rate = file_information.m_rate;
+        information.m_channel_count = file_information.m_channel_count;
+        information.m_bits_per_sample = file_information.m_bits_per_sample;
+        information.m_total_samples = track.m_end_sample.saturating_sub(track.m_start_sample);
+        information.m_bitrate = file_information.m_bitrate;
+        information.m_codec = file_information.m_codec.clone();
+        information.m_is_lossless = file_information.m_is_lossless;
+        information.m_pictures = file_information.m_pictures.clone();
+        information.m_comments = file_information.m_comments.clone();
+
+        cue_tracks.push(CueTrack
+        {
+            m_str_path_to_music: str_path_to_music.to_string(),
+            m_track: track.clone(),
+            m_information: information,
+        });
+    }
+
+    return cue_tracks;
+}
+
+/// Decode the samples of a track of a cue sheet, from its first sample to its end
+pub struct CueTrackDecoder
+{
+    m_decoder: FileDecoder,
+
+    /// Index of the sample following the last sample of the track
+    m_end_sample: u64,
+}
+
+impl CueTrackDecoder
+{
+    /// Open the file of a track and move to the start of the track
+    ///
+    /// # Params
+    /// registry: the registry choosing the decoder of the file
+    /// cue_track: the track to decode
+    pub fn open(registry: &AudioReaderRegistry, cue_track: &CueTrack) -> Result<CueTrackDecoder, AudioReaderError>
+    {
+        let mut decoder = registry.open_decoder(cue_track.m_str_path_to_music.clone())?;
+        let start_sample = cue_track.m_track.m_start_sample;
+        if start_sample != 0
+        {
+            decoder.seek(start_sample)?;
+        }
+
+        return Ok(CueTrackDecoder
+        {
+            m_decoder: decoder,
+            m_end_sample: cue_track.m_track.m_end_sample,
+        });
+    }
+
+    /// Apply the gain of the track to the samples decoded from now on
+    ///
+    /// # Params
+    /// replay_gain: the gain chosen by the settings of the player, None to decode the samples unchanged
+    pub fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        self.m_decoder.set_replay_gain(replay_gain);
+    }
+
+    /// Decode the next samples of the track
+    ///
+    /// # Return
+    /// The samples, or None when all the samples of the track have been decoded
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        let mut decoded_block = match self.m_decoder.read_next_block()?
+        {
+            None => return Ok(None),
+            Some(decoded_block) => decoded_block,
+        };
+
+        //
+        // Cut the block at the end of the track
+        if decoded_block.m_first_sample >= self.m_end_sample
+        {
+            return Ok(None);
+        }
+        let kept_sample_count = (self.m_end_sample - decoded_block.m_first_sample).saturating_mul(decoded_block.m_channel_count as u64);
+        if (decoded_block.m_samples.len() as u64) > kept_sample_count
+        {
+            decoded_block.m_samples.truncate(kept_sample_count as usize);
+        }
+
+        return Ok(Some(decoded_block));
+    }
+
+    /// Decode all the remaining samples of the track
+    ///
+    /// # Return
+    /// The interleaved samples of all the channels
+    pub fn read_all_samples(&mut self) -> Result<Vec<i32>, AudioReaderError>
+    {
+        let mut samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = self.read_next_block()?
+        {
+            samples.extend_from_slice(&decoded_block.m_samples);
+        }
+
+        return Ok(samples);
+    }
+}
+
+#[cfg(test)]
+mod test_cue_sheet
+{
+    use crate::audio_reader::TagDate;
+    use crate::audio_reader::cue_sheet::{decode_cue_sheet_text, read_cue_tracks, CueSheet, CueSheetIndex, CueTrackDecoder};
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::music_player::MusicPlayer;
+    use crate::audio_reader::reader_registry::AudioReaderRegistry;
+    use crate::audio_reader::replay_gain::ReplayGain;
+
+    const CUE_SHEET: &str = "REM GENRE Rock\r
+REM DATE 1994\r
+REM DISCID 8A0B2C0D\r
+REM COMMENT \"ExactAudioCopy v1.6\"\r
+REM REPLAYGAIN_ALBUM_GAIN -3.21 dB\r
+REM REPLAYGAIN_ALBUM_PEAK 0.988831\r
+PERFORMER \"The Band\"\r
+TITLE \"The Album\"\r
+FILE \"The Album.wav\" WAVE\r
+  TRACK 01 AUDIO\r
+    TITLE \"First Song\"\r
+    FLAGS DCP PRE\r
+    ISRC GBAYE0000351\r
+    REM REPLAYGAIN_TRACK_GAIN -2.50 dB\r
+    INDEX 01 00:00:00\r
+  TRACK 02 AUDIO\r
+    TITLE Second Song\r
+    PERFORMER \"Guest Singer\"\r
+    PREGAP 00:02:00\r
+    INDEX 00 04:58:30\r
+    INDEX 01 05:00:00\r
+    INDEX 02 05:10:74\r
+";
+
+    /// Encode a flac file of 44.1 kHz mono with a ramp of samples, each sample giving its position
+    fn create_flac_file(str_path: &str, sample_count: usize, vorbis_comments: &[&str])
+    {
+        let mut settings = FlacEncoderSettings::new(44100, 1, 16);
+        settings.m_vorbis_comments = vorbis_comments.iter().map(|comment| comment.to_string()).collect();
+        let samples: Vec<i32> = (0..sample_count).map(|sample| (sample % 30000) as i32).collect();
+        let mut encoder = FlacEncoder::create(str_path.to_string(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn parse_commands()
+    {
+        let cue_sheet = CueSheet::parse(CUE_SHEET).unwrap();
+        assert_eq!(cue_sheet.m_title, "The Album");
+        assert_eq!(cue_sheet.m_performer, "The Band");
+        assert_eq!(cue_sheet.m_files.len(), 1);
+        assert_eq!(cue_sheet.m_files[0].m_path, "The Album.wav");
+        assert_eq!(cue_sheet.m_files[0].m_file_type, "WAVE");
+        assert_eq!(cue_sheet.m_remarks["COMMENT"], vec!["ExactAudioCopy v1.6"]);
+        assert_eq!(cue_sheet.m_tracks.len(), 2);
+
+        let first_track = &cue_sheet.m_tracks[0];
+        assert_eq!(first_track.m_flags, vec!["DCP", "PRE"]);
+        assert_eq!(first_track.m_isrc, "GBAYE0000351");
+        let second_track = &cue_sheet.m_tracks[1];
+        assert_eq!(second_track.m_title, "Second Song");
+        assert_eq!(second_track.m_indexes, vec![
+            CueSheetIndex { m_index_number: 0, m_file_index: 0, m_frame: (4 * 60 + 58) * 75 + 30 },
+            CueSheetIndex { m_index_number: 1, m_file_index: 0, m_frame: 5 * 60 * 75 },
+            CueSheetIndex { m_index_number: 2, m_file_index: 0, m_frame: (5 * 60 + 10) * 75 + 74 },
+        ]);
+
+        //
+        // The fields of the album complete the ones of each track
+        let metadata = cue_sheet.get_track_metadata(first_track);
+        assert_eq!(metadata.m_title, "First Song");
+        assert_eq!(metadata.m_artists, vec!["The Band"]);
+        assert_eq!(metadata.m_album, "The Album");
+        assert_eq!(metadata.m_album_artists, vec!["The Band"]);
+        assert_eq!(metadata.m_genres, vec!["Rock"]);
+        assert_eq!(metadata.m_date, TagDate::parse("1994"));
+        assert_eq!((metadata.m_track_number, metadata.m_track_total), (Some(1), Some(2)));
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: -2.5, m_peak: None }));
+        assert_eq!(metadata.m_replay_gain.m_album, Some(ReplayGain { m_gain: -3.21, m_peak: Some(0.988831) }));
+        assert_eq!(metadata.m_extra.keys().collect::<Vec<&String>>(), vec!["COMMENT", "DISCID"]);
+        let metadata = cue_sheet.get_track_metadata(second_track);
+        assert_eq!(metadata.m_artists, vec!["Guest Singer"]);
+        assert_eq!(metadata.m_replay_gain.m_track, None);
+    }
+
+    #[test]
+    fn reject_invalid_commands()
+    {
+        assert!(CueSheet::parse("TRACK 01 AUDIO\nINDEX 01 00:00:00").is_err());
+        assert!(CueSheet::parse("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:60:00").is_err());
+        assert!(CueSheet::parse("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:75").is_err());
+        assert!(CueSheet::parse("FILE a.wav WAVE\nTRACK one AUDIO").is_err());
+        assert!(CueSheet::parse("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 999999999999999999:00:00").is_err());
+
+        //
+        // The unknown commands and the empty lines are ignored, the FILE path can be written without quotes
+        let cue_sheet = CueSheet::parse("\nCDTEXTFILE \"a.cdt\"\nFILE my file.flac WAVE\n\n  track 01 audio\n  UNKNOWN x\n  index 01 01:02:03").unwrap();
+        assert_eq!(cue_sheet.m_files[0].m_path, "my file.flac");
+        assert_eq!(cue_sheet.m_tracks[0].m_indexes[0].m_frame, (60 + 2) * 75 + 3);
+    }
+
+    #[test]
+    fn decode_charsets()
+    {
+        let text = "PERFORMER \"Кино\"\nTITLE \"Группа крови\"\nFILE \"Кино - Группа крови.flac\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"Группа крови на рукаве\"\n";
+        assert_eq!(decode_cue_sheet_text(text.as_bytes()), text);
+        assert_eq!(decode_cue_sheet_text(&[&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat()), text);
+        let utf16: Vec<u8> = [0xFFu8, 0xFE].iter().copied().chain(text.encode_utf16().flat_map(|unit| unit.to_le_bytes())).collect();
+        assert_eq!(decode_cue_sheet_text(&utf16), text);
+
+        //
+        // The legacy charsets have no marker, they are guessed from the text
+        let (windows_1251, _, _) = encoding_rs::WINDOWS_1251.encode(text);
+        assert_eq!(decode_cue_sheet_text(&windows_1251), text);
+        let text = "PERFORMER \"Café Noël\"\nTITLE \"Les Années Passées\"\n";
+        let (windows_1252, _, _) = encoding_rs::WINDOWS_1252.encode(text);
+        assert_eq!(decode_cue_sheet_text(&windows_1252), text);
+    }
+
+    #[test]
+    fn split_single_file_into_tracks()
+    {
+        let directory = std::env::temp_dir().join("quadrium_cue_single");
+        std::fs::create_dir_all(&directory).unwrap();
+        create_flac_file(&directory.join("The Album.flac").to_string_lossy(), 44100 * 3, &["ALBUM=Tag Album", "DATE=2001-05-04", "TITLE=Whole File"]);
+        let cue_sheet = "PERFORMER \"The Band\"\nFILE \"The Album.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"One\"\n    INDEX 01 00:00:00\n\
+            TRACK 02 AUDIO\n    TITLE \"Two\"\n    INDEX 00 00:01:00\n    INDEX 01 00:01:37\n  TRACK 03 AUDIO\n    INDEX 01 00:02:00\n";
+        let cue_path = directory.join("The Album.cue").to_string_lossy().to_string();
+        std::fs::write(&cue_path, cue_sheet).unwrap();
+
+        //
+        // The sheet names the wav file the disc was ripped to, the flac file with the same name is used
+        let registry = AudioReaderRegistry::with_default_readers();
+        let cue_tracks = read_cue_tracks(&registry, &cue_path).unwrap();
+        assert_eq!(cue_tracks.len(), 3);
+        assert!(cue_tracks.iter().all(|cue_track| cue_track.m_str_path_to_music.ends_with("The Album.flac")));
+        let positions: Vec<(u64, u64)> = cue_tracks.iter().map(|cue_track| (cue_track.m_track.m_start_sample, cue_track.m_track.m_end_sample)).collect();
+        assert_eq!(positions, vec![(0, 44100 + 37 * 588), (44100 + 37 * 588, 88200), (88200, 132300)]);
+        assert_eq!(cue_tracks[1].m_track.m_index_points.iter().map(|index_point| index_point.m_sample).collect::<Vec<u64>>(), vec![44100, 44100 + 37 * 588]);
+        assert_eq!(cue_tracks[2].m_information.m_total_samples, 44100);
+        assert_eq!(cue_tracks[2].m_information.m_rate, 44100);
+
+        //
+        // The tags of the file complete the album fields, not the fields of the tracks
+        let metadata = &cue_tracks[1].m_information.m_metadata;
+        assert_eq!(metadata.m_title, "Two");
+        assert_eq!(metadata.m_album, "Tag Album");
+        assert_eq!(metadata.m_date, TagDate::parse("2001-05-04"));
+        assert_eq!(cue_tracks[2].m_information.m_metadata.m_title, "");
+
+        //
+        // The decoder gives exactly the samples of the track
+        let samples = CueTrackDecoder::open(&registry, &cue_tracks[1]).unwrap().read_all_samples().unwrap();
+        let expected: Vec<i32> = (44100u64 + 37 * 588..88200).map(|sample| (sample % 30000) as i32).collect();
+        assert_eq!(samples, expected);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn split_several_files_into_tracks()
+    {
+        //
+        // The pre-gap of the second track is at the end of the first file, the second track starts the second file
+        let directory = std::env::temp_dir().join("quadrium_cue_several");
+        std::fs::create_dir_all(&directory).unwrap();
+        create_flac_file(&directory.join("01.flac").to_string_lossy(), 44100 * 2, &[]);
+        create_flac_file(&directory.join("02.flac").to_string_lossy(), 44100 * 2, &[]);
+        let cue_sheet = "FILE \"01.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:01:00\n\
+            FILE \"02.flac\" WAVE\n    INDEX 01 00:00:00\n  TRACK 03 AUDIO\n    INDEX 01 00:01:00\n  TRACK 04 MODE1/2352\n    INDEX 01 00:01:50\n";
+        let cue_path = directory.join("album.cue").to_string_lossy().to_string();
+        std::fs::write(&cue_path, cue_sheet).unwrap();
+
+        let registry = AudioReaderRegistry::with_default_readers();
+        let cue_tracks = read_cue_tracks(&registry, &cue_path).unwrap();
+        let tracks: Vec<(String, u64, u64)> = cue_tracks.iter()
+            .map(|cue_track| (cue_track.m_str_path_to_music.rsplit('/').next().unwrap().to_string(), cue_track.m_track.m_start_sample, cue_track.m_track.m_end_sample))
+            .collect();
+
+        //
+        // The data track ends the third track and is not played
+        assert_eq!(tracks, vec![
+            ("01.flac".to_string(), 0, 88200),
+            ("02.flac".to_string(), 0, 44100),
+            ("02.flac".to_string(), 44100, 44100 + 50 * 588),
+        ]);
+        assert_eq!(cue_tracks[1].m_track.m_index_points.len(), 1);
+        assert_eq!(cue_tracks[2].m_information.m_metadata.m_track_total, Some(3));
+
+        let samples = CueTrackDecoder::open(&registry, &cue_tracks[2]).unwrap().read_all_samples().unwrap();
+        assert_eq!(samples.len(), 50 * 588);
+        assert_eq!(samples[0], 44100 % 30000);
+
+        //
+        // A data track placed after the end of the file does not extend the previous track past the end
+        std::fs::write(&cue_path, cue_sheet.replace("00:01:50", "00:05:00")).unwrap();
+        let cue_tracks = read_cue_tracks(&registry, &cue_path).unwrap();
+        assert_eq!((cue_tracks[2].m_track.m_start_sample, cue_tracks[2].m_track.m_end_sample), (44100, 88200));
+        assert_eq!(cue_tracks[2].m_information.m_total_samples, 44100);
+        assert_eq!(CueTrackDecoder::open(&registry, &cue_tracks[2]).unwrap().read_all_samples().unwrap().len(), 44100);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn play_track_with_its_gain()
+    {
+        let directory = std::env::temp_dir().join("quadrium_cue_play");
+        std::fs::create_dir_all(&directory).unwrap();
+        create_flac_file(&directory.join("album.flac").to_string_lossy(), 44100 * 2, &["REPLAYGAIN_TRACK_GAIN=+6.00 dB"]);
+        let cue_sheet = "FILE \"album.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    REM REPLAYGAIN_TRACK_GAIN -6.02 dB\n    INDEX 01 00:01:00\n";
+        let cue_path = directory.join("album.cue").to_string_lossy().to_string();
+        std::fs::write(&cue_path, cue_sheet).unwrap();
+
+        //
+        // The gain of the track in the sheet is applied, not the one of the whole file
+        let mut music_player = MusicPlayer::new();
+        let replay_gain_scale = music_player.play(&cue_path, Some(2), false).unwrap().m_replay_gain_scale;
+        assert!((replay_gain_scale - 10f64.powf(-6.02 / 20.0)).abs() < 1e-6);
+        let expected: Vec<i32> = (44100u64..88200).map(|sample| ((sample % 30000) as f64 * replay_gain_scale).round() as i32).collect();
+        let mut decoded_samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = music_player.read_next_block().unwrap()
+        {
+            decoded_samples.extend(decoded_block.m_samples);
+        }
+        assert_eq!(decoded_samples, expected);
+
+        assert!(music_player.play(&directory.join("album.flac").to_string_lossy(), None, false).unwrap().m_replay_gain_scale > 1.9);
+        assert!(music_player.play(&cue_path, Some(3), false).is_err());
+        assert!(music_player.play(&cue_path, None, false).is_err());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,922 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use crate::audio_reader::AudioReaderError;
+use crate::audio_reader::flac_reader::{update_md5, FIXED_PREDICTOR_COEFFICIENTS};
+use crate::audio_reader::flac_writer::{create_vorbis_comment_block, write_metadata_block, DEFAULT_PADDING_SIZE, DEFAULT_VENDOR_STRING};
+use crate::utils::bit_writer::BitWriter;
+use crate::utils::crc::{compute_crc16, compute_crc8};
+
+/// Compression level used when no level is given, the same as the reference encoder
+pub const DEFAULT_COMPRESSION_LEVEL: u8 = 5;
+
+/// Number of seconds of audio between two points of the seek table
+const SEEK_POINT_INTERVAL: u64 = 10;
+
+/// Highest Rice parameter of the 4 bits coding method, the value 15 is the escape code
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Highest Rice parameter of the 5 bits coding method, the value 31 is the escape code
+const MAX_RICE2_PARAMETER: u32 = 30;
+
+/// Type of the metadata blocks written by the encoder
+const BLOCK_TYPE_STREAMINFO: u8 = 0;
+const BLOCK_TYPE_PADDING: u8 = 1;
+const BLOCK_TYPE_SEEKTABLE: u8 = 3;
+const BLOCK_TYPE_VORBIS_COMMENT: u8 = 4;
+
+/// Sample number of the placeholder seek points, which do not reference any frame
+const PLACEHOLDER_SEEK_POINT: u64 = u64::MAX;
+
+/// Settings of the encoding of a compression level
+struct CompressionParameters
+{
+    /// Number of samples per channel of each frame
+    m_block_size: usize,
+
+    /// Highest order of the LPC predictors tried, 0 to use only the fixed predictors
+    m_max_lpc_order: usize,
+
+    /// Highest number of partitions of the residual, as a power of 2
+    m_max_partition_order: u32,
+
+    /// True to try to code stereo frames with a side channel
+    m_use_stereo_decorrelation: bool,
+
+    /// True to encode all the LPC orders and keep the smallest, false to choose the order from an estimation
+    m_is_exhaustive_lpc_search: bool,
+}
+
+/// Parameters of the compression levels 0 (fastest) to 8 (smallest), close to the ones of the reference encoder
+const COMPRESSION_LEVELS: [CompressionParameters; 9] = [
+    CompressionParameters { m_block_size: 1152, m_max_lpc_order: 0, m_max_partition_order: 3, m_use_stereo_decorrelation: false, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 1152, m_max_lpc_order: 0, m_max_partition_order: 3, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 1152, m_max_lpc_order: 0, m_max_partition_order: 4, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 6, m_max_partition_order: 4, m_use_stereo_decorrelation: false, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 8, m_max_partition_order: 4, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 8, m_max_partition_order: 5, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 8, m_max_partition_order: 6, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 12, m_max_partition_order: 6, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: false },
+    CompressionParameters { m_block_size: 4096, m_max_lpc_order: 12, m_max_partition_order: 6, m_use_stereo_decorrelation: true, m_is_exhaustive_lpc_search: true },
+];
+
+/// Description of the audio given to the encoder
+pub struct FlacEncoderSettings
+{
+    pub m_rate: u32,
+    pub m_channel_count: u8,
+
+    /// From 4 to 32 bits
+    pub m_bits_per_sample: u8,
+
+    /// Number of samples per channel which will be encoded, used to reserve the seek table.
+    /// 0 if unknown, then the file has no seek table.
+    pub m_total_samples: u64,
+
+    /// From 0 (fastest) to 8 (smallest)
+    pub m_compression_level: u8,
+
+    /// The comments written in the file, each one written "FIELD=value"
+    pub m_vorbis_comments: Vec<String>,
+}
+
+impl FlacEncoderSettings
+{
+    /// Create the settings of an audio stream with the default compression level and no comment
+    pub fn new(rate: u32, channel_count: u8, bits_per_sample: u8) -> FlacEncoderSettings
+    {
+        return FlacEncoderSettings
+        {
+            m_rate: rate,
+            m_channel_count: channel_count,
+            m_bits_per_sample: bits_per_sample,
+            m_total_samples: 0,
+            m_compression_level: DEFAULT_COMPRESSION_LEVEL,
+            m_vorbis_comments: Vec::new(),
+        };
+    }
+}
+
+/// Seek point found while encoding the frames
+struct EncodedSeekPoint
+{
+    m_sample_number: u64,
+
+    /// Position of the frame from the first frame
+    m_frame_offset: u64,
+    m_sample_count: u16,
+}
+
+/// Encoder of interleaved samples to a flac stream.
+/// The metadata blocks are written first with a STREAMINFO to complete, which is rewritten when the encoding is finished
+/// with the MD5 of the audio, like the seek table.
+///
+/// # How to use it
+/// ```
+/// let mut encoder = FlacEncoder::create("path/to/file.flac".to_string(), FlacEncoderSettings::new(44100, 2, 16))?;
+/// encoder.write_samples(&interleaved_samples)?;
+/// encoder.finish()?;
+/// ```
+pub struct FlacEncoder<W: Write + Seek>
+{
+    m_output: W,
+    m_settings: FlacEncoderSettings,
+    m_parameters: &'static CompressionParameters,
+
+    /// Samples waiting for a whole block, interleaved
+    m_pending_samples: Vec<i32>,
+
+    m_frame_number: u64,
+    m_sample_count: u64,
+    m_md5_context: md5::Context,
+    m_min_frame_size: u32,
+    m_max_frame_size: u32,
+
+    /// Position in the output of the content of the STREAMINFO block
+    m_streaminfo_offset: u64,
+
+    /// Position in the output of the content of the SEEKTABLE block and number of points reserved
+    m_seek_table_offset: u64,
+    m_reserved_seek_point_count: usize,
+    m_seek_points: Vec<EncodedSeekPoint>,
+
+    /// Sample targeted by the next seek point
+    m_next_seek_target: u64,
+
+    /// Position in the output of the first frame, and of the next one
+    m_first_frame_offset: u64,
+    m_output_offset: u64,
+}
+
+/// Write a number coded like an UTF-8 character, used for the frame number
+fn write_utf8_coded_number(writer: &mut BitWriter, value: u64)
+{
+    if value < 0x80
+    {
+        writer.write_bits(value as u32, 8);
+        return;
+    }
+
+    //
+    // Each following byte holds 6 bits, the first byte holds the rest after its prefix
+    let mut byte_count: u32 = 2;
+    while byte_count < 7 && value >= 1u64 << (5 * byte_count + 1)
+    {
+        byte_count += 1;
+    }
+    let prefix: u32 = (0xFF00u32 >> byte_count) & 0xFF;
+    writer.write_bits(prefix | (value >> (6 * (byte_count - 1))) as u32, 8);
+    for byte_index in (0..byte_count - 1).rev()
+    {
+        writer.write_bits(0x80 | ((value >> (6 * byte_index)) & 0x3F) as u32, 8);
+    }
+}
+
+/// Fold a signed value to an unsigned one: the positive values become even, the negative ones odd
+fn fold_residual(value: i64) -> u64
+{
+    return ((value << 1) ^ (value >> 63)) as u64;
+}
+
+/// Compute the residual of a predictor
+///
+/// # Params
+/// samples: the samples of the subframe
+/// coefficients: the coefficients of the predictor, the first one is applied on the previous sample
+/// shift: the shift applied to the prediction
+///
+/// # Return
+/// The residual of the samples following the warm-up samples, None if a value does not fit on 32 bits
+fn compute_residual(samples: &[i64], coefficients: &[i64], shift: u32) -> Option<Vec<i64>>
+{
+    let order = coefficients.len();
+    let mut residual: Vec<i64> = Vec::with_capacity(samples.len().saturating_sub(order));
+    for i in order..samples.len()
+    {
+        let mut prediction: i64 = 0;
+        for (j, coefficient) in coefficients.iter().enumerate()
+        {
+            prediction += coefficient * samples[i - 1 - j];
+        }
+        let value = samples[i] - (prediction >> shift);
+        if value < i32::MIN as i64 || value > i32::MAX as i64
+        {
+            return None;
+        }
+        residual.push(value);
+    }
+    return Some(residual);
+}
+
+/// Get the Rice parameter minimizing the size of a partition and this size in bits
+///
+/// # Params
+/// sample_count: the number of values of the partition
+/// folded_sum: the sum of the folded values of the partition
+fn choose_rice_parameter(sample_count: u64, folded_sum: u64) -> (u32, u64)
+{
+    let mut best_parameter: u32 = 0;
+    let mut best_size = u64::MAX;
+    for parameter in 0..=MAX_RICE2_PARAMETER
+    {
+        let size = sample_count * (parameter as u64 + 1) + (folded_sum >> parameter);
+        if size < best_size
+        {
+            best_parameter = parameter;
+            best_size = size;
+        }
+    }
+    return (best_parameter, best_size);
+}
+
+/// Choose the number of partitions and the Rice parameter of each partition of a residual
+///
+/// # Return
+/// The partition order and the Rice parameters of the partitions
+fn choose_rice_partitions(residual: &[i64], block_size: usize, predictor_order: usize, max_partition_order: u32) -> (u32, Vec<u32>)
+{
+    //
+    // The block size must be divisible by the number of partitions, and the first partition must hold the warm-up samples
+    let mut partition_order = max_partition_order;
+    while partition_order > 0 && (block_size % (1 << partition_order) != 0 || (block_size >> partition_order) <= predictor_order)
+    {
+        partition_order -= 1;
+    }
+
+    //
+    // Sum the folded values of the smallest partitions, the bigger ones are the sums of two smaller ones
+    let partition_size = block_size >> partition_order;
+    let mut sums: Vec<(u64, u64)> = Vec::with_capacity(1 << partition_order);
+    for partition_index in 0..(1usize << partition_order)
+    {
+        let start = (partition_index * partition_size).max(predictor_order) - predictor_order;
+        let end = (partition_index + 1) * partition_size - predictor_order;
+        let folded_sum: u64 = residual[start..end].iter().map(|value| fold_residual(*value)).sum();
+        sums.push(((end - start) as u64, folded_sum));
+    }
+
+    let mut best_partition_order = 0;
+    let mut best_parameters: Vec<u32> = Vec::new();
+    let mut best_size = u64::MAX;
+    loop
+    {
+        let mut parameters: Vec<u32> = Vec::with_capacity(sums.len());
+        let mut size: u64 = 0;
+        for (sample_count, folded_sum) in sums.iter()
+        {
+            let (parameter, partition_size) = choose_rice_parameter(*sample_count, *folded_sum);
+            parameters.push(parameter);
+            size += partition_size;
+        }
+        let parameter_bits = if parameters.iter().any(|parameter| *parameter > MAX_RICE_PARAMETER) { 5 } else { 4 };
+        size += parameter_bits * parameters.len() as u64;
+        if size < best_size
+        {
+            best_partition_order = partition_order;
+            best_parameters = parameters;
+            best_size = size;
+        }
+
+        if partition_order == 0
+        {
+            break;
+        }
+        partition_order -= 1;
+        sums = sums.chunks(2).map(|pair| (pair[0].0 + pair[1].0, pair[0].1 + pair[1].1)).collect();
+    }
+
+    return (best_partition_order, best_parameters);
+}
+
+/// Write a residual coded with partitioned Rice codes
+fn write_residual(writer: &mut BitWriter, residual: &[i64], block_size: usize, predictor_order: usize, max_partition_order: u32)
+{
+    let (partition_order, parameters) = choose_rice_partitions(residual, block_size, predictor_order, max_partition_order);
+    let is_rice2 = parameters.iter().any(|parameter| *parameter > MAX_RICE_PARAMETER);
+    writer.write_bits(if is_rice2 { 1 } else { 0 }, 2);
+    writer.write_bits(partition_order, 4);
+
+    let partition_size = block_size >> partition_order;
+    let mut position = 0;
+    for (partition_index, parameter) in parameters.iter().enumerate()
+    {
+        writer.write_bits(*parameter, if is_rice2 { 5 } else { 4 });
+        let sample_count = if partition_index == 0 { partition_size - predictor_order } else { partition_size };
+        for value in residual[position..position + sample_count].iter()
+        {
+            let folded_value = fold_residual(*value);
+            writer.write_unary((folded_value >> parameter) as u32);
+            writer.write_bits_u64(folded_value & ((1u64 << parameter) - 1), *parameter);
+        }
+        position += sample_count;
+    }
+}
+
+/// Write a FIXED or LPC subframe from its residual
+fn write_predicted_subframe(samples: &[i64], bits_per_sample: u32, wasted_bits: u32, subframe_type: u32, predictor_order: usize,
+                            lpc_coefficients: Option<(&[i64], u32, u32)>, residual: &[i64], max_partition_order: u32) -> BitWriter
+{
+    let mut writer = BitWriter::new();
+    write_subframe_header(&mut writer, subframe_type, wasted_bits);
+    for warm_up_sample in samples[..predictor_order].iter()
+    {
+        writer.write_signed_bits(*warm_up_sample, bits_per_sample);
+    }
+    if let Some((coefficients, precision, shift)) = lpc_coefficients
+    {
+        writer.write_bits(precision - 1, 4);
+        writer.write_signed_bits(shift as i64, 5);
+        for coefficient in coefficients
+        {
+            writer.write_signed_bits(*coefficient, precision);
+        }
+    }
+    write_residual(&mut writer, residual, samples.len(), predictor_order, max_partition_order);
+    return writer;
+}
+
+fn write_subframe_header(writer: &mut BitWriter, subframe_type: u32, wasted_bits: u32)
+{
+    writer.write_bits(0, 1);
+    writer.write_bits(subframe_type, 6);
+    if wasted_bits > 0
+    {
+        writer.write_bits(1, 1);
+        writer.write_unary(wasted_bits - 1);
+    }
+    else
+    {
+        writer.write_bits(0, 1);
+    }
+}
+
+/// Get the precision of the quantized LPC coefficients, depending on the block size like the reference encoder
+fn get_lpc_precision(block_size: usize) -> u32
+{
+    return match block_size
+    {
+        0..=192 => 7,
+        193..=384 => 8,
+        385..=576 => 9,
+        577..=1152 => 10,
+        1153..=2304 => 11,
+        2305..=4608 => 12,
+        _ => 13,
+    };
+}
+
+/// Compute the LPC coefficients of all the orders up to max_order with the Levinson-Durbin recursion
+///
+/// # Return
+/// For each order from 1 to max_order, the coefficients and the prediction error
+fn compute_lpc_coefficients(samples: &[i64], max_order: usize) -> Vec<(Vec<f64>, f64)>
+{
+    //
+    // Apply a Tukey window (p = 0.5) to reduce the effect of the edges of the block
+    let sample_count = samples.len();
+    let taper_size = sample_count / 4;
+    let windowed_samples: Vec<f64> = samples.iter().enumerate().map(|(i, sample)|
+    {
+        let distance_to_edge = i.min(sample_count - 1 - i);
+        let weight = if distance_to_edge < taper_size
+        {
+            0.5 - 0.5 * (std::f64::consts::PI * distance_to_edge as f64 / taper_size as f64).cos()
+        }
+        else
+        {
+            1.0
+        };
+        return *sample as f64 * weight;
+    }).collect();
+
+    let mut autocorrelation: Vec<f64> = vec![0.0; max_order + 1];
+    for lag in 0..=max_order
+    {
+        autocorrelation[lag] = windowed_samples[lag..].iter().zip(windowed_samples.iter()).map(|(a, b)| a * b).sum();
+    }
+
+    let mut orders: Vec<(Vec<f64>, f64)> = Vec::with_capacity(max_order);
+    if autocorrelation[0] <= 0.0
+    {
+        return orders;
+    }
+    let mut coefficients: Vec<f64> = Vec::with_capacity(max_order);
+    let mut error = autocorrelation[0];
+    for order in 1..=max_order
+    {
+        let mut reflection = autocorrelation[order];
+        for (j, coefficient) in coefficients.iter().enumerate()
+        {
+            reflection -= coefficient * autocorrelation[order - 1 - j];
+        }
+        reflection /= error;
+
+        let previous_coefficients = coefficients.clone();
+        for j in 0..coefficients.len()
+        {
+            coefficients[j] -= reflection * previous_coefficients[order - 2 - j];
+        }
+        coefficients.push(reflection);
+        error *= 1.0 - reflection * reflection;
+        if error <= 0.0
+        {
+            break;
+        }
+        orders.push((coefficients.clone(), error));
+    }
+    return orders;
+}
+
+/// Quantize the LPC coefficients to integers on precision bits
+///
+/// # Return
+/// The quantized coefficients and the shift, None if the coefficients cannot be quantized
+fn quantize_lpc_coefficients(coefficients: &[f64], precision: u32) -> Option<(Vec<i64>, u32)>
+{
+    let max_coefficient = coefficients.iter().fold(0.0f64, |max, coefficient| max.max(coefficient.abs()));
+    if max_coefficient <= 0.0 || !max_coefficient.is_finite()
+    {
+        return None;
+    }
+
+    let shift = precision as i32 - 2 - max_coefficient.log2().floor() as i32;
+    if shift < 0
+    {
+        return None;
+    }
+    let shift = shift.min(15) as u32;
+
+    //
+    // Keep the rounding error to compensate it on the next coefficient
+    let max_value = (1i64 << (precision - 1)) - 1;
+    let min_value = -(1i64 << (precision - 1));
+    let mut error = 0.0;
+    let mut quantized_coefficients: Vec<i64> = Vec::with_capacity(coefficients.len());
+    for coefficient in coefficients
+    {
+        error += coefficient * (1u32 << shift) as f64;
+        let quantized_coefficient = (error.round() as i64).max(min_value).min(max_value);
+        error -= quantized_coefficient as f64;
+        quantized_coefficients.push(quantized_coefficient);
+    }
+    return Some((quantized_coefficients, shift));
+}
+
+/// Encode the samples of one channel of a frame with the smallest subframe type
+///
+/// # Params
+/// samples: the samples of the channel
+/// bits_per_sample: the bits per sample of the channel, one more than the stream for a side channel
+fn encode_subframe(samples: &[i64], bits_per_sample: u32, parameters: &CompressionParameters) -> BitWriter
+{
+    let block_size = samples.len();
+
+    //
+    // CONSTANT
+    if samples.iter().all(|sample| *sample == samples[0])
+    {
+        let mut writer = BitWriter::new();
+        write_subframe_header(&mut writer, 0, 0);
+        writer.write_signed_bits(samples[0], bits_per_sample);
+        return writer;
+    }
+
+    //
+    // Remove the low bits always at 0
+    let wasted_bits = samples.iter().fold(0i64, |bits, sample| bits | sample).trailing_zeros().min(bits_per_sample - 1);
+    let shifted_samples: Vec<i64>;
+    let samples = if wasted_bits > 0
+    {
+        shifted_samples = samples.iter().map(|sample| sample >> wasted_bits).collect();
+        &shifted_samples[..]
+    }
+    else
+    {
+        samples
+    };
+    let bits_per_sample = bits_per_sample - wasted_bits;
+
+    //
+    // VERBATIM is always possible
+    let mut best_writer = BitWriter::new();
+    write_subframe_header(&mut best_writer, 1, wasted_bits);
+    for sample in samples
+    {
+        best_writer.write_signed_bits(*sample, bits_per_sample);
+    }
+
+    //
+    // FIXED: keep the order with the smallest residual
+    let mut best_fixed: Option<(usize, Vec<i64>, u64)> = None;
+    for order in 0..=4usize.min(block_size - 1)
+    {
+        if let Some(residual) = compute_residual(samples, FIXED_PREDICTOR_COEFFICIENTS[order], 0)
+        {
+            let residual_sum: u64 = residual.iter().map(|value| value.unsigned_abs()).sum();
+            if best_fixed.as_ref().map_or(true, |(_order, _residual, best_sum)| residual_sum < *best_sum)
+            {
+                best_fixed = Some((order, residual, residual_sum));
+            }
+        }
+    }
+    if let Some((order, residual, _residual_sum)) = best_fixed
+    {
+        let writer = write_predicted_subframe(samples, bits_per_sample, wasted_bits, 8 + order as u32, order, None, &residual, parameters.m_max_partition_order);
+        if writer.get_bit_count() < best_writer.get_bit_count()
+        {
+            best_writer = writer;
+        }
+    }
+
+    //
+    // LPC: try all the orders, or the order with the smallest estimated size
+    let max_lpc_order = parameters.m_max_lpc_order.min(block_size - 1);
+    if max_lpc_order > 0
+    {
+        let precision = get_lpc_precision(block_size);
+        let lpc_orders = compute_lpc_coefficients(samples, max_lpc_order);
+        let mut candidate_orders: Vec<usize> = (1..=lpc_orders.len()).collect();
+        if !parameters.m_is_exhaustive_lpc_search && !lpc_orders.is_empty()
+        {
+            let estimate_size = |order: usize| -> f64
+            {
+                let residual_count = (block_size - order) as f64;
+                let bits_per_residual = (0.5 * (0.5 * lpc_orders[order - 1].1 / block_size as f64).log2()).max(0.0);
+                return residual_count * bits_per_residual + (order as u32 * (precision + bits_per_sample)) as f64;
+            };
+            let best_order = candidate_orders.iter().copied()
+                .min_by(|a, b| estimate_size(*a).partial_cmp(&estimate_size(*b)).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap_or(1);
+            candidate_orders = vec![best_order];
+        }
+
+        for order in candidate_orders
+        {
+            let (quantized_coefficients, shift) = match quantize_lpc_coefficients(&lpc_orders[order - 1].0, precision)
+            {
+                None => continue,
+                Some(quantization) => quantization,
+            };
+            if let Some(residual) = compute_residual(samples, &quantized_coefficients, shift)
+            {
+                let writer = write_predicted_subframe(samples, bits_per_sample, wasted_bits, 31 + order as u32, order,
+                                                      Some((&quantized_coefficients, precision, shift)), &residual, parameters.m_max_partition_order);
+                if writer.get_bit_count() < best_writer.get_bit_count()
+                {
+                    best_writer = writer;
+                }
+            }
+        }
+    }
+
+    return best_writer;
+}
+
+/// Create the content of a STREAMINFO block
+fn create_streaminfo_block(block_size: u32, min_frame_size: u32, max_frame_size: u32, rate: u32, channel_count: u8,
+                           bits_per_sample: u8, total_samples: u64, md5: u128) -> Vec<u8>
+{
+    let mut writer = BitWriter::new();
+    writer.write_bits(block_size, 16);
+    writer.write_bits(block_size, 16);
+    writer.write_bits(min_frame_size, 24);
+    writer.write_bits(max_frame_size, 24);
+    writer.write_bits(rate, 20);
+    writer.write_bits(channel_count as u32 - 1, 3);
+    writer.write_bits(bits_per_sample as u32 - 1, 5);
+
+    //
+    // A number of samples which does not fit on 36 bits is unknown
+    writer.write_bits_u64(if total_samples < 1 << 36 { total_samples } else { 0 }, 36);
+    writer.write_bits_u64((md5 >> 64) as u64, 64);
+    writer.write_bits_u64(md5 as u64, 64);
+    return writer.into_bytes();
+}
+
+/// Create the content of a SEEKTABLE block, completed with placeholder points
+fn create_seek_table_block(seek_points: &[EncodedSeekPoint], point_count: usize) -> Vec<u8>
+{
+    let mut data: Vec<u8> = Vec::with_capacity(point_count * 18);
+    for seek_point in seek_points.iter().take(point_count)
+    {
+        data.extend_from_slice(&seek_point.m_sample_number.to_be_bytes());
+        data.extend_from_slice(&seek_point.m_frame_offset.to_be_bytes());
+        data.extend_from_slice(&seek_point.m_sample_count.to_be_bytes());
+    }
+    for _i in seek_points.len()..point_count
+    {
+        data.extend_from_slice(&PLACEHOLDER_SEEK_POINT.to_be_bytes());
+        data.extend_from_slice(&[0; 10]);
+    }
+    return data;
+}
+
+impl FlacEncoder<File>
+{
+    /// Create a flac file and write its metadata blocks
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the created file
+    /// settings: the description of the audio and the compression level
+    pub fn create(str_path_to_music: String, settings: FlacEncoderSettings) -> Result<FlacEncoder<File>, AudioReaderError>
+    {
+        return FlacEncoder::new(File::create(str_path_to_music)?, settings);
+    }
+}
+
+impl<W: Write + Seek> FlacEncoder<W>
+{
+    /// Create an encoder writing a flac stream to an output, and write the metadata blocks
+    ///
+    /// # Params
+    /// output: where the stream is written, from its current position
+    /// settings: the description of the audio and the compression level
+    pub fn new(mut output: W, settings: FlacEncoderSettings) -> Result<FlacEncoder<W>, AudioReaderError>
+    {
+        if settings.m_bits_per_sample < 4 || settings.m_bits_per_sample > 32
+        {
+            return Err(AudioReaderError::InvalidValue("bits per sample"));
+        }
+        if settings.m_channel_count < 1 || settings.m_channel_count > 8
+        {
+            return Err(AudioReaderError::InvalidValue("channel count"));
+        }
+        if settings.m_rate < 1 || settings.m_rate > 0xFFFFF
+        {
+            return Err(AudioReaderError::InvalidValue("sample rate"));
+        }
+        let parameters = match COMPRESSION_LEVELS.get(settings.m_compression_level as usize)
+        {
+            None => return Err(AudioReaderError::InvalidValue("compression level")),
+            Some(parameters) => parameters,
+        };
+
+        //
+        // Reserve one seek point every SEEK_POINT_INTERVAL seconds
+        let seek_point_interval = settings.m_rate as u64 * SEEK_POINT_INTERVAL;
+        let reserved_seek_point_count = ((settings.m_total_samples + seek_point_interval - 1) / seek_point_interval) as usize;
+
+        let start_offset = output.seek(SeekFrom::Current(0))?;
+        let mut metadata: Vec<u8> = Vec::new();
+        metadata.extend_from_slice(b"fLaC");
+        let streaminfo_offset = start_offset + metadata.len() as u64 + 4;
+        write_metadata_block(&mut metadata, BLOCK_TYPE_STREAMINFO, &create_streaminfo_block(parameters.m_block_size as u32, 0, 0,
+            settings.m_rate, settings.m_channel_count, settings.m_bits_per_sample, 0, 0), false)?;
+        let seek_table_offset = start_offset + metadata.len() as u64 + 4;
+        if reserved_seek_point_count > 0
+        {
+            write_metadata_block(&mut metadata, BLOCK_TYPE_SEEKTABLE, &create_seek_table_block(&[], reserved_seek_point_count), false)?;
+        }
+        write_metadata_block(&mut metadata, BLOCK_TYPE_VORBIS_COMMENT, &create_vorbis_comment_block(DEFAULT_VENDOR_STRING, &settings.m_vorbis_comments), false)?;
+        write_metadata_block(&mut metadata, BLOCK_TYPE_PADDING, &vec![0; DEFAULT_PADDING_SIZE], true)?;
+        output.write_all(&metadata)?;
+        let first_frame_offset = start_offset + metadata.len() as u64;
+
+        return Ok(FlacEncoder
+        {
+            m_output: output,
+            m_settings: settings,
+            m_parameters: parameters,
+            m_pending_samples: Vec::new(),
+            m_frame_number: 0,
+            m_sample_count: 0,
+            m_md5_context: md5::Context::new(),
+            m_min_frame_size: 0,
+            m_max_frame_size: 0,
+            m_streaminfo_offset: streaminfo_offset,
+            m_seek_table_offset: seek_table_offset,
+            m_reserved_seek_point_count: reserved_seek_point_count,
+            m_seek_points: Vec::new(),
+            m_next_seek_target: 0,
+            m_first_frame_offset: first_frame_offset,
+            m_output_offset: first_frame_offset,
+        });
+    }
+
+    /// Encode samples. The frames are written as soon as enough samples are given.
+    ///
+    /// # Params
+    /// samples: the interleaved samples of all the channels, each one using the bits per sample of the settings
+    pub fn write_samples(&mut self, samples: &[i32]) -> Result<(), AudioReaderError>
+    {
+        let bits_per_sample = self.m_settings.m_bits_per_sample as u32;
+        let max_value = ((1i64 << (bits_per_sample - 1)) - 1) as i32;
+        let min_value = (-(1i64 << (bits_per_sample - 1))) as i32;
+        if samples.iter().any(|sample| *sample < min_value || *sample > max_value)
+        {
+            return Err(AudioReaderError::InvalidValue("sample value"));
+        }
+
+        self.m_pending_samples.extend_from_slice(samples);
+        let block_sample_count = self.m_parameters.m_block_size * self.m_settings.m_channel_count as usize;
+        let mut position = 0;
+        while self.m_pending_samples.len() - position >= block_sample_count
+        {
+            let block = self.m_pending_samples[position..position + block_sample_count].to_vec();
+            self.write_frame(&block)?;
+            position += block_sample_count;
+        }
+        self.m_pending_samples.drain(..position);
+
+        return Ok(());
+    }
+
+    /// Encode the last samples, then complete the STREAMINFO and SEEKTABLE blocks
+    ///
+    /// # Return
+    /// The output, placed after the end of the stream
+    pub fn finish(mut self) -> Result<W, AudioReaderError>
+    {
+        let channel_count = self.m_settings.m_channel_count as usize;
+        if self.m_pending_samples.len() % channel_count != 0
+        {
+            return Err(AudioReaderError::InvalidValue("number of samples"));
+        }
+        if !self.m_pending_samples.is_empty()
+        {
+            let block = std::mem::take(&mut self.m_pending_samples);
+            self.write_frame(&block)?;
+        }
+
+        let md5 = u128::from_be_bytes(self.m_md5_context.clone().compute().0);
+        let streaminfo = create_streaminfo_block(self.m_parameters.m_block_size as u32, self.m_min_frame_size, self.m_max_frame_size,
+            self.m_settings.m_rate, self.m_settings.m_channel_count, self.m_settings.m_bits_per_sample, self.m_sample_count, md5);
+        self.m_output.seek(SeekFrom::Start(self.m_streaminfo_offset))?;
+        self.m_output.write_all(&streaminfo)?;
+        if self.m_reserved_seek_point_count > 0
+        {
+            self.m_output.seek(SeekFrom::Start(self.m_seek_table_offset))?;
+            self.m_output.write_all(&create_seek_table_block(&self.m_seek_points, self.m_reserved_seek_point_count))?;
+        }
+        self.m_output.seek(SeekFrom::Start(self.m_output_offset))?;
+        self.m_output.flush()?;
+
+        return Ok(self.m_output);
+    }
+
+    /// Encode one frame and write it
+    ///
+    /// # Params
+    /// samples: the interleaved samples of the frame, one block or less for the last frame
+    fn write_frame(&mut self, samples: &[i32]) -> Result<(), AudioReaderError>
+    {
+        let channel_count = self.m_settings.m_channel_count as usize;
+        let block_size = samples.len() / channel_count;
+        let bits_per_sample = self.m_settings.m_bits_per_sample as u32;
+        let channels: Vec<Vec<i64>> = (0..channel_count)
+            .map(|channel_index| samples.iter().skip(channel_index).step_by(channel_count).map(|sample| *sample as i64).collect())
+            .collect();
+
+        //
+        // Encode each channel, and for stereo keep the smallest way to store the two channels
+        let mut channel_assignment = channel_count as u32 - 1;
+        let mut subframes: Vec<BitWriter> = channels.iter().map(|channel| encode_subframe(channel, bits_per_sample, self.m_parameters)).collect();
+        if channel_count == 2 && self.m_parameters.m_use_stereo_decorrelation
+        {
+            let side: Vec<i64> = channels[0].iter().zip(channels[1].iter()).map(|(left, right)| left - right).collect();
+            let mid: Vec<i64> = channels[0].iter().zip(channels[1].iter()).map(|(left, right)| (left + right) >> 1).collect();
+            let side_subframe = encode_subframe(&side, bits_per_sample + 1, self.m_parameters);
+            let mid_subframe = encode_subframe(&mid, bits_per_sample, self.m_parameters);
+
+            let left_size = subframes[0].get_bit_count();
+            let right_size = subframes[1].get_bit_count();
+            let side_size = side_subframe.get_bit_count();
+            let mid_size = mid_subframe.get_bit_count();
+            let sizes = [left_size + right_size, left_size + side_size, side_size + right_size, mid_size + side_size];
+            let best_assignment = (0..4).min_by_key(|assignment| sizes[*assignment]).unwrap_or(0);
+            match best_assignment
+            {
+                1 => { channel_assignment = 8; subframes[1] = side_subframe; }
+                2 => { channel_assignment = 9; subframes[0] = side_subframe; }
+                3 => { channel_assignment = 10; subframes = vec![mid_subframe, side_subframe]; }
+                _ => {}
+            }
+        }
+
+        //
+        // Frame header, the block size and the rate use the codes of the common values
+        let mut writer = BitWriter::new();
+        writer.write_bits(0x3FFE, 14);
+        writer.write_bits(0, 1);
+        writer.write_bits(0, 1);
+        let (block_size_code, block_size_bits) = match block_size
+        {
+            192 => (1, 0),
+            576 | 1152 | 2304 | 4608 => (2 + (block_size / 576).trailing_zeros(), 0),
+            256 | 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768 => (8 + (block_size / 256).trailing_zeros(), 0),
+            1..=256 => (6, 8),
+            _ => (7, 16),
+        };
+        writer.write_bits(block_size_code, 4);
+        let rate = self.m_settings.m_rate;
+        let (rate_code, rate_bits, coded_rate) = match rate
+        {
+            88200 => (1, 0, 0),
+            176400 => (2, 0, 0),
+            192000 => (3, 0, 0),
+            8000 => (4, 0, 0),
+            16000 => (5, 0, 0),
+            22050 => (6, 0, 0),
+            24000 => (7, 0, 0),
+            32000 => (8, 0, 0),
+            44100 => (9, 0, 0),
+            48000 => (10, 0, 0),
+            96000 => (11, 0, 0),
+            _ if rate % 1000 == 0 && rate / 1000 < 256 => (12, 8, rate / 1000),
+            _ if rate < 65536 => (13, 16, rate),
+            _ if rate % 10 == 0 && rate / 10 < 65536 => (14, 16, rate / 10),
+            _ => (0, 0, 0),
+        };
+        writer.write_bits(rate_code, 4);
+        writer.write_bits(channel_assignment, 4);
+        let sample_size_code = match bits_per_sample
+        {
+            8 => 1,
+            12 => 2,
+            16 => 4,
+            20 => 5,
+            24 => 6,
+            32 => 7,
+            _ => 0,
+        };
+        writer.write_bits(sample_size_code, 3);
+        writer.write_bits(0, 1);
+        write_utf8_coded_number(&mut writer, self.m_frame_number);
+        if block_size_bits > 0
+        {
+            writer.write_bits(block_size as u32 - 1, block_size_bits);
+        }
+        if rate_bits > 0
+        {
+            writer.write_bits(coded_rate, rate_bits);
+        }
+        let header_crc = compute_crc8(writer.get_bytes());
+        writer.write_bits(header_crc as u32, 8);
+
+        //
+        // Subframes then footer
+        for subframe in subframes.iter()
+        {
+            writer.append(subframe);
+        }
+        writer.align_to_byte();
+        let frame_crc = compute_crc16(writer.get_bytes());
+        writer.write_bits(frame_crc as u32, 16);
+        let frame = writer.into_bytes();
+
+        self.m_output.write_all(&frame)?;
+        self.add_seek_points(block_size);
+        update_md5(&mut self.m_md5_context, samples, self.m_settings.m_bits_per_sample);
+
+        let frame_size = frame.len() as u32;
+        if self.m_frame_number == 0 || frame_size < self.m_min_frame_size
+        {
+            self.m_min_frame_size = frame_size;
+        }
+        self.m_max_frame_size = self.m_max_frame_size.max(frame_size);
+        self.m_frame_number += 1;
+        self.m_sample_count += block_size as u64;
+        self.m_output_offset += frame.len() as u64;
+
+        return Ok(());
+    }
+
+    /// Add the seek points pointing to the frame being written
+    fn add_seek_points(&mut self, block_size: usize)
+    {
+        let seek_point_interval = self.m_settings.m_rate as u64 * SEEK_POINT_INTERVAL;
+        let frame_end = self.m_sample_count + block_size as u64;
+        while self.m_seek_points.len() < self.m_reserved_seek_point_count && self.m_next_seek_target < frame_end
+        {
+            //
+            // Each point is the frame containing its target, several targets inside the same frame give one point
+            if self.m_seek_points.last().is_none_or(|seek_point| seek_point.m_sample_number != self.m_sample_count)
+            {
+                self.m_seek_points.push(EncodedSeekPoint
+                {
+                    m_sample_number: self.m_sample_count,
+                    m_frame_offset: self.m_output_offset - self.m_first_frame_offset,
+                    m_sample_count: block_size as u16,
+                });
+            }
+            self.m_next_seek_target += seek_point_interval;
+        }
+    }
+}
@@ -20,22 +20,58 @@
 // Based on https://xiph.org/flac/format.html
 
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::convert::TryInto;
 use std::mem::size_of;
-use crate::utils;
-use crate::audio_reader::{AudioInformation, AudioReader};
-use crate::utils::file_reader::{read_u16_from_file, read_u32_from_file, read_u64_from_file, read_u8_from_file};
+use crate::utils::bit_reader::BitReader;
+use crate::utils::crc::{compute_crc16, compute_crc8};
+use crate::audio_reader::{AudioInformation, AudioPicture, AudioReader, AudioReaderError, DecodedBlock, PictureType, TrackIndexPoint, TrackMetadata, VirtualTrack};
+use crate::audio_reader::foreign_metadata::ForeignMetadata;
+use crate::audio_reader::ogg_demuxer::OggDemuxer;
+use crate::audio_reader::replay_gain::ReplayGainProcessor;
+use crate::audio_reader::vorbis_comment::VorbisComment;
+use crate::utils::file_reader::{read_bytes_from_file, read_u32_from_file};
+
+/// Number of bytes read from the source each time the decoder needs more data
+const DECODER_READ_SIZE: usize = 64 * 1024;
+
+/// Upper bound of the size of a frame (65535 samples of 8 channels on 33 bits plus the headers).
+/// A frame needing more data than that is corrupted.
+const MAX_FRAME_SIZE: usize = 3 * 1024 * 1024;
+
+/// Maximal number of samples per channel of a block of silence replacing lost frames
+const MAX_SILENCE_BLOCK_SIZE: u64 = 65536;
+
+/// Size of the part of the file under which the bisection of a seek stops and the frames are decoded one by one
+const BISECTION_STOP_SIZE: u64 = 64 * 1024;
+
+/// Minimal size of the parts of a file decoded by each thread of a parallel decoding
+const MIN_PARALLEL_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Number of parts of a file per thread of a parallel decoding, several parts per thread balance the work between the threads
+const PARALLEL_CHUNKS_PER_THREAD: u64 = 4;
+
+/// First bytes of the first packet of a FLAC logical stream inside an ogg stream
+const OGG_FLAC_SIGNATURE: &[u8] = b"\x7FFLAC";
+
+/// Size of the header of the first packet of the Ogg FLAC mapping, before the magic number of the flac stream
+const OGG_FLAC_HEADER_SIZE: usize = 9;
 
 /******************************************************
  * Declaration of the different structures needed
  * to extract informations of the flac files
  ******************************************************/
-struct MetaDataHeader
+pub(crate) struct MetaDataHeader
 {
-    m_is_last: bool,
-    m_block_type: u8,
-    m_length: u32,
+    pub(crate) m_is_last: bool,
+    pub(crate) m_block_type: u8,
+    pub(crate) m_length: u32,
+
+    /// Position in the file of the first byte of the block, after its header
+    pub(crate) m_offset: u64,
 }
 
+#[derive(Default)]
 struct StreamBlockInfo
 {
     m_min_block_size: u16,
@@ -45,19 +81,27 @@ struct StreamBlockInfo
     m_rate: u32,
     m_channel_count: u8,
     m_bits_per_sample: u8,
-    m_total_sample: u64
+    m_total_sample: u64,
+
+    /// MD5 signature of the unencoded audio, 0 if it has not been computed by the encoder
+    m_md5: u128,
 }
 
-struct ApplicationBlock
+/// APPLICATION block of a flac file, kept byte-exact whether its application is known or not
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApplicationBlock
 {
-    m_application_id: u32,
-    m_application_data: Vec<u8>
+    /// Id registered by the application, like "riff" for the foreign metadata of the flac tool
+    pub m_application_id: [u8; 4],
+
+    /// Content of the block after the id
+    pub m_application_data: Vec<u8>,
 }
 
-struct VorbisCommentBlock
+pub(crate) struct VorbisCommentBlock
 {
-    m_vendor_string: String,
-    m_user_comment_list: Vec<String>,
+    pub(crate) m_vendor_string: String,
+    pub(crate) m_user_comment_list: Vec<String>,
 }
 
 struct SeekPoint
@@ -98,14 +142,99 @@ struct CuesheetBlock
     m_cuesheet_tracks: Vec<CuesheetTrackBlock>
 }
 
+/// The way the channels of a frame are stored
+#[derive(Clone, Copy, PartialEq)]
+enum ChannelAssignment
+{
+    /// Each channel is coded separately, contains the number of channels
+    Independent(u8),
+
+    /// Left channel then side channel (left - right)
+    LeftSide,
+
+    /// Side channel then right channel
+    SideRight,
+
+    /// Mid channel ((left + right) / 2) then side channel
+    MidSide,
+}
+
 struct FrameHeader
 {
+    m_is_variable_block_size: bool,
+    m_block_size: u32,
+    m_rate: u32,
+    m_channel_assignment: ChannelAssignment,
+    m_bits_per_sample: u8,
 
+    /// Frame number for fixed block size streams, sample number of the first sample otherwise
+    m_coded_number: u64,
+    m_crc8: u8,
 }
 
 struct FrameFooter
 {
+    m_crc16: u16,
+}
+
+/// State of the MD5 signature of a flac file compared to its decoded audio
+#[derive(Debug, PartialEq)]
+pub enum FlacMd5Status
+{
+    /// The MD5 of the decoded audio is the one stored in the STREAMINFO block
+    Match,
+
+    /// The MD5 of the decoded audio is not the one stored in the STREAMINFO block
+    Mismatch,
+
+    /// The encoder did not store the MD5 of the audio
+    NoSignature,
+}
+
+/// Result of the verification of a flac file
+pub struct FlacVerification
+{
+    pub m_md5_status: FlacMd5Status,
+
+    /// The corrupted frames found while decoding the file
+    pub m_frame_errors: Vec<FlacFrameError>,
+}
+
+/// Part of a flac stream decoded by one thread of a parallel decoding, starting at a frame
+#[derive(Clone, Copy)]
+struct ParallelChunk
+{
+    /// Position in the stream of the first frame of the part
+    m_stream_offset: u64,
+
+    /// Index of the first sample of the part, and of the first sample of the next part
+    m_first_sample: u64,
+    m_end_sample: Option<u64>,
+}
+
+/// Progress of a parallel decoding, shared by the threads
+struct ParallelDecodingState
+{
+    /// Index of the next part to decode
+    m_next_chunk: usize,
+
+    /// Number of parts already given back in order
+    m_delivered_chunk_count: usize,
+    m_is_cancelled: bool,
+}
+
+/// Error which happened while decoding a frame.
+/// The samples of the frame are lost and replaced by silence.
+#[derive(Debug)]
+pub struct FlacFrameError
+{
+    /// Index in the stream of the first lost sample
+    pub m_sample_position: u64,
+
+    /// Position in the stream of the first byte of the lost frame
+    pub m_byte_position: u64,
 
+    pub m_error: AudioReaderError,
 }
 
 pub struct FlacFile
@@ -121,240 +250,461 @@ pub struct FlacReader
 {
 }
 
-/*****************************************************
- * Functions not exposed needed to decodes flac files
- *****************************************************/
+/// Part of the stream expected next by the stream decoder
+#[derive(Clone, Copy, PartialEq)]
+enum StreamDecoderState
+{
+    /// The 4 bytes "fLaC" starting the stream
+    MagicNumber,
+
+    /// The STREAMINFO block, always the first metadata block
+    StreamInfo,
+
+    /// The other metadata blocks
+    MetadataBlocks,
+
+    /// The audio frames, up to the end of the stream
+    Frames,
+}
 
-fn read_metadata_header(file: &File) -> MetaDataHeader
+/// Push-based decoder of a flac stream.
+/// The bytes of the stream are given in chunks of any size, and the samples of each frame are returned
+/// as soon as the whole frame has been received. Used to play flac from any source of bytes,
+/// like memory buffers, pipes or network streams.
+///
+/// # How to use it
+/// ```
+/// let mut stream_decoder = FlacStreamDecoder::new();
+/// while let Some(chunk) = source.next_chunk()
+/// {
+///     stream_decoder.push_data(&chunk);
+///     while let Some(decoded_block) = stream_decoder.read_next_block()?
+///     {
+///         // Play the samples
+///     }
+/// }
+/// stream_decoder.finish();
+/// while let Some(decoded_block) = stream_decoder.read_next_block()?
+/// {
+///     // Play the last samples
+/// }
+/// ```
+pub struct FlacStreamDecoder
 {
-    //
-    // Read Metadata block
-    let mut metadata_header = utils::file_reader::read_u32_from_file(&file).swap_bytes();
+    m_state: StreamDecoderState,
+    m_streaminfo: StreamBlockInfo,
+    m_seek_points: Vec<SeekPoint>,
 
-    let block_size = metadata_header & 0xFFFFF;
-    metadata_header >>= 24;
+    /// Position in the stream of the first frame, the seek points are relative to it
+    m_first_frame_offset: u64,
 
-   let block_type = metadata_header & 0x7F; // Get block type
-    metadata_header >>= 7;
+    /// Bytes received but not decoded yet, starting at m_buffer_position
+    m_buffer: Vec<u8>,
+    m_buffer_position: usize,
 
-    let b_last_block = metadata_header & 1;
+    /// True when all the bytes of the stream have been received
+    m_is_end_of_stream: bool,
 
-    return MetaDataHeader{
-        m_is_last: b_last_block == 1,
-        m_block_type: block_type as u8,
-        m_length: block_size,
+    /// Position in the stream of the first byte of the buffer
+    m_buffer_stream_offset: u64,
+
+    /// Index of the first sample of the next frame
+    m_next_sample: u64,
+
+    /// True while searching the next valid frame after a corrupted one
+    m_is_resynchronizing: bool,
+
+    /// All the corrupted frames found since the beginning of the stream
+    m_frame_errors: Vec<FlacFrameError>,
+}
+
+/// Position of a page of an Ogg FLAC stream where the decoding can restart
+struct OggFlacCheckpoint
+{
+    /// Position in the flac stream of the frame starting the page
+    m_stream_offset: u64,
+
+    /// Position of the page in the ogg stream
+    m_page_offset: u64,
+}
+
+/// Source of a flac stream stored inside an ogg stream (.oga files).
+/// Reading the packets of the FLAC mapping back to back gives the bytes of a native flac stream:
+/// the first packet holds the magic number and the STREAMINFO block after a small header,
+/// each following header packet holds one metadata block and each audio packet holds one frame.
+/// So the metadata parsers and the frame decoder read Ogg FLAC like native flac.
+pub struct OggFlacSource<R: Read>
+{
+    m_demuxer: OggDemuxer<R>,
+
+    /// The packet being read, converted to the bytes of the flac stream
+    m_packet: Vec<u8>,
+    m_packet_position: usize,
+
+    /// Position in the flac stream of the next byte read
+    m_stream_offset: u64,
+
+    /// The pages starting with a frame found so far, sorted by position
+    m_checkpoints: Vec<OggFlacCheckpoint>,
+
+    /// Size of the flac stream, known once the whole ogg stream has been read
+    m_stream_length: Option<u64>,
+}
+
+/// Source of the flac stream of a file, native or inside an ogg container
+pub enum FlacFileSource
+{
+    Native(File),
+    Ogg(Box<OggFlacSource<File>>),
+}
+
+/// Decoder of the audio frames of a flac file, or of any other source of bytes.
+/// Reads the source frame by frame and returns the samples of each frame as soon as it is decoded.
+/// Seeking is only available when the source implements Seek.
+pub struct FlacDecoder<R = FlacFileSource>
+{
+    m_source: R,
+    m_stream_decoder: FlacStreamDecoder,
+
+    /// End of the frame containing the sample asked by the last seek, returned by the next read
+    m_pending_block: Option<DecodedBlock>,
+
+    /// Index of the sample where the decoding stops, used to play only one track of the file
+    m_end_sample: Option<u64>,
+
+    /// Gain of the title applied to the decoded samples, None to keep the samples of the file
+    m_replay_gain: Option<ReplayGainProcessor>,
+}
+
+/*****************************************************
+ * Functions not exposed needed to decodes flac files
+ *****************************************************/
+
+/// Get the name of a metadata block type, used by the errors
+fn get_block_name(block_type: u8) -> &'static str
+{
+    return match block_type
+    {
+        0 => "STREAMINFO",
+        1 => "PADDING",
+        2 => "APPLICATION",
+        3 => "SEEKTABLE",
+        4 => "VORBIS_COMMENT",
+        5 => "CUESHEET",
+        6 => "PICTURE",
+        127 => "invalid",
+        _ => "reserved",
     };
 }
 
-fn read_streaminfo_block(file: &File) -> StreamBlockInfo
+/// Check that the data are the flac magic number
+fn check_magic_number(magic_number: &[u8]) -> Result<(), AudioReaderError>
 {
-    //
-    // Get the min block size of the file
-    let min_block_size: u16 = utils::file_reader::read_u16_from_file(file).swap_bytes();
+    if magic_number == b"fLaC"
+    {
+        return Ok(());
+    }
 
     //
-    // Get all information on 128 bits
-    let mut multi_info: u128 = utils::file_reader::read_u128_from_file(file).swap_bytes();
+    // Some taggers add an ID3v2 tag before the flac stream, which is not allowed by the format
+    if magic_number.starts_with(b"ID3")
+    {
+        return Err(AudioReaderError::UnsupportedFeature("ID3v2 tag before the flac stream"));
+    }
+    return Err(AudioReaderError::InvalidMagicNumber);
+}
 
-    //
-    // Get the total samples inside the files (36bits)
-    let total_samples: u64 = (multi_info & 0xFFFFFFFFF) as u64;
-    multi_info >>= 36;
+/// Check that the file starts with the flac magic number
+pub(crate) fn read_magic_number(file: &File) -> Result<(), AudioReaderError>
+{
+    let magic_number = read_bytes_from_file(file, 4).map_err(|_| AudioReaderError::InvalidMagicNumber)?;
+    return check_magic_number(&magic_number);
+}
 
-    //
-    // Get the number of bits per sample (5bits)
-    let bits_per_sample: u8 = (((multi_info & 0x1F) as u8)) + 1;
-    multi_info >>= 5;
+/// Decode the header of a metadata block
+///
+/// # Params
+/// metadata_header: the 32 bits of the header
+/// block_offset: the position of the first byte of the block, after the header
+fn parse_metadata_header(mut metadata_header: u32, block_offset: u64) -> Result<MetaDataHeader, AudioReaderError>
+{
+    let block_size = metadata_header & 0xFFFFFF;
+    metadata_header >>= 24;
 
-    //
-    // Get channel count on the next 3bits
-    let channel_count: u8 = (((multi_info & 0x7) as u8))  + 1;
-    multi_info >>= 3;
+    let block_type = metadata_header & 0x7F; // Get block type
+    metadata_header >>= 7;
 
-    // Get the sample rate on the next 20 bits
-    let rate: u32 = (multi_info & 0xFFFFF) as u32;
-    multi_info >>= 20;
+    let b_last_block = metadata_header & 1;
 
     //
-    // Get the max frame size on the 24 bits
-    let max_frame_size: u32 = (multi_info & 0xFFFFFF) as u32;
-    multi_info >>= 24;
+    // The type 127 is forbidden to avoid the confusion with a frame sync code
+    if block_type == 127
+    {
+        return Err(AudioReaderError::InvalidValue("metadata block type"));
+    }
 
-    //
-    // Get the min frame size on the 24 bits
-    let min_frame_size: u32 = (multi_info & 0xFFFFFF) as u32;
-    multi_info >>= 24;
+    return Ok(MetaDataHeader{
+        m_is_last: b_last_block == 1,
+        m_block_type: block_type as u8,
+        m_length: block_size,
+        m_offset: block_offset,
+    });
+}
+
+/// Read the header of a metadata block and check that the whole block is inside the file
+///
+/// # Params
+/// file: the file, placed at the beginning of the header
+/// file_length: the size of the file in bytes
+pub(crate) fn read_metadata_header(file: &File, file_length: u64) -> Result<MetaDataHeader, AudioReaderError>
+{
+    let block_offset = (&*file).seek(SeekFrom::Current(0))?;
+    if block_offset + 4 > file_length
+    {
+        return Err(AudioReaderError::TruncatedBlock { m_block_name: "metadata header", m_offset: block_offset });
+    }
 
     //
-    // Get the max block size on the first 16 bits
-    let max_block_size: u16 = (multi_info & 0xFFFF) as u16;
-    multi_info >>= 16;
+    // Read Metadata block
+    let metadata_header = parse_metadata_header(read_u32_from_file(&file)?.swap_bytes(), block_offset + 4)?;
+    if metadata_header.m_offset + metadata_header.m_length as u64 > file_length
+    {
+        return Err(AudioReaderError::TruncatedBlock { m_block_name: get_block_name(metadata_header.m_block_type), m_offset: metadata_header.m_offset });
+    }
 
-    assert!(multi_info == 0);
+    return Ok(metadata_header);
+}
 
-    //
-    // Get the md5
-    let _md5: u128 = utils::file_reader::read_u128_from_file(file).swap_bytes();
+/// Decode the content of a STREAMINFO block
+fn parse_streaminfo_block(data: &[u8]) -> Result<StreamBlockInfo, AudioReaderError>
+{
+    if data.len() != 34
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "STREAMINFO", m_length: data.len() as u32 });
+    }
 
     //
-    // Return the information
-    let stream_block = StreamBlockInfo{
+    // All the fields are big endian and are not aligned on bytes
+    let mut reader = BitReader::new(data);
+    let min_block_size = need_data(reader.read_bits(16))? as u16;
+    let max_block_size = need_data(reader.read_bits(16))? as u16;
+    let min_frame_size = need_data(reader.read_bits(24))?;
+    let max_frame_size = need_data(reader.read_bits(24))?;
+    let rate = need_data(reader.read_bits(20))?;
+    let channel_count = need_data(reader.read_bits(3))? as u8 + 1;
+    let bits_per_sample = need_data(reader.read_bits(5))? as u8 + 1;
+    let total_samples = need_data(reader.read_bits_u64(36))?;
+    let md5 = (need_data(reader.read_bits_u64(64))? as u128) << 64 | need_data(reader.read_bits_u64(64))? as u128;
+
+    return Ok(StreamBlockInfo{
         m_bits_per_sample: bits_per_sample,
         m_rate : rate,
         m_min_block_size: min_block_size,
         m_max_block_size: max_block_size,
         m_total_sample: total_samples,
+        m_md5: md5,
         m_min_frame_size: min_frame_size,
         m_max_frame_size: max_frame_size,
         m_channel_count: channel_count
-    };
-
-    return stream_block;
-}
-
-fn read_block_padding(file: &File, number_padding: u32)
-{
-    for _i in 0..number_padding
-    {
-        read_u8_from_file(file);
-    }
+    });
 }
 
-fn read_seek_table(file: &File, size_block: u32) -> SeekTableBlock
+/// Decode the content of a SEEKTABLE block
+fn parse_seek_table(data: &[u8]) -> Result<SeekTableBlock, AudioReaderError>
 {
     //
     // Get the number of seek point
     // Divide by 18 because it is the size in Bytes of a Seekpoint
-    let seek_point_count: u32 = size_block / 18;
-    let mut seekpoints = Vec::with_capacity(seek_point_count as usize);
-    for _i in 0..seek_point_count
-    {
-        let sample_number = read_u64_from_file(file).swap_bytes();
-        let sample_offset = read_u64_from_file(file).swap_bytes();
-        let number_samples = read_u16_from_file(file).swap_bytes();
-        seekpoints.push(SeekPoint
-        {
-            m_number_samples: number_samples,
-            m_sample_offset: sample_offset,
-            m_sample_number_first_sample: sample_number
-        });
+    if data.len() % 18 != 0
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "SEEKTABLE", m_length: data.len() as u32 });
     }
-    return SeekTableBlock
+    let seekpoints = data.chunks_exact(18).map(|seek_point_data| SeekPoint
+    {
+        m_sample_number_first_sample: u64::from_be_bytes(seek_point_data[0..8].try_into().unwrap()),
+        m_sample_offset: u64::from_be_bytes(seek_point_data[8..16].try_into().unwrap()),
+        m_number_samples: u16::from_be_bytes(seek_point_data[16..18].try_into().unwrap()),
+    }).collect();
+
+    return Ok(SeekTableBlock
     {
         m_seekPoints: seekpoints
-    };
+    });
 }
 
-fn read_block_application(file: &File, size_block: u32) -> ApplicationBlock
+/// Decode the content of an APPLICATION block
+fn parse_block_application(data: &[u8]) -> Result<ApplicationBlock, AudioReaderError>
 {
+    if data.len() < 4
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "APPLICATION", m_length: data.len() as u32 });
+    }
+
     //
     // Get the application id
-    let application_id = read_u32_from_file(file);
+    let application_id: [u8; 4] = data[0..4].try_into().unwrap();
 
     //
     // Get the data of the block coming from the application
-    // Depending the application id, informations must be decoded in different ways
-    let data_count = (size_block - 32)/8;
-    let mut data: Vec<u8> = Vec::new();
-    for _i in 0..data_count -1
+    // They are kept as they are, the known applications are decoded from them afterwards
+    return Ok(ApplicationBlock{
+        m_application_id: application_id,
+        m_application_data: data[4..].to_vec(),
+    });
+}
+
+/// Read a value on 32 bits from the content of a block
+fn parse_u32(reader: &mut BitReader, is_big_endian: bool) -> Result<u32, AudioReaderError>
+{
+    let value = need_data(reader.read_bits(32))?;
+    if is_big_endian
     {
-        data.push(read_u8_from_file(file));
+        return Ok(value);
     }
+    return Ok(value.swap_bytes());
+}
 
-    return ApplicationBlock{
-        m_application_id: application_id,
-        m_application_data: data,
-    };
+/// Read a string stored with its length on 32 bits before it, and check that it is inside the block
+///
+/// # Params
+/// reader: the content of the block, placed at the length of the string
+/// is_big_endian: true if the length is big endian, false if it is little endian
+/// block_name: the name of the block, used by the error
+fn parse_sized_string(reader: &mut BitReader, is_big_endian: bool, block_name: &'static str) -> Result<String, AudioReaderError>
+{
+    let string_length = parse_u32(reader, is_big_endian)?;
+    let string_bytes = reader.read_bytes(string_length as usize)
+        .ok_or(AudioReaderError::InvalidBlockLength { m_block_name: block_name, m_length: string_length })?;
+    return Ok(String::from_utf8_lossy(string_bytes).to_string());
 }
 
-fn read_vorbis_comment_block(file: &File, size_block: u32) -> VorbisCommentBlock
+/// Decode the content of a VORBIS_COMMENT block
+pub(crate) fn parse_vorbis_comment_block(data: &[u8]) -> Result<VorbisCommentBlock, AudioReaderError>
 {
     //
     // Get the vendor which realize the files
-    let vendor_length = read_u32_from_file(file);
-    let mut vendor_string: String = String::new();
-    for _i in 0..vendor_length
-    {
-        vendor_string.push(read_u8_from_file(file) as char);
-    }
+    // Unlike the other blocks, the lengths are little endian
+    let mut reader = BitReader::new(data);
+    let vendor_string = parse_sized_string(&mut reader, false, "VORBIS_COMMENT")?;
 
     //
     // Get the user comment list
     // It contains tag of the album, the artist...
     // Based on https://www.xiph.org/vorbis/doc/v-comment.html
-    let user_comment_list_length: u32 = read_u32_from_file(file);
+    let user_comment_list_length: u32 = parse_u32(&mut reader, false)?;
     let mut list_comment: Vec<String> = Vec::new();
     for _i in 0..user_comment_list_length
     {
-        let comment_size = read_u32_from_file(file);
-        let mut comment: String = String::new();
-        for _j in 0..comment_size
-        {
-            comment.push(read_u8_from_file(file) as char);
-        }
-
-        list_comment.push(comment);
+        list_comment.push(parse_sized_string(&mut reader, false, "VORBIS_COMMENT")?);
     }
-    return VorbisCommentBlock
+    return Ok(VorbisCommentBlock
     {
         m_vendor_string: vendor_string,
         m_user_comment_list: list_comment,
-    };
+    });
 }
 
-fn read_cuesheet_track_index_block(file: &File) -> CuesheetTrackIndexBlock
+pub(crate) fn read_vorbis_comment_block(file: &File, size_block: u32) -> Result<VorbisCommentBlock, AudioReaderError>
 {
-    let offset_samples = read_u64_from_file(file).swap_bytes();
-    let index_point_number = read_u8_from_file(file).swap_bytes();
+    return parse_vorbis_comment_block(&read_bytes_from_file(file, size_block as usize)?);
+}
 
+/// Decode the content of a PICTURE block
+fn parse_picture_block(data: &[u8]) -> Result<AudioPicture, AudioReaderError>
+{
     //
-    // Read empty data for padding
-    for _i in 0..3
+    // The block contains the type, the MIME type, the description, 4 values on 32 bits then the data
+    let mut reader = BitReader::new(data);
+    let picture_type = parse_u32(&mut reader, true)?;
+
+    let mime_type = parse_sized_string(&mut reader, true, "PICTURE")?;
+    let description = parse_sized_string(&mut reader, true, "PICTURE")?;
+
+    let width = parse_u32(&mut reader, true)?;
+    let height = parse_u32(&mut reader, true)?;
+    let color_depth = parse_u32(&mut reader, true)?;
+    let indexed_color_count = parse_u32(&mut reader, true)?;
+    let data_length = parse_u32(&mut reader, true)?;
+    let picture_data = reader.read_bytes(data_length as usize)
+        .ok_or(AudioReaderError::InvalidBlockLength { m_block_name: "PICTURE", m_length: data.len() as u32 })?;
+
+    return Ok(AudioPicture
     {
-        read_u8_from_file(file);
-    }
+        m_picture_type: PictureType::from(picture_type),
+        m_mime_type: mime_type,
+        m_description: description,
+        m_width: width,
+        m_height: height,
+        m_color_depth: color_depth,
+        m_indexed_color_count: indexed_color_count,
+        m_data: picture_data.to_vec(),
+    });
+}
+
+pub(crate) fn read_picture_block(file: &File, size_block: u32) -> Result<AudioPicture, AudioReaderError>
+{
+    return parse_picture_block(&read_bytes_from_file(file, size_block as usize)?);
+}
 
-    return CuesheetTrackIndexBlock
+fn parse_cuesheet_track_index_block(reader: &mut BitReader) -> Result<CuesheetTrackIndexBlock, AudioReaderError>
+{
+    let offset_samples = need_data(reader.read_bits_u64(64))?;
+    let index_point_number = need_data(reader.read_bits(8))? as u8;
+
+    //
+    // Read empty data for padding
+    need_data(reader.read_bytes(3))?;
+
+    return Ok(CuesheetTrackIndexBlock
     {
         m_offset_samples: offset_samples,
         m_index_point: index_point_number,
-    };
+    });
 }
 
-fn read_cuesheet_track_block(file: &File) -> CuesheetTrackBlock
+fn parse_cuesheet_track_block(reader: &mut BitReader, block_length: usize) -> Result<CuesheetTrackBlock, AudioReaderError>
 {
-    let track_offset_in_samples = read_u64_from_file(file).swap_bytes();
-    let track_number = read_u8_from_file(file).swap_bytes();
+    //
+    // A track uses 36 bytes followed by 12 bytes per index point
+    let remaining_size = block_length - reader.get_byte_position();
+    if remaining_size < 36
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "CUESHEET", m_length: remaining_size as u32 });
+    }
+
+    let track_offset_in_samples = need_data(reader.read_bits_u64(64))?;
+    let track_number = need_data(reader.read_bits(8))? as u8;
 
     //
     // Read track ISRC
     let mut read_track_isrc_array: [u8; 12] = [0; 12];
-    for iTrackISRCIndex in 0..12
-    {
-        read_track_isrc_array[iTrackISRCIndex] = read_u8_from_file(file);
-    }
+    read_track_isrc_array.copy_from_slice(need_data(reader.read_bytes(12))?);
 
     //
     // Read the different flags
-    let tmp_flags_track_type_pre_emphasis = read_u8_from_file(file).swap_bytes();
-    let is_audio: bool = tmp_flags_track_type_pre_emphasis & 0x1 == 1;
-    let is_pre_emphasis: bool = tmp_flags_track_type_pre_emphasis & 0x2 == 1;
+    let tmp_flags_track_type_pre_emphasis = need_data(reader.read_bits(8))?;
+    // The first bit is the track type (0 for audio), the second one is the pre-emphasis flag
+    let is_audio: bool = tmp_flags_track_type_pre_emphasis & 0x80 == 0;
+    let is_pre_emphasis: bool = tmp_flags_track_type_pre_emphasis & 0x40 != 0;
 
     //
     // Seek to the next fields as required by specification
-    for _iEmptyDataIndex in 0..13
+    need_data(reader.read_bytes(13))?;
+
+    let track_index_point_number = need_data(reader.read_bits(8))? as u8;
+    let remaining_size = block_length - reader.get_byte_position();
+    if remaining_size < track_index_point_number as usize * 12
     {
-        read_u8_from_file(file);
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "CUESHEET", m_length: remaining_size as u32 });
     }
 
-    let track_index_point_number = read_u8_from_file(file).swap_bytes();
     let mut cuesheet_track_indexes: Vec<CuesheetTrackIndexBlock> = Vec::new();
     for _i in 0..track_index_point_number
     {
-        cuesheet_track_indexes.push(read_cuesheet_track_index_block(file));
+        cuesheet_track_indexes.push(parse_cuesheet_track_index_block(reader)?);
     }
 
-    return CuesheetTrackBlock
+    return Ok(CuesheetTrackBlock
     {
         m_track_offset_samples: track_offset_in_samples,
         m_track_number: track_number,
@@ -363,215 +713,3203 @@ fn read_cuesheet_track_block(file: &File) -> CuesheetTrackBlock
         m_pre_emphasis: is_pre_emphasis,
         m_track_index_points_number: track_index_point_number,
         m_cuesheet_track_indices: cuesheet_track_indexes,
-    };
+    });
 }
 
-fn read_cuesheet_block(file: &File, size_block: u32) -> CuesheetBlock
+/// Decode the content of a CUESHEET block
+fn parse_cuesheet_block(data: &[u8]) -> Result<CuesheetBlock, AudioReaderError>
 {
     //
-    // Read media catalog number
-    let mut media_catalog_number: [u8; 128] = [0; 128];
-    for iMediaCatalogNumber in 0..128
+    // The fields before the tracks use 396 bytes
+    if data.len() < 396
     {
-        let current_number = read_u8_from_file(file);
-        media_catalog_number[iMediaCatalogNumber] = current_number;
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "CUESHEET", m_length: data.len() as u32 });
     }
+    let mut reader = BitReader::new(data);
+
+    //
+    // Read media catalog number
+    let mut media_catalog_number: [u8; 128] = [0; 128];
+    media_catalog_number.copy_from_slice(need_data(reader.read_bytes(128))?);
 
     //
     // Read the number of lead in
-    let number_lead_in = read_u64_from_file(file).swap_bytes();
+    let number_lead_in = need_data(reader.read_bits_u64(64))?;
 
     //
     // Read the flag if it is a CD
-    let is_cd_in_u8 = read_u8_from_file(file).swap_bytes();
-    let is_cd = is_cd_in_u8 & 0x1 == 1;
+    let is_cd_in_u8 = need_data(reader.read_bits(8))?;
+    let is_cd = is_cd_in_u8 & 0x80 != 0;
 
     //
     // Move seek to the number of tracks
-    for _iEmptyDataIndex in 0..258
-    {
-        read_u8_from_file(file);
-    }
+    need_data(reader.read_bytes(258))?;
 
     //
     // Read the number of track
-    let number_tracks = read_u8_from_file(file).swap_bytes();
+    let number_tracks = need_data(reader.read_bits(8))? as u8;
 
     //
     // Read the cuesheet tracks
     let mut cuesheet_tracks: Vec<CuesheetTrackBlock> = Vec::new();
     for _i in 0..number_tracks
     {
-        cuesheet_tracks.push(read_cuesheet_track_block(file));
+        cuesheet_tracks.push(parse_cuesheet_track_block(&mut reader, data.len())?);
     }
 
-    return CuesheetBlock
+    return Ok(CuesheetBlock
     {
         m_media_catalog_number: media_catalog_number,
         m_lead_in_number: number_lead_in,
         m_is_cd: is_cd,
         m_track_number: number_tracks,
         m_cuesheet_tracks: cuesheet_tracks,
-    }
+    });
 }
 
-fn read_frame_header(file: &File, stream_info: StreamBlockInfo)
+/// Convert the end of the data to the error of the decoding
+fn need_data<T>(value: Option<T>) -> Result<T, AudioReaderError>
 {
-    //
-    // Get the first 8-bit of the frame header to get the block strategy and sync code
-    let mut first_value = read_u32_from_file(file).swap_bytes();
-    let block_strategy = first_value & 0x1;
-    let reserved_bit = first_value & 0x2;
-    first_value >>= 2;
+    return value.ok_or(AudioReaderError::NotEnoughData);
+}
 
-    let sync_code = first_value & 0x3F;
-    if sync_code != 0x2F
+/// Read the frame or sample number coded like an UTF-8 character (up to 36 bits on 7 bytes)
+fn read_utf8_coded_number(reader: &mut BitReader) -> Result<u64, AudioReaderError>
+{
+    let first_byte = need_data(reader.read_bits(8))? as u8;
+    let byte_count = first_byte.leading_ones();
+    if byte_count == 0
     {
-        panic!("Error when reading the flac files");
+        return Ok(first_byte as u64);
     }
 
-    //
-    // Get the sample rate and the channel assignment of the frame
-    let rate_and_channel = read_u8_from_file(file).swap_bytes();
-    let channel_assignment = rate_and_channel & 0xF;
-    let sample_rate = rate_and_channel & 0xF0;
-
-
-}
+    if byte_count == 1 || byte_count > 7
+    {
+        return Err(AudioReaderError::InvalidValue("coded number"));
+    }
 
-/**************************************************
- * Public functions for flac files
- **************************************************/
+    //
+    // The first byte contains the highest bits after its leading ones
+    let mut value = (first_byte as u64) & ((1 << (7 - byte_count)) - 1);
+    for _i in 1..byte_count
+    {
+        let next_byte = need_data(reader.read_bits(8))?;
+        if next_byte & 0xC0 != 0x80
+        {
+            return Err(AudioReaderError::InvalidValue("coded number"));
+        }
+        value = (value << 6) | (next_byte & 0x3F) as u64;
+    }
 
-pub fn is_flac_file(file: & File) -> bool
-{
-    let value = utils::file_reader::read_u32_from_file(file);
-    let magic_number = 0x66 as u32 | (0x4C as u32) << 8 | (0x61 as u32) << 16 | (0x43 as u32) << 24;
-    magic_number == value
+    return Ok(value);
 }
 
-impl AudioReader for FlacReader
+/// Convert the audio tracks of a cuesheet to tracks which can be played separately.
+/// A track starts at its index point 1 and ends at the start of the next track, so the pre-gap of a track
+/// is played at the end of the previous one.
+///
+/// # Params
+/// cuesheet: the cuesheet block of the file
+/// total_samples: the number of samples of the stream, used when there is no lead-out track, 0 if unknown
+fn convert_cuesheet_to_tracks(cuesheet: &CuesheetBlock, total_samples: u64) -> Vec<VirtualTrack>
 {
-    fn read_information(&self, str_path_to_music : String) -> AudioInformation
-    {
-        //
-        // Init the audio reader
-        let mut audio_reader: AudioInformation = AudioInformation {
-            m_str_music_type : std::string::String::from(""),
-            m_str_music_name : std::string::String::from(""),
-            m_str_duration : std::string::String::from(""),
-            m_str_artist_name : std::string::String::from(""),
-            m_str_tracknumber : std::string::String::from(""),
-            m_str_album : std::string::String::from(""),
-            m_str_date: std::string::String::from(""),
-            m_rate : 0,
-            m_channel_count: 0,
-            m_bits_per_sample: 0
-        };
-
-        //
-        // Open the file
-        let file = match std::fs::File::open(str_path_to_music)
+    //
+    // The lead-out is the only track without index point, its offset is the end of the audio, never after the end of the stream
+    let mut end_of_audio = total_samples;
+    if let Some(lead_out_track) = cuesheet.m_cuesheet_tracks.iter().find(|cuesheet_track| cuesheet_track.m_cuesheet_track_indices.is_empty())
+    {
+        end_of_audio = match total_samples
         {
-            Err(why) => panic!("Could not open the file !"),
-            Ok(file) => file,
+            0 => lead_out_track.m_track_offset_samples,
+            _ => lead_out_track.m_track_offset_samples.min(total_samples),
         };
+    }
 
-        //
-        // Test if the file is a flac
-        let b_is_flac_file = is_flac_file(&file);
-        if b_is_flac_file
-        {
-            //
-            // Read the StreamInfoBlock
-            let mut header_stream_info = read_metadata_header(&file);
-            if header_stream_info.m_block_type == 0
+    let mut tracks: Vec<VirtualTrack> = Vec::new();
+    let mut is_previous_track_audio = false;
+    for cuesheet_track in cuesheet.m_cuesheet_tracks.iter().filter(|cuesheet_track| !cuesheet_track.m_cuesheet_track_indices.is_empty())
+    {
+        let index_points: Vec<TrackIndexPoint> = cuesheet_track.m_cuesheet_track_indices.iter()
+            .map(|track_index| TrackIndexPoint
             {
-                let stream_block = read_streaminfo_block(&file);
-
-                audio_reader.m_rate = stream_block.m_rate;
-                audio_reader.m_bits_per_sample = stream_block.m_bits_per_sample;
-                audio_reader.m_channel_count = stream_block.m_channel_count;
-            }
+                m_index_number: track_index.m_index_point,
+                m_sample: cuesheet_track.m_track_offset_samples + track_index.m_offset_samples,
+            })
+            .collect();
+        let start_sample = index_points.iter()
+            .find(|index_point| index_point.m_index_number == 1)
+            .or(index_points.first())
+            .map_or(cuesheet_track.m_track_offset_samples, |index_point| index_point.m_sample);
 
-            //
-            // Read the others metadatablock
-            while !header_stream_info.m_is_last
+        //
+        // The previous audio track ends where this one starts, a data track ends the audio track before it
+        if is_previous_track_audio
+        {
+            if let Some(previous_track) = tracks.last_mut()
             {
-                header_stream_info = read_metadata_header(&file);
-                if header_stream_info.m_block_type == 1
-                {
-                    println!("Padding of {0}", header_stream_info.m_length / 8);
-                    read_block_padding(&file, header_stream_info.m_length / 8);
-                }
-                else if header_stream_info.m_block_type == 2
-                {
-                    println!("ApplicationData");
-                    let application_data = read_block_application(&file, header_stream_info.m_length);
-                }
-                else if header_stream_info.m_block_type == 3
-                {
-                    println!("Seektable");
-                    let _seek_table_data = read_seek_table(&file, header_stream_info.m_length);
-                }
-                else if header_stream_info.m_block_type == 4
-                {
-                    let vorbis_comment = read_vorbis_comment_block(&file, header_stream_info.m_length);
-                    for comment in vorbis_comment.m_user_comment_list
-                    {
-                        //
-                        // TODO: Fix this bad implementation
-                        if comment.contains("ARTIST")
-                        {
-                            let artist_index = "ARTIST=".len();
-                            audio_reader.m_str_artist_name = comment[artist_index..].to_string();
-                        }
-                        else if comment.contains("TITLE")
-                        {
-                            let title_index = "TITLE=".len();
-                            audio_reader.m_str_music_name = comment[title_index..].to_string();
-                        }
-                        else if comment.contains("TRACKNUMBER")
-                        {
-                            let tracknumber_index = "TRACKNUMBER=".len();
-                            audio_reader.m_str_tracknumber = comment[tracknumber_index..].to_string();
-                        }
-                        else if comment.contains("DATE")
-                        {
-                            let date_index = "DATE=".len();
-                            audio_reader.m_str_date = comment[date_index..].to_string();
-                        }
-                        else if comment.contains("YEAR")
-                        {
-                            //
-                            // Not standard but can be used in flac files so...
-                            let date_index = "YEAR=".len();
-                            audio_reader.m_str_date = comment[date_index..].to_string();
-                        }
-                        else if comment.contains("ALBUM")
-                        {
-                            let album_index = "ALBUM=".len();
-                            audio_reader.m_str_album = comment[album_index..].to_string();
-                        }
-                        else if comment.contains("GENRE")
-                        {
-                            let genre_index = "GENRE=".len();
-                            audio_reader.m_str_music_type = comment[genre_index..].to_string();
-                        }
-                    }
-                }
-                else if header_stream_info.m_block_type == 5
-                {
-                    println!("Cuesheet");
-                    let cuesheet_block = read_cuesheet_block(&file, header_stream_info.m_length);
-                }
-                else if header_stream_info.m_block_type == 6
-                {
-                    println!("Picture");
-                }
+                previous_track.m_end_sample = start_sample;
             }
         }
+        is_previous_track_audio = cuesheet_track.m_is_audio;
+        if !cuesheet_track.m_is_audio
+        {
+            continue;
+        }
 
-        return audio_reader;
+        let isrc_length = cuesheet_track.m_track_isrc.iter().position(|character| *character == 0).unwrap_or(12);
+        tracks.push(VirtualTrack
+        {
+            m_track_number: cuesheet_track.m_track_number,
+            m_start_sample: start_sample,
+            m_end_sample: end_of_audio,
+            m_index_points: index_points,
+            m_isrc: String::from_utf8_lossy(&cuesheet_track.m_track_isrc[..isrc_length]).to_string(),
+            m_has_pre_emphasis: cuesheet_track.m_pre_emphasis,
+        });
     }
-}
\ No newline at end of file
+
+    return tracks;
+}
+
+fn read_frame_header(reader: &mut BitReader, stream_info: &StreamBlockInfo) -> Result<FrameHeader, AudioReaderError>
+{
+    //
+    // Check the sync code (14 bits) and the reserved bit
+    let sync_code = need_data(reader.read_bits(14))?;
+    if sync_code != 0x3FFE
+    {
+        return Err(AudioReaderError::InvalidSyncCode);
+    }
+    if need_data(reader.read_bit())?
+    {
+        return Err(AudioReaderError::InvalidValue("reserved bit of the frame header"));
+    }
+    let is_variable_block_size = need_data(reader.read_bit())?;
+
+    let block_size_code = need_data(reader.read_bits(4))?;
+    let rate_code = need_data(reader.read_bits(4))?;
+
+    //
+    // Get the channel assignment of the frame
+    let channel_assignment = match need_data(reader.read_bits(4))?
+    {
+        channel_code @ 0..=7 => ChannelAssignment::Independent(channel_code as u8 + 1),
+        8 => ChannelAssignment::LeftSide,
+        9 => ChannelAssignment::SideRight,
+        10 => ChannelAssignment::MidSide,
+        _ => return Err(AudioReaderError::InvalidValue("channel assignment")),
+    };
+
+    let bits_per_sample = match need_data(reader.read_bits(3))?
+    {
+        0 => stream_info.m_bits_per_sample,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => return Err(AudioReaderError::InvalidValue("sample size")),
+    };
+    if need_data(reader.read_bit())?
+    {
+        return Err(AudioReaderError::InvalidValue("reserved bit of the frame header"));
+    }
+
+    let coded_number = read_utf8_coded_number(reader)?;
+
+    //
+    // The block size and the rate can be stored at the end of the header
+    let block_size: u32 = match block_size_code
+    {
+        0 => return Err(AudioReaderError::InvalidValue("block size")),
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => need_data(reader.read_bits(8))? + 1,
+        7 => need_data(reader.read_bits(16))? + 1,
+        _ => 256 << (block_size_code - 8),
+    };
+
+    let rate: u32 = match rate_code
+    {
+        0 => stream_info.m_rate,
+        1 => 88200,
+        2 => 176400,
+        3 => 192000,
+        4 => 8000,
+        5 => 16000,
+        6 => 22050,
+        7 => 24000,
+        8 => 32000,
+        9 => 44100,
+        10 => 48000,
+        11 => 96000,
+        12 => need_data(reader.read_bits(8))? * 1000,
+        13 => need_data(reader.read_bits(16))?,
+        14 => need_data(reader.read_bits(16))? * 10,
+        _ => return Err(AudioReaderError::InvalidValue("sample rate")),
+    };
+
+    let crc8 = need_data(reader.read_bits(8))? as u8;
+
+    return Ok(FrameHeader
+    {
+        m_is_variable_block_size: is_variable_block_size,
+        m_block_size: block_size,
+        m_rate: rate,
+        m_channel_assignment: channel_assignment,
+        m_bits_per_sample: bits_per_sample,
+        m_coded_number: coded_number,
+        m_crc8: crc8,
+    });
+}
+
+/// Decode the residual coded with partitioned Rice codes and add it to the samples
+fn read_residual(reader: &mut BitReader, block_size: usize, predictor_order: usize, samples: &mut Vec<i64>) -> Result<(), AudioReaderError>
+{
+    let parameter_bits = match need_data(reader.read_bits(2))?
+    {
+        0 => 4,
+        1 => 5,
+        _ => return Err(AudioReaderError::InvalidValue("residual coding method")),
+    };
+    let escape_parameter: u32 = (1 << parameter_bits) - 1;
+
+    let partition_order = need_data(reader.read_bits(4))?;
+    let partition_size = block_size >> partition_order;
+    if partition_size << partition_order != block_size || partition_size < predictor_order
+    {
+        return Err(AudioReaderError::InvalidValue("residual partition order"));
+    }
+
+    for partition_index in 0..(1usize << partition_order)
+    {
+        //
+        // The first partition does not contain the warm-up samples
+        let mut sample_count = partition_size;
+        if partition_index == 0
+        {
+            sample_count -= predictor_order;
+        }
+
+        let rice_parameter = need_data(reader.read_bits(parameter_bits))?;
+        if rice_parameter == escape_parameter
+        {
+            //
+            // The residual of the partition is not coded, each value is written on the same number of bits
+            let raw_bits = need_data(reader.read_bits(5))?;
+            for _i in 0..sample_count
+            {
+                samples.push(need_data(reader.read_signed_bits(raw_bits))?);
+            }
+        }
+        else
+        {
+            for _i in 0..sample_count
+            {
+                let quotient = need_data(reader.read_unary())? as u64;
+                let remainder = need_data(reader.read_bits(rice_parameter))? as u64;
+                let folded_value = (quotient << rice_parameter) | remainder;
+
+                //
+                // Unfold the value: even numbers are positive, odd numbers are negative
+                samples.push(((folded_value >> 1) as i64) ^ ((folded_value & 1) as i64).wrapping_neg());
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// Coefficients of the fixed predictors of order 0 to 4
+pub(crate) const FIXED_PREDICTOR_COEFFICIENTS: [&[i64]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+/// Replace the residual stored after the warm-up samples by the samples, using the predictor coefficients
+fn restore_prediction(samples: &mut Vec<i64>, coefficients: &[i64], shift: u32)
+{
+    let order = coefficients.len();
+    for i in order..samples.len()
+    {
+        let mut prediction: i64 = 0;
+        for (j, coefficient) in coefficients.iter().enumerate()
+        {
+            prediction = prediction.wrapping_add(coefficient.wrapping_mul(samples[i - 1 - j]));
+        }
+        samples[i] = samples[i].wrapping_add(prediction >> shift);
+    }
+}
+
+fn read_subframe(reader: &mut BitReader, block_size: usize, frame_bits_per_sample: u32) -> Result<Vec<i64>, AudioReaderError>
+{
+    //
+    // Read the subframe header
+    if need_data(reader.read_bit())?
+    {
+        return Err(AudioReaderError::InvalidValue("subframe padding"));
+    }
+    let subframe_type = need_data(reader.read_bits(6))?;
+    let mut wasted_bits: u32 = 0;
+    if need_data(reader.read_bit())?
+    {
+        wasted_bits = need_data(reader.read_unary())? + 1;
+    }
+    if wasted_bits >= frame_bits_per_sample
+    {
+        return Err(AudioReaderError::InvalidValue("wasted bits"));
+    }
+    let bits_per_sample = frame_bits_per_sample - wasted_bits;
+
+    let mut samples: Vec<i64> = Vec::with_capacity(block_size);
+    match subframe_type
+    {
+        0 =>
+        {
+            //
+            // CONSTANT: one value for the whole block
+            let value = need_data(reader.read_signed_bits(bits_per_sample))?;
+            samples.resize(block_size, value);
+        }
+        1 =>
+        {
+            //
+            // VERBATIM: the samples are not compressed
+            for _i in 0..block_size
+            {
+                samples.push(need_data(reader.read_signed_bits(bits_per_sample))?);
+            }
+        }
+        8..=12 =>
+        {
+            //
+            // FIXED: the warm-up samples then the residual of a fixed polynomial predictor
+            let order = (subframe_type - 8) as usize;
+            if order > block_size
+            {
+                return Err(AudioReaderError::InvalidValue("predictor order"));
+            }
+            for _i in 0..order
+            {
+                samples.push(need_data(reader.read_signed_bits(bits_per_sample))?);
+            }
+            read_residual(reader, block_size, order, &mut samples)?;
+            restore_prediction(&mut samples, FIXED_PREDICTOR_COEFFICIENTS[order], 0);
+        }
+        32..=63 =>
+        {
+            //
+            // LPC: the warm-up samples, the quantized coefficients then the residual
+            let order = (subframe_type - 31) as usize;
+            if order > block_size
+            {
+                return Err(AudioReaderError::InvalidValue("predictor order"));
+            }
+            for _i in 0..order
+            {
+                samples.push(need_data(reader.read_signed_bits(bits_per_sample))?);
+            }
+
+            let precision = need_data(reader.read_bits(4))? + 1;
+            if precision == 16
+            {
+                return Err(AudioReaderError::InvalidValue("LPC coefficient precision"));
+            }
+            let shift = need_data(reader.read_signed_bits(5))?;
+            if shift < 0
+            {
+                return Err(AudioReaderError::InvalidValue("LPC shift"));
+            }
+            let mut coefficients: Vec<i64> = Vec::with_capacity(order);
+            for _i in 0..order
+            {
+                coefficients.push(need_data(reader.read_signed_bits(precision))?);
+            }
+
+            read_residual(reader, block_size, order, &mut samples)?;
+            restore_prediction(&mut samples, &coefficients, shift as u32);
+        }
+        _ => return Err(AudioReaderError::InvalidValue("subframe type")),
+    }
+
+    if wasted_bits > 0
+    {
+        for sample in samples.iter_mut()
+        {
+            *sample <<= wasted_bits;
+        }
+    }
+
+    return Ok(samples);
+}
+
+/// Decode the frame at the beginning of the data.
+///
+/// # Return
+/// The header of the frame, the interleaved samples of the frame and the size of the frame in bytes
+fn decode_frame(data: &[u8], stream_info: &StreamBlockInfo) -> Result<(FrameHeader, Vec<i32>, usize), AudioReaderError>
+{
+    let mut reader = BitReader::new(data);
+    let header = read_frame_header(&mut reader, stream_info)?;
+    let header_size = reader.get_byte_position();
+    if compute_crc8(&data[..header_size - 1]) != header.m_crc8
+    {
+        return Err(AudioReaderError::InvalidHeaderCrc);
+    }
+    let block_size = header.m_block_size as usize;
+    let bits_per_sample = header.m_bits_per_sample as u32;
+
+    //
+    // Read the subframe of each channel, the side channel has one more bit per sample
+    let channel_count = match header.m_channel_assignment
+    {
+        ChannelAssignment::Independent(channel_count) => channel_count as usize,
+        _ => 2,
+    };
+    let mut channels: Vec<Vec<i64>> = Vec::with_capacity(channel_count);
+    for channel_index in 0..channel_count
+    {
+        let is_side_channel = match header.m_channel_assignment
+        {
+            ChannelAssignment::LeftSide | ChannelAssignment::MidSide => channel_index == 1,
+            ChannelAssignment::SideRight => channel_index == 0,
+            ChannelAssignment::Independent(_) => false,
+        };
+        let subframe_bits_per_sample = if is_side_channel { bits_per_sample + 1 } else { bits_per_sample };
+        channels.push(read_subframe(&mut reader, block_size, subframe_bits_per_sample)?);
+    }
+
+    //
+    // Read the footer which starts on the next byte
+    reader.align_to_byte();
+    let footer = FrameFooter
+    {
+        m_crc16: need_data(reader.read_bits(16))? as u16,
+    };
+    let frame_size = reader.get_byte_position();
+    if compute_crc16(&data[..frame_size - 2]) != footer.m_crc16
+    {
+        return Err(AudioReaderError::InvalidFrameCrc);
+    }
+
+    //
+    // Restore the left and right channels then interleave the samples
+    let mut samples: Vec<i32> = Vec::with_capacity(block_size * channel_count);
+    for i in 0..block_size
+    {
+        match header.m_channel_assignment
+        {
+            ChannelAssignment::Independent(_) =>
+            {
+                for channel in channels.iter()
+                {
+                    samples.push(channel[i] as i32);
+                }
+            }
+            ChannelAssignment::LeftSide =>
+            {
+                let left = channels[0][i];
+                samples.push(left as i32);
+                samples.push(left.wrapping_sub(channels[1][i]) as i32);
+            }
+            ChannelAssignment::SideRight =>
+            {
+                let right = channels[1][i];
+                samples.push(channels[0][i].wrapping_add(right) as i32);
+                samples.push(right as i32);
+            }
+            ChannelAssignment::MidSide =>
+            {
+                let side = channels[1][i];
+                let mid = (channels[0][i] << 1) | (side & 1);
+                samples.push((mid.wrapping_add(side) >> 1) as i32);
+                samples.push((mid.wrapping_sub(side) >> 1) as i32);
+            }
+        }
+    }
+
+    return Ok((header, samples, frame_size));
+}
+
+/**************************************************
+ * Public functions for flac files
+ **************************************************/
+
+pub fn is_flac_file(file: & File) -> bool
+{
+    let start_offset = match (&*file).seek(SeekFrom::Current(0))
+    {
+        Err(_) => return false,
+        Ok(start_offset) => start_offset,
+    };
+    if read_magic_number(file).is_ok()
+    {
+        return true;
+    }
+
+    //
+    // FLAC can also be stored inside an ogg container
+    if (&*file).seek(SeekFrom::Start(start_offset)).is_err()
+    {
+        return false;
+    }
+    return OggFlacSource::new(file).is_ok();
+}
+
+impl AudioReader for FlacReader
+{
+    fn read_information(&self, str_path_to_music : String) -> Result<AudioInformation, AudioReaderError>
+    {
+        //
+        // The metadata blocks are read the same way from a native file and from an Ogg FLAC file
+        let mut source = FlacFileSource::open(str_path_to_music.clone())?;
+        let (mut audio_information, _application_blocks) = read_metadata_information(&mut source)?;
+
+        //
+        // The frames follow the metadata blocks until the end of the stream
+        // The length of an Ogg FLAC stream is only known by demuxing all its pages, the size of the file is close enough
+        let audio_start = source.stream_position()?;
+        let audio_end = match &mut source
+        {
+            FlacFileSource::Native(file) => file.seek(SeekFrom::End(0))?,
+            FlacFileSource::Ogg(_) => std::fs::metadata(&str_path_to_music)?.len(),
+        };
+        audio_information.set_average_bitrate(audio_end.saturating_sub(audio_start));
+        return Ok(audio_information);
+    }
+}
+
+/// Read the next bytes of a flac stream, which must be inside the stream
+///
+/// # Params
+/// source: the flac stream
+/// length: the number of bytes to read
+/// block_name: the name of the block read, used by the error
+/// block_offset: the position of the bytes in the stream, used by the error
+fn read_stream_bytes<R: Read>(source: &mut R, length: u32, block_name: &'static str, block_offset: u64) -> Result<Vec<u8>, AudioReaderError>
+{
+    let mut data: Vec<u8> = Vec::new();
+    source.take(length as u64).read_to_end(&mut data)?;
+    if data.len() != length as usize
+    {
+        return Err(AudioReaderError::TruncatedBlock { m_block_name: block_name, m_offset: block_offset });
+    }
+    return Ok(data);
+}
+
+/// Read the metadata blocks at the beginning of a flac stream and get the information of the audio
+///
+/// # Params
+/// source: the flac stream, placed at its magic number
+///
+/// # Return
+/// The information of the audio and the APPLICATION blocks, in the order of the stream
+fn read_metadata_information<R: Read>(mut source: R) -> Result<(AudioInformation, Vec<ApplicationBlock>), AudioReaderError>
+{
+    //
+    // Init the audio reader
+    let mut audio_reader: AudioInformation = AudioInformation {
+        m_metadata: TrackMetadata::new(),
+        m_rate : 0,
+        m_channel_count: 0,
+        m_bits_per_sample: 0,
+        m_total_samples: 0,
+        m_bitrate: 0,
+        m_codec: std::string::String::from("FLAC"),
+        m_is_lossless: true,
+        m_pictures: Vec::new(),
+        m_tracks: Vec::new(),
+        m_comments: Vec::new(),
+        m_cue_points: Vec::new(),
+        m_loops: Vec::new(),
+    };
+
+    //
+    // Test if it is a flac stream
+    let magic_number = read_stream_bytes(&mut source, 4, "magic number", 0).map_err(|_| AudioReaderError::InvalidMagicNumber)?;
+    check_magic_number(&magic_number)?;
+
+    let mut application_blocks: Vec<ApplicationBlock> = Vec::new();
+    let mut total_samples: u64 = 0;
+    let mut block_offset: u64 = 4;
+    let mut is_last_block = false;
+    while !is_last_block
+    {
+        let header_data = read_stream_bytes(&mut source, 4, "metadata header", block_offset)?;
+        let metadata_header = parse_metadata_header(u32::from_be_bytes(header_data[..].try_into().unwrap()), block_offset + 4)?;
+        let block_name = get_block_name(metadata_header.m_block_type);
+        is_last_block = metadata_header.m_is_last;
+        block_offset = metadata_header.m_offset + metadata_header.m_length as u64;
+
+        //
+        // The StreamInfoBlock is always the first one
+        let is_first_block = metadata_header.m_offset == 8;
+        if is_first_block && metadata_header.m_block_type != 0
+        {
+            return Err(AudioReaderError::MissingBlock("STREAMINFO"));
+        }
+
+        //
+        // Skip the blocks without information like the padding
+        if (metadata_header.m_block_type == 0 && !is_first_block) || metadata_header.m_block_type == 1 || metadata_header.m_block_type > 6
+        {
+            let skipped_length = std::io::copy(&mut (&mut source).take(metadata_header.m_length as u64), &mut std::io::sink())?;
+            if skipped_length != metadata_header.m_length as u64
+            {
+                return Err(AudioReaderError::TruncatedBlock { m_block_name: block_name, m_offset: metadata_header.m_offset });
+            }
+            continue;
+        }
+
+        let block_data = read_stream_bytes(&mut source, metadata_header.m_length, block_name, metadata_header.m_offset)?;
+        if metadata_header.m_block_type == 0
+        {
+            let stream_block = parse_streaminfo_block(&block_data)?;
+            total_samples = stream_block.m_total_sample;
+            audio_reader.m_total_samples = total_samples;
+            audio_reader.m_rate = stream_block.m_rate;
+            audio_reader.m_bits_per_sample = stream_block.m_bits_per_sample;
+            audio_reader.m_channel_count = stream_block.m_channel_count;
+        }
+        else if metadata_header.m_block_type == 2
+        {
+            application_blocks.push(parse_block_application(&block_data)?);
+        }
+        else if metadata_header.m_block_type == 3
+        {
+            let _seek_table_data = parse_seek_table(&block_data)?;
+        }
+        else if metadata_header.m_block_type == 4
+        {
+            VorbisComment::parse(&block_data)?.apply_to_information(&mut audio_reader);
+        }
+        else if metadata_header.m_block_type == 5
+        {
+            let cuesheet_block = parse_cuesheet_block(&block_data)?;
+            audio_reader.m_tracks = convert_cuesheet_to_tracks(&cuesheet_block, total_samples);
+        }
+        else if metadata_header.m_block_type == 6
+        {
+            audio_reader.m_pictures.push(parse_picture_block(&block_data)?);
+        }
+    }
+
+    return Ok((audio_reader, application_blocks));
+}
+
+/// Convert the errors of the decoding to the errors of the Read and Seek traits
+fn convert_to_io_error(error: AudioReaderError) -> std::io::Error
+{
+    return match error
+    {
+        AudioReaderError::Io(io_error) => io_error,
+        _ => std::io::Error::new(std::io::ErrorKind::InvalidData, error),
+    };
+}
+
+impl<R: Read> OggFlacSource<R>
+{
+    /// Find the FLAC logical stream of an ogg stream
+    ///
+    /// # Params
+    /// source: the ogg stream, placed at its first page
+    pub fn new(source: R) -> Result<OggFlacSource<R>, AudioReaderError>
+    {
+        let mut demuxer = OggDemuxer::new(source);
+        let first_packet = demuxer.find_stream(OGG_FLAC_SIGNATURE)?;
+        return Ok(OggFlacSource
+        {
+            m_demuxer: demuxer,
+            m_packet: convert_ogg_flac_first_packet(first_packet.m_data)?,
+            m_packet_position: 0,
+            m_stream_offset: 0,
+            m_checkpoints: Vec::new(),
+            m_stream_length: None,
+        });
+    }
+
+    /// Get the number of ogg pages lost because they were corrupted
+    pub fn get_lost_page_count(&self) -> u32
+    {
+        return self.m_demuxer.get_lost_page_count();
+    }
+
+    /// Read the next packet of the FLAC logical stream
+    ///
+    /// # Return
+    /// False at the end of the stream
+    fn read_next_packet(&mut self) -> Result<bool, AudioReaderError>
+    {
+        let packet = match self.m_demuxer.read_packet()?
+        {
+            None => return Ok(false),
+            Some(packet) => packet,
+        };
+
+        //
+        // The frames start with the first byte of their sync code, which never starts a metadata block
+        if packet.m_is_page_start && packet.m_data.first() == Some(&0xFF)
+            && self.m_checkpoints.last().map_or(true, |checkpoint| checkpoint.m_stream_offset < self.m_stream_offset)
+        {
+            self.m_checkpoints.push(OggFlacCheckpoint
+            {
+                m_stream_offset: self.m_stream_offset,
+                m_page_offset: packet.m_page_offset,
+            });
+        }
+
+        self.m_packet = if self.m_stream_offset == 0 { convert_ogg_flac_first_packet(packet.m_data)? } else { packet.m_data };
+        self.m_packet_position = 0;
+        return Ok(true);
+    }
+
+    /// Read and drop the next bytes of the flac stream
+    ///
+    /// # Params
+    /// stream_offset: the position in the flac stream where the skip stops
+    ///
+    /// # Return
+    /// The position reached, before stream_offset if the end of the stream is reached
+    fn skip_to(&mut self, stream_offset: u64) -> Result<u64, AudioReaderError>
+    {
+        while self.m_stream_offset < stream_offset
+        {
+            if self.m_packet_position >= self.m_packet.len() && !self.read_next_packet()?
+            {
+                break;
+            }
+            let skipped_size = ((self.m_packet.len() - self.m_packet_position) as u64).min(stream_offset - self.m_stream_offset);
+            self.m_packet_position += skipped_size as usize;
+            self.m_stream_offset += skipped_size;
+        }
+        return Ok(self.m_stream_offset);
+    }
+}
+
+/// Convert the first packet of the Ogg FLAC mapping to the beginning of a native flac stream
+fn convert_ogg_flac_first_packet(mut packet: Vec<u8>) -> Result<Vec<u8>, AudioReaderError>
+{
+    //
+    // The packet holds the signature, the version of the mapping, the number of header packets, "fLaC" and the STREAMINFO block
+    if packet.len() < OGG_FLAC_HEADER_SIZE + 4 || !packet.starts_with(OGG_FLAC_SIGNATURE)
+    {
+        return Err(AudioReaderError::InvalidValue("first Ogg FLAC packet"));
+    }
+    if packet[5] != 1
+    {
+        return Err(AudioReaderError::UnsupportedFeature("Ogg FLAC mapping version"));
+    }
+    packet.drain(..OGG_FLAC_HEADER_SIZE);
+    return Ok(packet);
+}
+
+impl<R: Read> Read for OggFlacSource<R>
+{
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize>
+    {
+        while self.m_packet_position >= self.m_packet.len()
+        {
+            if !self.read_next_packet().map_err(convert_to_io_error)?
+            {
+                return Ok(0);
+            }
+        }
+
+        let read_count = buffer.len().min(self.m_packet.len() - self.m_packet_position);
+        buffer[..read_count].copy_from_slice(&self.m_packet[self.m_packet_position..self.m_packet_position + read_count]);
+        self.m_packet_position += read_count;
+        self.m_stream_offset += read_count as u64;
+        return Ok(read_count);
+    }
+}
+
+impl<R: Read + Seek> OggFlacSource<R>
+{
+    /// Move to a position of the flac stream.
+    /// The reading restarts at the nearest known page before the position, then the bytes up to the position are skipped.
+    fn move_to(&mut self, stream_offset: u64) -> Result<(), AudioReaderError>
+    {
+        let current_offset = self.m_stream_offset;
+        let is_checkpoint_closer = self.m_checkpoints.iter()
+            .any(|checkpoint| checkpoint.m_stream_offset > current_offset && checkpoint.m_stream_offset <= stream_offset);
+        if stream_offset < current_offset || is_checkpoint_closer
+        {
+            match self.m_checkpoints.iter().rev().find(|checkpoint| checkpoint.m_stream_offset <= stream_offset)
+            {
+                Some(checkpoint) =>
+                {
+                    self.m_demuxer.seek_to_page(checkpoint.m_page_offset)?;
+                    self.m_stream_offset = checkpoint.m_stream_offset;
+                }
+                None =>
+                {
+                    //
+                    // Restart from the first page, the first packet of the FLAC logical stream is read again
+                    self.m_demuxer.seek_to_page(0)?;
+                    self.m_stream_offset = 0;
+                }
+            }
+            self.m_packet.clear();
+            self.m_packet_position = 0;
+        }
+
+        //
+        // Like a file, the position can be after the end of the stream, where nothing can be read
+        self.skip_to(stream_offset)?;
+        self.m_stream_offset = stream_offset;
+        return Ok(());
+    }
+
+    /// Get the size of the flac stream, by reading the ogg stream up to its end the first time
+    fn get_stream_length(&mut self) -> Result<u64, AudioReaderError>
+    {
+        if let Some(stream_length) = self.m_stream_length
+        {
+            return Ok(stream_length);
+        }
+
+        let current_offset = self.m_stream_offset;
+        if let Some(checkpoint) = self.m_checkpoints.last()
+        {
+            if checkpoint.m_stream_offset > current_offset
+            {
+                self.m_demuxer.seek_to_page(checkpoint.m_page_offset)?;
+                self.m_stream_offset = checkpoint.m_stream_offset;
+                self.m_packet.clear();
+                self.m_packet_position = 0;
+            }
+        }
+        let stream_length = self.skip_to(u64::MAX)?;
+        self.m_stream_length = Some(stream_length);
+        self.move_to(current_offset)?;
+        return Ok(stream_length);
+    }
+}
+
+impl<R: Read + Seek> Seek for OggFlacSource<R>
+{
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64>
+    {
+        let (base_offset, delta) = match position
+        {
+            SeekFrom::Start(stream_offset) => (stream_offset, 0),
+            SeekFrom::Current(delta) => (self.m_stream_offset, delta),
+            SeekFrom::End(delta) => (self.get_stream_length().map_err(convert_to_io_error)?, delta),
+        };
+        let stream_offset = if delta < 0 { base_offset.checked_sub(delta.unsigned_abs()) } else { base_offset.checked_add(delta as u64) };
+        let stream_offset = stream_offset.ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+        self.move_to(stream_offset).map_err(convert_to_io_error)?;
+        return Ok(stream_offset);
+    }
+}
+
+impl FlacFileSource
+{
+    /// Open a flac file, native or inside an ogg container
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the file
+    pub fn open(str_path_to_music: String) -> Result<FlacFileSource, AudioReaderError>
+    {
+        let mut file = File::open(str_path_to_music)?;
+        let mut capture_pattern = [0u8; 4];
+        let is_ogg_file = file.read_exact(&mut capture_pattern).is_ok() && &capture_pattern == b"OggS";
+        file.seek(SeekFrom::Start(0))?;
+        if is_ogg_file
+        {
+            return Ok(FlacFileSource::Ogg(Box::new(OggFlacSource::new(file)?)));
+        }
+        return Ok(FlacFileSource::Native(file));
+    }
+}
+
+impl Read for FlacFileSource
+{
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize>
+    {
+        return match self
+        {
+            FlacFileSource::Native(file) => file.read(buffer),
+            FlacFileSource::Ogg(ogg_source) => ogg_source.read(buffer),
+        };
+    }
+}
+
+impl Seek for FlacFileSource
+{
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64>
+    {
+        return match self
+        {
+            FlacFileSource::Native(file) => file.seek(position),
+            FlacFileSource::Ogg(ogg_source) => ogg_source.seek(position),
+        };
+    }
+}
+
+impl FlacStreamDecoder
+{
+    /// Create a decoder waiting for the first bytes of a flac stream
+    pub fn new() -> FlacStreamDecoder
+    {
+        return FlacStreamDecoder
+        {
+            m_state: StreamDecoderState::MagicNumber,
+            m_streaminfo: StreamBlockInfo::default(),
+            m_seek_points: Vec::new(),
+            m_first_frame_offset: 0,
+            m_buffer: Vec::new(),
+            m_buffer_position: 0,
+            m_is_end_of_stream: false,
+            m_buffer_stream_offset: 0,
+            m_next_sample: 0,
+            m_is_resynchronizing: false,
+            m_frame_errors: Vec::new(),
+        };
+    }
+
+    /// Give the next bytes of the stream to the decoder
+    ///
+    /// # Params
+    /// data: the bytes following the ones already given, of any size
+    pub fn push_data(&mut self, data: &[u8])
+    {
+        self.drop_decoded_bytes();
+        self.m_buffer.extend_from_slice(data);
+    }
+
+    /// Tell the decoder that all the bytes of the stream have been given.
+    /// The next reads decode the remaining frames, then return None.
+    pub fn finish(&mut self)
+    {
+        self.m_is_end_of_stream = true;
+    }
+
+    /// Return true when all the frames of the stream have been returned
+    pub fn is_finished(&self) -> bool
+    {
+        return self.m_state == StreamDecoderState::Frames
+            && self.m_is_end_of_stream
+            && self.m_buffer_position >= self.m_buffer.len()
+            && !self.m_is_resynchronizing;
+    }
+
+    /// Return true when all the metadata blocks have been read, the information of the stream are then available
+    pub fn is_metadata_read(&self) -> bool
+    {
+        return self.m_state == StreamDecoderState::Frames;
+    }
+
+    /// Remove from the buffer the bytes already decoded
+    fn drop_decoded_bytes(&mut self)
+    {
+        self.m_buffer.drain(..self.m_buffer_position);
+        self.m_buffer_stream_offset += self.m_buffer_position as u64;
+        self.m_buffer_position = 0;
+    }
+
+    /// Read the next bytes of a source at the end of the buffer
+    ///
+    /// # Params
+    /// source: the source of the stream
+    fn read_from<R: Read>(&mut self, source: &mut R) -> Result<(), AudioReaderError>
+    {
+        self.drop_decoded_bytes();
+        let previous_length = self.m_buffer.len();
+        self.m_buffer.resize(previous_length + DECODER_READ_SIZE, 0);
+        let read_count = loop
+        {
+            match source.read(&mut self.m_buffer[previous_length..])
+            {
+                Ok(read_count) => break read_count,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) =>
+                {
+                    self.m_buffer.truncate(previous_length);
+                    return Err(AudioReaderError::Io(error));
+                }
+            }
+        };
+        self.m_buffer.truncate(previous_length + read_count);
+        self.m_is_end_of_stream = read_count == 0;
+
+        return Ok(());
+    }
+
+    /// Forget the buffered bytes and restart the decoding at another position of the stream
+    ///
+    /// # Params
+    /// stream_offset: the position in the stream of the next bytes given to the decoder
+    fn restart_at(&mut self, stream_offset: u64)
+    {
+        self.m_buffer.clear();
+        self.m_buffer_position = 0;
+        self.m_buffer_stream_offset = stream_offset;
+        self.m_is_end_of_stream = false;
+        self.m_is_resynchronizing = false;
+    }
+
+    /// Read the metadata blocks available in the buffer
+    ///
+    /// # Return
+    /// True when all the metadata blocks have been read, false if more bytes are needed
+    fn read_metadata_blocks(&mut self) -> Result<bool, AudioReaderError>
+    {
+        while self.m_state != StreamDecoderState::Frames
+        {
+            let available_data = &self.m_buffer[self.m_buffer_position..];
+            let stream_offset = self.m_buffer_stream_offset + self.m_buffer_position as u64;
+            if self.m_state == StreamDecoderState::MagicNumber
+            {
+                if available_data.len() < 4
+                {
+                    if self.m_is_end_of_stream
+                    {
+                        return Err(AudioReaderError::InvalidMagicNumber);
+                    }
+                    return Ok(false);
+                }
+                check_magic_number(&available_data[..4])?;
+                self.m_buffer_position += 4;
+                self.m_state = StreamDecoderState::StreamInfo;
+                continue;
+            }
+
+            //
+            // Wait for the whole block, its content is decoded from the buffer
+            if available_data.len() < 4
+            {
+                if self.m_is_end_of_stream
+                {
+                    return Err(AudioReaderError::TruncatedBlock { m_block_name: "metadata header", m_offset: stream_offset });
+                }
+                return Ok(false);
+            }
+            let header_value = u32::from_be_bytes(available_data[..4].try_into().unwrap());
+            let metadata_header = parse_metadata_header(header_value, stream_offset + 4)?;
+            if self.m_state == StreamDecoderState::StreamInfo && metadata_header.m_block_type != 0
+            {
+                return Err(AudioReaderError::MissingBlock("STREAMINFO"));
+            }
+            let block_end = 4 + metadata_header.m_length as usize;
+            if available_data.len() < block_end
+            {
+                if self.m_is_end_of_stream
+                {
+                    return Err(AudioReaderError::TruncatedBlock { m_block_name: get_block_name(metadata_header.m_block_type), m_offset: metadata_header.m_offset });
+                }
+                return Ok(false);
+            }
+
+            //
+            // Only the STREAMINFO and SEEKTABLE blocks are needed to decode the audio
+            let block_data = &available_data[4..block_end];
+            if metadata_header.m_block_type == 0
+            {
+                self.m_streaminfo = parse_streaminfo_block(block_data)?;
+            }
+            else if metadata_header.m_block_type == 3
+            {
+                self.m_seek_points.append(&mut parse_seek_table(block_data)?.m_seekPoints);
+            }
+            self.m_buffer_position += block_end;
+
+            if metadata_header.m_is_last
+            {
+                //
+                // Placeholder points do not reference any frame
+                self.m_seek_points.retain(|seek_point| seek_point.m_sample_number_first_sample != u64::MAX);
+                self.m_first_frame_offset = stream_offset + block_end as u64;
+                self.m_state = StreamDecoderState::Frames;
+            }
+            else
+            {
+                self.m_state = StreamDecoderState::MetadataBlocks;
+            }
+        }
+
+        return Ok(true);
+    }
+
+    /// Get the index of the first sample of a frame from its header
+    fn get_frame_first_sample(&self, header: &FrameHeader) -> u64
+    {
+        if header.m_is_variable_block_size
+        {
+            return header.m_coded_number;
+        }
+
+        //
+        // All the frames of a fixed block size stream have the same size, except the last one
+        let mut nominal_block_size = self.m_streaminfo.m_max_block_size as u64;
+        if nominal_block_size == 0
+        {
+            nominal_block_size = header.m_block_size as u64;
+        }
+        return header.m_coded_number * nominal_block_size;
+    }
+
+    /// Create a block of silence replacing lost samples
+    fn create_silence_block(&mut self, sample_count: u64) -> DecodedBlock
+    {
+        let channel_count = self.m_streaminfo.m_channel_count;
+        let silence_block = DecodedBlock
+        {
+            m_first_sample: self.m_next_sample,
+            m_rate: self.m_streaminfo.m_rate,
+            m_channel_count: channel_count,
+            m_bits_per_sample: self.m_streaminfo.m_bits_per_sample,
+            m_samples: vec![0; sample_count as usize * channel_count as usize],
+        };
+        self.m_next_sample += sample_count;
+        return silence_block;
+    }
+
+    /// Move the buffer position to the next frame sync code after the current position
+    fn skip_to_next_sync_code(&mut self)
+    {
+        let mut position = self.m_buffer_position + 1;
+        while position + 1 < self.m_buffer.len()
+        {
+            if self.m_buffer[position] == 0xFF && self.m_buffer[position + 1] & 0xFE == 0xF8
+            {
+                break;
+            }
+            position += 1;
+        }
+
+        //
+        // Keep the last byte, it can be the beginning of a sync code
+        self.m_buffer_position = position.min(self.m_buffer.len());
+    }
+
+    /// Return true when the decoding of the frame must wait for more bytes
+    fn is_waiting_for_data(&self, error: &AudioReaderError) -> bool
+    {
+        return matches!(error, AudioReaderError::NotEnoughData)
+            && !self.m_is_end_of_stream
+            && self.m_buffer.len() - self.m_buffer_position < MAX_FRAME_SIZE;
+    }
+
+    /// Decode the next frame of the stream.
+    /// When a frame is corrupted, the error is saved, the decoder searches the next valid frame
+    /// and the lost samples are returned as silence.
+    ///
+    /// # Return
+    /// The samples of the frame, or None when more bytes are needed or when the stream is finished
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        if !self.read_metadata_blocks()?
+        {
+            return Ok(None);
+        }
+
+        loop
+        {
+            if self.m_buffer_position < self.m_buffer.len()
+            {
+                match decode_frame(&self.m_buffer[self.m_buffer_position..], &self.m_streaminfo)
+                {
+                    Ok((header, samples, frame_size)) =>
+                    {
+                        //
+                        // After a corrupted frame, fill the gap with silence before the valid frame
+                        // The silence is split in blocks to keep a bounded memory when a lot of frames are lost.
+                        // A frame placed after the end of the stream has a wrong number and does not define the gap.
+                        if self.m_is_resynchronizing
+                        {
+                            let frame_first_sample = self.get_frame_first_sample(&header);
+                            let total_samples = self.m_streaminfo.m_total_sample;
+                            let is_frame_in_stream = total_samples == 0 || frame_first_sample < total_samples;
+                            if is_frame_in_stream && frame_first_sample > self.m_next_sample
+                            {
+                                let silence_size = (frame_first_sample - self.m_next_sample).min(MAX_SILENCE_BLOCK_SIZE);
+                                return Ok(Some(self.create_silence_block(silence_size)));
+                            }
+                            self.m_is_resynchronizing = false;
+                        }
+
+                        self.m_buffer_position += frame_size;
+                        let decoded_block = DecodedBlock
+                        {
+                            m_first_sample: self.m_next_sample,
+                            m_rate: header.m_rate,
+                            m_channel_count: (samples.len() / header.m_block_size as usize) as u8,
+                            m_bits_per_sample: header.m_bits_per_sample,
+                            m_samples: samples,
+                        };
+                        self.m_next_sample += header.m_block_size as u64;
+                        return Ok(Some(decoded_block));
+                    }
+                    Err(error) if self.is_waiting_for_data(&error) => return Ok(None),
+                    Err(error) =>
+                    {
+                        //
+                        // Only the first error is saved, the next ones come from the search of the next frame
+                        if !self.m_is_resynchronizing
+                        {
+                            self.m_is_resynchronizing = true;
+                            self.m_frame_errors.push(FlacFrameError
+                            {
+                                m_sample_position: self.m_next_sample,
+                                m_byte_position: self.m_buffer_stream_offset + self.m_buffer_position as u64,
+                                m_error: error,
+                            });
+                        }
+                        self.skip_to_next_sync_code();
+                        continue;
+                    }
+                }
+            }
+            else if self.m_is_end_of_stream && self.m_is_resynchronizing
+            {
+                //
+                // The end of the stream is corrupted, replace the lost block by silence up to the end of the stream
+                self.m_is_resynchronizing = false;
+                let total_samples = self.m_streaminfo.m_total_sample;
+                if total_samples > self.m_next_sample
+                {
+                    let silence_size = (total_samples - self.m_next_sample).min(MAX_SILENCE_BLOCK_SIZE);
+                    return Ok(Some(self.create_silence_block(silence_size)));
+                }
+            }
+
+            return Ok(None);
+        }
+    }
+
+    /// Search the first valid frame starting at or after the current position, without decoding it
+    ///
+    /// # Return
+    /// The position in the stream and the index of the first sample of the frame found,
+    /// None when more bytes are needed or when there is no frame until the end of the stream
+    fn find_next_frame(&mut self) -> Option<(u64, u64)>
+    {
+        while self.m_buffer_position < self.m_buffer.len()
+        {
+            if self.m_buffer[self.m_buffer_position] == 0xFF
+            {
+                match decode_frame(&self.m_buffer[self.m_buffer_position..], &self.m_streaminfo)
+                {
+                    Ok((header, _samples, _frame_size)) =>
+                    {
+                        self.m_next_sample = self.get_frame_first_sample(&header);
+                        let frame_offset = self.m_buffer_stream_offset + self.m_buffer_position as u64;
+                        return Some((frame_offset, self.m_next_sample));
+                    }
+                    Err(error) if self.is_waiting_for_data(&error) => return None,
+                    Err(_) => {}
+                }
+            }
+            self.skip_to_next_sync_code();
+        }
+
+        return None;
+    }
+
+    /// Get all the corrupted frames found since the beginning of the stream
+    pub fn get_frame_errors(&self) -> &Vec<FlacFrameError>
+    {
+        return &self.m_frame_errors;
+    }
+
+    /// Get the sample rate of the stream, 0 until the metadata blocks have been read
+    pub fn get_rate(&self) -> u32
+    {
+        return self.m_streaminfo.m_rate;
+    }
+
+    /// Get the number of channels of the stream, 0 until the metadata blocks have been read
+    pub fn get_channel_count(&self) -> u8
+    {
+        return self.m_streaminfo.m_channel_count;
+    }
+
+    /// Get the bits per sample of the stream, 0 until the metadata blocks have been read
+    pub fn get_bits_per_sample(&self) -> u8
+    {
+        return self.m_streaminfo.m_bits_per_sample;
+    }
+
+    /// Get the number of samples per channel of the stream, 0 if unknown
+    pub fn get_total_samples(&self) -> u64
+    {
+        return self.m_streaminfo.m_total_sample;
+    }
+
+    /// Get the MD5 signature of the audio stored in the STREAMINFO block, 0 if not stored
+    pub fn get_md5(&self) -> u128
+    {
+        return self.m_streaminfo.m_md5;
+    }
+}
+
+impl FlacDecoder<FlacFileSource>
+{
+    /// Open a flac file, native or Ogg FLAC, and read its metadata blocks to be ready to decode the first frame
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    pub fn open(str_path_to_music: String) -> Result<FlacDecoder<FlacFileSource>, AudioReaderError>
+    {
+        return FlacDecoder::new(FlacFileSource::open(str_path_to_music)?);
+    }
+}
+
+impl<R: Read> FlacDecoder<R>
+{
+    /// Create a decoder reading a flac stream from a source, and read its metadata blocks
+    /// to be ready to decode the first frame.
+    /// The positions of the seek table are relative to the position of the source when the decoder is created.
+    ///
+    /// # Params
+    /// source: the source of the flac stream, placed at the beginning of the stream
+    pub fn new(source: R) -> Result<FlacDecoder<R>, AudioReaderError>
+    {
+        let mut decoder = FlacDecoder
+        {
+            m_source: source,
+            m_stream_decoder: FlacStreamDecoder::new(),
+            m_pending_block: None,
+            m_end_sample: None,
+            m_replay_gain: None,
+        };
+        while !decoder.m_stream_decoder.read_metadata_blocks()?
+        {
+            decoder.m_stream_decoder.read_from(&mut decoder.m_source)?;
+        }
+
+        return Ok(decoder);
+    }
+
+    /// Decode the next frame of the source.
+    /// When a frame is corrupted, the error is saved, the decoder searches the next valid frame
+    /// and the lost samples are returned as silence.
+    ///
+    /// # Return
+    /// The samples of the frame, or None when all the frames of the track have been decoded
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        let mut decoded_block = match self.decode_next_block()?
+        {
+            None => return Ok(None),
+            Some(decoded_block) => decoded_block,
+        };
+
+        //
+        // Cut the block at the end of the selected track
+        if let Some(end_sample) = self.m_end_sample
+        {
+            if decoded_block.m_first_sample >= end_sample
+            {
+                return Ok(None);
+            }
+            let kept_sample_count = (end_sample - decoded_block.m_first_sample) * decoded_block.m_channel_count as u64;
+            if (decoded_block.m_samples.len() as u64) > kept_sample_count
+            {
+                decoded_block.m_samples.truncate(kept_sample_count as usize);
+            }
+        }
+        if let Some(replay_gain) = &self.m_replay_gain
+        {
+            replay_gain.process(&mut decoded_block);
+        }
+
+        return Ok(Some(decoded_block));
+    }
+
+    /// Decode the next frame of the source, or the silence replacing corrupted frames
+    fn decode_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        if let Some(pending_block) = self.m_pending_block.take()
+        {
+            return Ok(Some(pending_block));
+        }
+
+        loop
+        {
+            if let Some(decoded_block) = self.m_stream_decoder.read_next_block()?
+            {
+                return Ok(Some(decoded_block));
+            }
+            if self.m_stream_decoder.is_finished()
+            {
+                return Ok(None);
+            }
+            self.m_stream_decoder.read_from(&mut self.m_source)?;
+        }
+    }
+
+    /// Apply the gain of the title to the samples decoded from now on
+    ///
+    /// # Params
+    /// replay_gain: the gain chosen by the settings of the player, None to decode the samples unchanged
+    pub fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        self.m_replay_gain = replay_gain;
+    }
+
+    /// Get all the corrupted frames found since the source was opened
+    pub fn get_frame_errors(&self) -> &Vec<FlacFrameError>
+    {
+        return self.m_stream_decoder.get_frame_errors();
+    }
+
+    /// Decode all the remaining frames of the source
+    ///
+    /// # Return
+    /// The interleaved samples of all the frames
+    pub fn read_all_samples(&mut self) -> Result<Vec<i32>, AudioReaderError>
+    {
+        let mut samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = self.read_next_block()?
+        {
+            samples.extend_from_slice(&decoded_block.m_samples);
+        }
+
+        return Ok(samples);
+    }
+
+    pub fn get_rate(&self) -> u32
+    {
+        return self.m_stream_decoder.get_rate();
+    }
+
+    pub fn get_channel_count(&self) -> u8
+    {
+        return self.m_stream_decoder.get_channel_count();
+    }
+
+    pub fn get_bits_per_sample(&self) -> u8
+    {
+        return self.m_stream_decoder.get_bits_per_sample();
+    }
+
+    /// Get the number of samples per channel of the stream, 0 if unknown
+    pub fn get_total_samples(&self) -> u64
+    {
+        return self.m_stream_decoder.get_total_samples();
+    }
+
+    /// Get the MD5 signature of the audio stored in the STREAMINFO block, 0 if not stored
+    pub fn get_md5(&self) -> u128
+    {
+        return self.m_stream_decoder.get_md5();
+    }
+}
+
+impl<R: Read + Seek> FlacDecoder<R>
+{
+    /// Restrict the decoding to one track of the file.
+    /// The next read block starts at the beginning of the track and the reading stops at its end.
+    ///
+    /// # Params
+    /// track: a track of the file, coming from its cuesheet
+    ///
+    /// # Return
+    /// The InvalidValue error if the track is empty or ends after the end of the stream
+    pub fn select_track(&mut self, track: &VirtualTrack) -> Result<(), AudioReaderError>
+    {
+        let total_samples = self.get_total_samples();
+        if track.m_start_sample >= track.m_end_sample || (total_samples != 0 && track.m_end_sample > total_samples)
+        {
+            return Err(AudioReaderError::InvalidValue("track position"));
+        }
+        self.m_end_sample = Some(track.m_end_sample);
+        return self.seek(track.m_start_sample);
+    }
+
+    /// Move the decoder to the first valid frame starting at or after a position of the source
+    ///
+    /// # Params
+    /// stream_offset: the position in the source where the search starts
+    ///
+    /// # Return
+    /// The position in the source and the index of the first sample of the frame found, None if there is no frame after the position
+    fn move_to_frame_after(&mut self, stream_offset: u64) -> Result<Option<(u64, u64)>, AudioReaderError>
+    {
+        self.m_source.seek(SeekFrom::Start(stream_offset))?;
+        self.m_stream_decoder.restart_at(stream_offset);
+        self.m_pending_block = None;
+
+        loop
+        {
+            if let Some(frame_position) = self.m_stream_decoder.find_next_frame()
+            {
+                return Ok(Some(frame_position));
+            }
+            if self.m_stream_decoder.is_finished()
+            {
+                return Ok(None);
+            }
+            self.m_stream_decoder.read_from(&mut self.m_source)?;
+        }
+    }
+
+    /// Move the decoder to a sample of the stream, the next read block starts with this sample.
+    /// The nearest previous point of the seek table gives the frame where the decoding starts.
+    /// Without seek table, this frame is found by a bisection on the frames of the source.
+    ///
+    /// # Params
+    /// sample_index: index of the sample in the stream
+    pub fn seek(&mut self, sample_index: u64) -> Result<(), AudioReaderError>
+    {
+        let total_samples = self.get_total_samples();
+        if total_samples != 0 && sample_index >= total_samples
+        {
+            return Err(AudioReaderError::InvalidSeekPosition);
+        }
+
+        //
+        // Find a frame starting before the sample
+        let first_frame_offset = self.m_stream_decoder.m_first_frame_offset;
+        let seek_points = &self.m_stream_decoder.m_seek_points;
+        let nearest_seek_point = seek_points.iter()
+            .filter(|seek_point| seek_point.m_sample_number_first_sample <= sample_index)
+            .max_by_key(|seek_point| seek_point.m_sample_number_first_sample);
+        let mut start_offset = first_frame_offset;
+        if let Some(seek_point) = nearest_seek_point
+        {
+            start_offset += seek_point.m_sample_offset;
+        }
+        else if seek_points.is_empty()
+        {
+            let mut low_offset = first_frame_offset;
+            let mut high_offset = self.m_source.seek(SeekFrom::End(0))?;
+            while high_offset.saturating_sub(low_offset) > BISECTION_STOP_SIZE
+            {
+                let middle_offset = low_offset + (high_offset - low_offset) / 2;
+                match self.move_to_frame_after(middle_offset)?
+                {
+                    Some((frame_offset, frame_first_sample)) if frame_first_sample <= sample_index => low_offset = frame_offset,
+                    _ => high_offset = middle_offset,
+                }
+            }
+            start_offset = low_offset;
+        }
+
+        //
+        // Decode the frames until the one containing the sample and keep the end of this frame
+        if self.move_to_frame_after(start_offset)?.is_none()
+        {
+            return Err(AudioReaderError::InvalidSeekPosition);
+        }
+        while let Some(mut decoded_block) = self.decode_next_block()?
+        {
+            let channel_count = decoded_block.m_channel_count as u64;
+            let block_sample_count = decoded_block.m_samples.len() as u64 / channel_count;
+            if decoded_block.m_first_sample + block_sample_count > sample_index
+            {
+                let skipped_sample_count = sample_index.saturating_sub(decoded_block.m_first_sample);
+                decoded_block.m_samples.drain(..(skipped_sample_count * channel_count) as usize);
+                decoded_block.m_first_sample += skipped_sample_count;
+                self.m_pending_block = Some(decoded_block);
+                return Ok(());
+            }
+        }
+
+        return Err(AudioReaderError::InvalidSeekPosition);
+    }
+}
+
+/// Add samples to the MD5 of the audio.
+/// As required by the flac format, each sample is hashed as a signed little endian integer
+/// on the minimal number of bytes needed by the bits per sample.
+pub(crate) fn update_md5(md5_context: &mut md5::Context, samples: &[i32], bits_per_sample: u8)
+{
+    let byte_count = (bits_per_sample as usize + 7) / 8;
+    let mut bytes: Vec<u8> = Vec::with_capacity(samples.len() * byte_count);
+    for sample in samples
+    {
+        bytes.extend_from_slice(&sample.to_le_bytes()[..byte_count]);
+    }
+    md5_context.consume(&bytes);
+}
+
+/// Cut a flac stream in parts starting at frames, to decode them in parallel
+///
+/// # Params
+/// decoder: a decoder of the stream, whose position is lost
+/// stream_length: the size of the stream in bytes
+/// chunk_count: the number of parts wanted, fewer parts are returned if the frames are too big
+fn split_stream_in_chunks(decoder: &mut FlacDecoder<FlacFileSource>, stream_length: u64, chunk_count: u64) -> Result<Vec<ParallelChunk>, AudioReaderError>
+{
+    let first_frame_offset = decoder.m_stream_decoder.m_first_frame_offset;
+    let frames_length = stream_length.saturating_sub(first_frame_offset);
+
+    let mut chunks: Vec<ParallelChunk> = Vec::new();
+    for chunk_index in 0..chunk_count
+    {
+        //
+        // Start at the point of the seek table before the wanted position if there is one, it is the beginning of a frame.
+        // Otherwise, the frame is found by scanning the stream from the wanted position.
+        let mut search_offset = first_frame_offset + frames_length * chunk_index / chunk_count;
+        let previous_chunk_offset = chunks.last().map(|chunk: &ParallelChunk| chunk.m_stream_offset);
+        let seek_point_offset = decoder.m_stream_decoder.m_seek_points.iter()
+            .filter(|seek_point| seek_point.m_sample_number_first_sample != u64::MAX)
+            .map(|seek_point| first_frame_offset + seek_point.m_sample_offset)
+            .filter(|frame_offset| *frame_offset <= search_offset && previous_chunk_offset.map_or(true, |chunk_offset| *frame_offset > chunk_offset))
+            .max();
+        if let Some(frame_offset) = seek_point_offset
+        {
+            search_offset = frame_offset;
+        }
+
+        match decoder.move_to_frame_after(search_offset)?
+        {
+            Some((frame_offset, frame_first_sample)) =>
+            {
+                if chunks.last().map_or(true, |chunk| frame_offset > chunk.m_stream_offset && frame_first_sample > chunk.m_first_sample)
+                {
+                    chunks.push(ParallelChunk { m_stream_offset: frame_offset, m_first_sample: frame_first_sample, m_end_sample: None });
+                }
+            }
+            None => break,
+        }
+    }
+
+    //
+    // Each part stops where the next one starts
+    for chunk_index in 1..chunks.len()
+    {
+        chunks[chunk_index - 1].m_end_sample = Some(chunks[chunk_index].m_first_sample);
+    }
+    return Ok(chunks);
+}
+
+/// Decode one part of a flac stream
+///
+/// # Return
+/// The decoded blocks of the part and the corrupted frames found
+fn decode_chunk(decoder: &mut FlacDecoder<FlacFileSource>, chunk: &ParallelChunk) -> Result<(Vec<DecodedBlock>, Vec<FlacFrameError>), AudioReaderError>
+{
+    decoder.m_end_sample = chunk.m_end_sample;
+    let mut decoded_blocks: Vec<DecodedBlock> = Vec::new();
+    if decoder.move_to_frame_after(chunk.m_stream_offset)?.is_some()
+    {
+        while let Some(decoded_block) = decoder.read_next_block()?
+        {
+            decoded_blocks.push(decoded_block);
+        }
+    }
+    return Ok((decoded_blocks, std::mem::take(&mut decoder.m_stream_decoder.m_frame_errors)));
+}
+
+impl FlacReader
+{
+    /// Decode all the audio of a flac file and compare its MD5 with the signature stored in the file
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    ///
+    /// # Return
+    /// The state of the MD5 signature and the corrupted frames found
+    pub fn verify(&self, str_path_to_music: String) -> Result<FlacVerification, AudioReaderError>
+    {
+        let decoder = FlacDecoder::open(str_path_to_music.clone())?;
+        let bits_per_sample = decoder.get_bits_per_sample();
+        let stored_md5 = decoder.get_md5();
+        drop(decoder);
+
+        let mut md5_context = md5::Context::new();
+        let frame_errors = self.decode_parallel(str_path_to_music, 0, |decoded_block|
+        {
+            update_md5(&mut md5_context, &decoded_block.m_samples, bits_per_sample);
+        })?;
+
+        let md5_status = if stored_md5 == 0
+        {
+            FlacMd5Status::NoSignature
+        }
+        else if u128::from_be_bytes(md5_context.compute().0) == stored_md5
+        {
+            FlacMd5Status::Match
+        }
+        else
+        {
+            FlacMd5Status::Mismatch
+        };
+
+        return Ok(FlacVerification
+        {
+            m_md5_status: md5_status,
+            m_frame_errors: frame_errors,
+        });
+    }
+
+    /// Decode all the audio of a flac file on several threads, for the analysis of whole files.
+    /// The file is cut in parts starting at frames found through the seek table or by scanning the stream,
+    /// each part is decoded by a thread and the blocks are given back in the order of the stream.
+    /// The playback must use FlacDecoder, which returns the first samples without decoding the whole file.
+    /// An Ogg FLAC file cannot be cut without reading it entirely, so it is decoded by the calling thread.
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    /// thread_count: the number of threads decoding the file, 0 to use all the processors
+    /// on_decoded_block: called with each decoded block, in the order of the stream
+    ///
+    /// # Return
+    /// The corrupted frames found, in the order of the stream
+    pub fn decode_parallel<F: FnMut(DecodedBlock)>(&self, str_path_to_music: String, thread_count: usize, mut on_decoded_block: F) -> Result<Vec<FlacFrameError>, AudioReaderError>
+    {
+        let thread_count = if thread_count == 0
+        {
+            std::thread::available_parallelism().map_or(1, |processor_count| processor_count.get())
+        }
+        else
+        {
+            thread_count
+        };
+
+        let mut decoder = FlacDecoder::open(str_path_to_music.clone())?;
+        let mut chunks: Vec<ParallelChunk> = Vec::new();
+        if let FlacFileSource::Native(_) = decoder.m_source
+        {
+            let stream_length = decoder.m_source.seek(SeekFrom::End(0))?;
+            let chunk_count = (stream_length / MIN_PARALLEL_CHUNK_SIZE).min(thread_count as u64 * PARALLEL_CHUNKS_PER_THREAD);
+            if thread_count > 1 && chunk_count > 1
+            {
+                chunks = split_stream_in_chunks(&mut decoder, stream_length, chunk_count)?;
+            }
+        }
+
+        //
+        // A small file is decoded by the calling thread, from the beginning
+        if chunks.len() < 2
+        {
+            let mut decoder = FlacDecoder::open(str_path_to_music)?;
+            while let Some(decoded_block) = decoder.read_next_block()?
+            {
+                on_decoded_block(decoded_block);
+            }
+            return Ok(decoder.m_stream_decoder.m_frame_errors);
+        }
+        drop(decoder);
+
+        //
+        // The threads take the parts in order, and wait when too many decoded parts are waiting to be given back
+        let max_waiting_chunk_count = thread_count * 2;
+        let state = std::sync::Mutex::new(ParallelDecodingState { m_next_chunk: 0, m_delivered_chunk_count: 0, m_is_cancelled: false });
+        let state_changed = std::sync::Condvar::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut frame_errors: Vec<FlacFrameError> = Vec::new();
+        let mut result: Result<(), AudioReaderError> = Ok(());
+
+        std::thread::scope(|scope|
+        {
+            for _i in 0..thread_count.min(chunks.len())
+            {
+                let sender = sender.clone();
+                let (chunks, state, state_changed, str_path_to_music) = (&chunks, &state, &state_changed, &str_path_to_music);
+                scope.spawn(move ||
+                {
+                    let mut decoder = match FlacDecoder::open(str_path_to_music.clone())
+                    {
+                        Err(error) =>
+                        {
+                            let _ = sender.send((0, Err(error)));
+                            return;
+                        }
+                        Ok(decoder) => decoder,
+                    };
+                    loop
+                    {
+                        let chunk_index =
+                        {
+                            let mut state = state.lock().unwrap();
+                            while !state.m_is_cancelled && state.m_next_chunk >= state.m_delivered_chunk_count + max_waiting_chunk_count
+                            {
+                                state = state_changed.wait(state).unwrap();
+                            }
+                            if state.m_is_cancelled || state.m_next_chunk >= chunks.len()
+                            {
+                                return;
+                            }
+                            state.m_next_chunk += 1;
+                            state.m_next_chunk - 1
+                        };
+                        if sender.send((chunk_index, decode_chunk(&mut decoder, &chunks[chunk_index]))).is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            //
+            // Give back the decoded parts in order
+            let mut decoded_chunks: std::collections::BTreeMap<usize, (Vec<DecodedBlock>, Vec<FlacFrameError>)> = std::collections::BTreeMap::new();
+            let mut next_chunk_index = 0;
+            while next_chunk_index < chunks.len()
+            {
+                match receiver.recv()
+                {
+                    Err(_) => break,
+                    Ok((_chunk_index, Err(error))) =>
+                    {
+                        result = Err(error);
+                        break;
+                    }
+                    Ok((chunk_index, Ok(decoded_chunk))) =>
+                    {
+                        decoded_chunks.insert(chunk_index, decoded_chunk);
+                    }
+                }
+
+                while let Some((decoded_blocks, chunk_frame_errors)) = decoded_chunks.remove(&next_chunk_index)
+                {
+                    decoded_blocks.into_iter().for_each(&mut on_decoded_block);
+                    frame_errors.extend(chunk_frame_errors);
+                    next_chunk_index += 1;
+                    state.lock().unwrap().m_delivered_chunk_count = next_chunk_index;
+                    state_changed.notify_all();
+                }
+            }
+
+            state.lock().unwrap().m_is_cancelled = true;
+            state_changed.notify_all();
+        });
+
+        result?;
+        return Ok(frame_errors);
+    }
+
+    /// Decode all the samples of a flac file on several threads
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    /// thread_count: the number of threads decoding the file, 0 to use all the processors
+    ///
+    /// # Return
+    /// The interleaved samples of the whole file
+    pub fn read_all_samples_parallel(&self, str_path_to_music: String, thread_count: usize) -> Result<Vec<i32>, AudioReaderError>
+    {
+        let mut samples: Vec<i32> = Vec::new();
+        self.decode_parallel(str_path_to_music, thread_count, |decoded_block| samples.extend_from_slice(&decoded_block.m_samples))?;
+        return Ok(samples);
+    }
+
+    /// Read the APPLICATION blocks of a flac file, with their data as they are stored
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    pub fn read_application_blocks(&self, str_path_to_music: String) -> Result<Vec<ApplicationBlock>, AudioReaderError>
+    {
+        let (_audio_information, application_blocks) = read_metadata_information(FlacFileSource::open(str_path_to_music)?)?;
+        return Ok(application_blocks);
+    }
+
+    /// Read the chunks of the RIFF or AIFF file a flac file was encoded from, stored by "flac --keep-foreign-metadata"
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    ///
+    /// # Return
+    /// The foreign metadata, or None if the file does not contain them
+    pub fn read_foreign_metadata(&self, str_path_to_music: String) -> Result<Option<ForeignMetadata>, AudioReaderError>
+    {
+        return ForeignMetadata::from_application_blocks(&self.read_application_blocks(str_path_to_music)?);
+    }
+}
+
+#[cfg(test)]
+mod test_flac_reader
+{
+    use std::convert::TryInto;
+    use std::path::{Path, PathBuf};
+    use crate::audio_reader::{AudioPicture, AudioReader, AudioReaderError, PictureType, VirtualTrack};
+    use crate::audio_reader::cue_sheet::split_embedded_tracks;
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::flac_reader::{is_flac_file, ApplicationBlock, FlacDecoder, FlacMd5Status, FlacReader, FlacStreamDecoder, FIXED_PREDICTOR_COEFFICIENTS};
+    use crate::audio_reader::flac_writer::{write_metadata_block, FlacMetadataWriter, DEFAULT_PADDING_SIZE};
+    use crate::audio_reader::foreign_metadata::ForeignFormat;
+    use crate::audio_reader::music_player::MusicPlayer;
+    use crate::audio_reader::ogg_demuxer::OggDemuxer;
+    use crate::audio_reader::wav_reader::write_chunk;
+    use crate::utils::bit_writer::BitWriter;
+    use crate::utils::crc::{compute_crc16, compute_crc8, update_crc32};
+
+    /// Path of the IETF CELLAR flac test files, cloned as a git submodule.
+    /// The tests using them are ignored by default, run 'git submodule update --init' then 'cargo test -- --ignored' to run them.
+    const FLAC_TEST_FILES_PATH: &str = "Test/FlacReaderTest/flac-test-files";
+
+    /// List the flac files of a directory of the test files, sorted by name.
+    /// Fail when the submodule has not been cloned, an ignored test must not pass without its files.
+    fn list_flac_files(directory_name: &str) -> Vec<PathBuf>
+    {
+        let directory = Path::new(FLAC_TEST_FILES_PATH).join(directory_name);
+        let entries = std::fs::read_dir(&directory)
+            .unwrap_or_else(|_| panic!("{0} not found, run 'git submodule update --init' to get the test files", directory.display()));
+
+        let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |extension| extension == "flac"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty(), "no flac file in {0}", directory.display());
+        return files;
+    }
+
+    /// STREAMINFO block read from the bytes of a file, without the reader
+    struct RawStreamInfo
+    {
+        m_min_block_size: u16,
+        m_max_block_size: u16,
+        m_rate: u32,
+        m_channel_count: u8,
+        m_bits_per_sample: u8,
+        m_total_samples: u64,
+        m_md5: u128,
+    }
+
+    /// Read the STREAMINFO block which follows the magic number of a native flac file
+    fn read_raw_stream_info(path: &Path) -> Result<RawStreamInfo, String>
+    {
+        let data = std::fs::read(path).map_err(|error| format!("cannot read: {0}", error))?;
+        if data.len() < 42 || &data[0..4] != b"fLaC" || data[4] & 0x7F != 0
+        {
+            return Err("no STREAMINFO block after the magic number".to_string());
+        }
+        let block = &data[8..42];
+        let packed_fields = u64::from_be_bytes(block[10..18].try_into().unwrap());
+        return Ok(RawStreamInfo
+        {
+            m_min_block_size: u16::from_be_bytes([block[0], block[1]]),
+            m_max_block_size: u16::from_be_bytes([block[2], block[3]]),
+            m_rate: (packed_fields >> 44) as u32,
+            m_channel_count: ((packed_fields >> 41) & 0x7) as u8 + 1,
+            m_bits_per_sample: ((packed_fields >> 36) & 0x1F) as u8 + 1,
+            m_total_samples: packed_fields & 0xF_FFFF_FFFF,
+            m_md5: u128::from_be_bytes(block[18..34].try_into().unwrap()),
+        });
+    }
+
+    /// Decode a whole file then compare its STREAMINFO block with the decoder, its frames and the MD5 of the decoded audio
+    ///
+    /// # Return
+    /// The description of the first difference found
+    fn check_file(path: &Path) -> Result<(), String>
+    {
+        let mut decoder = FlacDecoder::open(path.to_string_lossy().to_string()).map_err(|error| format!("cannot open: {0}", error))?;
+
+        //
+        // The decoder must give the fields of the STREAMINFO block of the file
+        let is_native_file = std::fs::read(path).is_ok_and(|data| data.starts_with(b"fLaC"));
+        let stream_info = if is_native_file { Some(read_raw_stream_info(path)?) } else { None };
+        if let Some(stream_info) = &stream_info
+        {
+            let expected_fields = (stream_info.m_rate, stream_info.m_channel_count, stream_info.m_bits_per_sample, stream_info.m_total_samples, stream_info.m_md5);
+            let decoder_fields = (decoder.get_rate(), decoder.get_channel_count(), decoder.get_bits_per_sample(), decoder.get_total_samples(), decoder.get_md5());
+            if decoder_fields != expected_fields
+            {
+                return Err(format!("STREAMINFO read as {0:?} instead of {1:?}", decoder_fields, expected_fields));
+            }
+        }
+
+        let byte_per_sample = (decoder.get_bits_per_sample() as usize + 7) / 8;
+        let mut md5_context = md5::Context::new();
+        let mut decoded_sample_count: u64 = 0;
+        let mut previous_block_size: Option<u64> = None;
+        while let Some(decoded_block) = decoder.read_next_block().map_err(|error| format!("cannot decode: {0:?}", error))?
+        {
+            if decoded_block.m_rate != decoder.get_rate()
+                || decoded_block.m_channel_count != decoder.get_channel_count()
+                || decoded_block.m_bits_per_sample != decoder.get_bits_per_sample()
+            {
+                return Err(format!("frame at sample {0} is {1} Hz, {2} channels, {3} bits while STREAMINFO is {4} Hz, {5} channels, {6} bits",
+                                   decoded_block.m_first_sample, decoded_block.m_rate, decoded_block.m_channel_count, decoded_block.m_bits_per_sample,
+                                   decoder.get_rate(), decoder.get_channel_count(), decoder.get_bits_per_sample()));
+            }
+
+            //
+            // Only the last block can be smaller than the minimum block size
+            let block_size = (decoded_block.m_samples.len() / decoded_block.m_channel_count as usize) as u64;
+            if let (Some(stream_info), Some(previous_block_size)) = (&stream_info, previous_block_size)
+            {
+                if previous_block_size < stream_info.m_min_block_size as u64 || previous_block_size > stream_info.m_max_block_size as u64
+                {
+                    return Err(format!("block of {0} samples before sample {1} while STREAMINFO allows {2} to {3}", previous_block_size,
+                                       decoded_block.m_first_sample, stream_info.m_min_block_size, stream_info.m_max_block_size));
+                }
+            }
+            previous_block_size = Some(block_size);
+
+            for sample in decoded_block.m_samples.iter()
+            {
+                md5_context.consume(&sample.to_le_bytes()[..byte_per_sample]);
+            }
+            decoded_sample_count += (decoded_block.m_samples.len() / decoded_block.m_channel_count as usize) as u64;
+        }
+
+        if let Some(frame_error) = decoder.get_frame_errors().first()
+        {
+            return Err(format!("corrupted frame at sample {0}: {1}", frame_error.m_sample_position, frame_error.m_error));
+        }
+        if decoder.get_total_samples() != 0 && decoder.get_total_samples() != decoded_sample_count
+        {
+            return Err(format!("{0} samples decoded while STREAMINFO announces {1}", decoded_sample_count, decoder.get_total_samples()));
+        }
+        if decoder.get_md5() == 0
+        {
+            println!("{0}: no MD5 in STREAMINFO, the audio is not checked", path.display());
+        }
+        else if u128::from_be_bytes(md5_context.compute().0) != decoder.get_md5()
+        {
+            return Err("MD5 of the decoded audio does not match STREAMINFO".to_string());
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    #[ignore = "needs the flac-test-files submodule"]
+    fn decode_subset_files()
+    {
+        let files = list_flac_files("subset");
+
+        let mut failures: Vec<String> = Vec::new();
+        for file in files.iter()
+        {
+            if let Err(error) = check_file(file)
+            {
+                failures.push(format!("{0}: {1}", file.display(), error));
+            }
+        }
+
+        assert!(failures.is_empty(), "{0} of {1} subset files failed:\n{2}", failures.len(), files.len(), failures.join("\n"));
+    }
+
+    #[test]
+    #[ignore = "needs the flac-test-files submodule"]
+    fn decode_subset_files_by_chunks()
+    {
+        let files = list_flac_files("subset");
+
+        //
+        // Pushing the file in small chunks of different sizes must give the same samples as reading the file
+        for file in files.iter()
+        {
+            let expected_samples = FlacDecoder::open(file.to_string_lossy().to_string()).unwrap().read_all_samples().unwrap();
+            let data = std::fs::read(file).unwrap();
+
+            let mut stream_decoder = FlacStreamDecoder::new();
+            let mut samples: Vec<i32> = Vec::new();
+            let mut position = 0;
+            let mut chunk_size = 1;
+            while position < data.len()
+            {
+                let chunk_end = (position + chunk_size).min(data.len());
+                stream_decoder.push_data(&data[position..chunk_end]);
+                position = chunk_end;
+                chunk_size = chunk_size * 7 % 1021 + 1;
+                while let Some(decoded_block) = stream_decoder.read_next_block().unwrap()
+                {
+                    samples.extend_from_slice(&decoded_block.m_samples);
+                }
+            }
+            stream_decoder.finish();
+            while let Some(decoded_block) = stream_decoder.read_next_block().unwrap()
+            {
+                samples.extend_from_slice(&decoded_block.m_samples);
+            }
+
+            assert!(stream_decoder.is_finished());
+            assert!(samples == expected_samples, "{0}: the samples decoded by chunks are different", file.display());
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the flac-test-files submodule"]
+    fn write_tags_of_subset_files()
+    {
+        let files = list_flac_files("subset");
+
+        for file in files.iter()
+        {
+            let expected_samples = FlacDecoder::open(file.to_string_lossy().to_string()).unwrap().read_all_samples().unwrap();
+            let copy_path = std::env::temp_dir().join(format!("quadrium_write_tags_{0}", file.file_name().unwrap().to_string_lossy()));
+            std::fs::copy(file, &copy_path).unwrap();
+            let str_copy_path = copy_path.to_string_lossy().to_string();
+
+            //
+            // The first save adds a padding if the file has not enough space, the second one must use it
+            let mut writer = FlacMetadataWriter::open(str_copy_path.clone()).unwrap();
+            writer.set_vorbis_comment("TITLE", "First title").unwrap();
+            writer.add_picture(AudioPicture
+            {
+                m_picture_type: PictureType::FrontCover,
+                m_mime_type: "image/png".to_string(),
+                m_description: "Cover".to_string(),
+                m_width: 1,
+                m_height: 1,
+                m_color_depth: 24,
+                m_indexed_color_count: 0,
+                m_data: vec![0x89, 0x50, 0x4E, 0x47],
+            });
+            writer.save().unwrap();
+            let file_length = std::fs::metadata(&copy_path).unwrap().len();
+
+            let mut writer = FlacMetadataWriter::open(str_copy_path.clone()).unwrap();
+            assert_eq!(writer.get_vorbis_comment_values("title"), vec!["First title"]);
+            writer.set_vorbis_comment("TITLE", "Second title").unwrap();
+            writer.save().unwrap();
+            assert_eq!(std::fs::metadata(&copy_path).unwrap().len(), file_length, "{0}: the padding is not used", file.display());
+
+            let audio_information = FlacReader {}.read_information(str_copy_path.clone()).unwrap();
+            assert_eq!(audio_information.m_metadata.m_title, "Second title");
+            //
+            // Some test files already have a front cover
+            assert!(audio_information.m_pictures.iter().any(|picture| picture.m_description == "Cover"), "{0}: the picture is not written", file.display());
+            let samples = FlacDecoder::open(str_copy_path).unwrap().read_all_samples().unwrap();
+            assert!(samples == expected_samples, "{0}: the audio changed while writing the tags", file.display());
+
+            std::fs::remove_file(&copy_path).unwrap();
+        }
+    }
+
+    /// Precision of the LPC coefficients of the subframes built by hand
+    const LPC_PRECISION: u32 = 15;
+
+    /// Way a subframe built by hand stores the samples of its channel
+    enum SubframeCoding
+    {
+        Constant,
+        Verbatim,
+        Fixed(usize),
+        /// The quantized coefficients and their shift
+        Lpc(&'static [i64], u32),
+    }
+
+    /// Channel assignment of a frame built by hand, with the coding and the samples of each channel
+    type HandBuiltFrame = (u32, Vec<(SubframeCoding, Vec<i64>)>);
+
+    /// Write the samples of a channel in a subframe, the residual of the predictors is coded in a single rice partition
+    fn write_subframe(writer: &mut BitWriter, coding: &SubframeCoding, samples: &[i64], bits_per_sample: u32)
+    {
+        let (subframe_type, coefficients, shift): (u32, &[i64], u32) = match coding
+        {
+            SubframeCoding::Constant => (0, &[], 0),
+            SubframeCoding::Verbatim => (1, &[], 0),
+            SubframeCoding::Fixed(order) => (8 + *order as u32, FIXED_PREDICTOR_COEFFICIENTS[*order], 0),
+            SubframeCoding::Lpc(coefficients, shift) => (31 + coefficients.len() as u32, *coefficients, *shift),
+        };
+
+        //
+        // Padding bit, type and no wasted bits
+        writer.write_bits(subframe_type << 1, 8);
+        match coding
+        {
+            SubframeCoding::Constant => writer.write_signed_bits(samples[0], bits_per_sample),
+            SubframeCoding::Verbatim =>
+            {
+                for sample in samples.iter()
+                {
+                    writer.write_signed_bits(*sample, bits_per_sample);
+                }
+            }
+            SubframeCoding::Fixed(_) | SubframeCoding::Lpc(_, _) =>
+            {
+                let order = coefficients.len();
+                for sample in samples[..order].iter()
+                {
+                    writer.write_signed_bits(*sample, bits_per_sample);
+                }
+                if let SubframeCoding::Lpc(_, _) = coding
+                {
+                    writer.write_bits(LPC_PRECISION - 1, 4);
+                    writer.write_signed_bits(shift as i64, 5);
+                    for coefficient in coefficients.iter()
+                    {
+                        writer.write_signed_bits(*coefficient, LPC_PRECISION);
+                    }
+                }
+
+                //
+                // Fold the residual to positive values, then choose a rice parameter keeping the unary parts short
+                let folded_residual: Vec<u64> = (order..samples.len()).map(|i|
+                {
+                    let prediction = coefficients.iter().enumerate().map(|(j, coefficient)| coefficient * samples[i - 1 - j]).sum::<i64>() >> shift;
+                    let residual = samples[i] - prediction;
+                    return ((residual << 1) ^ (residual >> 63)) as u64;
+                }).collect();
+                let rice_parameter = (0..15).find(|parameter| folded_residual.iter().all(|value| value >> parameter < 64)).unwrap();
+                writer.write_bits(0, 2);
+                writer.write_bits(0, 4);
+                writer.write_bits(rice_parameter, 4);
+                for value in folded_residual.iter()
+                {
+                    writer.write_unary((value >> rice_parameter) as u32);
+                    writer.write_bits_u64(value & ((1 << rice_parameter) - 1), rice_parameter);
+                }
+            }
+        }
+    }
+
+    /// Create a frame of 16 bits samples with a fixed block size, each channel stored in a subframe built by hand
+    ///
+    /// # Params
+    /// frame_number: the number of the frame, lower than 128 to be coded on one byte
+    /// channel_assignment: 1 for independent channels, 8 for left and side, 9 for side and right, 10 for mid and side
+    /// subframes: the coding and the samples of each channel
+    fn create_frame(frame_number: u8, channel_assignment: u32, subframes: &[(SubframeCoding, Vec<i64>)]) -> Vec<u8>
+    {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0xFFF8, 16);
+
+        //
+        // Block size on 8 bits after the frame number, rate of STREAMINFO, 16 bits per sample
+        writer.write_bits(0b0110, 4);
+        writer.write_bits(0, 4);
+        writer.write_bits(channel_assignment, 4);
+        writer.write_bits(0b100, 3);
+        writer.write_bits(0, 1);
+        writer.write_bits(frame_number as u32, 8);
+        writer.write_bits(subframes[0].1.len() as u32 - 1, 8);
+        let crc8 = compute_crc8(writer.get_bytes());
+        writer.write_bits(crc8 as u32, 8);
+
+        for (channel_index, (coding, samples)) in subframes.iter().enumerate()
+        {
+            let is_side_channel = match channel_assignment
+            {
+                8 | 10 => channel_index == 1,
+                9 => channel_index == 0,
+                _ => false,
+            };
+            write_subframe(&mut writer, coding, samples, if is_side_channel { 17 } else { 16 });
+        }
+        writer.align_to_byte();
+        let crc16 = compute_crc16(writer.get_bytes());
+        writer.write_bits(crc16 as u32, 16);
+        return writer.into_bytes();
+    }
+
+    #[test]
+    fn decode_each_subframe_type_and_stereo_mode()
+    {
+        //
+        // Full scale samples make the side channel use its 17th bit
+        let block_size = 16;
+        let left: Vec<i64> = (0..block_size as i64).map(|i| if i == 5 { 32767 } else { (i * i * 97) % 4001 - 2000 }).collect();
+        let right: Vec<i64> = (0..block_size as i64).map(|i| if i == 5 { -32768 } else { 1500 - i * 211 }).collect();
+        let side: Vec<i64> = left.iter().zip(right.iter()).map(|(left, right)| left - right).collect();
+        let mid: Vec<i64> = left.iter().zip(right.iter()).map(|(left, right)| (left + right) >> 1).collect();
+        let constant = vec![-1234i64; block_size];
+
+        let mut frames: Vec<HandBuiltFrame> = Vec::new();
+        frames.push((1, vec![(SubframeCoding::Constant, constant.clone()), (SubframeCoding::Verbatim, right.clone())]));
+        for order in 0..5
+        {
+            frames.push((1, vec![(SubframeCoding::Fixed(order), left.clone()), (SubframeCoding::Lpc(&[1800, -800], 10), right.clone())]));
+        }
+        frames.push((8, vec![(SubframeCoding::Fixed(2), left.clone()), (SubframeCoding::Verbatim, side.clone())]));
+        frames.push((9, vec![(SubframeCoding::Lpc(&[-3, 2, 7], 2), side.clone()), (SubframeCoding::Fixed(1), right.clone())]));
+        frames.push((10, vec![(SubframeCoding::Fixed(3), mid), (SubframeCoding::Lpc(&[3, -3, 1], 0), side)]));
+
+        //
+        // Stream of 2 channels of 16 bits at 44100 Hz, with a STREAMINFO block only
+        let mut stream = BitWriter::new();
+        stream.write_bits(u32::from_be_bytes(*b"fLaC"), 32);
+        stream.write_bits(0x8000_0022, 32);
+        stream.write_bits(block_size as u32, 16);
+        stream.write_bits(block_size as u32, 16);
+        stream.write_bits_u64(0, 48);
+        stream.write_bits(44100, 20);
+        stream.write_bits(1, 3);
+        stream.write_bits(15, 5);
+        stream.write_bits_u64((frames.len() * block_size) as u64, 36);
+        stream.write_bits_u64(0, 64);
+        stream.write_bits_u64(0, 64);
+        let mut data = stream.into_bytes();
+        let mut expected_samples: Vec<i32> = Vec::new();
+        for (frame_number, (channel_assignment, subframes)) in frames.iter().enumerate()
+        {
+            data.extend(create_frame(frame_number as u8, *channel_assignment, subframes));
+            let first_channel = if *channel_assignment == 1 { &subframes[0].1 } else { &left };
+            for i in 0..block_size
+            {
+                expected_samples.push(first_channel[i] as i32);
+                expected_samples.push(right[i] as i32);
+            }
+        }
+
+        let mut stream_decoder = FlacStreamDecoder::new();
+        stream_decoder.push_data(&data);
+        stream_decoder.finish();
+        let mut samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = stream_decoder.read_next_block().unwrap()
+        {
+            assert_eq!(decoded_block.m_first_sample as usize, samples.len() / 2);
+            samples.extend_from_slice(&decoded_block.m_samples);
+        }
+        assert!(stream_decoder.get_frame_errors().is_empty(), "{0:?}", stream_decoder.get_frame_errors().iter().map(|frame_error| frame_error.m_error.to_string()).collect::<Vec<String>>());
+        assert_eq!(samples, expected_samples);
+    }
+
+    /// Generate interleaved samples mixing a sine per channel and noise, using the whole range of the bits per sample
+    fn generate_samples(sample_count: usize, channel_count: u8, bits_per_sample: u8) -> Vec<i32>
+    {
+        let amplitude = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        let mut noise: u32 = 0x1234_5678;
+        let mut samples: Vec<i32> = Vec::with_capacity(sample_count * channel_count as usize);
+        for i in 0..sample_count
+        {
+            for channel_index in 0..channel_count
+            {
+                noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let sine = (i as f64 * 0.01 * (channel_index + 1) as f64).sin();
+                let random = (noise >> 8) as f64 / (1u32 << 24) as f64 - 0.5;
+                samples.push((amplitude * (0.9 * sine + 0.2 * random)).round().max(-amplitude).min(amplitude) as i32);
+            }
+        }
+        return samples;
+    }
+
+    #[test]
+    fn encode_and_decode_files()
+    {
+        for &(compression_level, channel_count, bits_per_sample, rate) in [(0, 1, 4, 8000), (1, 2, 8, 22050), (3, 2, 12, 11025), (5, 2, 16, 44100),
+                                                                          (6, 3, 20, 16000), (8, 2, 24, 8000), (5, 1, 32, 37800)].iter()
+        {
+            let sample_count = rate as usize * 11 + 1234;
+            let samples = generate_samples(sample_count, channel_count, bits_per_sample);
+            let path = std::env::temp_dir().join(format!("quadrium_encode_{0}_{1}_{2}.flac", compression_level, channel_count, bits_per_sample));
+            let str_path = path.to_string_lossy().to_string();
+
+            let mut settings = FlacEncoderSettings::new(rate, channel_count, bits_per_sample);
+            settings.m_total_samples = sample_count as u64;
+            settings.m_compression_level = compression_level;
+            settings.m_vorbis_comments.push("TITLE=Encoded".to_string());
+            let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+            for chunk in samples.chunks(10007 * channel_count as usize)
+            {
+                encoder.write_samples(chunk).unwrap();
+            }
+            encoder.finish().unwrap();
+
+            assert_eq!(check_file(&path), Ok(()), "{0}", str_path);
+            let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+            assert_eq!(decoder.get_total_samples(), sample_count as u64);
+            assert!(decoder.read_all_samples().unwrap() == samples, "{0}: the decoded samples are different", str_path);
+            assert_eq!(FlacReader {}.read_information(str_path.clone()).unwrap().m_metadata.m_title, "Encoded");
+
+            //
+            // Seek through the seek table
+            let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+            let target = rate as u64 * 10 + 17;
+            decoder.seek(target).unwrap();
+            let decoded_block = decoder.read_next_block().unwrap().unwrap();
+            assert_eq!(decoded_block.m_first_sample, target);
+            assert_eq!(decoded_block.m_samples[0], samples[target as usize * channel_count as usize]);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    /// Get the sample number and the frame offset of the points of the SEEKTABLE block of a native flac file
+    fn read_seek_points(path: &Path) -> Vec<(u64, u64)>
+    {
+        let data = std::fs::read(path).unwrap();
+        let mut position = 4;
+        loop
+        {
+            let block_type = data[position] & 0x7F;
+            let block_length = u32::from_be_bytes([0, data[position + 1], data[position + 2], data[position + 3]]) as usize;
+            position += 4;
+            if block_type == 3
+            {
+                return data[position..position + block_length].chunks_exact(18)
+                    .map(|point_data| (u64::from_be_bytes(point_data[0..8].try_into().unwrap()), u64::from_be_bytes(point_data[8..16].try_into().unwrap())))
+                    .collect();
+            }
+            assert!(data[position - 4] & 0x80 == 0, "no SEEKTABLE block");
+            position += block_length;
+        }
+    }
+
+    #[test]
+    fn encode_seek_table()
+    {
+        //
+        // A target every 10 seconds, the last ones inside frames which do not start on them
+        let rate = 44100u32;
+        let sample_count = rate as usize * 35 + 555;
+        let samples = generate_samples(sample_count, 1, 16);
+        let path = std::env::temp_dir().join("quadrium_encode_seek_table.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(rate, 1, 16);
+        settings.m_total_samples = sample_count as u64;
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        let seek_points = read_seek_points(&path);
+        assert_eq!(seek_points.len(), 4);
+        for (target_index, &(sample_number, frame_offset)) in seek_points.iter().enumerate()
+        {
+            let target = target_index as u64 * rate as u64 * 10;
+            assert!(sample_number <= target && target < sample_number + 4096, "point {0} at {1} for the target {2}", target_index, sample_number, target);
+            if target_index > 0
+            {
+                assert!(sample_number > seek_points[target_index - 1].0 && frame_offset > seek_points[target_index - 1].1);
+            }
+        }
+
+        let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+        let target = rate as u64 * 30 + 17;
+        decoder.seek(target).unwrap();
+        let decoded_block = decoder.read_next_block().unwrap().unwrap();
+        assert_eq!(decoded_block.m_first_sample, target);
+        assert_eq!(decoded_block.m_samples[0], samples[target as usize]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn encode_full_scale_stereo_files()
+    {
+        //
+        // Opposite full scale samples on both channels give a side channel one bit larger than the samples
+        for &compression_level in [0u8, 5, 8].iter()
+        {
+            for &bits_per_sample in [24u8, 32].iter()
+            {
+                let maximum = ((1i64 << (bits_per_sample - 1)) - 1) as i32;
+                let minimum = -maximum - 1;
+                let sample_count = 20000;
+                let mut noise: u32 = 0x8765_4321;
+                let mut samples: Vec<i32> = Vec::with_capacity(sample_count * 2);
+                for i in 0..sample_count
+                {
+                    noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let (left, right) = match (noise >> 16) % 4
+                    {
+                        0 => (maximum, minimum),
+                        1 => (minimum, maximum),
+                        2 => (maximum, maximum),
+                        _ => (minimum, if i % 2 == 0 { minimum } else { 0 }),
+                    };
+                    samples.push(left);
+                    samples.push(right);
+                }
+                let path = std::env::temp_dir().join(format!("quadrium_full_scale_{0}_{1}.flac", compression_level, bits_per_sample));
+                let str_path = path.to_string_lossy().to_string();
+
+                let mut settings = FlacEncoderSettings::new(48000, 2, bits_per_sample);
+                settings.m_total_samples = sample_count as u64;
+                settings.m_compression_level = compression_level;
+                let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+                encoder.write_samples(&samples).unwrap();
+                encoder.finish().unwrap();
+
+                assert_eq!(check_file(&path), Ok(()), "{0}", str_path);
+                assert!(FlacDecoder::open(str_path.clone()).unwrap().read_all_samples().unwrap() == samples, "{0}: the decoded samples are different", str_path);
+                assert_eq!(FlacReader {}.verify(str_path).unwrap().m_md5_status, FlacMd5Status::Match);
+
+                std::fs::remove_file(&path).unwrap();
+            }
+        }
+    }
+
+    /// Get the bytes of the audio frames of a native flac file, which follow the last metadata block
+    fn read_audio_frames(path: &Path) -> Vec<u8>
+    {
+        let data = std::fs::read(path).unwrap();
+        let mut position = 4;
+        loop
+        {
+            let is_last = data[position] & 0x80 != 0;
+            position += 4 + u32::from_be_bytes([0, data[position + 1], data[position + 2], data[position + 3]]) as usize;
+            if is_last
+            {
+                return data[position..].to_vec();
+            }
+        }
+    }
+
+    /// Get the identity of a file, which changes when it is replaced by another file
+    #[cfg(unix)]
+    fn get_file_identity(path: &Path) -> u64
+    {
+        use std::os::unix::fs::MetadataExt;
+        return std::fs::metadata(path).unwrap().ino();
+    }
+
+    #[cfg(not(unix))]
+    fn get_file_identity(_path: &Path) -> u64
+    {
+        return 0;
+    }
+
+    #[test]
+    fn write_tags_in_padding_or_rewrite_file()
+    {
+        let sample_count = 44100 * 2 + 77;
+        let samples = generate_samples(sample_count, 2, 16);
+        let path = std::env::temp_dir().join("quadrium_write_tags.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(44100, 2, 16);
+        settings.m_total_samples = sample_count as u64;
+        settings.m_vorbis_comments.push("ARTIST=Encoder artist".to_string());
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+        let audio_frames = read_audio_frames(&path);
+        let encoded_length = std::fs::metadata(&path).unwrap().len();
+        let encoded_identity = get_file_identity(&path);
+
+        //
+        // A title fits in the padding added by the encoder, only the metadata of the file are overwritten
+        let mut writer = FlacMetadataWriter::open(str_path.clone()).unwrap();
+        writer.set_vorbis_comment("TITLE", "Padded title").unwrap();
+        writer.save().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), encoded_length);
+        assert_eq!(get_file_identity(&path), encoded_identity);
+        assert!(read_audio_frames(&path) == audio_frames);
+
+        //
+        // A picture larger than the padding makes the whole file be rewritten, with a new padding
+        let picture = AudioPicture
+        {
+            m_picture_type: PictureType::FrontCover,
+            m_mime_type: "image/png".to_string(),
+            m_description: "Large cover".to_string(),
+            m_width: 64,
+            m_height: 64,
+            m_color_depth: 24,
+            m_indexed_color_count: 0,
+            m_data: vec![0x5A; DEFAULT_PADDING_SIZE * 2],
+        };
+        let mut writer = FlacMetadataWriter::open(str_path.clone()).unwrap();
+        writer.add_picture(picture);
+        writer.save().unwrap();
+        let rewritten_length = std::fs::metadata(&path).unwrap().len();
+        let rewritten_identity = get_file_identity(&path);
+        assert!(rewritten_length > encoded_length + DEFAULT_PADDING_SIZE as u64 * 2);
+        assert!(cfg!(not(unix)) || rewritten_identity != encoded_identity);
+        assert!(read_audio_frames(&path) == audio_frames);
+
+        //
+        // The next edit uses the new padding
+        let mut writer = FlacMetadataWriter::open(str_path.clone()).unwrap();
+        assert_eq!(writer.get_pictures().len(), 1);
+        writer.set_vorbis_comment("title", "Second title").unwrap();
+        writer.add_vorbis_comment("COMMENT", "First comment").unwrap();
+        writer.save().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), rewritten_length);
+        assert_eq!(get_file_identity(&path), rewritten_identity);
+        assert!(read_audio_frames(&path) == audio_frames);
+
+        let audio_information = FlacReader {}.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Second title");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Encoder artist"]);
+        assert_eq!(audio_information.m_comments, vec!["First comment"]);
+        assert_eq!(audio_information.m_pictures.len(), 1);
+        assert_eq!(audio_information.m_pictures[0].m_description, "Large cover");
+        assert_eq!(audio_information.m_pictures[0].m_mime_type, "image/png");
+        assert!(audio_information.m_pictures[0].m_data == vec![0x5A; DEFAULT_PADDING_SIZE * 2]);
+        assert!(FlacDecoder::open(str_path.clone()).unwrap().read_all_samples().unwrap() == samples);
+        assert_eq!(FlacReader {}.verify(str_path.clone()).unwrap().m_md5_status, FlacMd5Status::Match);
+
+        //
+        // The field names must be printable ASCII characters without '='
+        let mut writer = FlacMetadataWriter::open(str_path).unwrap();
+        for field in ["", "TI=TLE", "TITR\u{C9}", "LINE\nFEED"].iter()
+        {
+            assert!(matches!(writer.set_vorbis_comment(field, "Value"), Err(AudioReaderError::InvalidValue(_))), "{0:?}", field);
+            assert!(matches!(writer.add_vorbis_comment(field, "Value"), Err(AudioReaderError::InvalidValue(_))), "{0:?}", field);
+        }
+        assert_eq!(writer.get_vorbis_comment_values("TITLE"), vec!["Second title"]);
+
+        let temporary_path = path.with_file_name(".quadrium_write_tags.flac.quadrium-tmp");
+        assert!(!temporary_path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Write a page of an ogg stream
+    ///
+    /// # Params
+    /// header_type: the flags of the page
+    /// granule_position: the number of samples at the end of the last packet finished in the page
+    /// segment_table, data: the segments of the packets of the page
+    fn write_ogg_page(ogg_data: &mut Vec<u8>, header_type: u8, granule_position: u64, sequence_number: u32, segment_table: &[u8], data: &[u8])
+    {
+        let page_offset = ogg_data.len();
+        ogg_data.extend_from_slice(b"OggS");
+        ogg_data.push(0);
+        ogg_data.push(header_type);
+        ogg_data.extend_from_slice(&granule_position.to_le_bytes());
+        ogg_data.extend_from_slice(&0x5155_4144u32.to_le_bytes());
+        ogg_data.extend_from_slice(&sequence_number.to_le_bytes());
+        ogg_data.extend_from_slice(&[0; 4]);
+        ogg_data.push(segment_table.len() as u8);
+        ogg_data.extend_from_slice(segment_table);
+        ogg_data.extend_from_slice(data);
+        let crc = update_crc32(0, &ogg_data[page_offset..]);
+        ogg_data[page_offset + 22..page_offset + 26].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    /// Store a native flac stream inside an ogg stream with the Ogg FLAC mapping:
+    /// one packet per metadata block and per frame, several packets per page and packets continued on the next page
+    fn convert_to_ogg_flac(flac_data: &[u8], block_size: u64, total_samples: u64) -> Vec<u8>
+    {
+        //
+        // Cut the metadata blocks
+        let mut header_packets: Vec<Vec<u8>> = Vec::new();
+        let mut position = 4;
+        loop
+        {
+            let block_length = u32::from_be_bytes([0, flac_data[position + 1], flac_data[position + 2], flac_data[position + 3]]) as usize;
+            header_packets.push(flac_data[position..position + 4 + block_length].to_vec());
+            let is_last = flac_data[position] & 0x80 != 0;
+            position += 4 + block_length;
+            if is_last
+            {
+                break;
+            }
+        }
+
+        //
+        // Cut the frames at each sync code followed by the next frame number, coded like an UTF-8 character, and a valid header CRC
+        let mut frame_starts: Vec<usize> = Vec::new();
+        for frame_start in position..flac_data.len() - 1
+        {
+            let mut frame_number = [0u8; 4];
+            let frame_number = std::char::from_u32(frame_starts.len() as u32).unwrap().encode_utf8(&mut frame_number).as_bytes();
+            if flac_data[frame_start] == 0xFF && flac_data[frame_start + 1] == 0xF8
+                && flac_data[frame_start + 4..].starts_with(frame_number)
+                && (5..16).any(|header_length| frame_start + header_length < flac_data.len()
+                    && compute_crc8(&flac_data[frame_start..frame_start + header_length]) == flac_data[frame_start + header_length])
+            {
+                frame_starts.push(frame_start);
+            }
+        }
+        frame_starts.push(flac_data.len());
+
+        let mut first_packet: Vec<u8> = b"\x7FFLAC\x01\x00".to_vec();
+        first_packet.extend_from_slice(&(header_packets.len() as u16 - 1).to_be_bytes());
+        first_packet.extend_from_slice(b"fLaC");
+        first_packet.extend_from_slice(&header_packets[0]);
+
+        let mut ogg_data: Vec<u8> = Vec::new();
+        let mut sequence_number = 0;
+        write_ogg_page(&mut ogg_data, 0x02, 0, sequence_number, &[first_packet.len() as u8], &first_packet);
+
+        //
+        // The header packets, then the frames, are put in pages of about 4 kB
+        let mut packets: Vec<(&[u8], u64)> = header_packets[1..].iter().map(|packet| (&packet[..], 0)).collect();
+        let header_packet_count = packets.len();
+        for (frame_index, frame_bounds) in frame_starts.windows(2).enumerate()
+        {
+            packets.push((&flac_data[frame_bounds[0]..frame_bounds[1]], (frame_index as u64 + 1) * block_size));
+        }
+        let mut segment_table: Vec<u8> = Vec::new();
+        let mut page_data: Vec<u8> = Vec::new();
+        let mut granule_position = 0;
+        let mut is_continued = false;
+        for (packet_index, (packet, packet_granule_position)) in packets.iter().enumerate()
+        {
+            let mut packet_position = 0;
+            loop
+            {
+                let segment_size = (packet.len() - packet_position).min(255);
+                segment_table.push(segment_size as u8);
+                page_data.extend_from_slice(&packet[packet_position..packet_position + segment_size]);
+                packet_position += segment_size;
+                let is_packet_end = segment_size < 255;
+                if is_packet_end
+                {
+                    granule_position = (*packet_granule_position).min(total_samples);
+                }
+
+                let is_last_header = packet_index + 1 == header_packet_count;
+                if segment_table.len() == 255 || page_data.len() >= 4096 || (is_packet_end && is_last_header)
+                {
+                    sequence_number += 1;
+                    write_ogg_page(&mut ogg_data, if is_continued { 0x01 } else { 0 }, granule_position, sequence_number, &segment_table, &page_data);
+                    is_continued = !is_packet_end;
+                    segment_table.clear();
+                    page_data.clear();
+                }
+                if is_packet_end
+                {
+                    break;
+                }
+            }
+        }
+        sequence_number += 1;
+        write_ogg_page(&mut ogg_data, if is_continued { 0x05 } else { 0x04 }, total_samples, sequence_number, &segment_table, &page_data);
+
+        return ogg_data;
+    }
+
+    #[test]
+    fn decode_ogg_flac_files()
+    {
+        for &has_seek_table in [true, false].iter()
+        {
+            let sample_count = 44100 * 12 + 789;
+            let samples = generate_samples(sample_count, 2, 16);
+            let flac_path = std::env::temp_dir().join(format!("quadrium_ogg_flac_{0}.flac", has_seek_table));
+            let ogg_path = flac_path.with_extension("oga");
+            let str_ogg_path = ogg_path.to_string_lossy().to_string();
+
+            let mut settings = FlacEncoderSettings::new(44100, 2, 16);
+            settings.m_total_samples = if has_seek_table { sample_count as u64 } else { 0 };
+            settings.m_compression_level = 0;
+            settings.m_vorbis_comments.push("TITLE=Ogg".to_string());
+            let mut encoder = FlacEncoder::create(flac_path.to_string_lossy().to_string(), settings).unwrap();
+            encoder.write_samples(&samples).unwrap();
+            encoder.finish().unwrap();
+            let flac_data = std::fs::read(&flac_path).unwrap();
+            std::fs::write(&ogg_path, convert_to_ogg_flac(&flac_data, 4096, sample_count as u64)).unwrap();
+
+            //
+            // The Ogg FLAC file gives the same information and samples as the native one
+            assert!(is_flac_file(&std::fs::File::open(&ogg_path).unwrap()));
+            let audio_information = FlacReader {}.read_information(str_ogg_path.clone()).unwrap();
+            assert_eq!(audio_information.m_metadata.m_title, "Ogg");
+            assert_eq!((audio_information.m_rate, audio_information.m_channel_count, audio_information.m_bits_per_sample), (44100, 2, 16));
+
+            //
+            // The bitrate comes from the size of the file, the pages only add a few bytes
+            let native_bitrate = FlacReader {}.read_information(flac_path.to_string_lossy().to_string()).unwrap().m_bitrate as f64;
+            assert!((audio_information.m_bitrate as f64 - native_bitrate).abs() <= native_bitrate * 0.02);
+            assert_eq!(check_file(&ogg_path), Ok(()));
+            assert!(FlacDecoder::open(str_ogg_path.clone()).unwrap().read_all_samples().unwrap() == samples);
+
+            //
+            // Seek forward and backward, with the seek table or by bisection
+            let mut decoder = FlacDecoder::open(str_ogg_path.clone()).unwrap();
+            for &target in [500_000u64, 12_345, sample_count as u64 - 1, 0, 441_000].iter()
+            {
+                decoder.seek(target).unwrap();
+                let decoded_block = decoder.read_next_block().unwrap().unwrap();
+                assert_eq!(decoded_block.m_first_sample, target);
+                assert_eq!(&decoded_block.m_samples[..2], &samples[target as usize * 2..target as usize * 2 + 2]);
+            }
+
+            std::fs::remove_file(&flac_path).unwrap();
+            std::fs::remove_file(&ogg_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn resynchronize_after_corrupted_ogg_page()
+    {
+        let mut ogg_data: Vec<u8> = Vec::new();
+        write_ogg_page(&mut ogg_data, 0x02, 0, 0, &[5], b"first");
+        let corrupted_page_offset = ogg_data.len();
+        write_ogg_page(&mut ogg_data, 0, 0, 1, &[6], b"second");
+        write_ogg_page(&mut ogg_data, 0x04, 0, 2, &[4], b"last");
+
+        //
+        // The corrupted number of segments covers the next page, which must still be found
+        ogg_data[corrupted_page_offset + 26] = 200;
+        let mut demuxer = OggDemuxer::new(std::io::Cursor::new(ogg_data));
+        assert!(demuxer.read_page().unwrap().unwrap().is_first());
+        assert!(demuxer.read_page().unwrap().unwrap().is_last());
+        assert!(demuxer.read_page().unwrap().is_none());
+        assert_eq!(demuxer.get_lost_page_count(), 1);
+    }
+
+    #[test]
+    fn decode_files_in_parallel()
+    {
+        for &has_seek_table in [true, false].iter()
+        {
+            let sample_count = 44100 * 30 + 321;
+            let samples = generate_samples(sample_count, 2, 16);
+            let path = std::env::temp_dir().join(format!("quadrium_parallel_{0}.flac", has_seek_table));
+            let str_path = path.to_string_lossy().to_string();
+
+            let mut settings = FlacEncoderSettings::new(44100, 2, 16);
+            settings.m_total_samples = if has_seek_table { sample_count as u64 } else { 0 };
+            settings.m_compression_level = 0;
+            let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+            encoder.write_samples(&samples).unwrap();
+            encoder.finish().unwrap();
+
+            let reader = FlacReader {};
+            assert!(reader.read_all_samples_parallel(str_path.clone(), 4).unwrap() == samples);
+            assert_eq!(reader.verify(str_path.clone()).unwrap().m_md5_status, FlacMd5Status::Match);
+
+            //
+            // The corrupted frames are replaced by the same silence as with the sequential decoding
+            let mut data = std::fs::read(&path).unwrap();
+            for corrupted_position in (data.len() / 7..data.len()).step_by(data.len() / 7)
+            {
+                data[corrupted_position] ^= 0x10;
+            }
+            std::fs::write(&path, &data).unwrap();
+            let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+            let expected_samples = decoder.read_all_samples().unwrap();
+            let mut parallel_samples: Vec<i32> = Vec::new();
+            let frame_errors = reader.decode_parallel(str_path.clone(), 3, |decoded_block| parallel_samples.extend_from_slice(&decoded_block.m_samples)).unwrap();
+            assert!(parallel_samples == expected_samples);
+            assert_eq!(frame_errors.iter().map(|frame_error| frame_error.m_sample_position).collect::<Vec<u64>>(),
+                       decoder.get_frame_errors().iter().map(|frame_error| frame_error.m_sample_position).collect::<Vec<u64>>());
+            assert!(!frame_errors.is_empty());
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn replace_corrupted_frame_by_silence()
+    {
+        let sample_count = 8000 * 25 + 123;
+        let samples = generate_samples(sample_count, 2, 16);
+        let path = std::env::temp_dir().join("quadrium_corrupted_crc.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(8000, 2, 16);
+        settings.m_total_samples = sample_count as u64;
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        //
+        // The frame before the second seek point ends with the last byte of its CRC-16, the blocks have 4096 samples
+        let seek_points = read_seek_points(&path);
+        let mut data = std::fs::read(&path).unwrap();
+        let first_frame_offset = data.len() - read_audio_frames(&path).len();
+        data[first_frame_offset + seek_points[1].1 as usize - 1] ^= 0xFF;
+        std::fs::write(&path, &data).unwrap();
+        let corrupted_first_sample = seek_points[1].0 - 4096;
+
+        let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+        let mut decoded_samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = decoder.read_next_block().unwrap()
+        {
+            assert_eq!(decoded_block.m_first_sample as usize * 2, decoded_samples.len());
+            if decoded_block.m_first_sample == corrupted_first_sample
+            {
+                assert_eq!(decoded_block.m_samples, vec![0; 4096 * 2]);
+            }
+            decoded_samples.extend(decoded_block.m_samples);
+        }
+        assert_eq!(decoded_samples.len() as u64, decoder.get_total_samples() * 2);
+        let corrupted_range = corrupted_first_sample as usize * 2..(corrupted_first_sample as usize + 4096) * 2;
+        assert!(decoded_samples[..corrupted_range.start] == samples[..corrupted_range.start]);
+        assert!(decoded_samples[corrupted_range.end..] == samples[corrupted_range.end..]);
+
+        let frame_errors = decoder.get_frame_errors();
+        assert_eq!(frame_errors.len(), 1);
+        assert_eq!(frame_errors[0].m_sample_position, corrupted_first_sample);
+        assert!(matches!(frame_errors[0].m_error, AudioReaderError::InvalidFrameCrc));
+        assert!(FlacReader {}.read_all_samples_parallel(str_path.clone(), 3).unwrap() == decoded_samples);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Track of a CUESHEET block: offset, number, ISRC, flags (0x80 for a data track, 0x40 for the pre-emphasis) and index points
+    type CuesheetTrackFields<'a> = (u64, u8, &'a [u8; 12], u8, &'a [(u64, u8)]);
+
+    /// Create the content of a CUESHEET block of a CD
+    fn create_cuesheet_block(tracks: &[CuesheetTrackFields]) -> Vec<u8>
+    {
+        let mut data: Vec<u8> = vec![0; 128];
+        data.extend_from_slice(&88200u64.to_be_bytes());
+        data.push(0x80);
+        data.extend_from_slice(&[0; 258]);
+        data.push(tracks.len() as u8);
+        for (offset, track_number, isrc, flags, index_points) in tracks.iter()
+        {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.push(*track_number);
+            data.extend_from_slice(*isrc);
+            data.push(*flags);
+            data.extend_from_slice(&[0; 13]);
+            data.push(index_points.len() as u8);
+            for (index_offset, index_number) in index_points.iter()
+            {
+                data.extend_from_slice(&index_offset.to_be_bytes());
+                data.push(*index_number);
+                data.extend_from_slice(&[0; 3]);
+            }
+        }
+        return data;
+    }
+
+    #[test]
+    fn read_and_select_cuesheet_tracks()
+    {
+        let sample_count = 44100 * 6;
+        let samples = generate_samples(sample_count, 1, 16);
+        let path = std::env::temp_dir().join("quadrium_cuesheet.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(44100, 1, 16);
+        settings.m_total_samples = sample_count as u64;
+        settings.m_vorbis_comments = vec!["TITLE=Whole disc".to_string(), "ALBUM=Disc".to_string(), "REPLAYGAIN_TRACK_GAIN=+6.00 dB".to_string()];
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        //
+        // An audio track, a data track, an audio track with a pre-gap and the pre-emphasis, and the lead-out before the end of the stream.
+        // The CUESHEET follows the STREAMINFO block, which is not the last block.
+        let cuesheet = create_cuesheet_block(&[
+            (0, 1, b"GBAYE0000351", 0x00, &[(0, 1)]),
+            (44100, 2, b"\0\0\0\0\0\0\0\0\0\0\0\0", 0x80, &[(0, 1)]),
+            (88200, 3, b"USRC17607839", 0x40, &[(0, 0), (44100, 1)]),
+            (220500, 170, b"\0\0\0\0\0\0\0\0\0\0\0\0", 0x00, &[]),
+        ]);
+        let flac_data = std::fs::read(&path).unwrap();
+        let mut cuesheet_data = flac_data[..42].to_vec();
+        write_metadata_block(&mut cuesheet_data, 5, &cuesheet, false).unwrap();
+        cuesheet_data.extend_from_slice(&flac_data[42..]);
+        std::fs::write(&path, &cuesheet_data).unwrap();
+
+        let audio_information = FlacReader {}.read_information(str_path.clone()).unwrap();
+        let tracks = &audio_information.m_tracks;
+        assert_eq!(tracks.len(), 2);
+        assert_eq!((tracks[0].m_track_number, tracks[0].m_start_sample, tracks[0].m_end_sample), (1, 0, 44100));
+        assert_eq!(tracks[0].m_isrc, "GBAYE0000351");
+        assert!(!tracks[0].m_has_pre_emphasis);
+        assert_eq!((tracks[1].m_track_number, tracks[1].m_start_sample, tracks[1].m_end_sample), (3, 132300, 220500));
+        assert_eq!(tracks[1].m_index_points.iter().map(|index_point| (index_point.m_index_number, index_point.m_sample)).collect::<Vec<(u8, u64)>>(), vec![(0, 88200), (1, 132300)]);
+        assert!(tracks[1].m_has_pre_emphasis);
+
+        //
+        // The tracks are listed like the ones of a cue sheet, without the title and the track gain of the whole file
+        let embedded_tracks = split_embedded_tracks(&str_path, &audio_information);
+        assert_eq!(embedded_tracks.len(), 2);
+        let metadata = &embedded_tracks[1].m_information.m_metadata;
+        assert_eq!((metadata.m_title.as_str(), metadata.m_album.as_str(), metadata.m_track_number, metadata.m_track_total), ("", "Disc", Some(3), Some(2)));
+        assert_eq!(metadata.m_replay_gain.m_track, None);
+        assert_eq!(embedded_tracks[1].m_information.m_total_samples, 88200);
+
+        //
+        // The decoding starts at the index point 1 of the track and stops at the lead-out
+        let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+        decoder.select_track(&tracks[1]).unwrap();
+        let first_block = decoder.read_next_block().unwrap().unwrap();
+        assert_eq!(first_block.m_first_sample, 132300);
+        let mut track_samples = first_block.m_samples;
+        track_samples.extend(decoder.read_all_samples().unwrap());
+        assert!(track_samples == samples[132300..220500]);
+
+        //
+        // The player plays the track asked by its number, without the track gain of the whole file
+        let mut music_player = MusicPlayer::new();
+        assert_eq!(music_player.play(&str_path, Some(1), false).unwrap().m_replay_gain_scale, 1.0);
+        let mut track_samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = music_player.read_next_block().unwrap()
+        {
+            track_samples.extend(decoded_block.m_samples);
+        }
+        assert!(track_samples == samples[..44100]);
+        assert!(matches!(music_player.play(&str_path, Some(2), false), Err(AudioReaderError::InvalidValue(_))));
+
+        //
+        // An empty track or a track ending after the stream is rejected
+        let mut decoder = FlacDecoder::open(str_path.clone()).unwrap();
+        for (start_sample, end_sample) in [(44100, 44100), (88200, 44100), (0, sample_count as u64 + 1)]
+        {
+            let track = VirtualTrack { m_start_sample: start_sample, m_end_sample: end_sample, ..tracks[0].clone() };
+            assert!(matches!(decoder.select_track(&track), Err(AudioReaderError::InvalidValue(_))), "{0}..{1}", start_sample, end_sample);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keep_foreign_metadata()
+    {
+        let sample_count = 1000;
+        let samples = generate_samples(sample_count, 1, 16);
+        let path = std::env::temp_dir().join("quadrium_foreign_metadata.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(44100, 1, 16);
+        settings.m_total_samples = sample_count as u64;
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        //
+        // Build the WAV file the flac file comes from, with chunks before and after the samples
+        let audio_data: Vec<u8> = samples.iter().flat_map(|sample| (*sample as i16).to_le_bytes().to_vec()).collect();
+        let mut chunks_before_audio: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks_before_audio, b"fmt ", &[1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0], false);
+        write_chunk(&mut chunks_before_audio, b"bext", b"odd", false);
+        let mut chunks_after_audio: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks_after_audio, b"iXML", b"<BWFXML></BWFXML>", false);
+        let mut wav_header: Vec<u8> = b"RIFF".to_vec();
+        wav_header.extend_from_slice(&((4 + chunks_before_audio.len() + 8 + audio_data.len() + chunks_after_audio.len()) as u32).to_le_bytes());
+        wav_header.extend_from_slice(b"WAVE");
+        wav_header.extend_from_slice(&chunks_before_audio);
+        wav_header.extend_from_slice(b"data");
+        wav_header.extend_from_slice(&(audio_data.len() as u32).to_le_bytes());
+
+        //
+        // Store the WAV chunks like "flac --keep-foreign-metadata", with a block of an unknown application between them
+        let mut application_blocks: Vec<ApplicationBlock> = Vec::new();
+        let foreign_parts: Vec<&[u8]> = vec![&wav_header[0..12], &wav_header[12..36], &wav_header[36..48], &wav_header[48..], &chunks_after_audio];
+        for (part_index, part) in foreign_parts.iter().enumerate()
+        {
+            application_blocks.push(ApplicationBlock { m_application_id: *b"riff", m_application_data: part.to_vec() });
+            if part_index == 1
+            {
+                application_blocks.push(ApplicationBlock { m_application_id: *b"TEST", m_application_data: vec![0, 1, 2, 3, 0xFF] });
+            }
+        }
+        let mut flac_data = std::fs::read(&path).unwrap();
+        let mut metadata: Vec<u8> = Vec::new();
+        for block in application_blocks.iter()
+        {
+            let mut block_data = block.m_application_id.to_vec();
+            block_data.extend_from_slice(&block.m_application_data);
+            write_metadata_block(&mut metadata, 2, &block_data, false).unwrap();
+        }
+        let streaminfo_end = 4 + 4 + 34;
+        flac_data.splice(streaminfo_end..streaminfo_end, metadata);
+        std::fs::write(&path, &flac_data).unwrap();
+
+        let check_foreign_metadata = ||
+        {
+            assert_eq!(FlacReader {}.read_application_blocks(str_path.clone()).unwrap(), application_blocks);
+            let foreign_metadata = FlacReader {}.read_foreign_metadata(str_path.clone()).unwrap().unwrap();
+            assert_eq!(foreign_metadata.m_format, ForeignFormat::Riff);
+            assert_eq!(&foreign_metadata.m_form_type, b"WAVE");
+            assert_eq!(foreign_metadata.get_chunk(b"bext").unwrap().m_data, b"odd\0".to_vec());
+            assert_eq!(foreign_metadata.get_chunk(b"data").unwrap().m_size, audio_data.len() as u32);
+            assert_eq!(foreign_metadata.get_bytes_before_audio(), wav_header);
+            assert_eq!(foreign_metadata.get_bytes_after_audio(), chunks_after_audio);
+        };
+        check_foreign_metadata();
+
+        //
+        // The blocks must stay byte-exact when the tags are edited
+        let mut writer = FlacMetadataWriter::open(str_path.clone()).unwrap();
+        writer.set_vorbis_comment("TITLE", "Foreign").unwrap();
+        writer.save().unwrap();
+        check_foreign_metadata();
+        assert!(FlacDecoder::open(str_path.clone()).unwrap().read_all_samples().unwrap() == samples);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the flac-test-files submodule"]
+    fn decode_uncommon_files()
+    {
+        //
+        // Uncommon files use features that most decoders do not support, like a stream starting without STREAMINFO or changing its format.
+        // Such a file can be rejected, but a file which is decoded must give the samples of its MD5.
+        let files = list_flac_files("uncommon");
+        let mut failures: Vec<String> = Vec::new();
+        let mut decoded_file_count = 0;
+        for file in files.iter()
+        {
+            let file_path = file.clone();
+            match std::panic::catch_unwind(move || check_file(&file_path))
+            {
+                Ok(Ok(())) =>
+                {
+                    println!("{0}: OK", file.display());
+                    decoded_file_count += 1;
+                }
+                Ok(Err(error)) if error.starts_with("corrupted frame") || error.starts_with("MD5") || error.contains("samples decoded") || error.starts_with("STREAMINFO") =>
+                {
+                    failures.push(format!("{0}: {1}", file.display(), error));
+                }
+                Ok(Err(error)) => println!("{0}: rejected, {1}", file.display(), error),
+                Err(_) => failures.push(format!("{0}: the reader panicked", file.display())),
+            }
+        }
+
+        assert!(failures.is_empty(), "{0} of {1} uncommon files were decoded wrongly:\n{2}", failures.len(), files.len(), failures.join("\n"));
+        assert!(decoded_file_count > 0, "no uncommon file could be decoded");
+    }
+
+    #[test]
+    #[ignore = "needs the flac-test-files submodule"]
+    fn reject_faulty_files()
+    {
+        let files = list_flac_files("faulty");
+
+        //
+        // A faulty file can be rejected or decoded with errors, but it must never make the reader panic
+        let mut panicking_files: Vec<String> = Vec::new();
+        let mut rejected_files: Vec<String> = Vec::new();
+        for file in files.iter()
+        {
+            let file_path = file.clone();
+            match std::panic::catch_unwind(move || check_file(&file_path))
+            {
+                Ok(Ok(())) => println!("{0}: accepted", file.display()),
+                Ok(Err(error)) => rejected_files.push(format!("{0}: {1}", file.display(), error)),
+                Err(_) => panicking_files.push(file.display().to_string()),
+            }
+
+            let file_path = file.to_string_lossy().to_string();
+            match std::panic::catch_unwind(move || FlacReader {}.read_information(file_path))
+            {
+                Ok(Ok(_)) => println!("{0}: metadata accepted", file.display()),
+                Ok(Err(error)) => println!("{0}: metadata rejected, {1}", file.display(), error),
+                Err(_) => panicking_files.push(file.display().to_string()),
+            }
+        }
+
+        println!("{0} of {1} faulty files rejected:\n{2}", rejected_files.len(), files.len(), rejected_files.join("\n"));
+        assert!(panicking_files.is_empty(), "the reader panicked on:\n{0}", panicking_files.join("\n"));
+    }
+
+    #[test]
+    fn reject_malformed_files()
+    {
+        let path = std::env::temp_dir().join("quadrium_malformed.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut encoder = FlacEncoder::create(str_path.clone(), FlacEncoderSettings::new(8000, 1, 16)).unwrap();
+        encoder.write_samples(&generate_samples(1000, 1, 16)).unwrap();
+        encoder.finish().unwrap();
+        let flac_data = std::fs::read(&path).unwrap();
+
+        //
+        // The STREAMINFO block starts the file, it is not the last block
+        let mut bad_magic = flac_data.clone();
+        bad_magic[3] = b'X';
+        let truncated_streaminfo = flac_data[..4 + 4 + 10].to_vec();
+        let truncated_header = flac_data[..4 + 2].to_vec();
+        let mut short_streaminfo = flac_data[..4 + 4 + 20].to_vec();
+        short_streaminfo[4] = 0x80;
+        short_streaminfo[5..8].copy_from_slice(&[0, 0, 20]);
+        let mut odd_seek_table = flac_data[..4 + 4 + 34].to_vec();
+        write_metadata_block(&mut odd_seek_table, 3, &[0; 17], true).unwrap();
+        let mut long_vendor_string = flac_data[..4 + 4 + 34].to_vec();
+        write_metadata_block(&mut long_vendor_string, 4, &[0xFF, 0xFF, 0, 0, b'Q'], true).unwrap();
+
+        let read_both = |data: &[u8]| -> Vec<Result<(), AudioReaderError>>
+        {
+            std::fs::write(&path, data).unwrap();
+            return vec![FlacReader {}.read_information(str_path.clone()).map(|_| ()), FlacDecoder::open(str_path.clone()).map(|_| ())];
+        };
+        for result in read_both(&bad_magic)
+        {
+            assert!(matches!(result, Err(AudioReaderError::InvalidMagicNumber)), "{0:?}", result.err());
+        }
+        for result in read_both(&truncated_streaminfo)
+        {
+            assert!(matches!(result, Err(AudioReaderError::TruncatedBlock { m_block_name: "STREAMINFO", .. })), "{0:?}", result.err());
+        }
+        for result in read_both(&truncated_header)
+        {
+            assert!(matches!(result, Err(AudioReaderError::TruncatedBlock { m_block_name: "metadata header", m_offset: 4 })), "{0:?}", result.err());
+        }
+        for result in read_both(&short_streaminfo)
+        {
+            assert!(matches!(result, Err(AudioReaderError::InvalidBlockLength { m_block_name: "STREAMINFO", m_length: 20 })), "{0:?}", result.err());
+        }
+        for result in read_both(&odd_seek_table)
+        {
+            assert!(matches!(result, Err(AudioReaderError::InvalidBlockLength { m_block_name: "SEEKTABLE", m_length: 17 })), "{0:?}", result.err());
+        }
+        let result = read_both(&long_vendor_string).remove(0);
+        assert!(matches!(result, Err(AudioReaderError::InvalidBlockLength { .. })), "{0:?}", result.err());
+
+        std::fs::remove_file(&path).unwrap();
+        let missing_file = std::env::temp_dir().join("quadrium_missing.flac").to_string_lossy().to_string();
+        let result = FlacReader {}.read_information(missing_file.clone());
+        assert!(matches!(&result, Err(AudioReaderError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound), "{0:?}", result.err());
+        assert!(matches!(FlacDecoder::open(missing_file), Err(AudioReaderError::Io(_))));
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use crate::audio_reader::{AudioPicture, AudioReaderError, PictureType};
+use crate::audio_reader::flac_reader::{read_magic_number, read_metadata_header, read_picture_block, read_vorbis_comment_block};
+use crate::audio_reader::vorbis_comment::is_valid_key;
+use crate::utils::file_reader::read_bytes_from_file;
+
+/// Size of the padding added when the whole file is rewritten, to allow the next edits to be made in place
+pub(crate) const DEFAULT_PADDING_SIZE: usize = 8192;
+
+/// Maximal length of the content of a metadata block, stored on 24 bits
+const MAX_BLOCK_LENGTH: usize = 0xFFFFFF;
+
+/// Vendor string written when the file has no VORBIS_COMMENT block
+pub(crate) const DEFAULT_VENDOR_STRING: &str = "Quadrium";
+
+/// Type of the metadata blocks handled by the writer
+const BLOCK_TYPE_STREAMINFO: u8 = 0;
+const BLOCK_TYPE_PADDING: u8 = 1;
+const BLOCK_TYPE_VORBIS_COMMENT: u8 = 4;
+const BLOCK_TYPE_PICTURE: u8 = 6;
+
+/// Metadata block kept as it is by the writer
+struct RawMetadataBlock
+{
+    m_block_type: u8,
+    m_data: Vec<u8>,
+}
+
+/// Editor of the tags of a flac file: the Vorbis comments and the pictures.
+/// The other metadata blocks and the audio frames are kept unchanged.
+///
+/// # How to use it
+/// ```
+/// let mut writer = FlacMetadataWriter::open("path/to/file.flac".to_string())?;
+/// writer.set_vorbis_comment("TITLE", "New title")?;
+/// writer.remove_pictures(PictureType::FrontCover);
+/// writer.save()?;
+/// ```
+pub struct FlacMetadataWriter
+{
+    m_str_path_to_music: String,
+    m_vendor_string: String,
+
+    /// The comments of the file, each one written "FIELD=value"
+    m_vorbis_comments: Vec<String>,
+    m_pictures: Vec<AudioPicture>,
+}
+
+/// Get the field name of a comment, the part before the first '='
+fn get_comment_field(comment: &str) -> &str
+{
+    return match comment.find('=')
+    {
+        Some(separator_index) => &comment[..separator_index],
+        None => comment,
+    };
+}
+
+/// Append a string with its length on 32 bits before it
+fn write_sized_string(data: &mut Vec<u8>, string: &[u8], is_big_endian: bool)
+{
+    let length = string.len() as u32;
+    if is_big_endian
+    {
+        data.extend_from_slice(&length.to_be_bytes());
+    }
+    else
+    {
+        data.extend_from_slice(&length.to_le_bytes());
+    }
+    data.extend_from_slice(string);
+}
+
+/// Create the content of a VORBIS_COMMENT block.
+/// Unlike the other blocks, the lengths are little endian.
+pub(crate) fn create_vorbis_comment_block(vendor_string: &str, comments: &[String]) -> Vec<u8>
+{
+    let mut data: Vec<u8> = Vec::new();
+    write_sized_string(&mut data, vendor_string.as_bytes(), false);
+    data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments
+    {
+        write_sized_string(&mut data, comment.as_bytes(), false);
+    }
+    return data;
+}
+
+/// Create the content of a PICTURE block
+fn create_picture_block(picture: &AudioPicture) -> Vec<u8>
+{
+    let mut data: Vec<u8> = Vec::new();
+    data.extend_from_slice(&u32::from(picture.m_picture_type).to_be_bytes());
+    write_sized_string(&mut data, picture.m_mime_type.as_bytes(), true);
+    write_sized_string(&mut data, picture.m_description.as_bytes(), true);
+    data.extend_from_slice(&picture.m_width.to_be_bytes());
+    data.extend_from_slice(&picture.m_height.to_be_bytes());
+    data.extend_from_slice(&picture.m_color_depth.to_be_bytes());
+    data.extend_from_slice(&picture.m_indexed_color_count.to_be_bytes());
+    write_sized_string(&mut data, &picture.m_data, true);
+    return data;
+}
+
+/// Append a metadata block, with its header, to the metadata of a file
+pub(crate) fn write_metadata_block(metadata: &mut Vec<u8>, block_type: u8, data: &[u8], is_last: bool) -> Result<(), AudioReaderError>
+{
+    if data.len() > MAX_BLOCK_LENGTH
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "metadata", m_length: data.len() as u32 });
+    }
+
+    let mut header = (block_type as u32) << 24 | data.len() as u32;
+    if is_last
+    {
+        header |= 1 << 31;
+    }
+    metadata.extend_from_slice(&header.to_be_bytes());
+    metadata.extend_from_slice(data);
+    return Ok(());
+}
+
+/// Get the path of the temporary file used to rewrite a file, in the same directory to be renamed atomically
+pub(crate) fn get_temporary_path(path: &Path) -> PathBuf
+{
+    let mut file_name = std::ffi::OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(".quadrium-tmp");
+    return path.with_file_name(file_name);
+}
+
+impl FlacMetadataWriter
+{
+    /// Read the Vorbis comments and the pictures of a flac file to edit them
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the flac file
+    pub fn open(str_path_to_music: String) -> Result<FlacMetadataWriter, AudioReaderError>
+    {
+        let mut writer = FlacMetadataWriter
+        {
+            m_str_path_to_music: str_path_to_music,
+            m_vendor_string: DEFAULT_VENDOR_STRING.to_string(),
+            m_vorbis_comments: Vec::new(),
+            m_pictures: Vec::new(),
+        };
+
+        let mut file = File::open(&writer.m_str_path_to_music)?;
+        let file_length = file.metadata()?.len();
+        read_magic_number(&file)?;
+        loop
+        {
+            let metadata_header = read_metadata_header(&file, file_length)?;
+            if metadata_header.m_block_type == BLOCK_TYPE_VORBIS_COMMENT
+            {
+                let vorbis_comment = read_vorbis_comment_block(&file, metadata_header.m_length)?;
+                writer.m_vendor_string = vorbis_comment.m_vendor_string;
+                writer.m_vorbis_comments.extend(vorbis_comment.m_user_comment_list);
+            }
+            else if metadata_header.m_block_type == BLOCK_TYPE_PICTURE
+            {
+                writer.m_pictures.push(read_picture_block(&file, metadata_header.m_length)?);
+            }
+
+            if metadata_header.m_is_last
+            {
+                break;
+            }
+            file.seek(SeekFrom::Start(metadata_header.m_offset + metadata_header.m_length as u64))?;
+        }
+
+        return Ok(writer);
+    }
+
+    /// Get all the comments, each one written "FIELD=value"
+    pub fn get_vorbis_comments(&self) -> &Vec<String>
+    {
+        return &self.m_vorbis_comments;
+    }
+
+    /// Get the values of a field, the field names are case insensitive
+    pub fn get_vorbis_comment_values(&self, field: &str) -> Vec<&str>
+    {
+        return self.m_vorbis_comments.iter()
+            .filter(|comment| get_comment_field(comment).eq_ignore_ascii_case(field))
+            .map(|comment| &comment[(field.len() + 1).min(comment.len())..])
+            .collect();
+    }
+
+    /// Replace all the values of a field by a single value
+    ///
+    /// # Return
+    /// The InvalidValue error if the field name is empty or uses a character not allowed by Vorbis comments
+    pub fn set_vorbis_comment(&mut self, field: &str, value: &str) -> Result<(), AudioReaderError>
+    {
+        if !is_valid_key(field)
+        {
+            return Err(AudioReaderError::InvalidValue("Vorbis comment field name"));
+        }
+        self.remove_vorbis_comment(field);
+        return self.add_vorbis_comment(field, value);
+    }
+
+    /// Add a value to a field, keeping its other values
+    ///
+    /// # Return
+    /// The InvalidValue error if the field name is empty or uses a character not allowed by Vorbis comments
+    pub fn add_vorbis_comment(&mut self, field: &str, value: &str) -> Result<(), AudioReaderError>
+    {
+        if !is_valid_key(field)
+        {
+            return Err(AudioReaderError::InvalidValue("Vorbis comment field name"));
+        }
+        self.m_vorbis_comments.push(format!("{0}={1}", field, value));
+        return Ok(());
+    }
+
+    /// Remove all the values of a field
+    pub fn remove_vorbis_comment(&mut self, field: &str)
+    {
+        self.m_vorbis_comments.retain(|comment| !get_comment_field(comment).eq_ignore_ascii_case(field));
+    }
+
+    pub fn get_pictures(&self) -> &Vec<AudioPicture>
+    {
+        return &self.m_pictures;
+    }
+
+    pub fn add_picture(&mut self, picture: AudioPicture)
+    {
+        self.m_pictures.push(picture);
+    }
+
+    /// Remove all the pictures of a type
+    pub fn remove_pictures(&mut self, picture_type: PictureType)
+    {
+        self.m_pictures.retain(|picture| picture.m_picture_type != picture_type);
+    }
+
+    pub fn remove_all_pictures(&mut self)
+    {
+        self.m_pictures.clear();
+    }
+
+    /// Write the comments and the pictures in the file.
+    /// When the new metadata fit in the space of the old ones, using the padding, only the metadata are overwritten.
+    /// Otherwise the whole file is written in a temporary file with a new padding, which then replaces the original one,
+    /// so the original file is never left half written.
+    pub fn save(&self) -> Result<(), AudioReaderError>
+    {
+        //
+        // Keep all the blocks except the ones which are rewritten and the padding
+        let mut file = File::open(&self.m_str_path_to_music)?;
+        let file_length = file.metadata()?.len();
+        read_magic_number(&file)?;
+        let mut kept_blocks: Vec<RawMetadataBlock> = Vec::new();
+        let first_frame_offset = loop
+        {
+            let metadata_header = read_metadata_header(&file, file_length)?;
+            let block_type = metadata_header.m_block_type;
+            if block_type != BLOCK_TYPE_PADDING && block_type != BLOCK_TYPE_VORBIS_COMMENT && block_type != BLOCK_TYPE_PICTURE
+            {
+                kept_blocks.push(RawMetadataBlock
+                {
+                    m_block_type: block_type,
+                    m_data: read_bytes_from_file(&file, metadata_header.m_length as usize)?,
+                });
+            }
+
+            let block_end = file.seek(SeekFrom::Start(metadata_header.m_offset + metadata_header.m_length as u64))?;
+            if metadata_header.m_is_last
+            {
+                break block_end;
+            }
+        };
+        if kept_blocks.first().map(|block| block.m_block_type) != Some(BLOCK_TYPE_STREAMINFO)
+        {
+            return Err(AudioReaderError::MissingBlock("STREAMINFO"));
+        }
+
+        //
+        // The STREAMINFO block stays the first one, the edited blocks follow the kept ones
+        let mut new_blocks: Vec<(u8, Vec<u8>)> = kept_blocks.into_iter()
+            .map(|block| (block.m_block_type, block.m_data))
+            .collect();
+        new_blocks.push((BLOCK_TYPE_VORBIS_COMMENT, create_vorbis_comment_block(&self.m_vendor_string, &self.m_vorbis_comments)));
+        for picture in self.m_pictures.iter()
+        {
+            new_blocks.push((BLOCK_TYPE_PICTURE, create_picture_block(picture)));
+        }
+
+        //
+        // The audio stays at the same offset when the old metadata and padding have room for the new ones.
+        // The padding needs at least its header, so a difference of less than 4 bytes cannot be filled
+        let old_metadata_size = first_frame_offset as usize - 4;
+        let new_metadata_size: usize = new_blocks.iter().map(|(_block_type, data)| 4 + data.len()).sum();
+        let available_size = old_metadata_size.checked_sub(new_metadata_size);
+        let is_padding_reused = match available_size
+        {
+            Some(available_size) => available_size == 0 || available_size >= 4,
+            None => false,
+        };
+        let padding_size = if is_padding_reused { available_size.unwrap_or(0).saturating_sub(4) } else { DEFAULT_PADDING_SIZE };
+        let has_padding = !is_padding_reused || available_size != Some(0);
+
+        let mut metadata: Vec<u8> = Vec::with_capacity(4 + new_metadata_size + 4 + padding_size);
+        metadata.extend_from_slice(b"fLaC");
+        let block_count = new_blocks.len();
+        for (block_index, (block_type, data)) in new_blocks.iter().enumerate()
+        {
+            write_metadata_block(&mut metadata, *block_type, data, !has_padding && block_index + 1 == block_count)?;
+        }
+        if has_padding
+        {
+            write_metadata_block(&mut metadata, BLOCK_TYPE_PADDING, &vec![0; padding_size], true)?;
+        }
+
+        if is_padding_reused
+        {
+            let mut output_file = OpenOptions::new().write(true).open(&self.m_str_path_to_music)?;
+            output_file.write_all(&metadata)?;
+            output_file.sync_all()?;
+            return Ok(());
+        }
+
+        //
+        // Write the new file next to the original one, then replace the original one
+        let path = Path::new(&self.m_str_path_to_music);
+        let temporary_path = get_temporary_path(path);
+        let result = (|| -> Result<(), AudioReaderError>
+        {
+            let mut output_file = File::create(&temporary_path)?;
+            output_file.write_all(&metadata)?;
+            file.seek(SeekFrom::Start(first_frame_offset))?;
+            std::io::copy(&mut file, &mut output_file)?;
+            output_file.set_permissions(file.metadata()?.permissions())?;
+            output_file.sync_all()?;
+            std::fs::rename(&temporary_path, path)?;
+            return Ok(());
+        })();
+        if result.is_err()
+        {
+            let _ = std::fs::remove_file(&temporary_path);
+        }
+
+        return result;
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Decoder of the APPLICATION blocks written by "flac --keep-foreign-metadata".
+// The first block holds the 12 bytes starting the original file, each next block holds one chunk
+// of the file with its header, except the chunk of the audio whose samples are not stored.
+
+use std::convert::TryInto;
+use crate::audio_reader::AudioReaderError;
+use crate::audio_reader::flac_reader::ApplicationBlock;
+
+/// Application ids of the foreign metadata
+const RIFF_APPLICATION_ID: &[u8; 4] = b"riff";
+const AIFF_APPLICATION_ID: &[u8; 4] = b"aiff";
+
+/// Size of the header of a chunk: its id and its size
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Size of the container header: its id, its size and its form type
+const CONTAINER_HEADER_SIZE: usize = 12;
+
+/// Format of the file a flac file was encoded from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForeignFormat
+{
+    /// RIFF/WAVE file, also in its RF64 and BW64 variants, with little endian sizes
+    Riff,
+
+    /// AIFF or AIFF-C file, with big endian sizes
+    Aiff,
+}
+
+/// Chunk of the original file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignChunk
+{
+    pub m_id: [u8; 4],
+
+    /// Size written in the header of the chunk. The size of the audio chunk counts samples which are not stored,
+    /// and the size of the RF64 chunks can be 0xFFFFFFFF.
+    pub m_size: u32,
+
+    /// Content of the chunk after its header, with its padding byte when its size is odd
+    pub m_data: Vec<u8>,
+}
+
+/// Chunks of the RIFF or AIFF file a flac file was encoded from, used to restore this file byte-exact
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignMetadata
+{
+    pub m_format: ForeignFormat,
+
+    /// Id of the container, like "RIFF", "RF64" or "FORM"
+    pub m_container_id: [u8; 4],
+    pub m_container_size: u32,
+
+    /// Type of the form, like "WAVE", "AIFF" or "AIFC"
+    pub m_form_type: [u8; 4],
+
+    /// All the chunks in the order of the file, including the audio chunk
+    pub m_chunks: Vec<ForeignChunk>,
+
+    /// Index in m_chunks of the chunk holding the audio ("data" or "SSND"), stored without its samples
+    pub m_audio_chunk_index: usize,
+}
+
+/// Write a size in the endianness of a format
+fn convert_size_to_bytes(size: u32, format: ForeignFormat) -> [u8; 4]
+{
+    return match format
+    {
+        ForeignFormat::Riff => size.to_le_bytes(),
+        ForeignFormat::Aiff => size.to_be_bytes(),
+    };
+}
+
+/// Read a size in the endianness of a format
+fn convert_bytes_to_size(bytes: &[u8], format: ForeignFormat) -> u32
+{
+    let bytes: [u8; 4] = bytes[0..4].try_into().unwrap();
+    return match format
+    {
+        ForeignFormat::Riff => u32::from_le_bytes(bytes),
+        ForeignFormat::Aiff => u32::from_be_bytes(bytes),
+    };
+}
+
+impl ForeignMetadata
+{
+    /// Decode the foreign metadata from the APPLICATION blocks of a flac file.
+    /// The blocks of other applications are ignored.
+    ///
+    /// # Params
+    /// application_blocks: all the APPLICATION blocks of the file, in the order of the file
+    ///
+    /// # Return
+    /// The foreign metadata, or None if there is no "riff" or "aiff" block
+    pub fn from_application_blocks(application_blocks: &[ApplicationBlock]) -> Result<Option<ForeignMetadata>, AudioReaderError>
+    {
+        let mut foreign_blocks = application_blocks.iter()
+            .filter(|block| &block.m_application_id == RIFF_APPLICATION_ID || &block.m_application_id == AIFF_APPLICATION_ID);
+        let first_block = match foreign_blocks.next()
+        {
+            None => return Ok(None),
+            Some(first_block) => first_block,
+        };
+
+        //
+        // The first block is the header of the container, which must match the application
+        let format = if &first_block.m_application_id == RIFF_APPLICATION_ID { ForeignFormat::Riff } else { ForeignFormat::Aiff };
+        let header = &first_block.m_application_data;
+        if header.len() != CONTAINER_HEADER_SIZE
+        {
+            return Err(AudioReaderError::InvalidBlockLength { m_block_name: "APPLICATION", m_length: header.len() as u32 + 4 });
+        }
+        let container_id: [u8; 4] = header[0..4].try_into().unwrap();
+        let form_type: [u8; 4] = header[8..12].try_into().unwrap();
+        let is_valid_container = match format
+        {
+            ForeignFormat::Riff => (&container_id == b"RIFF" || &container_id == b"RF64" || &container_id == b"BW64") && &form_type == b"WAVE",
+            ForeignFormat::Aiff => &container_id == b"FORM" && (&form_type == b"AIFF" || &form_type == b"AIFC"),
+        };
+        if !is_valid_container
+        {
+            return Err(AudioReaderError::InvalidValue("container of the foreign metadata"));
+        }
+
+        let audio_chunk_id = match format
+        {
+            ForeignFormat::Riff => b"data",
+            ForeignFormat::Aiff => b"SSND",
+        };
+        let mut chunks: Vec<ForeignChunk> = Vec::new();
+        let mut audio_chunk_index: Option<usize> = None;
+        for block in foreign_blocks
+        {
+            //
+            // All the foreign blocks come from the same file
+            if block.m_application_id != first_block.m_application_id
+            {
+                return Err(AudioReaderError::InvalidValue("application id of the foreign metadata"));
+            }
+            let data = &block.m_application_data;
+            if data.len() < CHUNK_HEADER_SIZE
+            {
+                return Err(AudioReaderError::InvalidBlockLength { m_block_name: "APPLICATION", m_length: data.len() as u32 + 4 });
+            }
+
+            let chunk_id: [u8; 4] = data[0..4].try_into().unwrap();
+            if &chunk_id == audio_chunk_id
+            {
+                if audio_chunk_index.is_some()
+                {
+                    return Err(AudioReaderError::InvalidValue("audio chunk of the foreign metadata"));
+                }
+                audio_chunk_index = Some(chunks.len());
+            }
+            chunks.push(ForeignChunk
+            {
+                m_id: chunk_id,
+                m_size: convert_bytes_to_size(&data[4..8], format),
+                m_data: data[CHUNK_HEADER_SIZE..].to_vec(),
+            });
+        }
+
+        return match audio_chunk_index
+        {
+            None => Err(AudioReaderError::MissingBlock("audio chunk of the foreign metadata")),
+            Some(audio_chunk_index) => Ok(Some(ForeignMetadata
+            {
+                m_format: format,
+                m_container_id: container_id,
+                m_container_size: convert_bytes_to_size(&header[4..8], format),
+                m_form_type: form_type,
+                m_chunks: chunks,
+                m_audio_chunk_index: audio_chunk_index,
+            })),
+        };
+    }
+
+    /// Get the first chunk with an id, like "bext", "iXML" or "smpl"
+    pub fn get_chunk(&self, chunk_id: &[u8; 4]) -> Option<&ForeignChunk>
+    {
+        return self.m_chunks.iter().find(|chunk| &chunk.m_id == chunk_id);
+    }
+
+    /// Write a chunk as it is stored in the original file
+    fn write_chunk(&self, bytes: &mut Vec<u8>, chunk: &ForeignChunk)
+    {
+        bytes.extend_from_slice(&chunk.m_id);
+        bytes.extend_from_slice(&convert_size_to_bytes(chunk.m_size, self.m_format));
+        bytes.extend_from_slice(&chunk.m_data);
+    }
+
+    /// Get the bytes of the original file before its samples, from its first byte up to the header of the audio chunk
+    pub fn get_bytes_before_audio(&self) -> Vec<u8>
+    {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&self.m_container_id);
+        bytes.extend_from_slice(&convert_size_to_bytes(self.m_container_size, self.m_format));
+        bytes.extend_from_slice(&self.m_form_type);
+        for chunk in self.m_chunks[..=self.m_audio_chunk_index].iter()
+        {
+            self.write_chunk(&mut bytes, chunk);
+        }
+        return bytes;
+    }
+
+    /// Get the bytes of the original file after its samples: the chunks following the audio chunk.
+    /// The padding byte of an audio chunk with an odd size is not stored and must be added between the samples and these bytes.
+    pub fn get_bytes_after_audio(&self) -> Vec<u8>
+    {
+        let mut bytes: Vec<u8> = Vec::new();
+        for chunk in self.m_chunks[self.m_audio_chunk_index + 1..].iter()
+        {
+            self.write_chunk(&mut bytes, chunk);
+        }
+        return bytes;
+    }
+}
@@ -0,0 +1,1182 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on https://id3.org/id3v2-00, https://id3.org/id3v2.3.0, https://id3.org/id3v2.4.0-structure,
+// https://id3.org/id3v2.4.0-frames and https://id3.org/ID3v1
+
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+use crate::audio_reader::{AudioInformation, AudioPicture, AudioReaderError, PictureType, TagDate, TrackMetadata};
+use crate::audio_reader::replay_gain::ReplayGainInfo;
+
+/// Size of the header of a tag
+const TAG_HEADER_SIZE: usize = 10;
+
+/// Size of an ID3v1 tag, stored at the end of the file
+const ID3V1_TAG_SIZE: usize = 128;
+
+/// Flags of the tag header
+const TAG_FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const TAG_FLAG_EXTENDED_HEADER: u8 = 0x40;
+
+/// Flag of the ID3v2.2 tag header, replaced by the extended header in ID3v2.3
+const TAG_FLAG_V22_COMPRESSION: u8 = 0x40;
+
+/// Flags of the format of an ID3v2.3 frame
+const FRAME_FLAG_V23_COMPRESSION: u8 = 0x80;
+const FRAME_FLAG_V23_ENCRYPTION: u8 = 0x40;
+const FRAME_FLAG_V23_GROUPING: u8 = 0x20;
+
+/// Flags of the format of an ID3v2.4 frame
+const FRAME_FLAG_V24_GROUPING: u8 = 0x40;
+const FRAME_FLAG_V24_COMPRESSION: u8 = 0x08;
+const FRAME_FLAG_V24_ENCRYPTION: u8 = 0x04;
+const FRAME_FLAG_V24_UNSYNCHRONISATION: u8 = 0x02;
+const FRAME_FLAG_V24_DATA_LENGTH: u8 = 0x01;
+
+/// Ids of the ID3v2.2 frames and of the ID3v2.3 frames replacing them
+const V22_FRAME_IDS: [(&str, &str); 34] = [
+    ("TT1", "TIT1"), ("TT2", "TIT2"), ("TT3", "TIT3"), ("TP1", "TPE1"), ("TP2", "TPE2"), ("TP3", "TPE3"), ("TP4", "TPE4"),
+    ("TAL", "TALB"), ("TRK", "TRCK"), ("TPA", "TPOS"), ("TCO", "TCON"), ("TYE", "TYER"), ("TDA", "TDAT"), ("TIM", "TIME"),
+    ("TCM", "TCOM"), ("TXT", "TEXT"), ("TLA", "TLAN"), ("TLE", "TLEN"), ("TBP", "TBPM"), ("TPB", "TPUB"), ("TCR", "TCOP"),
+    ("TEN", "TENC"), ("TSS", "TSSE"), ("TRC", "TSRC"), ("TOA", "TOPE"), ("TOT", "TOAL"), ("TXX", "TXXX"), ("COM", "COMM"),
+    ("ULT", "USLT"), ("SLT", "SYLT"), ("PIC", "APIC"), ("POP", "POPM"), ("CNT", "PCNT"), ("UFI", "UFID"),
+];
+
+/// Genres of ID3v1, with the extensions of Winamp, referenced by their index in the TCON frames
+const ID3V1_GENRES: [&str; 192] = [
+    "Blues", "Classic Rock", "Country", "Dance", "Disco", "Funk", "Grunge", "Hip-Hop", "Jazz", "Metal",
+    "New Age", "Oldies", "Other", "Pop", "R&B", "Rap", "Reggae", "Rock", "Techno", "Industrial",
+    "Alternative", "Ska", "Death Metal", "Pranks", "Soundtrack", "Euro-Techno", "Ambient", "Trip-Hop", "Vocal", "Jazz+Funk",
+    "Fusion", "Trance", "Classical", "Instrumental", "Acid", "House", "Game", "Sound Clip", "Gospel", "Noise",
+    "AlternRock", "Bass", "Soul", "Punk", "Space", "Meditative", "Instrumental Pop", "Instrumental Rock", "Ethnic", "Gothic",
+    "Darkwave", "Techno-Industrial", "Electronic", "Pop-Folk", "Eurodance", "Dream", "Southern Rock", "Comedy", "Cult", "Gangsta",
+    "Top 40", "Christian Rap", "Pop/Funk", "Jungle", "Native American", "Cabaret", "New Wave", "Psychadelic", "Rave", "Showtunes",
+    "Trailer", "Lo-Fi", "Tribal", "Acid Punk", "Acid Jazz", "Polka", "Retro", "Musical", "Rock & Roll", "Hard Rock",
+    "Folk", "Folk-Rock", "National Folk", "Swing", "Fast Fusion", "Bebob", "Latin", "Revival", "Celtic", "Bluegrass",
+    "Avantgarde", "Gothic Rock", "Progressive Rock", "Psychedelic Rock", "Symphonic Rock", "Slow Rock", "Big Band", "Chorus", "Easy Listening", "Acoustic",
+    "Humour", "Speech", "Chanson", "Opera", "Chamber Music", "Sonata", "Symphony", "Booty Bass", "Primus", "Porn Groove",
+    "Satire", "Slow Jam", "Club", "Tango", "Samba", "Folklore", "Ballad", "Power Ballad", "Rhythmic Soul", "Freestyle",
+    "Duet", "Punk Rock", "Drum Solo", "A capella", "Euro-House", "Dance Hall", "Goa", "Drum & Bass", "Club-House", "Hardcore",
+    "Terror", "Indie", "BritPop", "Afro-Punk", "Polsk Punk", "Beat", "Christian Gangsta Rap", "Heavy Metal", "Black Metal", "Crossover",
+    "Contemporary Christian", "Christian Rock", "Merengue", "Salsa", "Thrash Metal", "Anime", "JPop", "Synthpop", "Abstract", "Art Rock",
+    "Baroque", "Bhangra", "Big Beat", "Breakbeat", "Chillout", "Downtempo", "Dub", "EBM", "Eclectic", "Electro",
+    "Electroclash", "Emo", "Experimental", "Garage", "Global", "IDM", "Illbient", "Industro-Goth", "Jam Band", "Krautrock",
+    "Leftfield", "Lounge", "Math Rock", "New Romantic", "Nu-Breakz", "Post-Punk", "Post-Rock", "Psytrance", "Shoegaze", "Space Rock",
+    "Trop Rock", "World Music", "Neoclassical", "Audiobook", "Audio Theatre", "Neue Deutsche Welle", "Podcast", "Indie Rock", "G-Funk", "Dubstep",
+    "Garage Rock", "Psybient",
+];
+
+/// Frame of an ID3v2 tag, with its content not decoded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3Frame
+{
+    /// Id of the frame, like "TIT2". The ids of ID3v2.2 are replaced by the ones of ID3v2.3.
+    pub m_id: String,
+
+    /// Content of the frame, resynchronised and decompressed, still encrypted if the frame is encrypted
+    pub m_data: Vec<u8>,
+
+    /// Method used to encrypt the frame, registered by an ENCR frame, None if the frame is not encrypted
+    pub m_encryption_method: Option<u8>,
+}
+
+/// ID3v2 tag, stored at the beginning of MP3 files and inside the "id3 " chunks of WAV and AIFF files
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3Tag
+{
+    /// Major version of the tag: 2 for ID3v2.2, 3 for ID3v2.3, 4 for ID3v2.4
+    pub m_major_version: u8,
+    pub m_frames: Vec<Id3Frame>,
+}
+
+/// Text frame defined by the user, with a TXXX frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3UserText
+{
+    pub m_description: String,
+    pub m_values: Vec<String>,
+}
+
+/// Text in a language, stored by the COMM and USLT frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3LocalizedText
+{
+    /// ISO-639-2 code of the language, like "eng"
+    pub m_language: String,
+    pub m_description: String,
+    pub m_text: String,
+}
+
+/// Text shown at a time, like a line of lyrics
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3SynchronisedText
+{
+    /// Time of the text, in the unit of the timestamp format of its frame
+    pub m_timestamp: u32,
+    pub m_text: String,
+}
+
+/// Lyrics or other texts synchronised with the audio, stored by a SYLT frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3SynchronisedLyrics
+{
+    pub m_language: String,
+
+    /// 1 if the timestamps count MPEG frames, 2 if they count milliseconds
+    pub m_timestamp_format: u8,
+
+    /// 1 for lyrics, 2 for a transcription, 3 for the names of the movements, ...
+    pub m_content_type: u8,
+    pub m_description: String,
+    pub m_texts: Vec<Id3SynchronisedText>,
+}
+
+/// Rating and play count given by a user, stored by a POPM frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3Popularimeter
+{
+    /// Email of the user who gave the rating
+    pub m_email: String,
+
+    /// From 1 (worst) to 255 (best), 0 if unknown
+    pub m_rating: u8,
+    pub m_play_count: u64,
+}
+
+/// Data of an application, stored by a PRIV frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3PrivateData
+{
+    /// Identifier of the application which owns the data, usually an URL or an email
+    pub m_owner: String,
+    pub m_data: Vec<u8>,
+}
+
+/// ID3v1 or ID3v1.1 tag, stored in the last 128 bytes of MP3 files
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3v1Tag
+{
+    pub m_title: String,
+    pub m_artist: String,
+    pub m_album: String,
+    pub m_year: String,
+    pub m_comment: String,
+
+    /// Track number of ID3v1.1, 0 if unknown
+    pub m_track_number: u8,
+
+    /// Index of the genre in the list of ID3v1, None if unknown
+    pub m_genre: Option<u8>,
+}
+
+/// ID3 tags of a file: an ID3v2 tag at its beginning and an ID3v1 tag at its end
+#[derive(Clone, Debug, PartialEq)]
+pub struct Id3FileTags
+{
+    pub m_id3v2_tag: Option<Id3Tag>,
+    pub m_id3v1_tag: Option<Id3v1Tag>,
+
+    /// Number of bytes of the file taken by the two tags, which are not audio data
+    pub m_tag_byte_count: u64,
+}
+
+/// Read a size stored on 4 bytes of 7 bits, the highest bit of each byte being 0
+fn read_syncsafe_u32(data: &[u8]) -> u32
+{
+    return data[0..4].iter().fold(0, |size, byte| (size << 7) | (*byte & 0x7F) as u32);
+}
+
+/// Undo the unsynchronisation scheme, which inserts a byte at 0 after each byte at 0xFF to hide the MPEG sync words
+fn resynchronise(data: &[u8]) -> Vec<u8>
+{
+    let mut resynchronised_data: Vec<u8> = Vec::with_capacity(data.len());
+    let mut is_after_0xff = false;
+    for byte in data.iter()
+    {
+        if !(is_after_0xff && *byte == 0)
+        {
+            resynchronised_data.push(*byte);
+        }
+        is_after_0xff = *byte == 0xFF;
+    }
+    return resynchronised_data;
+}
+
+/// Decode a text encoded in ISO-8859-1
+fn decode_latin1(data: &[u8]) -> String
+{
+    return data.iter().map(|byte| *byte as char).collect();
+}
+
+/// Decode a text encoded in UTF-16
+///
+/// # Params
+/// data: the text, without its byte order mark
+/// is_big_endian: the byte order of the text
+fn decode_utf16(data: &[u8], is_big_endian: bool) -> String
+{
+    let code_units: Vec<u16> = data.chunks_exact(2)
+        .map(|bytes| if is_big_endian { u16::from_be_bytes([bytes[0], bytes[1]]) } else { u16::from_le_bytes([bytes[0], bytes[1]]) })
+        .collect();
+    return String::from_utf16_lossy(&code_units);
+}
+
+/// Decode a string, without its terminating null character
+///
+/// # Params
+/// data: the string
+/// encoding: 0 for ISO-8859-1, 1 for UTF-16 with a byte order mark, 2 for UTF-16 big endian, 3 for UTF-8
+fn decode_string(data: &[u8], encoding: u8) -> String
+{
+    let decoded_string = match encoding
+    {
+        0 => decode_latin1(data),
+        1 =>
+        {
+            //
+            // UTF-16 with a byte order mark, little endian when it is missing
+            if data.starts_with(&[0xFE, 0xFF])
+            {
+                decode_utf16(&data[2..], true)
+            }
+            else if data.starts_with(&[0xFF, 0xFE])
+            {
+                decode_utf16(&data[2..], false)
+            }
+            else
+            {
+                decode_utf16(data, false)
+            }
+        }
+        2 => decode_utf16(data, true),
+        _ => String::from_utf8_lossy(data).to_string(),
+    };
+    return decoded_string.trim_start_matches('\u{FEFF}').to_string();
+}
+
+/// Read a string ended by a null character, on 2 bytes for UTF-16
+///
+/// # Return
+/// The decoded string and the data after its null character, empty if the string is not ended
+fn read_terminated_string(data: &[u8], encoding: u8) -> (String, &[u8])
+{
+    let string_end = match encoding
+    {
+        1 | 2 => (0..data.len() / 2).map(|index| index * 2).find(|position| data[*position] == 0 && data[*position + 1] == 0),
+        _ => data.iter().position(|byte| *byte == 0),
+    };
+    return match string_end
+    {
+        None => (decode_string(data, encoding), &data[data.len()..]),
+        Some(string_end) =>
+        {
+            let terminator_size = if encoding == 1 || encoding == 2 { 2 } else { 1 };
+            (decode_string(&data[..string_end], encoding), &data[string_end + terminator_size..])
+        }
+    };
+}
+
+/// Decode the strings of a text frame separated by null characters, each one with its own byte order mark in ID3v2.4
+fn decode_strings(mut data: &[u8], encoding: u8) -> Vec<String>
+{
+    let mut strings: Vec<String> = Vec::new();
+    while !data.is_empty()
+    {
+        let (string, remaining_data) = read_terminated_string(data, encoding);
+        strings.push(string);
+        data = remaining_data;
+    }
+    return strings;
+}
+
+/// Decode the content of a text frame: an encoding byte followed by one or several strings separated by null characters
+///
+/// # Return
+/// The strings of the frame, empty if the encoding is not valid
+fn decode_text_frame(data: &[u8]) -> Vec<String>
+{
+    if data.is_empty() || data[0] > 3
+    {
+        return Vec::new();
+    }
+    return decode_strings(&data[1..], data[0]);
+}
+
+/// Decode the content of a COMM or USLT frame: an encoding byte, a language, a description and a text
+fn decode_localized_text_frame(data: &[u8]) -> Option<Id3LocalizedText>
+{
+    if data.len() < 4 || data[0] > 3
+    {
+        return None;
+    }
+    let (description, text) = read_terminated_string(&data[4..], data[0]);
+    return Some(Id3LocalizedText
+    {
+        m_language: decode_latin1(&data[1..4]),
+        m_description: description,
+        m_text: read_terminated_string(text, data[0]).0,
+    });
+}
+
+/// Convert an ID3v2.2 PIC frame, which gives the format of the image on 3 characters, to an APIC frame with a MIME type
+fn convert_v22_picture_frame(data: &[u8]) -> Vec<u8>
+{
+    if data.len() < 4
+    {
+        return data.to_vec();
+    }
+    let mime_type = match &data[1..4]
+    {
+        b"JPG" | b"jpg" => "image/jpeg".to_string(),
+        b"PNG" | b"png" => "image/png".to_string(),
+        b"-->" => "-->".to_string(),
+        image_format => format!("image/{}", decode_latin1(image_format).to_lowercase()),
+    };
+    let mut apic_data = vec![data[0]];
+    apic_data.extend_from_slice(mime_type.as_bytes());
+    apic_data.push(0);
+    apic_data.extend_from_slice(&data[4..]);
+    return apic_data;
+}
+
+/// Get the name of a genre of the ID3v1 list
+fn get_genre_name(genre_index: u32) -> Option<String>
+{
+    return ID3V1_GENRES.get(genre_index as usize).map(|genre| genre.to_string());
+}
+
+/// Resolve the references to the genres of ID3v1 of a TCON value
+///
+/// # Params
+/// value: a genre like "Rock", a reference like "17" in ID3v2.4 or "(17)" in ID3v2.3, or references followed by a refinement like "(4)Eurodisco"
+///
+/// # Return
+/// The names of the genres
+fn resolve_genre(value: &str) -> Vec<String>
+{
+    let resolve_reference = |reference: &str| match reference
+    {
+        "RX" => Some("Remix".to_string()),
+        "CR" => Some("Cover".to_string()),
+        _ => reference.parse::<u32>().ok().and_then(get_genre_name),
+    };
+    if let Some(genre) = resolve_reference(value)
+    {
+        return vec![genre];
+    }
+
+    //
+    // The text after the references refines them, a text starting with a parenthesis starts with "(("
+    let mut genres: Vec<String> = Vec::new();
+    let mut remaining_value = value;
+    while remaining_value.starts_with('(') && !remaining_value.starts_with("((")
+    {
+        let reference_end = match remaining_value.find(')')
+        {
+            None => break,
+            Some(reference_end) => reference_end,
+        };
+        match resolve_reference(&remaining_value[1..reference_end])
+        {
+            None => break,
+            Some(genre) => genres.push(genre),
+        }
+        remaining_value = &remaining_value[reference_end + 1..];
+    }
+    let refinement = remaining_value.strip_prefix('(').filter(|_| remaining_value.starts_with("((")).unwrap_or(remaining_value);
+    if !refinement.is_empty() && !genres.iter().any(|genre| genre == refinement)
+    {
+        genres.push(refinement.to_string());
+    }
+    return genres;
+}
+
+/// Read the header of a frame and the fields it adds before the content of the frame, then decode the content
+///
+/// # Params
+/// frame_header: the header of the frame, 6 bytes in ID3v2.2, 10 bytes after
+/// frame_data: the data of the frame after its header
+/// major_version: the version of the tag
+/// is_tag_unsynchronised: whether the tag flag of ID3v2.4 says all the frames are unsynchronised
+///
+/// # Return
+/// The frame, or None if its content cannot be decompressed
+fn decode_frame(frame_header: &[u8], frame_data: &[u8], major_version: u8, is_tag_unsynchronised: bool) -> Option<Id3Frame>
+{
+    if major_version == 2
+    {
+        let frame_id = decode_latin1(&frame_header[0..3]);
+        let frame_id = V22_FRAME_IDS.iter().find(|(v22_id, _)| *v22_id == frame_id).map_or(frame_id, |(_, id)| id.to_string());
+        let data = if frame_id == "APIC" { convert_v22_picture_frame(frame_data) } else { frame_data.to_vec() };
+        return Some(Id3Frame { m_id: frame_id, m_data: data, m_encryption_method: None });
+    }
+
+    //
+    // The flags add fields before the content, in the order of the flags
+    let format_flags = frame_header[9];
+    let (is_compressed, is_encrypted, is_unsynchronised, added_fields) = match major_version
+    {
+        3 => (format_flags & FRAME_FLAG_V23_COMPRESSION != 0, format_flags & FRAME_FLAG_V23_ENCRYPTION != 0, false,
+            [(FRAME_FLAG_V23_COMPRESSION, 4), (FRAME_FLAG_V23_ENCRYPTION, 1), (FRAME_FLAG_V23_GROUPING, 1)]),
+        _ => (format_flags & FRAME_FLAG_V24_COMPRESSION != 0, format_flags & FRAME_FLAG_V24_ENCRYPTION != 0,
+            is_tag_unsynchronised || format_flags & FRAME_FLAG_V24_UNSYNCHRONISATION != 0,
+            [(FRAME_FLAG_V24_GROUPING, 1), (FRAME_FLAG_V24_ENCRYPTION, 1), (FRAME_FLAG_V24_DATA_LENGTH, 4)]),
+    };
+    let encryption_flag = if major_version == 3 { FRAME_FLAG_V23_ENCRYPTION } else { FRAME_FLAG_V24_ENCRYPTION };
+    let mut position = 0;
+    let mut encryption_method: Option<u8> = None;
+    for (flag, field_size) in added_fields.iter()
+    {
+        if format_flags & flag != 0
+        {
+            if position + field_size > frame_data.len()
+            {
+                return None;
+            }
+            if *flag == encryption_flag
+            {
+                encryption_method = Some(frame_data[position]);
+            }
+            position += field_size;
+        }
+    }
+
+    //
+    // An encrypted frame is kept as it is, its content can only be decoded by the application which encrypted it
+    let mut data = frame_data[position..].to_vec();
+    if !is_encrypted
+    {
+        if is_unsynchronised
+        {
+            data = resynchronise(&data);
+        }
+        if is_compressed
+        {
+            data = miniz_oxide::inflate::decompress_to_vec_zlib(&data).ok()?;
+        }
+    }
+    return Some(Id3Frame { m_id: decode_latin1(&frame_header[0..4]), m_data: data, m_encryption_method: encryption_method });
+}
+
+impl Id3Tag
+{
+    /// Decode an ID3v2.2, ID3v2.3 or ID3v2.4 tag
+    ///
+    /// # Params
+    /// data: the tag, starting with its header "ID3"
+    pub fn parse(data: &[u8]) -> Result<Id3Tag, AudioReaderError>
+    {
+        if data.len() < TAG_HEADER_SIZE || &data[0..3] != b"ID3"
+        {
+            return Err(AudioReaderError::InvalidMagicNumber);
+        }
+        let major_version = data[3];
+        if major_version < 2 || major_version > 4
+        {
+            return Err(AudioReaderError::UnsupportedFeature("ID3v2 version"));
+        }
+        let flags = data[5];
+        if major_version == 2 && flags & TAG_FLAG_V22_COMPRESSION != 0
+        {
+            return Err(AudioReaderError::UnsupportedFeature("compressed ID3v2.2 tag"));
+        }
+        let tag_size = read_syncsafe_u32(&data[6..10]) as usize;
+        if data.len() < TAG_HEADER_SIZE + tag_size
+        {
+            return Err(AudioReaderError::TruncatedBlock { m_block_name: "ID3", m_offset: 0 });
+        }
+
+        //
+        // Before ID3v2.4, the whole tag is unsynchronised, extended header included
+        let is_tag_unsynchronised = flags & TAG_FLAG_UNSYNCHRONISATION != 0;
+        let tag_data = match is_tag_unsynchronised && major_version < 4
+        {
+            true => resynchronise(&data[TAG_HEADER_SIZE..TAG_HEADER_SIZE + tag_size]),
+            false => data[TAG_HEADER_SIZE..TAG_HEADER_SIZE + tag_size].to_vec(),
+        };
+
+        //
+        // Skip the extended header, its size counts itself in ID3v2.4 but not in ID3v2.3
+        let mut position = 0;
+        if major_version > 2 && flags & TAG_FLAG_EXTENDED_HEADER != 0
+        {
+            if tag_data.len() < 4
+            {
+                return Err(AudioReaderError::InvalidBlockLength { m_block_name: "ID3", m_length: tag_size as u32 });
+            }
+            position = match major_version
+            {
+                3 => 4 + u32::from_be_bytes(tag_data[0..4].try_into().unwrap()) as usize,
+                _ => read_syncsafe_u32(&tag_data[0..4]) as usize,
+            };
+        }
+
+        let (frame_header_size, frame_id_size) = if major_version == 2 { (6, 3) } else { (10, 4) };
+        let mut frames: Vec<Id3Frame> = Vec::new();
+        while position + frame_header_size <= tag_data.len()
+        {
+            //
+            // The padding after the last frame is made of bytes at 0
+            let frame_header = &tag_data[position..position + frame_header_size];
+            if !frame_header[0..frame_id_size].iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+            {
+                break;
+            }
+
+            //
+            // Some writers store the sizes of the ID3v2.4 frames like in ID3v2.3, with bytes which are not syncsafe
+            let frame_size = match major_version
+            {
+                2 => u32::from_be_bytes([0, frame_header[3], frame_header[4], frame_header[5]]) as usize,
+                3 => u32::from_be_bytes(frame_header[4..8].try_into().unwrap()) as usize,
+                _ if frame_header[4..8].iter().any(|byte| byte & 0x80 != 0) => u32::from_be_bytes(frame_header[4..8].try_into().unwrap()) as usize,
+                _ => read_syncsafe_u32(&frame_header[4..8]) as usize,
+            };
+            //
+            // A frame running past the end of the tag is truncated, the frames before it are kept
+            let frame_start = position + frame_header_size;
+            if frame_start + frame_size > tag_data.len()
+            {
+                break;
+            }
+
+            //
+            // A frame which cannot be decompressed is skipped, the other frames are still valid
+            if let Some(frame) = decode_frame(frame_header, &tag_data[frame_start..frame_start + frame_size], major_version, is_tag_unsynchronised)
+            {
+                frames.push(frame);
+            }
+            position = frame_start + frame_size;
+        }
+
+        return Ok(Id3Tag
+        {
+            m_major_version: major_version,
+            m_frames: frames,
+        });
+    }
+
+    /// Get the frames with an id which are not encrypted
+    fn get_frames<'a>(&'a self, frame_id: &'a str) -> impl Iterator<Item = &'a Id3Frame> + 'a
+    {
+        return self.m_frames.iter().filter(move |frame| frame.m_id == frame_id && frame.m_encryption_method.is_none());
+    }
+
+    /// Get the strings of the first text frame with an id
+    ///
+    /// # Params
+    /// frame_id: the id of a text frame, like "TIT2"
+    pub fn get_text_values(&self, frame_id: &str) -> Vec<String>
+    {
+        return match self.get_frames(frame_id).next()
+        {
+            None => Vec::new(),
+            Some(frame) => decode_text_frame(&frame.m_data),
+        };
+    }
+
+    /// Get the first string of the first text frame with an id, empty if there is no such frame
+    pub fn get_text(&self, frame_id: &str) -> String
+    {
+        return self.get_text_values(frame_id).into_iter().next().unwrap_or_default();
+    }
+
+    /// Get the genres of the TCON frame, with the references to the genres of ID3v1 replaced by their names
+    pub fn get_genres(&self) -> Vec<String>
+    {
+        let mut genres: Vec<String> = Vec::new();
+        for genre in self.get_text_values("TCON").iter().flat_map(|value| resolve_genre(value))
+        {
+            if !genres.contains(&genre)
+            {
+                genres.push(genre);
+            }
+        }
+        return genres;
+    }
+
+    /// Get the texts defined by the user of the TXXX frames
+    pub fn get_user_texts(&self) -> Vec<Id3UserText>
+    {
+        return self.get_frames("TXXX")
+            .filter_map(|frame| decode_text_frame(&frame.m_data).split_first().map(|(description, values)| Id3UserText
+            {
+                m_description: description.clone(),
+                m_values: values.to_vec(),
+            }))
+            .collect();
+    }
+
+    /// Get the first value of the TXXX frame with a description, compared without case, empty if there is no such frame
+    pub fn get_user_text(&self, description: &str) -> String
+    {
+        return self.get_user_texts().into_iter()
+            .find(|user_text| user_text.m_description.eq_ignore_ascii_case(description))
+            .and_then(|user_text| user_text.m_values.into_iter().next())
+            .unwrap_or_default();
+    }
+
+    /// Get the comments of the COMM frames
+    pub fn get_comments(&self) -> Vec<Id3LocalizedText>
+    {
+        return self.get_frames("COMM").filter_map(|frame| decode_localized_text_frame(&frame.m_data)).collect();
+    }
+
+    /// Get the unsynchronised lyrics of the USLT frames
+    pub fn get_lyrics(&self) -> Vec<Id3LocalizedText>
+    {
+        return self.get_frames("USLT").filter_map(|frame| decode_localized_text_frame(&frame.m_data)).collect();
+    }
+
+    /// Get the synchronised lyrics of the SYLT frames
+    pub fn get_synchronised_lyrics(&self) -> Vec<Id3SynchronisedLyrics>
+    {
+        let mut synchronised_lyrics: Vec<Id3SynchronisedLyrics> = Vec::new();
+        for frame in self.get_frames("SYLT")
+        {
+            let data = &frame.m_data;
+            if data.len() < 6 || data[0] > 3
+            {
+                continue;
+            }
+            let encoding = data[0];
+            let (description, mut remaining_data) = read_terminated_string(&data[6..], encoding);
+
+            //
+            // Each text is followed by its timestamp on 4 bytes
+            let mut texts: Vec<Id3SynchronisedText> = Vec::new();
+            while !remaining_data.is_empty()
+            {
+                let (text, data_after_text) = read_terminated_string(remaining_data, encoding);
+                if data_after_text.len() < 4
+                {
+                    break;
+                }
+                texts.push(Id3SynchronisedText { m_timestamp: u32::from_be_bytes(data_after_text[0..4].try_into().unwrap()), m_text: text });
+                remaining_data = &data_after_text[4..];
+            }
+
+            synchronised_lyrics.push(Id3SynchronisedLyrics
+            {
+                m_language: decode_latin1(&data[1..4]),
+                m_timestamp_format: data[4],
+                m_content_type: data[5],
+                m_description: description,
+                m_texts: texts,
+            });
+        }
+        return synchronised_lyrics;
+    }
+
+    /// Get the pictures of the APIC frames, their size is not read from the image and stays at 0
+    pub fn get_pictures(&self) -> Vec<AudioPicture>
+    {
+        let mut pictures: Vec<AudioPicture> = Vec::new();
+        for frame in self.get_frames("APIC")
+        {
+            let data = &frame.m_data;
+            if data.len() < 2 || data[0] > 3
+            {
+                continue;
+            }
+            let (mime_type, remaining_data) = read_terminated_string(&data[1..], 0);
+            if remaining_data.is_empty()
+            {
+                continue;
+            }
+            let (description, picture_data) = read_terminated_string(&remaining_data[1..], data[0]);
+            pictures.push(AudioPicture
+            {
+                m_picture_type: PictureType::from(remaining_data[0] as u32),
+                m_mime_type: mime_type,
+                m_description: description,
+                m_width: 0,
+                m_height: 0,
+                m_color_depth: 0,
+                m_indexed_color_count: 0,
+                m_data: picture_data.to_vec(),
+            });
+        }
+        return pictures;
+    }
+
+    /// Get the ratings and play counts of the POPM frames
+    pub fn get_popularimeters(&self) -> Vec<Id3Popularimeter>
+    {
+        let mut popularimeters: Vec<Id3Popularimeter> = Vec::new();
+        for frame in self.get_frames("POPM")
+        {
+            let (email, remaining_data) = read_terminated_string(&frame.m_data, 0);
+            if remaining_data.is_empty()
+            {
+                continue;
+            }
+
+            //
+            // The play count is stored on at least 4 bytes, or is missing
+            popularimeters.push(Id3Popularimeter
+            {
+                m_email: email,
+                m_rating: remaining_data[0],
+                m_play_count: remaining_data[1..].iter().take(8).fold(0, |play_count, byte| (play_count << 8) | *byte as u64),
+            });
+        }
+        return popularimeters;
+    }
+
+    /// Get the data of the applications stored in the PRIV frames
+    pub fn get_private_data(&self) -> Vec<Id3PrivateData>
+    {
+        return self.get_frames("PRIV")
+            .map(|frame|
+            {
+                let (owner, data) = read_terminated_string(&frame.m_data, 0);
+                Id3PrivateData { m_owner: owner, m_data: data.to_vec() }
+            })
+            .collect();
+    }
+
+    /// Get the tags stored in the text frames. The texts of the TXXX frames are the extra fields, by description.
+    pub fn get_metadata(&self) -> TrackMetadata
+    {
+        //
+        // ID3v2.4 replaced the year by the recording time, some writers use the frame of the other version
+        let date = match self.m_major_version
+        {
+            4 => Some(self.get_text("TDRC")).filter(|date| !date.is_empty()).unwrap_or_else(|| self.get_text("TYER")),
+            _ => Some(self.get_text("TYER")).filter(|date| !date.is_empty()).unwrap_or_else(|| self.get_text("TDRC")),
+        };
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = self.get_text("TIT2");
+        metadata.m_artists = self.get_text_values("TPE1");
+        metadata.m_album = self.get_text("TALB");
+        metadata.m_album_artists = self.get_text_values("TPE2");
+        metadata.m_composers = self.get_text_values("TCOM");
+        metadata.m_genres = self.get_genres();
+        metadata.set_track_text(&self.get_text("TRCK"));
+        metadata.set_disc_text(&self.get_text("TPOS"));
+        metadata.m_date = TagDate::parse(&date);
+        for user_text in self.get_user_texts()
+        {
+            for value in user_text.m_values
+            {
+                metadata.add_extra(&user_text.m_description, value);
+            }
+        }
+        metadata.m_replay_gain = ReplayGainInfo::take_from_extra(&mut metadata.m_extra);
+        return metadata;
+    }
+
+    /// Copy the tags to the information of a title, the fields not stored in the tag are kept
+    pub fn apply_to_information(&self, audio_information: &mut AudioInformation)
+    {
+        audio_information.m_metadata.apply(self.get_metadata());
+
+        //
+        // The comments of iTunes, like "iTunNORM", store data of the application and not text
+        for comment in self.get_comments()
+        {
+            if !comment.m_text.is_empty() && !comment.m_description.starts_with("iTun")
+            {
+                audio_information.m_comments.push(comment.m_text);
+            }
+        }
+        audio_information.m_pictures.extend(self.get_pictures());
+    }
+}
+
+impl Id3v1Tag
+{
+    /// Decode an ID3v1 or ID3v1.1 tag
+    ///
+    /// # Params
+    /// data: the 128 bytes of the tag, starting with "TAG"
+    pub fn parse(data: &[u8]) -> Result<Id3v1Tag, AudioReaderError>
+    {
+        if data.len() != ID3V1_TAG_SIZE || &data[0..3] != b"TAG"
+        {
+            return Err(AudioReaderError::InvalidMagicNumber);
+        }
+
+        //
+        // The fields are filled with null characters or spaces
+        let read_field = |start: usize, end: usize| decode_latin1(&data[start..end]).trim_end_matches(|character| character == '\0' || character == ' ').to_string();
+
+        //
+        // ID3v1.1 takes the last byte of the comment for the track number, after a null byte
+        let is_v11_tag = data[125] == 0 && data[126] != 0;
+        return Ok(Id3v1Tag
+        {
+            m_title: read_field(3, 33),
+            m_artist: read_field(33, 63),
+            m_album: read_field(63, 93),
+            m_year: read_field(93, 97),
+            m_comment: read_field(97, if is_v11_tag { 125 } else { 127 }),
+            m_track_number: if is_v11_tag { data[126] } else { 0 },
+            m_genre: if data[127] == 0xFF { None } else { Some(data[127]) },
+        });
+    }
+
+    /// Copy the tag to the information of a title, the fields not stored in the tag are kept
+    pub fn apply_to_information(&self, audio_information: &mut AudioInformation)
+    {
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = self.m_title.clone();
+        metadata.m_artists = Some(self.m_artist.clone()).filter(|artist| !artist.is_empty()).into_iter().collect();
+        metadata.m_album = self.m_album.clone();
+        metadata.m_genres = self.m_genre.and_then(|genre| get_genre_name(genre as u32)).into_iter().collect();
+        metadata.m_track_number = Some(self.m_track_number as u32).filter(|track_number| *track_number != 0);
+        metadata.m_date = TagDate::parse(&self.m_year);
+        audio_information.m_metadata.apply(metadata);
+        if !self.m_comment.is_empty()
+        {
+            audio_information.m_comments.push(self.m_comment.clone());
+        }
+    }
+}
+
+impl Id3FileTags
+{
+    /// Read the ID3v2 tag at the beginning of a file and the ID3v1 tag at its end
+    ///
+    /// # Params
+    /// source: the file
+    pub fn read<R: Read + Seek>(source: &mut R) -> Result<Id3FileTags, AudioReaderError>
+    {
+        source.seek(SeekFrom::Start(0))?;
+        let mut header = [0u8; TAG_HEADER_SIZE];
+        let mut id3v2_tag: Option<Id3Tag> = None;
+        let mut tag_byte_count: u64 = 0;
+        if source.read_exact(&mut header).is_ok() && &header[0..3] == b"ID3"
+        {
+            let tag_size = read_syncsafe_u32(&header[6..10]) as usize;
+            let mut tag_data = header.to_vec();
+            tag_data.resize(TAG_HEADER_SIZE + tag_size, 0);
+            source.read_exact(&mut tag_data[TAG_HEADER_SIZE..])?;
+            id3v2_tag = Some(Id3Tag::parse(&tag_data)?);
+            tag_byte_count += tag_data.len() as u64;
+        }
+
+        let mut id3v1_tag: Option<Id3v1Tag> = None;
+        let file_size = source.seek(SeekFrom::End(0))?;
+        if file_size >= ID3V1_TAG_SIZE as u64
+        {
+            let mut tag_data = vec![0u8; ID3V1_TAG_SIZE];
+            source.seek(SeekFrom::End(-(ID3V1_TAG_SIZE as i64)))?;
+            source.read_exact(&mut tag_data)?;
+            id3v1_tag = Id3v1Tag::parse(&tag_data).ok();
+            if id3v1_tag.is_some()
+            {
+                tag_byte_count += ID3V1_TAG_SIZE as u64;
+            }
+        }
+
+        return Ok(Id3FileTags
+        {
+            m_id3v2_tag: id3v2_tag,
+            m_id3v1_tag: id3v1_tag,
+            m_tag_byte_count: tag_byte_count,
+        });
+    }
+
+    /// Copy the tags to the information of a title.
+    /// The ID3v2 tag is preferred, the ID3v1 tag only fills the fields it does not give.
+    pub fn apply_to_information(&self, audio_information: &mut AudioInformation)
+    {
+        if let Some(id3v1_tag) = &self.m_id3v1_tag
+        {
+            id3v1_tag.apply_to_information(audio_information);
+
+            //
+            // The comment of ID3v1 is a truncated copy of the one of ID3v2 when both exist
+            let has_id3v2_comment = self.m_id3v2_tag.as_ref().map_or(false, |id3v2_tag| !id3v2_tag.get_comments().is_empty());
+            if has_id3v2_comment && !id3v1_tag.m_comment.is_empty()
+            {
+                audio_information.m_comments.pop();
+            }
+        }
+        if let Some(id3v2_tag) = &self.m_id3v2_tag
+        {
+            id3v2_tag.apply_to_information(audio_information);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_id3_tag
+{
+    use std::io::Cursor;
+    use crate::audio_reader::{AudioInformation, PictureType, TagDate};
+    use crate::audio_reader::replay_gain::ReplayGain;
+    use crate::audio_reader::id3_tag::{Id3FileTags, Id3LocalizedText, Id3Popularimeter, Id3SynchronisedText, Id3Tag, Id3UserText, Id3v1Tag};
+
+    /// Encode a string in UTF-16 with a byte order mark
+    fn encode_utf16(text: &str, is_big_endian: bool) -> Vec<u8>
+    {
+        let mut data: Vec<u8> = if is_big_endian { vec![0xFE, 0xFF] } else { vec![0xFF, 0xFE] };
+        for code_unit in text.encode_utf16()
+        {
+            data.extend_from_slice(&if is_big_endian { code_unit.to_be_bytes() } else { code_unit.to_le_bytes() });
+        }
+        return data;
+    }
+
+    /// Create the header of a tag followed by its content
+    fn create_tag(major_version: u8, flags: u8, content: &[u8]) -> Vec<u8>
+    {
+        let mut tag = b"ID3".to_vec();
+        tag.extend_from_slice(&[major_version, 0, flags]);
+        let size = content.len() as u32;
+        tag.extend_from_slice(&[(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F]);
+        tag.extend_from_slice(content);
+        return tag;
+    }
+
+    /// Create an ID3v2.3 frame, or an ID3v2.4 frame with a syncsafe size
+    fn create_frame(frame_id: &[u8; 4], is_syncsafe: bool, format_flags: u8, data: &[u8]) -> Vec<u8>
+    {
+        let mut frame = frame_id.to_vec();
+        let size = data.len() as u32;
+        if is_syncsafe
+        {
+            frame.extend_from_slice(&[(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F]);
+        }
+        else
+        {
+            frame.extend_from_slice(&size.to_be_bytes());
+        }
+        frame.extend_from_slice(&[0, format_flags]);
+        frame.extend_from_slice(data);
+        return frame;
+    }
+
+    /// Insert a byte at 0 after each byte at 0xFF
+    fn unsynchronise(data: &[u8]) -> Vec<u8>
+    {
+        let mut unsynchronised_data: Vec<u8> = Vec::new();
+        for byte in data.iter()
+        {
+            unsynchronised_data.push(*byte);
+            if *byte == 0xFF
+            {
+                unsynchronised_data.push(0);
+            }
+        }
+        return unsynchronised_data;
+    }
+
+    #[test]
+    fn read_id3v23_tag()
+    {
+        let mut content: Vec<u8> = Vec::new();
+
+        //
+        // Extended header with a CRC
+        content.extend_from_slice(&[0, 0, 0, 10, 0x80, 0, 0, 0, 0, 0, 0x12, 0x34, 0x56, 0x78]);
+        content.extend(create_frame(b"TIT2", false, 0, &[&[1u8][..], &encode_utf16("Titre \u{E9}t\u{E9}", false)].concat()));
+        content.extend(create_frame(b"TPE1", false, 0, b"\0Artist"));
+        content.extend(create_frame(b"TCON", false, 0, b"\0(17)(RX)Indie Rock"));
+        content.extend(create_frame(b"TYER", false, 0, b"\x031999"));
+        content.extend(create_frame(b"TXXX", false, 0, b"\x03REPLAYGAIN_TRACK_GAIN\0-6.50 dB"));
+        content.extend(create_frame(b"COMM", false, 0, b"\0engiTunNORM\0 00000000"));
+        content.extend(create_frame(b"COMM", false, 0, &[&b"\x01fra"[..], &encode_utf16("", true), &[0, 0], &encode_utf16("Commentaire", true)].concat()));
+
+        //
+        // Compressed album with the size of the decompressed data before the content, encrypted frame kept as it is
+        let album = b"\0Compressed album name".to_vec();
+        let compressed_album = [&(album.len() as u32).to_be_bytes()[..], &miniz_oxide::deflate::compress_to_vec_zlib(&album, 6)].concat();
+        content.extend(create_frame(b"TALB", false, 0x80, &compressed_album));
+        content.extend(create_frame(b"TRCK", false, 0x40, b"\x80\0Secret"));
+
+        //
+        // The picture contains 0xFF bytes, hidden by the unsynchronisation of the whole tag
+        let picture_data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        content.extend(create_frame(b"APIC", false, 0, &[&b"\0image/jpeg\0\x03Cover\0"[..], &picture_data].concat()));
+        content.extend_from_slice(&[0; 32]);
+        let tag = Id3Tag::parse(&create_tag(3, 0xC0, &unsynchronise(&content))).unwrap();
+
+        assert_eq!(tag.m_major_version, 3);
+        assert_eq!(tag.get_text("TIT2"), "Titre \u{E9}t\u{E9}");
+        assert_eq!(tag.get_text("TALB"), "Compressed album name");
+        assert_eq!(tag.get_text("TRCK"), "");
+        assert_eq!(tag.m_frames.iter().find(|frame| frame.m_id == "TRCK").unwrap().m_encryption_method, Some(0x80));
+        assert_eq!(tag.get_genres(), vec!["Rock", "Remix", "Indie Rock"]);
+        assert_eq!(tag.get_user_texts(), vec![Id3UserText { m_description: "REPLAYGAIN_TRACK_GAIN".to_string(), m_values: vec!["-6.50 dB".to_string()] }]);
+        assert_eq!(tag.get_user_text("replaygain_track_gain"), "-6.50 dB");
+        assert_eq!(tag.get_comments()[1], Id3LocalizedText { m_language: "fra".to_string(), m_description: String::new(), m_text: "Commentaire".to_string() });
+
+        let pictures = tag.get_pictures();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].m_picture_type, PictureType::FrontCover);
+        assert_eq!(pictures[0].m_mime_type, "image/jpeg");
+        assert_eq!(pictures[0].m_description, "Cover");
+        assert_eq!(pictures[0].m_data, picture_data);
+
+        let mut audio_information = AudioInformation::new();
+        tag.apply_to_information(&mut audio_information);
+        assert_eq!(audio_information.m_metadata.m_title, "Titre \u{E9}t\u{E9}");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Artist"]);
+        assert_eq!(audio_information.m_metadata.m_album, "Compressed album name");
+        assert_eq!(audio_information.m_metadata.m_genres, vec!["Rock", "Remix", "Indie Rock"]);
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 1999, m_month: None, m_day: None }));
+        assert_eq!(audio_information.m_comments, vec!["Commentaire"]);
+        assert_eq!(audio_information.m_pictures.len(), 1);
+    }
+
+    #[test]
+    fn read_id3v24_tag()
+    {
+        let mut content: Vec<u8> = Vec::new();
+
+        //
+        // Several values, each one with its own byte order mark
+        content.extend(create_frame(b"TPE1", true, 0, &[&[1u8][..], &encode_utf16("First", false), &[0, 0], &encode_utf16("Second", true)].concat()));
+        content.extend(create_frame(b"TCON", true, 0, b"\x0317\0Jazz\0"));
+        content.extend(create_frame(b"TDRC", true, 0, b"\x032004-05-06"));
+        content.extend(create_frame(b"TIT2", true, 0, &[&b"\x02"[..], &encode_utf16("Big endian", true)[2..]].concat()));
+
+        //
+        // Frame unsynchronised alone, with the length of its content before it
+        let private_data = vec![0xFF, 0xE0, 0xFF, 0x00, 0x01];
+        let priv_content = [&b"owner@example.com\0"[..], &private_data].concat();
+        let priv_frame_data = [&[0, 0, 0, priv_content.len() as u8][..], &unsynchronise(&priv_content)].concat();
+        content.extend(create_frame(b"PRIV", true, 0x03, &priv_frame_data));
+
+        content.extend(create_frame(b"USLT", true, 0, b"\x03engVerse\0First line\nSecond line"));
+        content.extend(create_frame(b"SYLT", true, 0, b"\x03eng\x02\x01Lyrics\0Hello\0\0\0\x03\xE8World\0\0\0\x07\xD0"));
+        content.extend(create_frame(b"POPM", true, 0, b"user@example.com\0\xC4\0\0\x01\x02"));
+        let tag = Id3Tag::parse(&create_tag(4, 0, &content)).unwrap();
+
+        assert_eq!(tag.get_text_values("TPE1"), vec!["First", "Second"]);
+        assert_eq!(tag.get_text("TIT2"), "Big endian");
+        assert_eq!(tag.get_genres(), vec!["Rock", "Jazz"]);
+        let private_data_frames = tag.get_private_data();
+        assert_eq!(private_data_frames[0].m_owner, "owner@example.com");
+        assert_eq!(private_data_frames[0].m_data, private_data);
+        assert_eq!(tag.get_lyrics()[0].m_text, "First line\nSecond line");
+
+        let synchronised_lyrics = &tag.get_synchronised_lyrics()[0];
+        assert_eq!(synchronised_lyrics.m_timestamp_format, 2);
+        assert_eq!(synchronised_lyrics.m_content_type, 1);
+        assert_eq!(synchronised_lyrics.m_description, "Lyrics");
+        assert_eq!(synchronised_lyrics.m_texts, vec![
+            Id3SynchronisedText { m_timestamp: 1000, m_text: "Hello".to_string() },
+            Id3SynchronisedText { m_timestamp: 2000, m_text: "World".to_string() },
+        ]);
+        assert_eq!(tag.get_popularimeters(), vec![Id3Popularimeter { m_email: "user@example.com".to_string(), m_rating: 196, m_play_count: 258 }]);
+
+        let mut audio_information = AudioInformation::new();
+        tag.apply_to_information(&mut audio_information);
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["First", "Second"]);
+        assert_eq!(audio_information.m_metadata.m_genres, vec!["Rock", "Jazz"]);
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 2004, m_month: Some(5), m_day: Some(6) }));
+    }
+
+    #[test]
+    fn read_replay_gain_of_user_texts()
+    {
+        let mut content = create_frame(b"TXXX", true, 0, b"\x03replaygain_track_gain\0-7.25 dB");
+        content.extend(create_frame(b"TXXX", true, 0, b"\x03replaygain_track_peak\01.052"));
+        content.extend(create_frame(b"TXXX", true, 0, b"\x03REPLAYGAIN_ALBUM_GAIN\0+1.5 dB"));
+        content.extend(create_frame(b"TXXX", true, 0, b"\x03CATALOGNUMBER\0ABC-1"));
+        let metadata = Id3Tag::parse(&create_tag(4, 0, &content)).unwrap().get_metadata();
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: -7.25, m_peak: Some(1.052) }));
+        assert_eq!(metadata.m_replay_gain.m_album, Some(ReplayGain { m_gain: 1.5, m_peak: None }));
+
+        //
+        // The gains are not extra fields
+        assert_eq!(metadata.m_extra.len(), 1);
+        assert_eq!(metadata.m_extra["CATALOGNUMBER"], vec!["ABC-1"]);
+    }
+
+    #[test]
+    fn read_id3v22_tag()
+    {
+        let mut content: Vec<u8> = Vec::new();
+        for (frame_id, data) in [(b"TT2", b"\0Old title".to_vec()), (b"TCO", b"\0(8)".to_vec()), (b"PIC", b"\0PNG\x04\0\x89PNG".to_vec())].iter()
+        {
+            content.extend_from_slice(*frame_id);
+            content.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+            content.extend_from_slice(data);
+        }
+        let tag = Id3Tag::parse(&create_tag(2, 0, &content)).unwrap();
+
+        assert_eq!(tag.get_text("TIT2"), "Old title");
+        assert_eq!(tag.get_genres(), vec!["Jazz"]);
+        let pictures = tag.get_pictures();
+        assert_eq!(pictures[0].m_mime_type, "image/png");
+        assert_eq!(pictures[0].m_picture_type, PictureType::BackCover);
+        assert_eq!(pictures[0].m_data, b"\x89PNG");
+    }
+
+    #[test]
+    fn keep_frames_before_truncated_frame()
+    {
+        //
+        // The size of the last frame runs past the end of the tag
+        let mut content = create_frame(b"TIT2", true, 0, b"\x03Kept title");
+        let mut truncated_frame = create_frame(b"TALB", true, 0, b"\x03Lost album");
+        truncated_frame[7] = 0x7F;
+        content.extend(truncated_frame);
+        let tag = Id3Tag::parse(&create_tag(4, 0, &content)).unwrap();
+
+        assert_eq!(tag.m_frames.len(), 1);
+        assert_eq!(tag.get_text("TIT2"), "Kept title");
+        assert_eq!(tag.get_text("TALB"), "");
+    }
+
+    #[test]
+    fn fall_back_to_id3v1_tag()
+    {
+        let mut id3v1_data = b"TAG".to_vec();
+        for (text, size) in [("V1 title", 30), ("V1 artist", 30), ("V1 album", 30), ("2001", 4), ("V1 comment", 28)].iter()
+        {
+            let mut field = text.as_bytes().to_vec();
+            field.resize(*size, 0);
+            id3v1_data.extend(field);
+        }
+        id3v1_data.extend_from_slice(&[0, 7, 8]);
+        let id3v1_tag = Id3v1Tag::parse(&id3v1_data).unwrap();
+        assert_eq!(id3v1_tag.m_track_number, 7);
+        assert_eq!(id3v1_tag.m_genre, Some(8));
+
+        //
+        // Audio between an ID3v2 tag giving only the title and the comment, and an ID3v1 tag
+        let id3v2_content = [create_frame(b"TIT2", false, 0, b"\0V2 title"), create_frame(b"COMM", false, 0, b"\0eng\0V2 comment")].concat();
+        let file_data = [create_tag(3, 0, &id3v2_content), vec![0xFF, 0xFB, 0x90, 0x00], id3v1_data.clone()].concat();
+        let file_tags = Id3FileTags::read(&mut Cursor::new(file_data)).unwrap();
+        let mut audio_information = AudioInformation::new();
+        file_tags.apply_to_information(&mut audio_information);
+        assert_eq!(audio_information.m_metadata.m_title, "V2 title");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["V1 artist"]);
+        assert_eq!(audio_information.m_metadata.m_album, "V1 album");
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 2001, m_month: None, m_day: None }));
+        assert_eq!(audio_information.m_metadata.m_track_number, Some(7));
+        assert_eq!(audio_information.m_metadata.m_genres, vec!["Jazz"]);
+        assert_eq!(audio_information.m_comments, vec!["V2 comment"]);
+
+        let file_tags = Id3FileTags::read(&mut Cursor::new([vec![0xFF, 0xFB, 0x90, 0x00], id3v1_data].concat())).unwrap();
+        assert!(file_tags.m_id3v2_tag.is_none());
+        let mut audio_information = AudioInformation::new();
+        file_tags.apply_to_information(&mut audio_information);
+        assert_eq!(audio_information.m_metadata.m_title, "V1 title");
+        assert_eq!(audio_information.m_comments, vec!["V1 comment"]);
+    }
+}
@@ -0,0 +1,978 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on ITU-R BS.1770-4 (loudness and true peak), EBU Tech 3341 and 3342 (gating and loudness range)
+// and https://wiki.hydrogenaud.io/index.php?title=ReplayGain_2.0_specification
+
+use std::f64::consts::PI;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::audio_reader::{AudioInformationError, AudioReader, AudioReaderError, DecodedBlock};
+use crate::audio_reader::ape_tag::{read_ape_tag, ApeTag};
+use crate::audio_reader::flac_reader::FlacReader;
+use crate::audio_reader::flac_writer::FlacMetadataWriter;
+use crate::audio_reader::reader_registry::{AudioFormat, AudioReaderRegistry};
+use crate::audio_reader::replay_gain::ReplayGain;
+use crate::Controller;
+use crate::Controller::EventManager::QuInformationData;
+
+/// Loudness of the reference level of ReplayGain 2.0, in LUFS
+const REPLAY_GAIN_REFERENCE_LOUDNESS: f64 = -18.0;
+
+/// Loudness under which the blocks are ignored, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Gate relative to the loudness of the blocks above the absolute gate, in LU, for the integrated loudness
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+
+/// Gate relative to the loudness of the blocks above the absolute gate, in LU, for the loudness range
+const LOUDNESS_RANGE_RELATIVE_GATE: f64 = -20.0;
+
+/// Number of sub-blocks of 100 ms in a gating block of 400 ms, the blocks overlap by 75 %
+const GATING_BLOCK_SUB_BLOCK_COUNT: usize = 4;
+
+/// Number of sub-blocks of 100 ms in a short-term block of 3 s, used by the loudness range
+const SHORT_TERM_SUB_BLOCK_COUNT: usize = 30;
+
+/// Percentiles of the short-term loudnesses between which the loudness range is measured
+const LOUDNESS_RANGE_LOW_PERCENTILE: f64 = 0.10;
+const LOUDNESS_RANGE_HIGH_PERCENTILE: f64 = 0.95;
+
+/// Weight of the surround channels in the loudness, +1.5 dB
+const SURROUND_CHANNEL_WEIGHT: f64 = 1.41;
+
+/// Number of samples around an interpolated sample used to compute it for the true peak
+const TRUE_PEAK_TAP_COUNT: usize = 12;
+
+/// Number of samples computed for each sample of the file to find the true peak
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Size of the header of an ID3v2 tag, and of its footer when it has one
+const ID3V2_HEADER_SIZE: usize = 10;
+
+/// Flag of an ID3v2 tag followed by a footer
+const ID3V2_FLAG_FOOTER: u8 = 0x10;
+
+/// Tag where the gains of a title are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GainTag
+{
+    /// The Vorbis comments of a native flac file
+    VorbisComment,
+
+    /// An APE tag at the end of a MP3 or AAC stream, formats without tags of their own
+    Ape,
+}
+
+/// Second order IIR filter, in transposed direct form II
+#[derive(Clone, Copy, Debug)]
+struct Biquad
+{
+    m_b: [f64; 3],
+
+    /// a1 and a2, a0 being 1
+    m_a: [f64; 2],
+    m_state: [f64; 2],
+}
+
+impl Biquad
+{
+    fn new(b: [f64; 3], a: [f64; 2]) -> Biquad
+    {
+        return Biquad
+        {
+            m_b: b,
+            m_a: a,
+            m_state: [0.0; 2],
+        };
+    }
+
+    fn process(&mut self, input: f64) -> f64
+    {
+        let output = self.m_b[0] * input + self.m_state[0];
+        self.m_state[0] = self.m_b[1] * input - self.m_a[0] * output + self.m_state[1];
+        self.m_state[1] = self.m_b[2] * input - self.m_a[1] * output;
+        return output;
+    }
+}
+
+/// Create the K-weighting filter of a rate: the high shelf modelling the head, then the RLB high pass.
+/// BS.1770 only gives the coefficients at 48 kHz, they are computed for the other rates from the analog prototypes of the filters.
+fn create_k_weighting_filters(rate: u32) -> [Biquad; 2]
+{
+    let rate = rate as f64;
+
+    let frequency = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let quality = 0.7071752369554196;
+    let k = (PI * frequency / rate).tan();
+    let high_gain = 10f64.powf(gain / 20.0);
+    let band_gain = high_gain.powf(0.4996667741545416);
+    let a0 = 1.0 + k / quality + k * k;
+    let shelf = Biquad::new(
+        [(high_gain + band_gain * k / quality + k * k) / a0, 2.0 * (k * k - high_gain) / a0, (high_gain - band_gain * k / quality + k * k) / a0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / quality + k * k) / a0]);
+
+    let frequency = 38.13547087602444;
+    let quality = 0.5003270373238773;
+    let k = (PI * frequency / rate).tan();
+    let a0 = 1.0 + k / quality + k * k;
+    let high_pass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / quality + k * k) / a0]);
+
+    return [shelf, high_pass];
+}
+
+/// Get the weight of each channel in the loudness, from the usual order of the channels: L, R, C, LFE, Ls, Rs.
+/// The LFE channel is not measured.
+fn get_channel_weights(channel_count: u8) -> Vec<f64>
+{
+    return (0..channel_count as usize).map(|channel| match (channel_count, channel)
+    {
+        (4, 2) | (4, 3) | (5, 3) | (5, 4) => SURROUND_CHANNEL_WEIGHT,
+        (count, 3) if count >= 6 => 0.0,
+        (count, channel) if count >= 6 && channel >= 4 => SURROUND_CHANNEL_WEIGHT,
+        _ => 1.0,
+    }).collect();
+}
+
+/// Create the windowed sinc filters interpolating the signal at 1/4, 2/4 and 3/4 of the way between the two samples in the middle of the history
+fn create_true_peak_filters() -> Vec<[f64; TRUE_PEAK_TAP_COUNT]>
+{
+    let half_width = (TRUE_PEAK_TAP_COUNT / 2) as f64;
+    return (1..TRUE_PEAK_OVERSAMPLING).map(|phase|
+    {
+        let position = half_width - 1.0 + phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+        let mut coefficients = [0.0; TRUE_PEAK_TAP_COUNT];
+        for (index, coefficient) in coefficients.iter_mut().enumerate()
+        {
+            let distance = position - index as f64;
+            let sinc = (PI * distance).sin() / (PI * distance);
+            let window = 0.5 * (1.0 + (PI * distance / half_width).cos());
+            *coefficient = sinc * window;
+        }
+
+        //
+        // A constant signal must keep its level
+        let sum: f64 = coefficients.iter().sum();
+        coefficients.iter_mut().for_each(|coefficient| *coefficient /= sum);
+        return coefficients;
+    }).collect();
+}
+
+/// Convert the mean square of the weighted channels to a loudness in LUFS, minus infinity for a silence
+fn convert_power_to_loudness(power: f64) -> f64
+{
+    return -0.691 + 10.0 * power.log10();
+}
+
+/// Compute the powers of the blocks made of consecutive sub-blocks, each block starting one sub-block after the previous one
+fn compute_block_powers(sub_block_powers: &[f64], sub_block_count: usize) -> Vec<f64>
+{
+    return sub_block_powers.windows(sub_block_count).map(|sub_blocks| sub_blocks.iter().sum::<f64>() / sub_block_count as f64).collect();
+}
+
+/// Get the mean of powers, None if there are none
+fn compute_mean_power<'a, I: Iterator<Item = &'a f64>>(powers: I) -> Option<f64>
+{
+    let (sum, count) = powers.fold((0.0, 0usize), |(sum, count), power| (sum + power, count + 1));
+    return match count
+    {
+        0 => None,
+        _ => Some(sum / count as f64),
+    };
+}
+
+/// Compute the integrated loudness of gating blocks, in LUFS: the loudness of the blocks above the absolute gate and
+/// above the gate relative to them. Minus infinity if all the blocks are under the absolute gate.
+fn compute_integrated_loudness(block_powers: &[f64]) -> f64
+{
+    let is_above_absolute_gate = |power: &&f64| convert_power_to_loudness(**power) > ABSOLUTE_GATE;
+    let relative_gate = match compute_mean_power(block_powers.iter().filter(is_above_absolute_gate))
+    {
+        None => return f64::NEG_INFINITY,
+        Some(power) => convert_power_to_loudness(power) + INTEGRATED_RELATIVE_GATE,
+    };
+    return compute_mean_power(block_powers.iter().filter(is_above_absolute_gate).filter(|power| convert_power_to_loudness(**power) > relative_gate))
+        .map_or(f64::NEG_INFINITY, convert_power_to_loudness);
+}
+
+/// Compute the loudness range of short-term blocks, in LU: the spread of the loudnesses of the blocks above the gates,
+/// between their 10th and 95th percentiles
+fn compute_loudness_range(short_term_powers: &[f64]) -> f64
+{
+    let loudnesses: Vec<f64> = short_term_powers.iter().map(|power| convert_power_to_loudness(*power)).filter(|loudness| *loudness > ABSOLUTE_GATE).collect();
+    let relative_gate = match compute_mean_power(short_term_powers.iter().filter(|power| convert_power_to_loudness(**power) > ABSOLUTE_GATE))
+    {
+        None => return 0.0,
+        Some(power) => convert_power_to_loudness(power) + LOUDNESS_RANGE_RELATIVE_GATE,
+    };
+    let mut loudnesses: Vec<f64> = loudnesses.into_iter().filter(|loudness| *loudness > relative_gate).collect();
+    if loudnesses.is_empty()
+    {
+        return 0.0;
+    }
+    loudnesses.sort_by(|first, second| first.total_cmp(second));
+    let get_percentile = |percentile: f64| loudnesses[((loudnesses.len() - 1) as f64 * percentile).round() as usize];
+    return get_percentile(LOUDNESS_RANGE_HIGH_PERCENTILE) - get_percentile(LOUDNESS_RANGE_LOW_PERCENTILE);
+}
+
+/// Loudness of a title or an album
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnessResult
+{
+    /// Integrated loudness in LUFS, minus infinity for a silence
+    pub m_integrated_loudness: f64,
+
+    /// Loudness range in LU
+    pub m_loudness_range: f64,
+
+    /// Highest absolute value of the signal between the samples, 1.0 being the full scale
+    pub m_true_peak: f64,
+
+    /// Highest absolute sample value, 1.0 being the full scale
+    pub m_sample_peak: f64,
+}
+
+impl LoudnessResult
+{
+    /// Get the ReplayGain 2.0 gain bringing the loudness to -18 LUFS, with the true peak as peak.
+    /// None for a silence, which has no loudness to correct.
+    pub fn get_replay_gain(&self) -> Option<ReplayGain>
+    {
+        if !self.m_integrated_loudness.is_finite()
+        {
+            return None;
+        }
+        return Some(ReplayGain
+        {
+            m_gain: (REPLAY_GAIN_REFERENCE_LOUDNESS - self.m_integrated_loudness) as f32,
+            m_peak: Some(self.m_true_peak as f32),
+        });
+    }
+}
+
+/// Measurement of a whole title, kept to compute the loudness of its album
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoudnessMeasurement
+{
+    /// Weighted mean square of the channels of each sub-block of 100 ms
+    pub m_sub_block_powers: Vec<f64>,
+    pub m_true_peak: f64,
+    pub m_sample_peak: f64,
+}
+
+impl LoudnessMeasurement
+{
+    /// Compute the loudness of the title
+    pub fn get_result(&self) -> LoudnessResult
+    {
+        return compute_album_result(std::slice::from_ref(self));
+    }
+}
+
+/// Compute the loudness of an album, as if its titles were played one after the other.
+/// The blocks do not overlap two titles, so the result does not depend on the order of the titles.
+///
+/// # Params
+/// measurements: the measurements of the titles of the album
+pub fn compute_album_result(measurements: &[LoudnessMeasurement]) -> LoudnessResult
+{
+    let gating_block_powers: Vec<f64> = measurements.iter().flat_map(|measurement| compute_block_powers(&measurement.m_sub_block_powers, GATING_BLOCK_SUB_BLOCK_COUNT)).collect();
+    let short_term_powers: Vec<f64> = measurements.iter().flat_map(|measurement| compute_block_powers(&measurement.m_sub_block_powers, SHORT_TERM_SUB_BLOCK_COUNT)).collect();
+    return LoudnessResult
+    {
+        m_integrated_loudness: compute_integrated_loudness(&gating_block_powers),
+        m_loudness_range: compute_loudness_range(&short_term_powers),
+        m_true_peak: measurements.iter().map(|measurement| measurement.m_true_peak).fold(0.0, f64::max),
+        m_sample_peak: measurements.iter().map(|measurement| measurement.m_sample_peak).fold(0.0, f64::max),
+    };
+}
+
+/// Measure the loudness and the peaks of a stream of samples
+pub struct LoudnessMeter
+{
+    m_channel_weights: Vec<f64>,
+
+    /// K-weighting filters of each channel
+    m_filters: Vec<[Biquad; 2]>,
+
+    /// Last samples of each channel, from the oldest, interpolated for the true peak
+    m_peak_histories: Vec<[f64; TRUE_PEAK_TAP_COUNT]>,
+    m_true_peak_filters: Vec<[f64; TRUE_PEAK_TAP_COUNT]>,
+
+    /// Number of samples of each channel in a sub-block of 100 ms
+    m_sub_block_length: usize,
+
+    /// Number of samples of each channel already added to the current sub-block
+    m_sub_block_position: usize,
+
+    /// Sum of the squares of the filtered samples of each channel in the current sub-block
+    m_sub_block_sums: Vec<f64>,
+    m_sub_block_powers: Vec<f64>,
+    m_true_peak: f64,
+    m_sample_peak: f64,
+}
+
+impl LoudnessMeter
+{
+    /// Create the meter of a stream
+    ///
+    /// # Params
+    /// rate: the sample rate of the stream
+    /// channel_count: the number of channels of the stream
+    pub fn new(rate: u32, channel_count: u8) -> LoudnessMeter
+    {
+        let channel_count = channel_count as usize;
+        return LoudnessMeter
+        {
+            m_channel_weights: get_channel_weights(channel_count as u8),
+            m_filters: vec![create_k_weighting_filters(rate); channel_count],
+            m_peak_histories: vec![[0.0; TRUE_PEAK_TAP_COUNT]; channel_count],
+            m_true_peak_filters: create_true_peak_filters(),
+            m_sub_block_length: ((rate as usize + 5) / 10).max(1),
+            m_sub_block_position: 0,
+            m_sub_block_sums: vec![0.0; channel_count],
+            m_sub_block_powers: Vec::new(),
+            m_true_peak: 0.0,
+            m_sample_peak: 0.0,
+        };
+    }
+
+    /// Add samples to the measurement
+    ///
+    /// # Params
+    /// samples: the samples of all the channels interleaved, 1.0 being the full scale
+    pub fn add_samples(&mut self, samples: &[f64])
+    {
+        let channel_count = self.m_channel_weights.len();
+        if channel_count == 0
+        {
+            return;
+        }
+        for frame in samples.chunks_exact(channel_count)
+        {
+            for (channel, sample) in frame.iter().enumerate()
+            {
+                let [shelf, high_pass] = &mut self.m_filters[channel];
+                let filtered = high_pass.process(shelf.process(*sample));
+                self.m_sub_block_sums[channel] += filtered * filtered;
+
+                let history = &mut self.m_peak_histories[channel];
+                history.copy_within(1.., 0);
+                history[TRUE_PEAK_TAP_COUNT - 1] = *sample;
+                for coefficients in self.m_true_peak_filters.iter()
+                {
+                    let interpolated: f64 = coefficients.iter().zip(history.iter()).map(|(coefficient, sample)| coefficient * sample).sum();
+                    self.m_true_peak = self.m_true_peak.max(interpolated.abs());
+                }
+                self.m_sample_peak = self.m_sample_peak.max(sample.abs());
+            }
+
+            self.m_sub_block_position += 1;
+            if self.m_sub_block_position == self.m_sub_block_length
+            {
+                let power: f64 = self.m_sub_block_sums.iter().zip(self.m_channel_weights.iter()).map(|(sum, weight)| sum * weight).sum();
+                self.m_sub_block_powers.push(power / self.m_sub_block_length as f64);
+                self.m_sub_block_sums.iter_mut().for_each(|sum| *sum = 0.0);
+                self.m_sub_block_position = 0;
+            }
+        }
+    }
+
+    /// Add a decoded block to the measurement, its samples are scaled from their bits per sample to the full scale
+    pub fn add_block(&mut self, block: &DecodedBlock)
+    {
+        let full_scale = (1u64 << (block.m_bits_per_sample.clamp(1, 32) - 1)) as f64;
+        let samples: Vec<f64> = block.m_samples.iter().map(|sample| *sample as f64 / full_scale).collect();
+        self.add_samples(&samples);
+    }
+
+    /// End the measurement. The samples of the last incomplete sub-block are only used by the peaks.
+    pub fn finish(self) -> LoudnessMeasurement
+    {
+        //
+        // The sample peak is part of the true peak, the interpolation can miss it by a tiny amount
+        return LoudnessMeasurement
+        {
+            m_sub_block_powers: self.m_sub_block_powers,
+            m_true_peak: self.m_true_peak.max(self.m_sample_peak),
+            m_sample_peak: self.m_sample_peak,
+        };
+    }
+}
+
+/// Decode a file and measure its loudness
+///
+/// # Params
+/// registry: the registry choosing the decoder of the file
+/// str_path_to_music: the path of the file
+pub fn measure_file(registry: &AudioReaderRegistry, str_path_to_music: &str) -> Result<LoudnessMeasurement, AudioReaderError>
+{
+    let mut meter: Option<LoudnessMeter> = None;
+    let mut add_block = |block: DecodedBlock| meter.get_or_insert_with(|| LoudnessMeter::new(block.m_rate, block.m_channel_count)).add_block(&block);
+    if registry.find_reader(str_path_to_music)?.m_format == AudioFormat::Flac
+    {
+        //
+        // The whole file is needed, the flac frames are decoded on all the processors
+        FlacReader {}.decode_parallel(str_path_to_music.to_string(), 0, add_block)?;
+    }
+    else
+    {
+        let mut decoder = registry.open_decoder(str_path_to_music.to_string())?;
+        while let Some(block) = decoder.read_next_block()?
+        {
+            add_block(block);
+        }
+    }
+    return meter.map(LoudnessMeter::finish).ok_or(AudioReaderError::MissingBlock("audio"));
+}
+
+/// Format a gain like the ReplayGain tags: "-6.48 dB"
+fn format_gain(replay_gain: &ReplayGain) -> String
+{
+    return format!("{:.2} dB", replay_gain.m_gain);
+}
+
+/// Format a peak like the ReplayGain tags: "0.988831"
+fn format_peak(replay_gain: &ReplayGain) -> String
+{
+    return format!("{:.6}", replay_gain.m_peak.unwrap_or(0.0));
+}
+
+/// Check whether a file is a MP3 or AAC stream without container, whose first frame follows its ID3v2 tag if it has one
+///
+/// # Params
+/// file: the file, after the header read
+/// header: the first bytes of the file
+fn is_mpeg_audio_stream(file: &mut File, header: &[u8; ID3V2_HEADER_SIZE]) -> Result<bool, AudioReaderError>
+{
+    let mut sync_word = [header[0], header[1]];
+    if &header[0..3] == b"ID3"
+    {
+        //
+        // The size of the tag is a syncsafe integer which does not count the header and the footer
+        let tag_size = header[6..10].iter().fold(0u64, |tag_size, byte| (tag_size << 7) | (byte & 0x7F) as u64);
+        let footer_size = if header[5] & ID3V2_FLAG_FOOTER != 0 { ID3V2_HEADER_SIZE as u64 } else { 0 };
+        file.seek(SeekFrom::Start(ID3V2_HEADER_SIZE as u64 + tag_size + footer_size))?;
+        if file.read_exact(&mut sync_word).is_err()
+        {
+            return Ok(false);
+        }
+    }
+
+    //
+    // The 11 bits of the sync word start the frames of MPEG audio, and the ADTS frames of AAC
+    return Ok(sync_word[0] == 0xFF && sync_word[1] & 0xE0 == 0xE0);
+}
+
+/// Find the tag where the gains of a file are written, from the format of its content.
+/// The gains of the Ogg, MP4, WAV and AIFF files are not written: the tags of these containers are not supported by the writers.
+///
+/// # Params
+/// registry: the registry recognizing the format of the file
+/// str_path_to_music: the path of the file
+///
+/// # Return
+/// The tag, or the UnsupportedFeature error when the gains of the file cannot be written
+fn find_gain_tag(registry: &AudioReaderRegistry, str_path_to_music: &str) -> Result<GainTag, AudioReaderError>
+{
+    let format = registry.find_reader(str_path_to_music)?.m_format;
+    let mut file = File::open(str_path_to_music)?;
+    let mut header = [0u8; ID3V2_HEADER_SIZE];
+    if file.read_exact(&mut header).is_err()
+    {
+        return Err(AudioReaderError::UnsupportedFeature("writing the gains in this format"));
+    }
+
+    if format == AudioFormat::Flac && header.starts_with(b"fLaC")
+    {
+        return Ok(GainTag::VorbisComment);
+    }
+    if header.starts_with(b"OggS")
+    {
+        return Err(AudioReaderError::UnsupportedFeature("writing the gains in the Vorbis comments of Ogg files"));
+    }
+    if format == AudioFormat::Symphonia && is_mpeg_audio_stream(&mut file, &header)?
+    {
+        return Ok(GainTag::Ape);
+    }
+    return Err(AudioReaderError::UnsupportedFeature("writing the gains in this format"));
+}
+
+/// Write the ReplayGain 2.0 tags of a title: in the Vorbis comments of the native flac files, in an APE tag for the MP3 and AAC
+/// streams without container. The album tags of a previous scan are removed when there is no album gain.
+///
+/// # Params
+/// registry: the registry recognizing the format of the file
+/// str_path_to_music: the path of the file
+/// track_gain: the gain of the title
+/// album_gain: the gain of the album of the title, None if the title is not part of an album
+pub fn write_replay_gain_tags(registry: &AudioReaderRegistry, str_path_to_music: &str, track_gain: &ReplayGain, album_gain: Option<&ReplayGain>) -> Result<(), AudioReaderError>
+{
+    let mut fields: Vec<(&str, String)> = vec![
+        ("REPLAYGAIN_TRACK_GAIN", format_gain(track_gain)),
+        ("REPLAYGAIN_TRACK_PEAK", format_peak(track_gain)),
+    ];
+    if let Some(album_gain) = album_gain
+    {
+        fields.push(("REPLAYGAIN_ALBUM_GAIN", format_gain(album_gain)));
+        fields.push(("REPLAYGAIN_ALBUM_PEAK", format_peak(album_gain)));
+    }
+    let removed_keys: &[&str] = match album_gain
+    {
+        None => &["REPLAYGAIN_ALBUM_GAIN", "REPLAYGAIN_ALBUM_PEAK"],
+        Some(_) => &[],
+    };
+
+    if find_gain_tag(registry, str_path_to_music)? == GainTag::VorbisComment
+    {
+        let mut writer = FlacMetadataWriter::open(str_path_to_music.to_string())?;
+        for (key, value) in fields.iter()
+        {
+            writer.set_vorbis_comment(key, value)?;
+        }
+        for key in removed_keys.iter()
+        {
+            writer.remove_vorbis_comment(key);
+        }
+        return writer.save();
+    }
+
+    let mut ape_tag = read_ape_tag(str_path_to_music)?.unwrap_or_else(ApeTag::new);
+    for (key, value) in fields.iter()
+    {
+        ape_tag.set_text(key, value)?;
+    }
+    for key in removed_keys.iter()
+    {
+        ape_tag.remove_item(key);
+    }
+    return ape_tag.write_to_file(str_path_to_music);
+}
+
+/// Result of the scan of a title, sent to the view
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoudnessScanResult
+{
+    pub m_str_path_to_music: String,
+    pub m_track_result: LoudnessResult,
+
+    /// Loudness of the album of the title, None if the title is not part of an album
+    pub m_album_result: Option<LoudnessResult>,
+}
+
+impl QuInformationData for LoudnessScanResult
+{
+    fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
+    {
+        let format_gain = |result: &LoudnessResult| result.get_replay_gain().map(|replay_gain| format!("{:.2}", replay_gain.m_gain)).unwrap_or_default();
+        let mut key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = Vec::new();
+        key_map.push(("path_file".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_path_to_music.clone()));
+        key_map.push(("integrated_loudness".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, format!("{:.2}", self.m_track_result.m_integrated_loudness)));
+        key_map.push(("loudness_range".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, format!("{:.2}", self.m_track_result.m_loudness_range)));
+        key_map.push(("true_peak".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, format!("{:.6}", self.m_track_result.m_true_peak)));
+        key_map.push(("track_gain".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, format_gain(&self.m_track_result)));
+        key_map.push(("album_gain".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_album_result.as_ref().map(format_gain).unwrap_or_default()));
+
+        return key_map;
+    }
+}
+
+/// Find the files read by the registry among paths, the directories are searched recursively
+///
+/// # Params
+/// registry: the registry recognizing the audio files
+/// paths: the paths of files and directories
+///
+/// # Return
+/// The paths of the audio files, sorted in each directory
+pub fn find_audio_files(registry: &AudioReaderRegistry, paths: &[String]) -> Vec<String>
+{
+    let mut audio_files: Vec<String> = Vec::new();
+    for path in paths
+    {
+        if Path::new(path).is_dir()
+        {
+            let mut entries: Vec<String> = match fs::read_dir(path)
+            {
+                Err(_) => continue,
+                Ok(entries) => entries.filter_map(|entry| entry.ok()).filter_map(|entry| entry.path().to_str().map(|path| path.to_string())).collect(),
+            };
+            entries.sort();
+            audio_files.extend(find_audio_files(registry, &entries));
+        }
+        else if registry.find_reader(path).is_ok()
+        {
+            audio_files.push(path.clone());
+        }
+    }
+    return audio_files;
+}
+
+/// Album and album artists of a title, None if the title is not part of an album
+type AlbumKey = Option<(String, String)>;
+
+/// Group titles by album, from the album and the album artists (or the artists) of their tags, in the order of their first title.
+/// The titles without album are each alone in their group.
+///
+/// # Return
+/// The groups, with true for the ones which are albums
+fn group_by_album(registry: &AudioReaderRegistry, audio_files: Vec<String>) -> Vec<(bool, Vec<String>)>
+{
+    let mut groups: Vec<(AlbumKey, Vec<String>)> = Vec::new();
+    for audio_file in audio_files
+    {
+        let album_key: AlbumKey = registry.read_information(audio_file.clone()).ok()
+            .filter(|audio_information| !audio_information.m_metadata.m_album.is_empty())
+            .map(|audio_information|
+            {
+                let metadata = audio_information.m_metadata;
+                let artists = match metadata.m_album_artists.is_empty()
+                {
+                    true => metadata.m_artists,
+                    false => metadata.m_album_artists,
+                };
+                (metadata.m_album, artists.join("; "))
+            });
+        match groups.iter_mut().find(|(group_key, _)| album_key.is_some() && *group_key == album_key)
+        {
+            Some((_, group)) => group.push(audio_file),
+            None => groups.push((album_key, vec![audio_file])),
+        }
+    }
+    return groups.into_iter().map(|(album_key, group)| (album_key.is_some(), group)).collect();
+}
+
+/// Scan the loudness of the titles of a library, album after album, and write their gains.
+/// A title which cannot be decoded is reported and left out of the album gain of its album,
+/// like a title whose gains cannot be written when the tags are written, which is not decoded.
+///
+/// # Params
+/// registry: the registry reading and decoding the files
+/// paths: the paths of files and directories to scan, the directories are searched recursively
+/// should_write_tags: true to write the gains in the tags of the titles, false to only measure them
+/// is_cancelled: stops the scan after the current album when set
+/// on_result: called with the result of each title, or with its error
+pub fn scan_library<F: FnMut(Result<LoudnessScanResult, AudioInformationError>)>(registry: &AudioReaderRegistry, paths: &[String], should_write_tags: bool,
+                                                                                 is_cancelled: &AtomicBool, mut on_result: F)
+{
+    for (is_album, group) in group_by_album(registry, find_audio_files(registry, paths))
+    {
+        if is_cancelled.load(Ordering::Relaxed)
+        {
+            return;
+        }
+
+        let mut measured_files: Vec<String> = Vec::new();
+        let mut measurements: Vec<LoudnessMeasurement> = Vec::new();
+        for audio_file in group
+        {
+            if should_write_tags
+            {
+                if let Err(error) = find_gain_tag(registry, &audio_file)
+                {
+                    on_result(Err(AudioInformationError { m_str_path_to_music: audio_file, m_str_error: error.to_string() }));
+                    continue;
+                }
+            }
+            match measure_file(registry, &audio_file)
+            {
+                Ok(measurement) =>
+                {
+                    measured_files.push(audio_file);
+                    measurements.push(measurement);
+                }
+                Err(error) => on_result(Err(AudioInformationError { m_str_path_to_music: audio_file, m_str_error: error.to_string() })),
+            }
+        }
+        let album_result = match is_album
+        {
+            true => Some(compute_album_result(&measurements)),
+            false => None,
+        };
+        let album_gain = album_result.and_then(|album_result| album_result.get_replay_gain());
+
+        for (audio_file, measurement) in measured_files.into_iter().zip(measurements.iter())
+        {
+            let track_result = measurement.get_result();
+            if should_write_tags
+            {
+                //
+                // A silence has no gain to write
+                let written = match track_result.get_replay_gain()
+                {
+                    None => Ok(()),
+                    Some(track_gain) => write_replay_gain_tags(registry, &audio_file, &track_gain, album_gain.as_ref()),
+                };
+                if let Err(error) = written
+                {
+                    on_result(Err(AudioInformationError { m_str_path_to_music: audio_file, m_str_error: error.to_string() }));
+                    continue;
+                }
+            }
+            on_result(Ok(LoudnessScanResult
+            {
+                m_str_path_to_music: audio_file,
+                m_track_result: track_result,
+                m_album_result: album_result,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_loudness_scanner
+{
+    use std::f64::consts::PI;
+    use std::sync::atomic::AtomicBool;
+    use crate::audio_reader::AudioReader;
+    use crate::audio_reader::ape_tag::read_ape_tag;
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::flac_reader::FlacReader;
+    use crate::audio_reader::loudness_scanner::{compute_album_result, scan_library, write_replay_gain_tags, LoudnessMeasurement, LoudnessMeter, LoudnessScanResult};
+    use crate::audio_reader::reader_registry::AudioReaderRegistry;
+    use crate::audio_reader::replay_gain::ReplayGain;
+
+    /// Create a sine with the same phase on all the channels, interleaved
+    fn create_sine(rate: u32, channel_count: usize, frequency: f64, amplitude: f64, phase: f64, duration: f64) -> Vec<f64>
+    {
+        let frame_count = (rate as f64 * duration) as usize;
+        return (0..frame_count)
+            .map(|frame| amplitude * (2.0 * PI * frequency * frame as f64 / rate as f64 + phase).sin())
+            .flat_map(|sample| std::iter::repeat_n(sample, channel_count))
+            .collect();
+    }
+
+    /// Measure interleaved samples
+    fn measure_samples(rate: u32, channel_count: u8, samples: &[f64]) -> LoudnessMeasurement
+    {
+        let mut meter = LoudnessMeter::new(rate, channel_count);
+        meter.add_samples(samples);
+        return meter.finish();
+    }
+
+    /// Convert a loudness to the power of the blocks, to compute the expected loudness of several signals
+    fn convert_loudness_to_power(loudness: f64) -> f64
+    {
+        return 10f64.powf((loudness + 0.691) / 10.0);
+    }
+
+    #[test]
+    fn measure_stereo_sine()
+    {
+        //
+        // EBU Tech 3341: a stereo sine of 1 kHz at -20 dBFS is at -20 LUFS
+        let samples = create_sine(48000, 2, 997.0, 0.1, 0.0, 5.0);
+        let result = measure_samples(48000, 2, &samples).get_result();
+        assert!((result.m_integrated_loudness + 20.0).abs() < 0.1, "{}", result.m_integrated_loudness);
+        assert!(result.m_loudness_range < 0.1);
+        assert!((result.m_sample_peak - 0.1).abs() < 1e-3);
+
+        //
+        // The same sine at 44.1 kHz and at 96 kHz gets the same loudness through the K-weighting computed for the rate
+        for rate in [44100, 96000]
+        {
+            let result = measure_samples(rate, 2, &create_sine(rate, 2, 997.0, 0.1, 0.0, 3.0)).get_result();
+            assert!((result.m_integrated_loudness + 20.0).abs() < 0.1, "{} at {}", result.m_integrated_loudness, rate);
+        }
+
+        //
+        // The LFE channel of 5.1 is not measured
+        let samples: Vec<f64> = create_sine(48000, 1, 997.0, 0.1, 0.0, 3.0).into_iter().flat_map(|sample| [sample, sample, 0.0, 0.9, 0.0, 0.0]).collect();
+        let result = measure_samples(48000, 6, &samples).get_result();
+        assert!((result.m_integrated_loudness + 20.0).abs() < 0.1, "{}", result.m_integrated_loudness);
+    }
+
+    #[test]
+    fn gate_silence()
+    {
+        let tone = create_sine(16000, 1, 1000.0, 0.1, 0.0, 5.0);
+        let tone_loudness = measure_samples(16000, 1, &tone).get_result().m_integrated_loudness;
+        let mut samples = tone.clone();
+        samples.extend(vec![0.0; 16000 * 5]);
+        let result = measure_samples(16000, 1, &samples).get_result();
+
+        //
+        // Only the few blocks overlapping the end of the tone lower the loudness, the silence alone would lower it by 3 dB
+        assert!((result.m_integrated_loudness - tone_loudness).abs() < 0.2, "{} instead of {}", result.m_integrated_loudness, tone_loudness);
+
+        //
+        // A silence has no loudness and no gain
+        let result = measure_samples(16000, 1, &vec![0.0; 16000 * 2]).get_result();
+        assert_eq!(result.m_integrated_loudness, f64::NEG_INFINITY);
+        assert_eq!(result.get_replay_gain(), None);
+    }
+
+    #[test]
+    fn measure_loudness_range()
+    {
+        //
+        // EBU Tech 3342: 20 s at -20 dBFS then 20 s at -30 dBFS have a range of 10 LU
+        let mut samples = create_sine(16000, 1, 1000.0, 0.1, 0.0, 20.0);
+        samples.extend(create_sine(16000, 1, 1000.0, 0.1 / 10f64.sqrt(), 0.0, 20.0));
+        let result = measure_samples(16000, 1, &samples).get_result();
+        assert!((result.m_loudness_range - 10.0).abs() < 1.0, "{}", result.m_loudness_range);
+    }
+
+    #[test]
+    fn find_true_peak_between_samples()
+    {
+        //
+        // A sine at a quarter of the rate shifted by 45 degrees never has a sample at its peak
+        let samples = create_sine(48000, 1, 12000.0, 0.5, PI / 4.0, 1.0);
+        let measurement = measure_samples(48000, 1, &samples);
+        assert!((measurement.m_sample_peak - 0.5 / 2f64.sqrt()).abs() < 1e-3);
+        assert!((measurement.m_true_peak - 0.5).abs() < 0.02, "{}", measurement.m_true_peak);
+    }
+
+    #[test]
+    fn compute_album_gain()
+    {
+        let loud = measure_samples(16000, 1, &create_sine(16000, 1, 1000.0, 0.2, 0.0, 10.0));
+        let quiet = measure_samples(16000, 1, &create_sine(16000, 1, 1000.0, 0.05, 0.0, 10.0));
+        let loud_result = loud.get_result();
+        let quiet_result = quiet.get_result();
+        assert!((loud_result.m_integrated_loudness - quiet_result.m_integrated_loudness - 12.04).abs() < 0.05);
+
+        //
+        // Both titles are above the relative gate, the album is the mean of their powers
+        let album_result = compute_album_result(&[loud.clone(), quiet.clone()]);
+        let expected_power = (convert_loudness_to_power(loud_result.m_integrated_loudness) + convert_loudness_to_power(quiet_result.m_integrated_loudness)) / 2.0;
+        let expected_loudness = -0.691 + 10.0 * expected_power.log10();
+        assert!((album_result.m_integrated_loudness - expected_loudness).abs() < 0.01);
+        assert_eq!(album_result.m_true_peak, loud.m_true_peak);
+
+        let album_gain = album_result.get_replay_gain().unwrap();
+        assert!((album_gain.m_gain as f64 - (-18.0 - expected_loudness)).abs() < 0.01);
+        assert_eq!(album_gain.m_peak, Some(loud.m_true_peak as f32));
+    }
+
+    #[test]
+    fn scan_and_write_flac_gains()
+    {
+        let directory = std::env::temp_dir().join("quadrium_loudness_scan");
+        std::fs::create_dir_all(&directory).unwrap();
+        for (name, amplitude) in [("1.flac", 0.2), ("2.flac", 0.05)]
+        {
+            let mut settings = FlacEncoderSettings::new(16000, 1, 16);
+            settings.m_vorbis_comments = vec!["ALBUM=Loudness".to_string(), "REPLAYGAIN_ALBUM_GAIN=+40.00 dB".to_string()];
+            let samples: Vec<i32> = create_sine(16000, 1, 1000.0, amplitude, 0.0, 5.0).iter().map(|sample| (sample * 32768.0).round() as i32).collect();
+            let mut encoder = FlacEncoder::create(directory.join(name).to_string_lossy().to_string(), settings).unwrap();
+            encoder.write_samples(&samples).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        let registry = AudioReaderRegistry::with_default_readers();
+        let mut scan_results: Vec<LoudnessScanResult> = Vec::new();
+        scan_library(&registry, &[directory.to_string_lossy().to_string()], true, &AtomicBool::new(false), |scan_result| scan_results.push(scan_result.ok().unwrap()));
+        assert_eq!(scan_results.len(), 2);
+        assert!(scan_results[0].m_str_path_to_music.ends_with("1.flac"));
+        let album_gain = scan_results[0].m_album_result.unwrap().get_replay_gain().unwrap();
+        assert_eq!(scan_results[1].m_album_result, scan_results[0].m_album_result);
+
+        for scan_result in scan_results.iter()
+        {
+            let replay_gain = FlacReader {}.read_information(scan_result.m_str_path_to_music.clone()).unwrap().m_metadata.m_replay_gain;
+            let track_gain = scan_result.m_track_result.get_replay_gain().unwrap();
+            assert!((replay_gain.m_track.unwrap().m_gain - track_gain.m_gain).abs() < 0.01);
+            assert!((replay_gain.m_track.unwrap().m_peak.unwrap() - track_gain.m_peak.unwrap()).abs() < 1e-5);
+            assert!((replay_gain.m_album.unwrap().m_gain - album_gain.m_gain).abs() < 0.01);
+        }
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn write_ape_gains()
+    {
+        let path = std::env::temp_dir().join("quadrium_loudness_gains.mp3");
+        let str_path = path.to_string_lossy().to_string();
+        let mut mp3_data = vec![0xFF, 0xFB, 0x90, 0x00];
+        mp3_data.resize(64, 0);
+        std::fs::write(&path, &mp3_data).unwrap();
+        let registry = AudioReaderRegistry::with_default_readers();
+        let track_gain = ReplayGain { m_gain: -6.48, m_peak: Some(0.988831) };
+        let album_gain = ReplayGain { m_gain: -5.5, m_peak: Some(1.0) };
+        write_replay_gain_tags(&registry, &str_path, &track_gain, Some(&album_gain)).unwrap();
+        let ape_tag = read_ape_tag(&str_path).unwrap().unwrap();
+        assert_eq!(ape_tag.get_metadata().m_replay_gain.m_track, Some(track_gain));
+        assert_eq!(ape_tag.get_metadata().m_replay_gain.m_album, Some(album_gain));
+
+        //
+        // The album gain of a previous scan is removed when the title is not part of an album anymore
+        write_replay_gain_tags(&registry, &str_path, &track_gain, None).unwrap();
+        let ape_tag = read_ape_tag(&str_path).unwrap().unwrap();
+        assert_eq!(ape_tag.get_metadata().m_replay_gain.m_album, None);
+        assert_eq!(std::fs::read(&path).unwrap()[..64], mp3_data[..]);
+
+        //
+        // The stream is recognized from its content, after its ID3v2 tag, whatever its extension
+        let mut id3_data = b"ID3\x04\x00\x00\x00\x00\x00\x0A".to_vec();
+        id3_data.resize(20, 0);
+        id3_data.extend_from_slice(&mp3_data);
+        let other_path = std::env::temp_dir().join("quadrium_loudness_gains.dat");
+        std::fs::write(&other_path, &id3_data).unwrap();
+        write_replay_gain_tags(&registry, &other_path.to_string_lossy(), &track_gain, None).unwrap();
+        assert_eq!(read_ape_tag(&other_path.to_string_lossy()).unwrap().unwrap().get_metadata().m_replay_gain.m_track, Some(track_gain));
+
+        //
+        // The gains of a WAV file or of a MP4 file have no tag to be written in, even with the extension of a MP3 file
+        for data in [&b"RIFF\0\0\0\0WAVE"[..], &b"\0\0\0\x18ftypM4A \0\0\0\0M4A mp42"[..]]
+        {
+            std::fs::write(&path, data).unwrap();
+            let error = write_replay_gain_tags(&registry, &str_path, &track_gain, None).unwrap_err();
+            assert_eq!(error.to_string(), "unsupported feature: writing the gains in this format");
+            assert_eq!(std::fs::read(&path).unwrap(), data);
+        }
+
+        //
+        // The Vorbis comments of the Ogg files are not written
+        let mut ogg_data = b"OggS".to_vec();
+        ogg_data.resize(28, 0);
+        ogg_data.extend_from_slice(b"\x01vorbis");
+        std::fs::write(&path, &ogg_data).unwrap();
+        let error = write_replay_gain_tags(&registry, &str_path, &track_gain, None).unwrap_err();
+        assert!(error.to_string().contains("Ogg"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&other_path).unwrap();
+    }
+
+    #[test]
+    fn skip_files_without_writable_tags()
+    {
+        let directory = std::env::temp_dir().join("quadrium_loudness_skip");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut ogg_data = b"OggS".to_vec();
+        ogg_data.resize(28, 0);
+        ogg_data.extend_from_slice(b"\x01vorbis");
+        std::fs::write(directory.join("1.ogg"), &ogg_data).unwrap();
+
+        //
+        // The file is reported before being decoded, with the reason why its gains cannot be written
+        let registry = AudioReaderRegistry::with_default_readers();
+        let mut errors: Vec<String> = Vec::new();
+        scan_library(&registry, &[directory.to_string_lossy().to_string()], true, &AtomicBool::new(false), |scan_result| errors.push(scan_result.err().unwrap().m_str_error));
+        assert_eq!(errors, vec!["unsupported feature: writing the gains in the Vorbis comments of Ogg files".to_string()]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
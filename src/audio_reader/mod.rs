@@ -16,25 +16,537 @@
  *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use crate::{audio_reader, Controller};
+use crate::audio_reader::music_player::MusicPlayer;
+use crate::audio_reader::replay_gain::ReplayGainInfo;
 use crate::Controller::EventManager::{EventManager, push_event_in_tmp_queue, QuEvent, QuInformationData};
 use crate::Controller::{QuEventType};
 
+/// Date given by a tag, from the year alone to the complete day
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TagDate
+{
+    pub m_year: u16,
+    pub m_month: Option<u8>,
+    pub m_day: Option<u8>,
+}
+
+impl TagDate
+{
+    /// Parse a date written "YYYY", "YYYY-MM" or "YYYY-MM-DD", with '/' or '.' also accepted between the parts.
+    /// The time following the day, like in "2004-05-06T10:20", is ignored.
+    ///
+    /// # Return
+    /// The date, None if the text does not start with a year
+    pub fn parse(text: &str) -> Option<TagDate>
+    {
+        let date_text = text.trim().split(&['T', ' '][..]).next().unwrap_or("");
+        let mut parts = date_text.split(&['-', '/', '.'][..]);
+        let year_text = parts.next()?;
+        if year_text.len() != 4
+        {
+            return None;
+        }
+        let year: u16 = year_text.parse().ok()?;
+
+        //
+        // A month or a day which cannot be read only makes the date less precise
+        let month: Option<u8> = parts.next().and_then(|month| month.parse().ok()).filter(|month| (1..=12).contains(month));
+        let day: Option<u8> = month.and(parts.next()).and_then(|day| day.parse().ok()).filter(|day| (1..=31).contains(day));
+        return Some(TagDate
+        {
+            m_year: year,
+            m_month: month,
+            m_day: day,
+        });
+    }
+}
+
+impl std::fmt::Display for TagDate
+{
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(formatter, "{:04}", self.m_year)?;
+        if let Some(month) = self.m_month
+        {
+            write!(formatter, "-{:02}", month)?;
+            if let Some(day) = self.m_day
+            {
+                write!(formatter, "-{:02}", day)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Parse a position written "3" or "3/12", like the track and the disc numbers of the tags
+///
+/// # Return
+/// The number and the total, each None if it is missing or cannot be read
+pub fn parse_number_and_total(text: &str) -> (Option<u32>, Option<u32>)
+{
+    let (number, total) = match text.split_once('/')
+    {
+        Some((number, total)) => (number, Some(total)),
+        None => (text, None),
+    };
+    let parse_number = |number: &str| number.trim().parse::<u32>().ok().filter(|number| *number != 0);
+    return (parse_number(number), total.and_then(parse_number));
+}
+
+/// The tags of a title, filled the same way by all the readers whatever the format of the tags
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackMetadata
+{
+    /// Title of the track, empty if unknown
+    pub m_title: String,
+    pub m_artists: Vec<String>,
+
+    /// Name of the album, empty if unknown
+    pub m_album: String,
+
+    /// Artists of the whole album, which can differ from the artists of the track on a compilation
+    pub m_album_artists: Vec<String>,
+    pub m_composers: Vec<String>,
+    pub m_genres: Vec<String>,
+    pub m_track_number: Option<u32>,
+
+    /// Number of tracks of the disc
+    pub m_track_total: Option<u32>,
+    pub m_disc_number: Option<u32>,
+
+    /// Number of discs of the album
+    pub m_disc_total: Option<u32>,
+
+    /// Date of the release or of the recording
+    pub m_date: Option<TagDate>,
+
+    /// Gains to play the title at the same loudness as the other titles
+    pub m_replay_gain: ReplayGainInfo,
+
+    /// The text fields without a field of their own by key in upper case, with all their values
+    pub m_extra: BTreeMap<String, Vec<String>>,
+}
+
+impl TrackMetadata
+{
+    /// Create metadata without any tag
+    pub fn new() -> TrackMetadata
+    {
+        return TrackMetadata
+        {
+            m_title: String::new(),
+            m_artists: Vec::new(),
+            m_album: String::new(),
+            m_album_artists: Vec::new(),
+            m_composers: Vec::new(),
+            m_genres: Vec::new(),
+            m_track_number: None,
+            m_track_total: None,
+            m_disc_number: None,
+            m_disc_total: None,
+            m_date: None,
+            m_replay_gain: ReplayGainInfo::new(),
+            m_extra: BTreeMap::new(),
+        };
+    }
+
+    /// Set the track number, and the total if the text gives it, from a text like "3/12".
+    /// The values which cannot be read are kept.
+    pub fn set_track_text(&mut self, text: &str)
+    {
+        let (number, total) = parse_number_and_total(text);
+        self.m_track_number = number.or(self.m_track_number);
+        self.m_track_total = total.or(self.m_track_total);
+    }
+
+    /// Set the disc number, and the total if the text gives it, from a text like "1/2".
+    /// The values which cannot be read are kept.
+    pub fn set_disc_text(&mut self, text: &str)
+    {
+        let (number, total) = parse_number_and_total(text);
+        self.m_disc_number = number.or(self.m_disc_number);
+        self.m_disc_total = total.or(self.m_disc_total);
+    }
+
+    /// Add a value to a field without a field of its own
+    ///
+    /// # Params
+    /// key: the key of the field, stored in upper case
+    /// value: the value added after the previous values of the field
+    pub fn add_extra(&mut self, key: &str, value: String)
+    {
+        self.m_extra.entry(key.to_ascii_uppercase()).or_default().push(value);
+    }
+
+    /// Replace the fields given by the metadata of a tag, the fields the tag does not give are kept
+    pub fn apply(&mut self, tag_metadata: TrackMetadata)
+    {
+        let previous_metadata = std::mem::replace(self, tag_metadata);
+        self.fill_missing(previous_metadata);
+    }
+
+    /// Fill the fields which are still empty with the ones of less preferred metadata
+    pub fn fill_missing(&mut self, other: TrackMetadata)
+    {
+        if self.m_title.is_empty()
+        {
+            self.m_title = other.m_title;
+        }
+        if self.m_album.is_empty()
+        {
+            self.m_album = other.m_album;
+        }
+        let lists = [
+            (&mut self.m_artists, other.m_artists),
+            (&mut self.m_album_artists, other.m_album_artists),
+            (&mut self.m_composers, other.m_composers),
+            (&mut self.m_genres, other.m_genres),
+        ];
+        for (list, values) in lists
+        {
+            if list.is_empty()
+            {
+                *list = values;
+            }
+        }
+        let numbers = [
+            (&mut self.m_track_number, other.m_track_number),
+            (&mut self.m_track_total, other.m_track_total),
+            (&mut self.m_disc_number, other.m_disc_number),
+            (&mut self.m_disc_total, other.m_disc_total),
+        ];
+        for (number, value) in numbers
+        {
+            *number = number.or(value);
+        }
+        self.m_date = self.m_date.or(other.m_date);
+        self.m_replay_gain.fill_missing(other.m_replay_gain);
+        for (key, values) in other.m_extra
+        {
+            self.m_extra.entry(key).or_insert(values);
+        }
+    }
+}
+
 /// Structure that define all the information needed to define a title
 pub struct AudioInformation
 {
-    pub m_str_music_name : String,
-    pub m_str_music_type : String,
-    pub m_str_artist_name : String,
-    pub m_str_tracknumber: String,
-    pub m_str_album : String,
-    pub m_str_date: String,
-    pub m_str_duration: String,
+    /// The tags of the title, merged from all the tags of the file
+    pub m_metadata: TrackMetadata,
+
+    pub m_rate: u32,
+    pub m_channel_count: u8,
+    pub m_bits_per_sample: u8,
+
+    /// Number of samples of each channel, 0 if unknown
+    pub m_total_samples: u64,
+
+    /// Average bitrate of the audio data in bits per second, 0 if unknown
+    pub m_bitrate: u32,
+
+    /// Name of the codec of the audio data, like "FLAC" or "PCM"
+    pub m_codec: String,
+    pub m_is_lossless: bool,
+
+    /// All the pictures embedded inside the file
+    pub m_pictures: Vec<AudioPicture>,
+
+    /// The tracks stored inside the file, like the tracks of a CD image. Empty if the file is a single track.
+    pub m_tracks: Vec<VirtualTrack>,
+
+    /// Free text comments about the title, like the annotations of AIFF files
+    pub m_comments: Vec<String>,
+
+    /// The markers placed on the samples of the stream, sorted by sample
+    pub m_cue_points: Vec<CuePoint>,
+
+    /// The parts of the stream repeated by a sampler while a note is played
+    pub m_loops: Vec<AudioLoop>,
+}
+
+impl AudioInformation
+{
+    /// Create information without any tag, picture or property of the stream
+    pub fn new() -> AudioInformation
+    {
+        return AudioInformation
+        {
+            m_metadata: TrackMetadata::new(),
+            m_rate: 0,
+            m_channel_count: 0,
+            m_bits_per_sample: 0,
+            m_total_samples: 0,
+            m_bitrate: 0,
+            m_codec: String::new(),
+            m_is_lossless: false,
+            m_pictures: Vec::new(),
+            m_tracks: Vec::new(),
+            m_comments: Vec::new(),
+            m_cue_points: Vec::new(),
+            m_loops: Vec::new(),
+        };
+    }
+
+    /// Get the duration of the stream from its number of samples and its rate, None if one of them is unknown
+    pub fn get_duration(&self) -> Option<Duration>
+    {
+        if self.m_rate == 0 || self.m_total_samples == 0
+        {
+            return None;
+        }
+        let seconds = self.m_total_samples / self.m_rate as u64;
+        let nanoseconds = (self.m_total_samples % self.m_rate as u64) * 1_000_000_000 / self.m_rate as u64;
+        return Some(Duration::new(seconds, nanoseconds as u32));
+    }
+
+    /// Compute the average bitrate from the size of the audio data, kept unknown if the duration is unknown
+    ///
+    /// # Params
+    /// audio_byte_count: the number of bytes of the audio data, without the tags
+    pub fn set_average_bitrate(&mut self, audio_byte_count: u64)
+    {
+        if let Some(duration) = self.get_duration()
+        {
+            self.m_bitrate = (audio_byte_count as f64 * 8.0 / duration.as_secs_f64()).round() as u32;
+        }
+    }
+
+    /// Fill the tags which are still empty with the ones read from a less preferred tag.
+    /// The comments and the pictures are taken from the first tag which has some, to not repeat them.
+    pub fn fill_missing_tags(&mut self, other: AudioInformation)
+    {
+        self.m_metadata.fill_missing(other.m_metadata);
+        if self.m_comments.is_empty()
+        {
+            self.m_comments = other.m_comments;
+        }
+        if self.m_pictures.is_empty()
+        {
+            self.m_pictures = other.m_pictures;
+        }
+    }
+}
+
+/// Format of the tags which can be stored together in a file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TagFormat
+{
+    /// The metadata of the container, like the Vorbis comments or the MP4 atoms
+    Native,
+    Id3v2,
+    Ape,
+    Id3v1,
+}
+
+/// Order in which the tags of a file are preferred when they give different values, from the most preferred one
+pub const DEFAULT_TAG_PRIORITY: [TagFormat; 4] = [TagFormat::Native, TagFormat::Id3v2, TagFormat::Ape, TagFormat::Id3v1];
+
+/// Marker placed on a sample of the stream, like the markers of AIFF files
+#[derive(Clone, Debug, PartialEq)]
+pub struct CuePoint
+{
+    /// Id of the marker, unique inside the file
+    pub m_id: u32,
+    pub m_sample: u64,
+    pub m_label: String,
+}
+
+/// Way a loop is played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopMode
+{
+    /// From its start to its end, then again from its start
+    Forward,
+
+    /// From its start to its end, then back from its end to its start
+    ForwardBackward,
+}
+
+/// Part of the stream repeated by a sampler, between two cue points
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioLoop
+{
+    /// Index of the first sample of the loop
+    pub m_start_sample: u64,
+
+    /// Index of the sample following the last sample of the loop
+    pub m_end_sample: u64,
+    pub m_mode: LoopMode,
+
+    /// True for the loop played when the note is released, false for the loop played while the note is held
+    pub m_is_release_loop: bool,
+}
+
+/// Index point of a track, defined by its number and its sample in the stream
+#[derive(Clone)]
+pub struct TrackIndexPoint
+{
+    /// 0 for the pre-gap, 1 for the start of the track, the next ones for the subdivisions of the track
+    pub m_index_number: u8,
+    pub m_sample: u64,
+}
+
+/// Track stored inside a part of an audio file, played like a file of its own
+#[derive(Clone)]
+pub struct VirtualTrack
+{
+    pub m_track_number: u8,
+
+    /// Index of the first sample of the track in the stream
+    pub m_start_sample: u64,
+
+    /// Index of the sample following the last sample of the track
+    pub m_end_sample: u64,
+    pub m_index_points: Vec<TrackIndexPoint>,
+
+    /// International Standard Recording Code, empty if unknown
+    pub m_isrc: String,
+    pub m_has_pre_emphasis: bool,
+}
+
+/// Type of an embedded picture, the values are the ones of the ID3v2 APIC frame also used by flac
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PictureType
+{
+    Other,
+    FileIcon,
+    OtherFileIcon,
+    FrontCover,
+    BackCover,
+    LeafletPage,
+    Media,
+    LeadArtist,
+    Artist,
+    Conductor,
+    Band,
+    Composer,
+    Lyricist,
+    RecordingLocation,
+    DuringRecording,
+    DuringPerformance,
+    MovieScreenCapture,
+    BrightColouredFish,
+    Illustration,
+    BandLogo,
+    PublisherLogo,
+
+    /// Value not defined by the specification
+    Unknown(u32),
+}
+
+impl From<u32> for PictureType
+{
+    fn from(value: u32) -> PictureType
+    {
+        return match value
+        {
+            0 => PictureType::Other,
+            1 => PictureType::FileIcon,
+            2 => PictureType::OtherFileIcon,
+            3 => PictureType::FrontCover,
+            4 => PictureType::BackCover,
+            5 => PictureType::LeafletPage,
+            6 => PictureType::Media,
+            7 => PictureType::LeadArtist,
+            8 => PictureType::Artist,
+            9 => PictureType::Conductor,
+            10 => PictureType::Band,
+            11 => PictureType::Composer,
+            12 => PictureType::Lyricist,
+            13 => PictureType::RecordingLocation,
+            14 => PictureType::DuringRecording,
+            15 => PictureType::DuringPerformance,
+            16 => PictureType::MovieScreenCapture,
+            17 => PictureType::BrightColouredFish,
+            18 => PictureType::Illustration,
+            19 => PictureType::BandLogo,
+            20 => PictureType::PublisherLogo,
+            _ => PictureType::Unknown(value),
+        };
+    }
+}
+
+impl From<PictureType> for u32
+{
+    fn from(picture_type: PictureType) -> u32
+    {
+        return match picture_type
+        {
+            PictureType::Other => 0,
+            PictureType::FileIcon => 1,
+            PictureType::OtherFileIcon => 2,
+            PictureType::FrontCover => 3,
+            PictureType::BackCover => 4,
+            PictureType::LeafletPage => 5,
+            PictureType::Media => 6,
+            PictureType::LeadArtist => 7,
+            PictureType::Artist => 8,
+            PictureType::Conductor => 9,
+            PictureType::Band => 10,
+            PictureType::Composer => 11,
+            PictureType::Lyricist => 12,
+            PictureType::RecordingLocation => 13,
+            PictureType::DuringRecording => 14,
+            PictureType::DuringPerformance => 15,
+            PictureType::MovieScreenCapture => 16,
+            PictureType::BrightColouredFish => 17,
+            PictureType::Illustration => 18,
+            PictureType::BandLogo => 19,
+            PictureType::PublisherLogo => 20,
+            PictureType::Unknown(value) => value,
+        };
+    }
+}
+
+/// Picture embedded inside an audio file, like the cover of the album
+#[derive(Clone)]
+pub struct AudioPicture
+{
+    pub m_picture_type: PictureType,
+
+    /// MIME type of the data, like "image/jpeg". "-->" means the data is the URL of the picture.
+    pub m_mime_type: String,
+    pub m_description: String,
+    pub m_width: u32,
+    pub m_height: u32,
+
+    /// Bits per pixel
+    pub m_color_depth: u32,
 
+    /// Number of colors of indexed pictures like GIF, 0 otherwise
+    pub m_indexed_color_count: u32,
+
+    /// The content of the picture file
+    pub m_data: Vec<u8>,
+}
+
+impl AudioInformation
+{
+    /// Get the front cover of the album, or the first picture if there is no front cover
+    pub fn get_front_cover(&self) -> Option<&AudioPicture>
+    {
+        return self.m_pictures.iter()
+            .find(|picture| picture.m_picture_type == PictureType::FrontCover)
+            .or(self.m_pictures.first());
+    }
+}
+
+/// Block of samples decoded from an audio file
+pub struct DecodedBlock
+{
+    /// Index in the stream of the first sample of the block
+    pub m_first_sample: u64,
     pub m_rate: u32,
     pub m_channel_count: u8,
     pub m_bits_per_sample: u8,
+
+    /// Samples of all the channels interleaved
+    pub m_samples: Vec<i32>,
 }
 
 impl QuInformationData for AudioInformation
@@ -44,16 +556,149 @@ impl QuInformationData for AudioInformation
     fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
     {
         let mut key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = Vec::new();
-        key_map.push(("music_name".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_music_name.clone()));
-        key_map.push(("music_type".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_music_type.clone()));
-        key_map.push(("artist_name".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_artist_name.clone()));
-        key_map.push(("track_number".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_tracknumber.clone()));
-        key_map.push(("album".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_album.clone()));
-        key_map.push(("date".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_date.clone()));
-        key_map.push(("duration".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_duration.clone()));
+        let metadata = &self.m_metadata;
+        let duration = self.get_duration().map(|duration| format!("{}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60)).unwrap_or_default();
+        key_map.push(("music_name".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_title.clone()));
+        key_map.push(("music_type".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_genres.join(", ")));
+        key_map.push(("artist_name".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_artists.join(", ")));
+        key_map.push(("album_artist".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_album_artists.join(", ")));
+        key_map.push(("composer".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_composers.join(", ")));
+        key_map.push(("track_number".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_track_number.map(|number| number.to_string()).unwrap_or_default()));
+        key_map.push(("track_total".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_track_total.map(|total| total.to_string()).unwrap_or_default()));
+        key_map.push(("disc_number".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_disc_number.map(|number| number.to_string()).unwrap_or_default()));
+        key_map.push(("disc_total".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_disc_total.map(|total| total.to_string()).unwrap_or_default()));
+        key_map.push(("album".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_album.clone()));
+        key_map.push(("date".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_date.map(|date| date.to_string()).unwrap_or_default()));
+        key_map.push(("duration".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, duration));
+        key_map.push(("track_gain".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_replay_gain.m_track.map(|replay_gain| format!("{:.2} dB", replay_gain.m_gain)).unwrap_or_default()));
+        key_map.push(("album_gain".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, metadata.m_replay_gain.m_album.map(|replay_gain| format!("{:.2} dB", replay_gain.m_gain)).unwrap_or_default()));
+        key_map.push(("codec".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_codec.clone()));
+        key_map.push(("bitrate".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_bitrate.to_string()));
+        key_map.push(("is_lossless".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_is_lossless.to_string()));
         key_map.push(("track_rate".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_rate.to_string()));
         key_map.push(("channel_count".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_channel_count.to_string()));
         key_map.push(("bits_per_sample".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_bits_per_sample.to_string()));
+        key_map.push(("picture_count".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_pictures.len().to_string()));
+        key_map.push(("track_count".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_tracks.len().to_string()));
+        key_map.push(("cue_point_count".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_cue_points.len().to_string()));
+
+        return key_map;
+    }
+}
+
+/// Errors which can happen while reading or decoding an audio file
+#[derive(Debug)]
+pub enum AudioReaderError
+{
+    /// The file cannot be opened or read
+    Io(std::io::Error),
+
+    /// The file does not start with the magic number of its format
+    InvalidMagicNumber,
+
+    /// A block required by the format is missing, contains the name of the block
+    MissingBlock(&'static str),
+
+    /// The file ends before the end of a block
+    TruncatedBlock
+    {
+        m_block_name: &'static str,
+
+        /// Position in the file of the first byte of the block
+        m_offset: u64,
+    },
+
+    /// The length of a block, or of a field inside it, is not valid for its type
+    InvalidBlockLength
+    {
+        m_block_name: &'static str,
+        m_length: u32,
+    },
+
+    /// The file uses a feature of its format which is not supported by the reader
+    UnsupportedFeature(&'static str),
+
+    /// The data end in the middle of a frame
+    NotEnoughData,
+
+    /// The frame does not start with the frame sync code
+    InvalidSyncCode,
+
+    /// A field uses a reserved or invalid value
+    InvalidValue(&'static str),
+
+    /// The CRC-8 of the frame header does not match its content
+    InvalidHeaderCrc,
+
+    /// The CRC-16 of the frame footer does not match the content of the frame
+    InvalidFrameCrc,
+
+    /// The sample asked by a seek is not inside the stream
+    InvalidSeekPosition,
+
+    /// No reader recognizes the content or the extension of the file
+    UnsupportedFormat,
+}
+
+impl std::fmt::Display for AudioReaderError
+{
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        return match self
+        {
+            AudioReaderError::Io(error) => write!(formatter, "cannot read the file: {}", error),
+            AudioReaderError::InvalidMagicNumber => write!(formatter, "the file is not in a supported format"),
+            AudioReaderError::MissingBlock(block_name) => write!(formatter, "the {} block is missing", block_name),
+            AudioReaderError::TruncatedBlock { m_block_name, m_offset } =>
+                write!(formatter, "the file ends inside the {} block at byte {}", m_block_name, m_offset),
+            AudioReaderError::InvalidBlockLength { m_block_name, m_length } =>
+                write!(formatter, "invalid length {} in the {} block", m_length, m_block_name),
+            AudioReaderError::UnsupportedFeature(feature) => write!(formatter, "unsupported feature: {}", feature),
+            AudioReaderError::NotEnoughData => write!(formatter, "the data end in the middle of a frame"),
+            AudioReaderError::InvalidSyncCode => write!(formatter, "invalid frame sync code"),
+            AudioReaderError::InvalidValue(field) => write!(formatter, "invalid value of the {}", field),
+            AudioReaderError::InvalidHeaderCrc => write!(formatter, "the CRC of the frame header does not match"),
+            AudioReaderError::InvalidFrameCrc => write!(formatter, "the CRC of the frame does not match"),
+            AudioReaderError::InvalidSeekPosition => write!(formatter, "the seek position is outside of the stream"),
+            AudioReaderError::UnsupportedFormat => write!(formatter, "unsupported format"),
+        };
+    }
+}
+
+impl std::error::Error for AudioReaderError
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        return match self
+        {
+            AudioReaderError::Io(error) => Some(error),
+            _ => None,
+        };
+    }
+}
+
+impl From<std::io::Error> for AudioReaderError
+{
+    fn from(error: std::io::Error) -> AudioReaderError
+    {
+        return AudioReaderError::Io(error);
+    }
+}
+
+/// Error sent to the view when the information of a music cannot be read
+pub struct AudioInformationError
+{
+    pub m_str_path_to_music: String,
+    pub m_str_error: String,
+}
+
+impl QuInformationData for AudioInformationError
+{
+    fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
+    {
+        let mut key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = Vec::new();
+        key_map.push(("path_file".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_path_to_music.clone()));
+        key_map.push(("error".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_error.clone()));
 
         return key_map;
     }
@@ -67,6 +712,7 @@ impl QuInformationData for AudioInformation
 pub fn register_event_listeners(event_manager: Arc<Mutex<EventManager::<QuEventType>>>)
 {
     let tmp_event_queue = event_manager.lock().unwrap().get_temporary_queue().clone();
+    let reader_registry = audio_reader::reader_registry::AudioReaderRegistry::with_default_readers();
     event_manager.lock().unwrap().register_listener(QuEventType::EAskRetrieveMusicInformation, move |event| {
         let argument = event.m_event_arg.convert_to_key_map();
         if argument.len() != 1
@@ -74,20 +720,204 @@ pub fn register_event_listeners(event_manager: Arc<Mutex<EventManager::<QuEventT
             return;
         }
 
-        let flac_reader = audio_reader::flac_reader::FlacReader
+        //
+        // Each track of a cue sheet is sent like a music of its own
+        if argument[0].2.to_ascii_lowercase().ends_with(".cue")
+        {
+            match audio_reader::cue_sheet::read_cue_tracks(&reader_registry, &argument[0].2)
+            {
+                Ok(cue_tracks) =>
+                {
+                    for cue_track in cue_tracks
+                    {
+                        push_event_in_tmp_queue(QuEvent::<QuEventType>
+                        {
+                            m_event_type: QuEventType::EMusicInformationRetrieved,
+                            m_event_arg: Arc::new(cue_track),
+                        }, tmp_event_queue.clone());
+                    }
+                }
+                Err(error) => push_event_in_tmp_queue(QuEvent::<QuEventType>
+                {
+                    m_event_type: QuEventType::EMusicInformationError,
+                    m_event_arg: Arc::new(AudioInformationError
+                    {
+                        m_str_path_to_music: argument[0].2.clone(),
+                        m_str_error: error.to_string(),
+                    }),
+                }, tmp_event_queue.clone()),
+            }
+            return;
+        }
+
+        let event_to_send = match reader_registry.read_information(argument[0].2.clone())
         {
+            //
+            // The tracks of the cuesheet of a flac file are sent like the ones of a cue sheet
+            Ok(audio_information) if !audio_information.m_tracks.is_empty() =>
+            {
+                for embedded_track in audio_reader::cue_sheet::split_embedded_tracks(&argument[0].2, &audio_information)
+                {
+                    push_event_in_tmp_queue(QuEvent::<QuEventType>
+                    {
+                        m_event_type: QuEventType::EMusicInformationRetrieved,
+                        m_event_arg: Arc::new(embedded_track),
+                    }, tmp_event_queue.clone());
+                }
+                return;
+            }
+            Ok(audio_information) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EMusicInformationRetrieved,
+                m_event_arg: Arc::new(audio_information),
+            },
+            Err(error) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EMusicInformationError,
+                m_event_arg: Arc::new(AudioInformationError
+                {
+                    m_str_path_to_music: argument[0].2.clone(),
+                    m_str_error: error.to_string(),
+                }),
+            },
+        };
 
+        push_event_in_tmp_queue(event_to_send, tmp_event_queue.clone());
+    });
+
+    //
+    // The music asked to be played is opened with the gain chosen by the ReplayGain settings of the player.
+    // The argument gives the file with the key "path_file", or a cue sheet with the number of the track in "track_number",
+    // and "album_playback" is "true" when its album is played in order.
+    let play_event_queue = event_manager.lock().unwrap().get_temporary_queue().clone();
+    let music_player = Arc::new(Mutex::new(MusicPlayer::new()));
+    let played_music_player = music_player.clone();
+    event_manager.lock().unwrap().register_listener(QuEventType::EAskReadMusic, move |event| {
+        let argument = event.m_event_arg.convert_to_key_map();
+        let find_argument = |key: &str| argument.iter().find(|(argument_key, _, _)| argument_key == key).map(|(_, _, value)| value.clone());
+        let str_path_to_music = match find_argument("path_file")
+        {
+            None => return,
+            Some(str_path_to_music) => str_path_to_music,
+        };
+        let track_number = find_argument("track_number").and_then(|track_number| track_number.parse::<u8>().ok());
+        let is_album_playback = find_argument("album_playback").is_some_and(|value| value == "true");
+
+        let event_to_send = match played_music_player.lock().unwrap().play(&str_path_to_music, track_number, is_album_playback)
+        {
+            Ok(played_music_state) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EReadMusicState,
+                m_event_arg: Arc::new(played_music_state),
+            },
+            Err(error) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EReadMusicError,
+                m_event_arg: Arc::new(AudioInformationError
+                {
+                    m_str_path_to_music: str_path_to_music,
+                    m_str_error: error.to_string(),
+                }),
+            },
         };
+        push_event_in_tmp_queue(event_to_send, play_event_queue.clone());
+    });
 
-        let audio_information = flac_reader.read_information(argument[0].2.clone());
+    //
+    // The settings are changed with the keys "replay_gain_mode" (off, track, album or auto), "replay_gain_preamp" in dB
+    // and "prevent_clipping" ("true" or "false"), the missing or invalid ones are kept.
+    // The gain of the music being played changes at once, its new state is sent.
+    let settings_event_queue = event_manager.lock().unwrap().get_temporary_queue().clone();
+    let settings_music_player = music_player.clone();
+    event_manager.lock().unwrap().register_listener(QuEventType::EAskChangeReplayGainSettings, move |event| {
+        let mut music_player = settings_music_player.lock().unwrap();
+        let settings = music_player.get_replay_gain_settings().update_from_key_map(&event.m_event_arg.convert_to_key_map());
+        music_player.set_replay_gain_settings(settings);
+        if let Some(played_music_state) = music_player.get_state()
+        {
+            push_event_in_tmp_queue(QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EReadMusicState,
+                m_event_arg: Arc::new(played_music_state),
+            }, settings_event_queue.clone());
+        }
+    });
 
-        let event_to_send = QuEvent::<QuEventType>
+    //
+    // The audio output pulls the samples of the music being played block after block.
+    // The end of the music is sent as its state, with "is_finished" at "true".
+    let block_event_queue = event_manager.lock().unwrap().get_temporary_queue().clone();
+    event_manager.lock().unwrap().register_listener(QuEventType::EAskReadMusicBlock, move |_event| {
+        let mut music_player = music_player.lock().unwrap();
+        let event_to_send = match music_player.read_next_block()
         {
-            m_event_type: QuEventType::EMusicInformationRetrieved,
-            m_event_arg: Arc::new(audio_information),
+            Ok(Some(decoded_block)) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EMusicBlockRead,
+                m_event_arg: Arc::new(decoded_block),
+            },
+            Ok(None) => match music_player.get_state()
+            {
+                None => return,
+                Some(played_music_state) => QuEvent::<QuEventType>
+                {
+                    m_event_type: QuEventType::EReadMusicState,
+                    m_event_arg: Arc::new(played_music_state),
+                },
+            },
+            Err(error) => QuEvent::<QuEventType>
+            {
+                m_event_type: QuEventType::EReadMusicError,
+                m_event_arg: Arc::new(AudioInformationError
+                {
+                    m_str_path_to_music: music_player.get_state().map(|played_music_state| played_music_state.m_str_path_to_music).unwrap_or_default(),
+                    m_str_error: error.to_string(),
+                }),
+            },
         };
+        push_event_in_tmp_queue(event_to_send, block_event_queue.clone());
+    });
 
-        push_event_in_tmp_queue(event_to_send, tmp_event_queue.clone());
+    //
+    // The scan decodes every file, it runs on its own thread to not block the other events.
+    // The argument gives the files and directories to scan with the key "path_file".
+    let scan_event_queue = event_manager.lock().unwrap().get_temporary_queue().clone();
+    // Each scan has its own cancel flag: a new scan does not revive a cancelled scan still running, and a cancel stops the current scan only.
+    let current_scan_cancel: Arc<Mutex<Option<Arc<AtomicBool>>>> = Arc::new(Mutex::new(None));
+    let scan_cancel = current_scan_cancel.clone();
+    event_manager.lock().unwrap().register_listener(QuEventType::EAskScanLoudness, move |event| {
+        let paths: Vec<String> = event.m_event_arg.convert_to_key_map().into_iter()
+            .filter(|(key, _, _)| key == "path_file")
+            .map(|(_, _, path)| path)
+            .collect();
+        let tmp_event_queue = scan_event_queue.clone();
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        *scan_cancel.lock().unwrap() = Some(is_cancelled.clone());
+        std::thread::spawn(move || {
+            let reader_registry = audio_reader::reader_registry::AudioReaderRegistry::with_default_readers();
+            audio_reader::loudness_scanner::scan_library(&reader_registry, &paths, true, &is_cancelled, |scan_result| {
+                let event_to_send = match scan_result
+                {
+                    Ok(scan_result) => QuEvent::<QuEventType>
+                    {
+                        m_event_type: QuEventType::ELoudnessScanned,
+                        m_event_arg: Arc::new(scan_result),
+                    },
+                    Err(error) => QuEvent::<QuEventType>
+                    {
+                        m_event_type: QuEventType::ELoudnessScanError,
+                        m_event_arg: Arc::new(error),
+                    },
+                };
+                push_event_in_tmp_queue(event_to_send, tmp_event_queue.clone());
+            });
+        });
+    });
+    event_manager.lock().unwrap().register_listener(QuEventType::EAskCancelLoudnessScan, move |_event| {
+        if let Some(is_cancelled) = current_scan_cancel.lock().unwrap().take()
+        {
+            is_cancelled.store(true, Ordering::Relaxed);
+        }
     });
 }
 
@@ -96,11 +926,163 @@ pub fn register_event_listeners(event_manager: Arc<Mutex<EventManager::<QuEventT
 // @deprecated
 pub mod flac_reader;
 
+//
+// Declare the module flac_writer to edit the tags of the flac files
+pub mod flac_writer;
+
+//
+// Declare the module flac_encoder to create flac files from samples
+pub mod flac_encoder;
+
+//
+// Declare the module ogg_demuxer to read the audio stored inside ogg containers
+pub mod ogg_demuxer;
+
+//
+// Declare the module foreign_metadata to restore the chunks of the RIFF and AIFF files stored inside flac files
+pub mod foreign_metadata;
+
+//
+// Declare the module id3_tag to read the ID3v1 and ID3v2 tags stored inside the audio files
+pub mod id3_tag;
+
+//
+// Declare the module ape_tag to read and write the APE tags stored at the end of the audio files
+pub mod ape_tag;
+
+//
+// Declare the module vorbis_comment to read the Vorbis comments of the flac, Ogg Vorbis and Opus files
+pub mod vorbis_comment;
+
+//
+// Declare the module replay_gain to read the gains of the tags and apply them to the played samples
+pub mod replay_gain;
+
+//
+// Declare the module loudness_scanner to measure the loudness of the titles and write their gains
+pub mod loudness_scanner;
+
+//
+// Declare the module cue_sheet to split the files described by a cue sheet into tracks
+pub mod cue_sheet;
+
+//
+// Declare the module music_player to open the played music and give its samples with their gain
+pub mod music_player;
+
+//
+// Declare the module wav_reader to read the WAV files
+pub mod wav_reader;
+
+//
+// Declare the module pcm_decoder to decode the uncompressed samples of the WAV and AIFF files
+pub mod pcm_decoder;
+
+//
+// Declare the module aiff_reader to read the AIFF and AIFF-C files
+pub mod aiff_reader;
+
+//
+// Declare the module symphonia_reader to read the formats without a native reader, like MP3, AAC, Vorbis and ALAC
+pub mod symphonia_reader;
+
+//
+// Declare the module reader_registry to choose the reader of each file from its content
+pub mod reader_registry;
+
 /// trait to create reader of music file
 /// Quadrium can read different audio files such as WAV, Flac... This interface defines the way to create the reader of these files.
 /// This is a private interface. The user will only access to the MusicReaderManager.
 pub trait AudioReader
 {
     /// \brief Read information about the audio files
-    fn read_information(&self, str_path_to_music : String) -> AudioInformation;
-}
\ No newline at end of file
+    /// Returns an error if the file cannot be read or is not valid
+    fn read_information(&self, str_path_to_music : String) -> Result<AudioInformation, AudioReaderError>;
+}
+
+#[cfg(test)]
+mod test_track_metadata
+{
+    use std::time::Duration;
+    use crate::audio_reader::{parse_number_and_total, AudioInformation, TagDate, TrackMetadata};
+
+    #[test]
+    fn parse_dates()
+    {
+        assert_eq!(TagDate::parse("2004"), Some(TagDate { m_year: 2004, m_month: None, m_day: None }));
+        assert_eq!(TagDate::parse("2004-05"), Some(TagDate { m_year: 2004, m_month: Some(5), m_day: None }));
+        assert_eq!(TagDate::parse("2004-05-06T10:20:30"), Some(TagDate { m_year: 2004, m_month: Some(5), m_day: Some(6) }));
+        assert_eq!(TagDate::parse("2004/05/06"), Some(TagDate { m_year: 2004, m_month: Some(5), m_day: Some(6) }));
+
+        //
+        // The invalid parts only make the date less precise
+        assert_eq!(TagDate::parse("2004-13-06"), Some(TagDate { m_year: 2004, m_month: None, m_day: None }));
+        assert_eq!(TagDate::parse("06.05.2004"), None);
+        assert_eq!(TagDate::parse(""), None);
+
+        assert_eq!(TagDate::parse("2004-5-6").unwrap().to_string(), "2004-05-06");
+        assert_eq!(TagDate::parse("2004").unwrap().to_string(), "2004");
+    }
+
+    #[test]
+    fn parse_numbers_and_totals()
+    {
+        assert_eq!(parse_number_and_total("3"), (Some(3), None));
+        assert_eq!(parse_number_and_total("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_and_total(" 03 / 12 "), (Some(3), Some(12)));
+        assert_eq!(parse_number_and_total("/12"), (None, Some(12)));
+        assert_eq!(parse_number_and_total("A1"), (None, None));
+    }
+
+    #[test]
+    fn merge_metadata()
+    {
+        let mut preferred_metadata = TrackMetadata::new();
+        preferred_metadata.m_title = "Preferred title".to_string();
+        preferred_metadata.m_artists = vec!["Preferred artist".to_string()];
+        preferred_metadata.set_track_text("3");
+        preferred_metadata.add_extra("label", "Preferred label".to_string());
+
+        let mut other_metadata = TrackMetadata::new();
+        other_metadata.m_title = "Other title".to_string();
+        other_metadata.m_album = "Other album".to_string();
+        other_metadata.m_genres = vec!["Jazz".to_string(), "Blues".to_string()];
+        other_metadata.set_track_text("4/12");
+        other_metadata.add_extra("LABEL", "Other label".to_string());
+        other_metadata.add_extra("MOOD", "Calm".to_string());
+
+        let mut merged_metadata = preferred_metadata.clone();
+        merged_metadata.fill_missing(other_metadata.clone());
+        assert_eq!(merged_metadata.m_title, "Preferred title");
+        assert_eq!(merged_metadata.m_artists, vec!["Preferred artist"]);
+        assert_eq!(merged_metadata.m_album, "Other album");
+        assert_eq!(merged_metadata.m_genres, vec!["Jazz", "Blues"]);
+        assert_eq!((merged_metadata.m_track_number, merged_metadata.m_track_total), (Some(3), Some(12)));
+        assert_eq!(merged_metadata.m_extra["LABEL"], vec!["Preferred label"]);
+        assert_eq!(merged_metadata.m_extra["MOOD"], vec!["Calm"]);
+
+        //
+        // A tag applied after the other replaces the fields it gives
+        let mut applied_metadata = preferred_metadata;
+        applied_metadata.apply(other_metadata);
+        assert_eq!(applied_metadata.m_title, "Other title");
+        assert_eq!(applied_metadata.m_artists, vec!["Preferred artist"]);
+        assert_eq!(applied_metadata.m_track_number, Some(4));
+        assert_eq!(applied_metadata.m_extra["LABEL"], vec!["Other label"]);
+    }
+
+    #[test]
+    fn compute_duration_and_bitrate()
+    {
+        let mut audio_information = AudioInformation::new();
+        assert_eq!(audio_information.get_duration(), None);
+        audio_information.set_average_bitrate(1000);
+        assert_eq!(audio_information.m_bitrate, 0);
+
+        audio_information.m_rate = 44100;
+        audio_information.m_total_samples = 66150;
+        assert_eq!(audio_information.get_duration(), Some(Duration::from_millis(1500)));
+        audio_information.set_average_bitrate(300000);
+        assert_eq!(audio_information.m_bitrate, 1600000);
+    }
+}
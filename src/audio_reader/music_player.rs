@@ -0,0 +1,250 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::audio_reader::{AudioReader, AudioReaderError, DecodedBlock};
+use crate::audio_reader::cue_sheet::{read_cue_tracks, split_embedded_tracks, CueTrackDecoder};
+use crate::audio_reader::reader_registry::{AudioReaderRegistry, FileDecoder};
+use crate::audio_reader::replay_gain::{ReplayGainInfo, ReplayGainProcessor, ReplayGainSettings};
+use crate::Controller;
+use crate::Controller::EventManager::QuInformationData;
+
+/// State of the music opened to be played, sent to the view
+pub struct PlayedMusicState
+{
+    pub m_str_path_to_music: String,
+
+    /// Factor by which the played samples are multiplied by the ReplayGain settings, 1.0 without gain
+    pub m_replay_gain_scale: f64,
+
+    /// True when all the samples of the music have been read
+    pub m_is_finished: bool,
+}
+
+impl QuInformationData for PlayedMusicState
+{
+    fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
+    {
+        let mut key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = Vec::new();
+        key_map.push(("path_file".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_str_path_to_music.clone()));
+        key_map.push(("replay_gain_scale".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, format!("{:.6}", self.m_replay_gain_scale)));
+        key_map.push(("is_finished".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, self.m_is_finished.to_string()));
+
+        return key_map;
+    }
+}
+
+impl QuInformationData for DecodedBlock
+{
+    ///
+    /// The samples are sent interleaved, separated by commas
+    fn convert_to_key_map(&self) -> Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)>
+    {
+        let samples = self.m_samples.iter().map(|sample| sample.to_string()).collect::<Vec<String>>().join(",");
+        let key_map: Vec<(String, Controller::EventManager::QuAvailableTypeInEvent, String)> = vec![
+            ("first_sample".to_string(), Controller::EventManager::QuAvailableTypeInEvent::Uint64, self.m_first_sample.to_string()),
+            ("track_rate".to_string(), Controller::EventManager::QuAvailableTypeInEvent::Uint32, self.m_rate.to_string()),
+            ("channel_count".to_string(), Controller::EventManager::QuAvailableTypeInEvent::Uint8, self.m_channel_count.to_string()),
+            ("bits_per_sample".to_string(), Controller::EventManager::QuAvailableTypeInEvent::Uint8, self.m_bits_per_sample.to_string()),
+            ("samples".to_string(), Controller::EventManager::QuAvailableTypeInEvent::String, samples),
+        ];
+
+        return key_map;
+    }
+}
+
+/// Decoder of the music being played, a whole file or a track of a cue sheet
+enum PlayedDecoder
+{
+    File(FileDecoder),
+    CueTrack(CueTrackDecoder),
+}
+
+impl PlayedDecoder
+{
+    fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        match self
+        {
+            PlayedDecoder::File(decoder) => decoder.set_replay_gain(replay_gain),
+            PlayedDecoder::CueTrack(decoder) => decoder.set_replay_gain(replay_gain),
+        }
+    }
+
+    fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        return match self
+        {
+            PlayedDecoder::File(decoder) => decoder.read_next_block(),
+            PlayedDecoder::CueTrack(decoder) => decoder.read_next_block(),
+        };
+    }
+}
+
+/// Open the decoder of a music to play it
+///
+/// # Params
+/// registry: the registry reading and decoding the files
+/// str_path_to_music: the path of the music, or of a cue sheet
+/// track_number: the number of the played track of the cue sheet or of the cuesheet embedded in the file, unused for the other files
+///
+/// # Return
+/// The decoder, and the gains of the played music
+fn open_played_decoder(registry: &AudioReaderRegistry, str_path_to_music: &str, track_number: Option<u8>) -> Result<(PlayedDecoder, ReplayGainInfo), AudioReaderError>
+{
+    if str_path_to_music.to_ascii_lowercase().ends_with(".cue")
+    {
+        let cue_track = read_cue_tracks(registry, str_path_to_music)?.into_iter()
+            .find(|cue_track| Some(cue_track.m_track.m_track_number) == track_number)
+            .ok_or(AudioReaderError::InvalidValue("cue sheet track number"))?;
+        let decoder = CueTrackDecoder::open(registry, &cue_track)?;
+        return Ok((PlayedDecoder::CueTrack(decoder), cue_track.m_information.m_metadata.m_replay_gain));
+    }
+
+    let audio_information = registry.read_information(str_path_to_music.to_string())?;
+    if let Some(track_number) = track_number.filter(|_| !audio_information.m_tracks.is_empty())
+    {
+        let embedded_track = split_embedded_tracks(str_path_to_music, &audio_information).into_iter()
+            .find(|embedded_track| embedded_track.m_track.m_track_number == track_number)
+            .ok_or(AudioReaderError::InvalidValue("cuesheet track number"))?;
+        let mut decoder = registry.open_decoder(str_path_to_music.to_string())?;
+        decoder.select_track(&embedded_track.m_track)?;
+        return Ok((PlayedDecoder::File(decoder), embedded_track.m_information.m_metadata.m_replay_gain));
+    }
+
+    let decoder = registry.open_decoder(str_path_to_music.to_string())?;
+    return Ok((PlayedDecoder::File(decoder), audio_information.m_metadata.m_replay_gain));
+}
+
+/// Music opened to be played, with what is needed to choose its gain again when the settings change
+struct PlayedMusic
+{
+    m_str_path_to_music: String,
+    m_decoder: PlayedDecoder,
+    m_replay_gain_info: ReplayGainInfo,
+    m_is_album_playback: bool,
+    m_is_finished: bool,
+}
+
+/// Player of the music: opens the asked music and gives its samples with the gain chosen by the ReplayGain settings.
+/// The audio output pulls the samples block after block.
+///
+/// # How to use it
+/// ```
+/// let mut music_player = MusicPlayer::new();
+/// music_player.set_replay_gain_settings(ReplayGainSettings { m_mode: ReplayGainMode::Album, m_preamp: 3.0, m_prevent_clipping: true });
+/// music_player.play("path/to/file.flac", None, true)?;
+/// while let Some(decoded_block) = music_player.read_next_block()?
+/// {
+///     // Send the samples to the audio output
+/// }
+/// ```
+pub struct MusicPlayer
+{
+    m_registry: AudioReaderRegistry,
+    m_replay_gain_settings: ReplayGainSettings,
+
+    /// The music being played, None before the first music or when it cannot be opened
+    m_played_music: Option<PlayedMusic>,
+}
+
+impl MusicPlayer
+{
+    /// Create a player without music, with the default ReplayGain settings
+    pub fn new() -> MusicPlayer
+    {
+        return MusicPlayer
+        {
+            m_registry: AudioReaderRegistry::with_default_readers(),
+            m_replay_gain_settings: ReplayGainSettings::new(),
+            m_played_music: None,
+        };
+    }
+
+    pub fn get_replay_gain_settings(&self) -> ReplayGainSettings
+    {
+        return self.m_replay_gain_settings;
+    }
+
+    /// Change the ReplayGain settings, the samples of the music being played read from now on use the new gain
+    pub fn set_replay_gain_settings(&mut self, settings: ReplayGainSettings)
+    {
+        self.m_replay_gain_settings = settings;
+        if let Some(played_music) = &mut self.m_played_music
+        {
+            played_music.m_decoder.set_replay_gain(Some(ReplayGainProcessor::new(&settings, &played_music.m_replay_gain_info, played_music.m_is_album_playback)));
+        }
+    }
+
+    /// Open a music to play it in place of the current one
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the music, or of a cue sheet
+    /// track_number: the number of the played track of the cue sheet or of the cuesheet embedded in the file, unused for the other files
+    /// is_album_playback: true when the album of the music is played in order
+    ///
+    /// # Return
+    /// The state of the opened music
+    pub fn play(&mut self, str_path_to_music: &str, track_number: Option<u8>, is_album_playback: bool) -> Result<PlayedMusicState, AudioReaderError>
+    {
+        self.m_played_music = None;
+        let (mut decoder, replay_gain_info) = open_played_decoder(&self.m_registry, str_path_to_music, track_number)?;
+        decoder.set_replay_gain(Some(ReplayGainProcessor::new(&self.m_replay_gain_settings, &replay_gain_info, is_album_playback)));
+        self.m_played_music = Some(PlayedMusic
+        {
+            m_str_path_to_music: str_path_to_music.to_string(),
+            m_decoder: decoder,
+            m_replay_gain_info: replay_gain_info,
+            m_is_album_playback: is_album_playback,
+            m_is_finished: false,
+        });
+
+        return Ok(self.get_state().unwrap());
+    }
+
+    /// Get the state of the music being played, None if there is no music
+    pub fn get_state(&self) -> Option<PlayedMusicState>
+    {
+        return self.m_played_music.as_ref().map(|played_music| PlayedMusicState
+        {
+            m_str_path_to_music: played_music.m_str_path_to_music.clone(),
+            m_replay_gain_scale: self.m_replay_gain_settings.compute_scale(&played_music.m_replay_gain_info, played_music.m_is_album_playback),
+            m_is_finished: played_music.m_is_finished,
+        });
+    }
+
+    /// Decode the next samples of the music being played, with its gain
+    ///
+    /// # Return
+    /// The samples, or None when there is no music or when all its samples have been read
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        let played_music = match &mut self.m_played_music
+        {
+            None => return Ok(None),
+            Some(played_music) => played_music,
+        };
+        if played_music.m_is_finished
+        {
+            return Ok(None);
+        }
+
+        let decoded_block = played_music.m_decoder.read_next_block()?;
+        played_music.m_is_finished = decoded_block.is_none();
+        return Ok(decoded_block);
+    }
+}
@@ -0,0 +1,416 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+use crate::audio_reader::AudioReaderError;
+use crate::utils::crc::update_crc32;
+
+/// Size of the fixed part of a page header, before the segment table
+const PAGE_HEADER_SIZE: usize = 27;
+
+/// Flags of the header type of a page
+const PAGE_FLAG_CONTINUED: u8 = 0x01;
+const PAGE_FLAG_FIRST: u8 = 0x02;
+const PAGE_FLAG_LAST: u8 = 0x04;
+
+/// A page of an ogg stream, described by RFC 3533
+pub struct OggPage
+{
+    m_header_type: u8,
+
+    /// Position of the end of the last packet finished in the page, its meaning depends on the codec
+    m_granule_position: u64,
+    m_serial_number: u32,
+    m_sequence_number: u32,
+
+    /// Size of each segment, a packet ends with a segment smaller than 255 bytes
+    m_segment_table: Vec<u8>,
+    m_data: Vec<u8>,
+
+    /// Position of the page from the beginning of the source
+    m_offset: u64,
+}
+
+impl OggPage
+{
+    /// Return true if the page starts with the end of a packet of the previous page
+    pub fn is_continued(&self) -> bool
+    {
+        return self.m_header_type & PAGE_FLAG_CONTINUED != 0;
+    }
+
+    /// Return true if the page is the first one of its logical stream
+    pub fn is_first(&self) -> bool
+    {
+        return self.m_header_type & PAGE_FLAG_FIRST != 0;
+    }
+
+    /// Return true if the page is the last one of its logical stream
+    pub fn is_last(&self) -> bool
+    {
+        return self.m_header_type & PAGE_FLAG_LAST != 0;
+    }
+}
+
+/// A packet of the selected logical stream
+pub struct OggPacket
+{
+    pub m_data: Vec<u8>,
+
+    /// Position from the beginning of the source of the page where the packet starts
+    pub m_page_offset: u64,
+
+    /// True if the packet is the first one of its page, the decoding can then restart at this page
+    pub m_is_page_start: bool,
+
+    /// Granule position of the page where the packet ends
+    pub m_granule_position: u64,
+}
+
+/// Demuxer of the pages of an ogg stream into the packets of one of its logical streams.
+/// The pages with a wrong CRC are dropped, and the packets which lost a part are dropped too.
+pub struct OggDemuxer<R: Read>
+{
+    m_source: R,
+
+    /// Number of bytes read from the source since the creation of the demuxer, without the unread bytes
+    m_source_offset: u64,
+
+    /// Bytes read from the source after the capture pattern of a corrupted page, where the next page is searched
+    m_unread_bytes: VecDeque<u8>,
+
+    /// The logical stream read, None until it is found
+    m_serial_number: Option<u32>,
+
+    /// Sequence number of the next page of the logical stream, None if it is unknown
+    m_next_sequence_number: Option<u32>,
+
+    /// Data of the packet continued on the next page, with the position of its first page
+    m_partial_packet: Vec<u8>,
+    m_partial_packet_offset: u64,
+    m_is_partial_packet_page_start: bool,
+
+    /// True when the start of the current packet has been lost, the rest of the packet must be dropped
+    m_is_skipping_packet: bool,
+
+    m_packets: VecDeque<OggPacket>,
+    m_lost_page_count: u32,
+    m_is_end_of_stream: bool,
+}
+
+impl<R: Read> OggDemuxer<R>
+{
+    /// Create a demuxer reading the pages from the current position of a source
+    pub fn new(source: R) -> OggDemuxer<R>
+    {
+        return OggDemuxer
+        {
+            m_source: source,
+            m_source_offset: 0,
+            m_unread_bytes: VecDeque::new(),
+            m_serial_number: None,
+            m_next_sequence_number: None,
+            m_partial_packet: Vec::new(),
+            m_partial_packet_offset: 0,
+            m_is_partial_packet_page_start: false,
+            m_is_skipping_packet: false,
+            m_packets: VecDeque::new(),
+            m_lost_page_count: 0,
+            m_is_end_of_stream: false,
+        };
+    }
+
+    /// Get the number of bytes read from the source since the creation of the demuxer
+    pub fn get_source_offset(&self) -> u64
+    {
+        return self.m_source_offset;
+    }
+
+    /// Get the number of pages dropped because they were corrupted or missing
+    pub fn get_lost_page_count(&self) -> u32
+    {
+        return self.m_lost_page_count;
+    }
+
+    /// Fill a buffer from the unread bytes, then from the source
+    ///
+    /// # Return
+    /// The number of bytes read, smaller than the buffer if the end of the source is reached
+    fn read_available(&mut self, buffer: &mut [u8]) -> Result<usize, AudioReaderError>
+    {
+        let unread_count = self.m_unread_bytes.len().min(buffer.len());
+        for (buffer_byte, unread_byte) in buffer.iter_mut().zip(self.m_unread_bytes.drain(..unread_count))
+        {
+            *buffer_byte = unread_byte;
+        }
+        self.m_source_offset += unread_count as u64;
+
+        let mut position = unread_count;
+        while position < buffer.len()
+        {
+            match self.m_source.read(&mut buffer[position..])
+            {
+                Ok(0) => break,
+                Ok(read_count) =>
+                {
+                    position += read_count;
+                    self.m_source_offset += read_count as u64;
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(AudioReaderError::Io(error)),
+            }
+        }
+        return Ok(position);
+    }
+
+    /// Fill a buffer from the unread bytes, then from the source
+    ///
+    /// # Return
+    /// False if the end of the source is reached before the buffer is full
+    fn read_exact_or_end(&mut self, buffer: &mut [u8]) -> Result<bool, AudioReaderError>
+    {
+        return Ok(self.read_available(buffer)? == buffer.len());
+    }
+
+    /// Read the next valid page of the source, whatever its logical stream
+    ///
+    /// # Return
+    /// The page, or None at the end of the source
+    pub fn read_page(&mut self) -> Result<Option<OggPage>, AudioReaderError>
+    {
+        let mut header = [0u8; PAGE_HEADER_SIZE];
+        if !self.read_exact_or_end(&mut header)?
+        {
+            return Ok(None);
+        }
+
+        loop
+        {
+            //
+            // Search the capture pattern byte by byte when the stream is not synchronized
+            let page_offset = self.m_source_offset - PAGE_HEADER_SIZE as u64;
+            if &header[0..4] != b"OggS" || header[4] != 0
+            {
+                header.copy_within(1.., 0);
+                if !self.read_exact_or_end(&mut header[PAGE_HEADER_SIZE - 1..])?
+                {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            //
+            // A page cut by the end of the source is lost like a corrupted one
+            let mut segment_table = vec![0u8; header[26] as usize];
+            let segment_table_length = self.read_available(&mut segment_table)?;
+            let mut data: Vec<u8> = Vec::new();
+            let mut is_complete = segment_table_length == segment_table.len();
+            if is_complete
+            {
+                data.resize(segment_table.iter().map(|segment_size| *segment_size as usize).sum(), 0);
+                let data_length = self.read_available(&mut data)?;
+                is_complete = data_length == data.len();
+                data.truncate(data_length);
+            }
+            else
+            {
+                segment_table.truncate(segment_table_length);
+            }
+
+            //
+            // The CRC is computed with its own field at 0
+            let stored_crc = u32::from_le_bytes(header[22..26].try_into().unwrap());
+            let mut crc_header = header;
+            crc_header[22..26].copy_from_slice(&[0; 4]);
+            if !is_complete || update_crc32(update_crc32(update_crc32(0, &crc_header), &segment_table), &data) != stored_crc
+            {
+                //
+                // The segment table of a corrupted page cannot give the position of the next page,
+                // which is searched from the byte following the capture pattern of the corrupted page
+                self.m_lost_page_count += 1;
+                let mut unread_bytes: VecDeque<u8> = header[1..].iter().chain(segment_table.iter()).chain(data.iter()).copied().collect();
+                self.m_source_offset -= unread_bytes.len() as u64;
+                unread_bytes.append(&mut self.m_unread_bytes);
+                self.m_unread_bytes = unread_bytes;
+                if !self.read_exact_or_end(&mut header)?
+                {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            return Ok(Some(OggPage
+            {
+                m_header_type: header[5],
+                m_granule_position: u64::from_le_bytes(header[6..14].try_into().unwrap()),
+                m_serial_number: u32::from_le_bytes(header[14..18].try_into().unwrap()),
+                m_sequence_number: u32::from_le_bytes(header[18..22].try_into().unwrap()),
+                m_segment_table: segment_table,
+                m_data: data,
+                m_offset: page_offset,
+            }));
+        }
+    }
+
+    /// Find the first logical stream whose first packet starts with a signature, and read this packet.
+    /// The following packets read come from this logical stream.
+    ///
+    /// # Params
+    /// signature: the first bytes of the packet identifying the codec
+    pub fn find_stream(&mut self, signature: &[u8]) -> Result<OggPacket, AudioReaderError>
+    {
+        //
+        // The first pages of all the logical streams are at the beginning of the source
+        loop
+        {
+            let page = match self.read_page()?
+            {
+                None => return Err(AudioReaderError::UnsupportedFeature("ogg stream without a supported codec")),
+                Some(page) => page,
+            };
+            if !page.is_first()
+            {
+                return Err(AudioReaderError::UnsupportedFeature("ogg stream without a supported codec"));
+            }
+            if page.m_data.starts_with(signature)
+            {
+                self.m_serial_number = Some(page.m_serial_number);
+                self.add_page(page);
+                return match self.m_packets.pop_front()
+                {
+                    None => Err(AudioReaderError::InvalidValue("first ogg packet")),
+                    Some(packet) => Ok(packet),
+                };
+            }
+        }
+    }
+
+    /// Cut a page of the selected logical stream into packets
+    fn add_page(&mut self, page: OggPage)
+    {
+        //
+        // A missing page breaks the packet continued from the previous page
+        if self.m_next_sequence_number != Some(page.m_sequence_number)
+        {
+            if self.m_next_sequence_number.is_some()
+            {
+                self.m_lost_page_count += 1;
+            }
+            self.m_partial_packet.clear();
+            self.m_is_skipping_packet = page.is_continued();
+        }
+        else if !page.is_continued()
+        {
+            self.m_partial_packet.clear();
+            self.m_is_skipping_packet = false;
+        }
+        self.m_next_sequence_number = Some(page.m_sequence_number.wrapping_add(1));
+
+        let mut position = 0;
+        let mut is_page_start = !page.is_continued();
+        for segment_size in page.m_segment_table.iter()
+        {
+            let segment_size = *segment_size as usize;
+            if self.m_partial_packet.is_empty() && !self.m_is_skipping_packet
+            {
+                self.m_partial_packet_offset = page.m_offset;
+                self.m_is_partial_packet_page_start = is_page_start;
+            }
+            if !self.m_is_skipping_packet
+            {
+                self.m_partial_packet.extend_from_slice(&page.m_data[position..position + segment_size]);
+            }
+            position += segment_size;
+
+            if segment_size < 255
+            {
+                if !self.m_is_skipping_packet
+                {
+                    self.m_packets.push_back(OggPacket
+                    {
+                        m_data: std::mem::take(&mut self.m_partial_packet),
+                        m_page_offset: self.m_partial_packet_offset,
+                        m_is_page_start: self.m_is_partial_packet_page_start,
+                        m_granule_position: page.m_granule_position,
+                    });
+                }
+                self.m_is_skipping_packet = false;
+                is_page_start = false;
+            }
+        }
+        if page.is_last()
+        {
+            self.m_is_end_of_stream = true;
+        }
+    }
+
+    /// Read the next packet of the selected logical stream
+    ///
+    /// # Return
+    /// The packet, or None at the end of the logical stream
+    pub fn read_packet(&mut self) -> Result<Option<OggPacket>, AudioReaderError>
+    {
+        loop
+        {
+            if let Some(packet) = self.m_packets.pop_front()
+            {
+                return Ok(Some(packet));
+            }
+            if self.m_is_end_of_stream
+            {
+                return Ok(None);
+            }
+
+            match self.read_page()?
+            {
+                None =>
+                {
+                    self.m_is_end_of_stream = true;
+                }
+                Some(page) =>
+                {
+                    if Some(page.m_serial_number) == self.m_serial_number
+                    {
+                        self.add_page(page);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> OggDemuxer<R>
+{
+    /// Move the demuxer to a page of the source and forget the packets read before
+    ///
+    /// # Params
+    /// source_offset: the position of the page, from the position of the source when the demuxer was created
+    pub fn seek_to_page(&mut self, source_offset: u64) -> Result<(), AudioReaderError>
+    {
+        self.m_source.seek(SeekFrom::Current(source_offset as i64 - (self.m_source_offset + self.m_unread_bytes.len() as u64) as i64))?;
+        self.m_source_offset = source_offset;
+        self.m_unread_bytes.clear();
+        self.m_partial_packet.clear();
+        self.m_packets.clear();
+        self.m_is_end_of_stream = false;
+        self.m_next_sequence_number = None;
+        return Ok(());
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use crate::audio_reader::{AudioReaderError, DecodedBlock};
+use crate::audio_reader::replay_gain::ReplayGainProcessor;
+
+/// Number of samples per channel of the blocks returned by the decoder
+const DECODED_BLOCK_SIZE: u64 = 4096;
+
+/// Type of the uncompressed samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PcmSampleFormat
+{
+    /// Two's complement integers
+    SignedInteger,
+
+    /// Integers with an offset of half their range, like the 8 bits samples of WAV files
+    UnsignedInteger,
+
+    /// IEEE 754 floating point values between -1 and 1, on 32 or 64 bits
+    Float,
+}
+
+/// Layout of the uncompressed samples of a WAV or AIFF file
+#[derive(Clone, Copy)]
+pub(crate) struct PcmFormat
+{
+    pub(crate) m_sample_format: PcmSampleFormat,
+    pub(crate) m_is_big_endian: bool,
+    pub(crate) m_channel_count: u8,
+    pub(crate) m_rate: u32,
+
+    /// Number of bits used to store each sample, a multiple of 8
+    pub(crate) m_container_bits: u16,
+
+    /// Number of significant bits of each sample, on the left of its container
+    pub(crate) m_valid_bits: u16,
+
+    /// Speaker position of each channel, with the bits of the dwChannelMask field of WAV files
+    pub(crate) m_channel_mask: u32,
+}
+
+impl PcmFormat
+{
+    /// Get the size of the samples of all the channels at a time
+    pub(crate) fn get_block_align(&self) -> u64
+    {
+        return self.m_channel_count as u64 * self.m_container_bits as u64 / 8;
+    }
+
+    /// Get the name of the codec of the samples shown to the user
+    pub(crate) fn get_codec_name(&self) -> &'static str
+    {
+        return match self.m_sample_format
+        {
+            PcmSampleFormat::Float => "PCM float",
+            PcmSampleFormat::SignedInteger | PcmSampleFormat::UnsignedInteger => "PCM",
+        };
+    }
+
+    /// Get the number of bits per second of the samples of all the channels
+    pub(crate) fn get_bitrate(&self) -> u32
+    {
+        return (self.m_rate as u64 * self.get_block_align() * 8).min(u32::MAX as u64) as u32;
+    }
+}
+
+/// Decoder of the uncompressed samples of a WAV or AIFF file, used to play it
+pub struct PcmDecoder<R = File>
+{
+    m_source: R,
+    m_format: PcmFormat,
+
+    /// Position of the first sample in the source
+    m_data_offset: u64,
+
+    /// Number of bytes of samples, a multiple of the block align
+    m_data_length: u64,
+
+    /// Index of the next sample to decode
+    m_next_sample: u64,
+
+    /// Gain of the title applied to the decoded samples, None to keep the samples of the file
+    m_replay_gain: Option<ReplayGainProcessor>,
+}
+
+/// Get the speaker positions of the channels when the file does not give them, the same as flac uses
+pub(crate) fn get_default_channel_mask(channel_count: u8) -> u32
+{
+    return match channel_count
+    {
+        1 => 0x4,
+        2 => 0x3,
+        3 => 0x7,
+        4 => 0x33,
+        5 => 0x37,
+        6 => 0x3F,
+        7 => 0x70F,
+        8 => 0x63F,
+        _ => 0,
+    };
+}
+
+/// Convert a float sample between -1 and 1 to an integer on 32 bits, clipping the values outside of this range
+fn convert_float_sample(value: f64) -> i32
+{
+    if value.is_nan()
+    {
+        return 0;
+    }
+    return (value * 2147483648.0).round().max(i32::MIN as f64).min(i32::MAX as f64) as i32;
+}
+
+impl<R: Read + Seek> PcmDecoder<R>
+{
+    /// Create a decoder of samples found by the reader of a format
+    ///
+    /// # Params
+    /// source: the source of the file
+    /// format: the layout of the samples
+    /// data_offset: the position of the first sample in the source
+    /// data_length: the number of bytes of samples, cut to a whole number of samples
+    pub(crate) fn new(mut source: R, format: PcmFormat, data_offset: u64, data_length: u64) -> Result<PcmDecoder<R>, AudioReaderError>
+    {
+        source.seek(SeekFrom::Start(data_offset))?;
+        return Ok(PcmDecoder
+        {
+            m_source: source,
+            m_format: format,
+            m_data_offset: data_offset,
+            m_data_length: data_length - data_length % format.get_block_align(),
+            m_next_sample: 0,
+            m_replay_gain: None,
+        });
+    }
+
+    /// Convert a stored sample to an integer
+    fn decode_sample(&self, bytes: &[u8]) -> i32
+    {
+        let format = &self.m_format;
+        if format.m_sample_format == PcmSampleFormat::Float
+        {
+            return match (format.m_container_bits, format.m_is_big_endian)
+            {
+                (32, true) => convert_float_sample(f32::from_be_bytes(bytes.try_into().unwrap()) as f64),
+                (32, false) => convert_float_sample(f32::from_le_bytes(bytes.try_into().unwrap()) as f64),
+                (_, true) => convert_float_sample(f64::from_be_bytes(bytes.try_into().unwrap())),
+                (_, false) => convert_float_sample(f64::from_le_bytes(bytes.try_into().unwrap())),
+            };
+        }
+
+        let mut value: u32 = 0;
+        if format.m_is_big_endian
+        {
+            for byte in bytes.iter()
+            {
+                value = (value << 8) | *byte as u32;
+            }
+        }
+        else
+        {
+            for byte in bytes.iter().rev()
+            {
+                value = (value << 8) | *byte as u32;
+            }
+        }
+        if format.m_sample_format == PcmSampleFormat::UnsignedInteger
+        {
+            value ^= 1 << (format.m_container_bits - 1);
+        }
+
+        //
+        // Put the value on the left of an i32 to get the sign, then remove the unused bits
+        let value = (value << (32 - format.m_container_bits)) as i32;
+        return value >> (32 - format.m_valid_bits);
+    }
+
+    /// Decode the next samples
+    ///
+    /// # Return
+    /// The samples, or None when all the samples have been decoded
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        let sample_count = (self.get_total_samples() - self.m_next_sample).min(DECODED_BLOCK_SIZE);
+        if sample_count == 0
+        {
+            return Ok(None);
+        }
+
+        let mut data = vec![0u8; (sample_count * self.m_format.get_block_align()) as usize];
+        self.m_source.read_exact(&mut data)?;
+        let byte_per_sample = self.m_format.m_container_bits as usize / 8;
+        let samples: Vec<i32> = data.chunks_exact(byte_per_sample).map(|bytes| self.decode_sample(bytes)).collect();
+
+        let mut decoded_block = DecodedBlock
+        {
+            m_first_sample: self.m_next_sample,
+            m_rate: self.m_format.m_rate,
+            m_channel_count: self.m_format.m_channel_count,
+            m_bits_per_sample: self.get_bits_per_sample(),
+            m_samples: samples,
+        };
+        if let Some(replay_gain) = &self.m_replay_gain
+        {
+            replay_gain.process(&mut decoded_block);
+        }
+        self.m_next_sample += sample_count;
+        return Ok(Some(decoded_block));
+    }
+
+    /// Apply the gain of the title to the samples decoded from now on
+    ///
+    /// # Params
+    /// replay_gain: the gain chosen by the settings of the player, None to decode the samples unchanged
+    pub fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        self.m_replay_gain = replay_gain;
+    }
+
+    /// Decode all the remaining samples
+    ///
+    /// # Return
+    /// The interleaved samples of all the channels
+    pub fn read_all_samples(&mut self) -> Result<Vec<i32>, AudioReaderError>
+    {
+        let mut samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = self.read_next_block()?
+        {
+            samples.extend_from_slice(&decoded_block.m_samples);
+        }
+
+        return Ok(samples);
+    }
+
+    /// Move the decoder to a sample of the stream, the next read block starts with this sample
+    ///
+    /// # Params
+    /// sample_index: index of the sample in the stream
+    pub fn seek(&mut self, sample_index: u64) -> Result<(), AudioReaderError>
+    {
+        if sample_index >= self.get_total_samples()
+        {
+            return Err(AudioReaderError::InvalidSeekPosition);
+        }
+        self.m_source.seek(SeekFrom::Start(self.m_data_offset + sample_index * self.m_format.get_block_align()))?;
+        self.m_next_sample = sample_index;
+        return Ok(());
+    }
+
+    pub fn get_rate(&self) -> u32
+    {
+        return self.m_format.m_rate;
+    }
+
+    pub fn get_channel_count(&self) -> u8
+    {
+        return self.m_format.m_channel_count;
+    }
+
+    /// Get the number of significant bits of the decoded samples, 32 for the float samples
+    pub fn get_bits_per_sample(&self) -> u8
+    {
+        return match self.m_format.m_sample_format
+        {
+            PcmSampleFormat::Float => 32,
+            _ => self.m_format.m_valid_bits as u8,
+        };
+    }
+
+    /// Get the type of the samples stored in the file
+    pub fn get_sample_format(&self) -> PcmSampleFormat
+    {
+        return self.m_format.m_sample_format;
+    }
+
+    /// Get the speaker position of each channel, with the bits of the dwChannelMask field of WAVE_FORMAT_EXTENSIBLE
+    pub fn get_channel_mask(&self) -> u32
+    {
+        return self.m_format.m_channel_mask;
+    }
+
+    /// Get the number of samples per channel of the stream
+    pub fn get_total_samples(&self) -> u64
+    {
+        return self.m_data_length / self.m_format.get_block_align();
+    }
+}
@@ -0,0 +1,439 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use crate::audio_reader::{AudioInformation, AudioReader, AudioReaderError, DecodedBlock, TagFormat, VirtualTrack, DEFAULT_TAG_PRIORITY};
+use crate::audio_reader::aiff_reader::{open_aiff_decoder, AiffReader};
+use crate::audio_reader::flac_reader::{FlacDecoder, FlacFileSource, FlacReader};
+use crate::audio_reader::pcm_decoder::PcmDecoder;
+use crate::audio_reader::replay_gain::ReplayGainProcessor;
+use crate::audio_reader::symphonia_reader::{SymphoniaDecoder, SymphoniaReader};
+use crate::audio_reader::wav_reader::{open_wav_decoder, WavReader};
+
+/// Number of bytes read at the beginning of a file to compare them with the magic bytes
+const PROBE_HEADER_SIZE: usize = 64;
+
+/// Way to recognize the files of a reader, with the confidence given to the reader when it matches
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatProbe
+{
+    /// Bytes found at a position from the beginning of the file.
+    /// The confidences of all the magic bytes found are added.
+    MagicBytes
+    {
+        m_offset: usize,
+        m_bytes: &'static [u8],
+        m_confidence: u32,
+    },
+
+    /// Bytes of a container shared by several formats, like "RIFF" for WAV, AVI and WebP.
+    /// Their confidence is only added when magic bytes of the format are also found.
+    ContainerBytes
+    {
+        m_offset: usize,
+        m_bytes: &'static [u8],
+        m_confidence: u32,
+    },
+
+    /// Extension of the file, compared without case.
+    /// Only used between readers which recognize the content of the file with the same confidence.
+    Extension
+    {
+        m_extension: &'static str,
+        m_confidence: u32,
+    },
+}
+
+/// Format of the files of a registered reader, which also chooses their decoder
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioFormat
+{
+    Flac,
+    Wav,
+    Aiff,
+
+    /// The formats decoded by Symphonia: MP3, AAC, Ogg Vorbis, MP4 and Matroska
+    Symphonia,
+}
+
+impl AudioFormat
+{
+    /// Get the name of the format shown to the user, like "FLAC"
+    pub fn get_name(&self) -> &'static str
+    {
+        return match self
+        {
+            AudioFormat::Flac => "FLAC",
+            AudioFormat::Wav => "WAV",
+            AudioFormat::Aiff => "AIFF",
+            AudioFormat::Symphonia => "Symphonia",
+        };
+    }
+}
+
+/// Reader registered with the probes of its files
+pub struct RegisteredReader
+{
+    pub m_format: AudioFormat,
+    pub m_reader: Box<dyn AudioReader + Send + Sync>,
+    pub m_probes: Vec<FormatProbe>,
+}
+
+/// Decoder of an audio file of any format, chosen by the registry
+pub enum FileDecoder
+{
+    Flac(Box<FlacDecoder<FlacFileSource>>),
+    Pcm(PcmDecoder<File>),
+    Symphonia(SymphoniaDecoder),
+}
+
+/// Choose the reader of a file among the registered readers, from the content of the file and then from its extension
+pub struct AudioReaderRegistry
+{
+    m_readers: Vec<RegisteredReader>,
+}
+
+impl AudioReaderRegistry
+{
+    /// Create a registry without any reader
+    pub fn new() -> AudioReaderRegistry
+    {
+        return AudioReaderRegistry
+        {
+            m_readers: Vec::new(),
+        };
+    }
+
+    /// Create a registry with all the readers of Quadrium.
+    /// The native readers are preferred to Symphonia, which only gets the formats they do not read.
+    pub fn with_default_readers() -> AudioReaderRegistry
+    {
+        return AudioReaderRegistry::with_tag_priority(&DEFAULT_TAG_PRIORITY);
+    }
+
+    /// Create a registry with all the readers of Quadrium, preferring the tags of a file in an order
+    ///
+    /// # Params
+    /// tag_priority: the formats of the tags, from the most preferred one, used when a file has several tags
+    pub fn with_tag_priority(tag_priority: &[TagFormat]) -> AudioReaderRegistry
+    {
+        let mut registry = AudioReaderRegistry::new();
+        registry.register(AudioFormat::Flac, Box::new(FlacReader {}), vec![
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: b"fLaC", m_confidence: 100 },
+            //
+            // Ogg FLAC: the first packet of the first page starts with the FLAC mapping header.
+            // A bare Ogg page is not enough, the other codecs of Ogg are read by Symphonia or not at all.
+            FormatProbe::MagicBytes { m_offset: 28, m_bytes: b"\x7FFLAC", m_confidence: 100 },
+            FormatProbe::Extension { m_extension: "flac", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "oga", m_confidence: 5 },
+        ]);
+        registry.register(AudioFormat::Wav, Box::new(WavReader {}), vec![
+            FormatProbe::ContainerBytes { m_offset: 0, m_bytes: b"RIFF", m_confidence: 40 },
+            FormatProbe::ContainerBytes { m_offset: 0, m_bytes: b"RF64", m_confidence: 40 },
+            FormatProbe::ContainerBytes { m_offset: 0, m_bytes: b"BW64", m_confidence: 40 },
+            FormatProbe::MagicBytes { m_offset: 8, m_bytes: b"WAVE", m_confidence: 60 },
+            FormatProbe::Extension { m_extension: "wav", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "wave", m_confidence: 10 },
+        ]);
+        registry.register(AudioFormat::Aiff, Box::new(AiffReader {}), vec![
+            FormatProbe::ContainerBytes { m_offset: 0, m_bytes: b"FORM", m_confidence: 40 },
+            FormatProbe::MagicBytes { m_offset: 8, m_bytes: b"AIFF", m_confidence: 60 },
+            FormatProbe::MagicBytes { m_offset: 8, m_bytes: b"AIFC", m_confidence: 60 },
+            FormatProbe::Extension { m_extension: "aif", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "aiff", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "aifc", m_confidence: 10 },
+        ]);
+        registry.register(AudioFormat::Symphonia, Box::new(SymphoniaReader { m_tag_priority: tag_priority.to_vec() }), vec![
+            //
+            // MP3 and AAC files starting with an ID3v2 tag, or directly with the sync word of their first frame.
+            // The flac files starting with an ID3v2 tag are also read by Symphonia, which skips the tag: FlacReader rejects them.
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: b"ID3", m_confidence: 50 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xFB], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xFA], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xF3], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xF2], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xE3], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xF1], m_confidence: 30 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0xFF, 0xF9], m_confidence: 30 },
+            //
+            // Ogg Vorbis, the Symphonia release used by Quadrium has no Opus decoder
+            FormatProbe::MagicBytes { m_offset: 28, m_bytes: b"\x01vorbis", m_confidence: 100 },
+            FormatProbe::MagicBytes { m_offset: 4, m_bytes: b"ftyp", m_confidence: 80 },
+            FormatProbe::MagicBytes { m_offset: 0, m_bytes: &[0x1A, 0x45, 0xDF, 0xA3], m_confidence: 80 },
+            FormatProbe::Extension { m_extension: "mp3", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "mp2", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "aac", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "m4a", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "mp4", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "ogg", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "oga", m_confidence: 5 },
+            FormatProbe::Extension { m_extension: "mka", m_confidence: 10 },
+            FormatProbe::Extension { m_extension: "webm", m_confidence: 10 },
+        ]);
+
+        return registry;
+    }
+
+    /// Add a reader to the registry
+    ///
+    /// # Params
+    /// format: the format read by the reader, which chooses the decoder of its files
+    /// reader: the reader of the format
+    /// probes: the magic bytes and the extensions of the files of the format
+    pub fn register(&mut self, format: AudioFormat, reader: Box<dyn AudioReader + Send + Sync>, probes: Vec<FormatProbe>)
+    {
+        self.m_readers.push(RegisteredReader
+        {
+            m_format: format,
+            m_reader: reader,
+            m_probes: probes,
+        });
+    }
+
+    /// Find the reader of a file from the first bytes of the file and from its extension.
+    /// The content decides first, the extension only separates the readers with the same confidence.
+    ///
+    /// # Params
+    /// header: the first bytes of the file, or the whole file if it is shorter
+    /// extension: the extension of the file, empty if it has none
+    ///
+    /// # Return
+    /// The best reader, or None if no reader recognizes the content of the file
+    pub fn find_reader_from_header(&self, header: &[u8], extension: &str) -> Option<&RegisteredReader>
+    {
+        let mut best_reader: Option<(&RegisteredReader, (u32, u32))> = None;
+        for registered_reader in self.m_readers.iter()
+        {
+            let mut content_confidence = 0;
+            let mut container_confidence = 0;
+            let mut extension_confidence = 0;
+            for probe in registered_reader.m_probes.iter()
+            {
+                match probe
+                {
+                    FormatProbe::MagicBytes { m_offset, m_bytes, m_confidence } =>
+                    {
+                        if header.get(*m_offset..*m_offset + m_bytes.len()) == Some(*m_bytes)
+                        {
+                            content_confidence += m_confidence;
+                        }
+                    }
+                    FormatProbe::ContainerBytes { m_offset, m_bytes, m_confidence } =>
+                    {
+                        if header.get(*m_offset..*m_offset + m_bytes.len()) == Some(*m_bytes)
+                        {
+                            container_confidence += m_confidence;
+                        }
+                    }
+                    FormatProbe::Extension { m_extension, m_confidence } =>
+                    {
+                        if m_extension.eq_ignore_ascii_case(extension)
+                        {
+                            extension_confidence = extension_confidence.max(*m_confidence);
+                        }
+                    }
+                }
+            }
+
+            //
+            // The container and the extension alone do not choose a reader
+            // The first registered reader wins between readers with the same confidences
+            if content_confidence > 0
+            {
+                content_confidence += container_confidence;
+            }
+            let confidence = (content_confidence, extension_confidence);
+            if content_confidence > 0 && best_reader.map_or(true, |(_, best_confidence)| confidence > best_confidence)
+            {
+                best_reader = Some((registered_reader, confidence));
+            }
+        }
+
+        return best_reader.map(|(registered_reader, _)| registered_reader);
+    }
+
+    /// Find the reader of a file
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the file
+    ///
+    /// # Return
+    /// The best reader, or the UnsupportedFormat error if no reader recognizes the file
+    pub fn find_reader(&self, str_path_to_music: &str) -> Result<&RegisteredReader, AudioReaderError>
+    {
+        let mut header: Vec<u8> = Vec::new();
+        File::open(str_path_to_music)?.take(PROBE_HEADER_SIZE as u64).read_to_end(&mut header)?;
+        let extension = Path::new(str_path_to_music).extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        return self.find_reader_from_header(&header, extension).ok_or(AudioReaderError::UnsupportedFormat);
+    }
+
+    /// Open the decoder of a file, native for the formats read by Quadrium and Symphonia for the other ones
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the file
+    pub fn open_decoder(&self, str_path_to_music: String) -> Result<FileDecoder, AudioReaderError>
+    {
+        return Ok(match self.find_reader(&str_path_to_music)?.m_format
+        {
+            AudioFormat::Flac => FileDecoder::Flac(Box::new(FlacDecoder::open(str_path_to_music)?)),
+            AudioFormat::Wav => FileDecoder::Pcm(open_wav_decoder(str_path_to_music)?),
+            AudioFormat::Aiff => FileDecoder::Pcm(open_aiff_decoder(str_path_to_music)?),
+            AudioFormat::Symphonia => FileDecoder::Symphonia(SymphoniaDecoder::open(str_path_to_music)?),
+        });
+    }
+}
+
+impl FileDecoder
+{
+    /// Move the decoder to a sample of the file, the next read block starts with this sample
+    ///
+    /// # Params
+    /// sample_index: index of the sample in the file
+    pub fn seek(&mut self, sample_index: u64) -> Result<(), AudioReaderError>
+    {
+        return match self
+        {
+            FileDecoder::Flac(decoder) => decoder.seek(sample_index),
+            FileDecoder::Pcm(decoder) => decoder.seek(sample_index),
+            FileDecoder::Symphonia(decoder) => decoder.seek(sample_index),
+        };
+    }
+
+    /// Restrict the decoding to a track stored inside the file, only the flac files store tracks in their cuesheet
+    ///
+    /// # Params
+    /// track: a track of the file
+    pub fn select_track(&mut self, track: &VirtualTrack) -> Result<(), AudioReaderError>
+    {
+        return match self
+        {
+            FileDecoder::Flac(decoder) => decoder.select_track(track),
+            _ => Err(AudioReaderError::UnsupportedFeature("tracks stored inside this format")),
+        };
+    }
+
+    /// Apply the gain of the title to the samples decoded from now on
+    ///
+    /// # Params
+    /// replay_gain: the gain chosen by the settings of the player, None to decode the samples unchanged
+    pub fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        match self
+        {
+            FileDecoder::Flac(decoder) => decoder.set_replay_gain(replay_gain),
+            FileDecoder::Pcm(decoder) => decoder.set_replay_gain(replay_gain),
+            FileDecoder::Symphonia(decoder) => decoder.set_replay_gain(replay_gain),
+        }
+    }
+
+    /// Decode the next samples of the file
+    ///
+    /// # Return
+    /// The samples, or None when all the samples of the file have been decoded
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        return match self
+        {
+            FileDecoder::Flac(decoder) => decoder.read_next_block(),
+            FileDecoder::Pcm(decoder) => decoder.read_next_block(),
+            FileDecoder::Symphonia(decoder) => decoder.read_next_block(),
+        };
+    }
+}
+
+impl AudioReader for AudioReaderRegistry
+{
+    fn read_information(&self, str_path_to_music: String) -> Result<AudioInformation, AudioReaderError>
+    {
+        return self.find_reader(&str_path_to_music)?.m_reader.read_information(str_path_to_music);
+    }
+}
+
+#[cfg(test)]
+mod test_reader_registry
+{
+    use crate::audio_reader::{AudioReader, AudioReaderError};
+    use crate::audio_reader::reader_registry::{AudioFormat, AudioReaderRegistry};
+
+    #[test]
+    fn find_reader_from_content()
+    {
+        let registry = AudioReaderRegistry::with_default_readers();
+        let find_format = |header: &[u8], extension: &str| registry.find_reader_from_header(header, extension).map(|registered_reader| registered_reader.m_format);
+
+        let mut ogg_flac_header = b"OggS".to_vec();
+        ogg_flac_header.resize(28, 0);
+        ogg_flac_header.extend_from_slice(b"\x7FFLAC");
+        let mut ogg_vorbis_header = b"OggS".to_vec();
+        ogg_vorbis_header.resize(28, 0);
+        ogg_vorbis_header.extend_from_slice(b"\x01vorbis");
+        assert_eq!(find_format(b"fLaC\0\0\0\x22", "flac"), Some(AudioFormat::Flac));
+        assert_eq!(find_format(&ogg_flac_header, "ogg"), Some(AudioFormat::Flac));
+        assert_eq!(find_format(&ogg_vorbis_header, "oga"), Some(AudioFormat::Symphonia));
+        assert_eq!(find_format(b"RF64\xFF\xFF\xFF\xFFWAVE", ""), Some(AudioFormat::Wav));
+        assert_eq!(find_format(b"FORM\0\0\0\0AIFC", "aiff"), Some(AudioFormat::Aiff));
+        assert_eq!(find_format(b"\0\0\0\x20ftypM4A ", ""), Some(AudioFormat::Symphonia));
+
+        //
+        // An ID3v2 tag before a flac stream is not allowed by the format, Symphonia reads these files
+        assert_eq!(find_format(b"ID3\x04\0\0\0\0\0\0fLaC", "flac"), Some(AudioFormat::Symphonia));
+
+        //
+        // The other formats of the RIFF and IFF containers are not read
+        assert_eq!(find_format(b"RIFF\0\0\0\0AVI LIST", "wav"), None);
+        assert_eq!(find_format(b"RIFF\0\0\0\0WEBPVP8 ", ""), None);
+        assert_eq!(find_format(b"FORM\0\0\0\08SVXVHDR", "aiff"), None);
+
+        //
+        // The content decides before the extension, which never chooses a reader alone
+        assert_eq!(find_format(b"RIFF\0\0\0\0WAVE", "flac"), Some(AudioFormat::Wav));
+        assert_eq!(find_format(b"\0\0\0\0", "MP3"), None);
+        assert_eq!(find_format(b"\0\0\0\0", "txt"), None);
+
+        //
+        // No reader decodes Opus
+        let mut ogg_opus_header = b"OggS".to_vec();
+        ogg_opus_header.resize(28, 0);
+        ogg_opus_header.extend_from_slice(b"OpusHead");
+        assert_eq!(find_format(&ogg_opus_header, "opus"), None);
+        assert_eq!(find_format(&ogg_opus_header, "ogg"), None);
+    }
+
+    #[test]
+    fn reject_unsupported_files()
+    {
+        let path = std::env::temp_dir().join("quadrium_unsupported.txt");
+        let str_path = path.to_string_lossy().to_string();
+        std::fs::write(&path, b"Not an audio file").unwrap();
+
+        let registry = AudioReaderRegistry::with_default_readers();
+        assert!(matches!(registry.find_reader(&str_path), Err(AudioReaderError::UnsupportedFormat)));
+        assert!(matches!(registry.read_information(str_path.clone()), Err(AudioReaderError::UnsupportedFormat)));
+
+        //
+        // An unknown content is rejected even with the extension of a supported format
+        let mp3_path = std::env::temp_dir().join("quadrium_unsupported.mp3");
+        std::fs::copy(&path, &mp3_path).unwrap();
+        assert!(matches!(registry.read_information(mp3_path.to_string_lossy().to_string()), Err(AudioReaderError::UnsupportedFormat)));
+
+        std::fs::remove_file(&mp3_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,559 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on https://wiki.hydrogenaud.io/index.php?title=ReplayGain_2.0_specification and RFC 7845 (R128 gains of Opus)
+
+use std::collections::BTreeMap;
+use crate::audio_reader::DecodedBlock;
+use crate::Controller::EventManager::QuAvailableTypeInEvent;
+
+/// Difference in dB between the reference level of ReplayGain 2.0 (-18 LUFS) and the one of the R128 gains of Opus (-23 LUFS)
+const R128_TO_REPLAY_GAIN_OFFSET: f32 = 5.0;
+
+/// Gain to apply to a title or an album to play it at the reference level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGain
+{
+    /// Gain in dB
+    pub m_gain: f32,
+
+    /// Highest absolute sample value, 1.0 being the full scale. None if the tag does not give it.
+    pub m_peak: Option<f32>,
+}
+
+/// Gains of a title stored by its tags, each None if unknown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGainInfo
+{
+    pub m_track: Option<ReplayGain>,
+    pub m_album: Option<ReplayGain>,
+}
+
+/// Gain chosen by the player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayGainMode
+{
+    /// The titles are played without any gain
+    Off,
+
+    /// Each title is played at the same loudness
+    Track,
+
+    /// The titles of an album keep the differences of loudness between them
+    Album,
+
+    /// The album gain when the titles of an album are played one after the other, the track gain otherwise
+    Auto,
+}
+
+/// Way the player applies the gains
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGainSettings
+{
+    pub m_mode: ReplayGainMode,
+
+    /// Gain in dB added to the gain of the tags
+    pub m_preamp: f32,
+
+    /// Lower the gain when the stored peak would be amplified above the full scale
+    pub m_prevent_clipping: bool,
+}
+
+/// Parse a gain written like "-6.48 dB"
+fn parse_gain(text: &str) -> Option<f32>
+{
+    let text = text.trim();
+    let value = ["dB", "db", "DB"].iter().find_map(|unit| text.strip_suffix(unit)).unwrap_or(text);
+    return value.trim().parse::<f32>().ok().filter(|gain| gain.is_finite());
+}
+
+/// Parse a R128 gain of Opus, a number of 1/256 dB relative to -23 LUFS, and convert it to a gain relative to the ReplayGain reference
+fn parse_r128_gain(text: &str) -> Option<f32>
+{
+    return text.trim().parse::<i16>().ok().map(|gain| gain as f32 / 256.0 + R128_TO_REPLAY_GAIN_OFFSET);
+}
+
+/// Remove the values of a field from the extra fields, and get the first one
+fn take_value(extra: &mut BTreeMap<String, Vec<String>>, key: &str) -> Option<String>
+{
+    return extra.remove(key).and_then(|values| values.into_iter().next());
+}
+
+impl ReplayGainInfo
+{
+    /// Create information without any gain
+    pub fn new() -> ReplayGainInfo
+    {
+        return ReplayGainInfo
+        {
+            m_track: None,
+            m_album: None,
+        };
+    }
+
+    /// Move the gains out of the extra fields of a tag: the REPLAYGAIN_* fields of ReplayGain and the R128_* fields of Opus.
+    /// The ReplayGain fields are preferred when a tag has both.
+    ///
+    /// # Params
+    /// extra: the extra fields of the tag by key in upper case, the fields of the gains are removed from it
+    pub fn take_from_extra(extra: &mut BTreeMap<String, Vec<String>>) -> ReplayGainInfo
+    {
+        let mut take_gain = |gain_key: &str, peak_key: &str, r128_key: &str|
+        {
+            let peak = take_value(extra, peak_key).and_then(|peak| peak.trim().parse::<f32>().ok()).filter(|peak| peak.is_finite() && *peak >= 0.0);
+            let replay_gain = take_value(extra, gain_key).and_then(|gain| parse_gain(&gain));
+            let r128_gain = take_value(extra, r128_key).and_then(|gain| parse_r128_gain(&gain));
+            return replay_gain.or(r128_gain).map(|gain| ReplayGain { m_gain: gain, m_peak: peak });
+        };
+        let track = take_gain("REPLAYGAIN_TRACK_GAIN", "REPLAYGAIN_TRACK_PEAK", "R128_TRACK_GAIN");
+        let album = take_gain("REPLAYGAIN_ALBUM_GAIN", "REPLAYGAIN_ALBUM_PEAK", "R128_ALBUM_GAIN");
+        return ReplayGainInfo
+        {
+            m_track: track,
+            m_album: album,
+        };
+    }
+
+    /// Fill the gains which are still unknown with the ones of a less preferred tag
+    pub fn fill_missing(&mut self, other: ReplayGainInfo)
+    {
+        self.m_track = self.m_track.or(other.m_track);
+        self.m_album = self.m_album.or(other.m_album);
+    }
+}
+
+impl ReplayGainMode
+{
+    /// Parse the name of a mode, "off", "track", "album" or "auto" in any case
+    pub fn parse(text: &str) -> Option<ReplayGainMode>
+    {
+        return match text.trim().to_ascii_lowercase().as_str()
+        {
+            "off" => Some(ReplayGainMode::Off),
+            "track" => Some(ReplayGainMode::Track),
+            "album" => Some(ReplayGainMode::Album),
+            "auto" => Some(ReplayGainMode::Auto),
+            _ => None,
+        };
+    }
+}
+
+impl ReplayGainSettings
+{
+    /// Create the default settings: the track gain without preamp, lowered to not clip
+    pub fn new() -> ReplayGainSettings
+    {
+        return ReplayGainSettings
+        {
+            m_mode: ReplayGainMode::Track,
+            m_preamp: 0.0,
+            m_prevent_clipping: true,
+        };
+    }
+
+    /// Change the settings given by the argument of an event
+    ///
+    /// # Params
+    /// key_map: the argument, with the keys "replay_gain_mode", "replay_gain_preamp" (a gain like "+3.00 dB") and "prevent_clipping" ("true" or "false")
+    ///
+    /// # Return
+    /// The new settings, the missing or invalid values are kept
+    pub fn update_from_key_map(&self, key_map: &[(String, QuAvailableTypeInEvent, String)]) -> ReplayGainSettings
+    {
+        let mut settings = *self;
+        for (key, _, value) in key_map.iter()
+        {
+            match key.as_str()
+            {
+                "replay_gain_mode" => settings.m_mode = ReplayGainMode::parse(value).unwrap_or(settings.m_mode),
+                "replay_gain_preamp" => settings.m_preamp = parse_gain(value).unwrap_or(settings.m_preamp),
+                "prevent_clipping" => settings.m_prevent_clipping = value.trim().parse::<bool>().unwrap_or(settings.m_prevent_clipping),
+                _ => (),
+            }
+        }
+        return settings;
+    }
+
+    /// Choose the gain of a title for the mode, using the other gain when the one of the mode is unknown
+    ///
+    /// # Params
+    /// replay_gain_info: the gains stored by the tags of the title
+    /// is_album_playback: true when the title is played among the other titles of its album, used by the auto mode
+    ///
+    /// # Return
+    /// The gain, None if the mode is off or if the title has no gain
+    pub fn choose_gain(&self, replay_gain_info: &ReplayGainInfo, is_album_playback: bool) -> Option<ReplayGain>
+    {
+        let prefers_album = match self.m_mode
+        {
+            ReplayGainMode::Off => return None,
+            ReplayGainMode::Track => false,
+            ReplayGainMode::Album => true,
+            ReplayGainMode::Auto => is_album_playback,
+        };
+        return match prefers_album
+        {
+            true => replay_gain_info.m_album.or(replay_gain_info.m_track),
+            false => replay_gain_info.m_track.or(replay_gain_info.m_album),
+        };
+    }
+
+    /// Compute the factor by which the samples of a title are multiplied
+    ///
+    /// # Params
+    /// replay_gain_info: the gains stored by the tags of the title
+    /// is_album_playback: true when the title is played among the other titles of its album, used by the auto mode
+    ///
+    /// # Return
+    /// The factor, 1.0 when the mode is off or the title has no gain
+    pub fn compute_scale(&self, replay_gain_info: &ReplayGainInfo, is_album_playback: bool) -> f64
+    {
+        let replay_gain = match self.choose_gain(replay_gain_info, is_album_playback)
+        {
+            None => return 1.0,
+            Some(replay_gain) => replay_gain,
+        };
+        let mut scale = 10f64.powf((replay_gain.m_gain + self.m_preamp) as f64 / 20.0);
+
+        //
+        // The peak multiplied by the scale must stay at or below the full scale
+        if let Some(peak) = replay_gain.m_peak.filter(|peak| self.m_prevent_clipping && *peak > 0.0)
+        {
+            scale = scale.min(1.0 / peak as f64);
+        }
+        return scale;
+    }
+}
+
+/// Apply the gain of a title to its decoded samples before they are played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGainProcessor
+{
+    m_scale: f64,
+}
+
+impl ReplayGainProcessor
+{
+    /// Create the processor of a title
+    ///
+    /// # Params
+    /// settings: the way the player applies the gains
+    /// replay_gain_info: the gains stored by the tags of the title
+    /// is_album_playback: true when the title is played among the other titles of its album, used by the auto mode
+    pub fn new(settings: &ReplayGainSettings, replay_gain_info: &ReplayGainInfo, is_album_playback: bool) -> ReplayGainProcessor
+    {
+        return ReplayGainProcessor
+        {
+            m_scale: settings.compute_scale(replay_gain_info, is_album_playback),
+        };
+    }
+
+    /// Get the factor by which the samples are multiplied
+    pub fn get_scale(&self) -> f64
+    {
+        return self.m_scale;
+    }
+
+    /// Multiply the samples of a block by the gain.
+    /// The samples are limited to the range of their bits per sample, which only happens when clipping is not prevented.
+    pub fn process(&self, block: &mut DecodedBlock)
+    {
+        if self.m_scale == 1.0
+        {
+            return;
+        }
+        let bits_per_sample = block.m_bits_per_sample.clamp(1, 32) as u32;
+        let max_sample = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        let min_sample = -(1i64 << (bits_per_sample - 1)) as f64;
+        for sample in block.m_samples.iter_mut()
+        {
+            *sample = (*sample as f64 * self.m_scale).round().clamp(min_sample, max_sample) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_replay_gain
+{
+    use crate::audio_reader::{AudioReader, DecodedBlock};
+    use crate::audio_reader::ape_tag::ApeTag;
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::flac_reader::FlacReader;
+    use crate::audio_reader::reader_registry::{AudioReaderRegistry, FileDecoder};
+    use crate::audio_reader::replay_gain::{ReplayGain, ReplayGainInfo, ReplayGainMode, ReplayGainProcessor, ReplayGainSettings};
+    use crate::audio_reader::symphonia_reader::SymphoniaDecoder;
+    use crate::audio_reader::music_player::MusicPlayer;
+    use crate::audio_reader::vorbis_comment::VorbisComment;
+    use crate::Controller::EventManager::QuAvailableTypeInEvent;
+
+    /// Gains of a title quieter than its album, with peaks
+    fn create_replay_gain_info() -> ReplayGainInfo
+    {
+        return ReplayGainInfo
+        {
+            m_track: Some(ReplayGain { m_gain: 6.0, m_peak: Some(0.25) }),
+            m_album: Some(ReplayGain { m_gain: -3.0, m_peak: Some(0.9) }),
+        };
+    }
+
+    #[test]
+    fn read_vorbis_comment_gains()
+    {
+        let path = std::env::temp_dir().join("quadrium_replay_gain.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(44100, 1, 16);
+        settings.m_total_samples = 100;
+        settings.m_vorbis_comments = ["REPLAYGAIN_TRACK_GAIN=-6.48 dB", "REPLAYGAIN_TRACK_PEAK=0.988831", "replaygain_album_gain=-5.12 dB",
+            "REPLAYGAIN_ALBUM_PEAK=1.000000", "REPLAYGAIN_REFERENCE_LOUDNESS=89.0 dB"].iter()
+            .map(|comment| comment.to_string())
+            .collect();
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&[0; 100]).unwrap();
+        encoder.finish().unwrap();
+
+        let metadata = FlacReader {}.read_information(str_path.clone()).unwrap().m_metadata;
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: -6.48, m_peak: Some(0.988831) }));
+        assert_eq!(metadata.m_replay_gain.m_album, Some(ReplayGain { m_gain: -5.12, m_peak: Some(1.0) }));
+        assert_eq!(metadata.m_extra.keys().collect::<Vec<&String>>(), vec!["REPLAYGAIN_REFERENCE_LOUDNESS"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_opus_r128_gains()
+    {
+        //
+        // The R128 gains are relative to -23 LUFS, 5 dB below the reference of ReplayGain
+        let comments: Vec<String> = ["R128_TRACK_GAIN=-2560", "R128_ALBUM_GAIN=384", "R128_TRACK_GAIN_INVALID=1"].iter().map(|comment| comment.to_string()).collect();
+        let metadata = VorbisComment::from_comments("libopus".to_string(), &comments).get_metadata();
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: -5.0, m_peak: None }));
+        assert_eq!(metadata.m_replay_gain.m_album, Some(ReplayGain { m_gain: 6.5, m_peak: None }));
+
+        //
+        // A ReplayGain field is preferred to the R128 field of the same gain
+        let comments: Vec<String> = ["R128_TRACK_GAIN=-2560", "REPLAYGAIN_TRACK_GAIN=-1 dB"].iter().map(|comment| comment.to_string()).collect();
+        let metadata = VorbisComment::from_comments("libopus".to_string(), &comments).get_metadata();
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: -1.0, m_peak: None }));
+        assert!(metadata.m_extra.is_empty());
+    }
+
+    #[test]
+    fn read_ape_gains()
+    {
+        let mut ape_tag = ApeTag::new();
+        ape_tag.set_text("Replaygain_Track_Gain", "+2.30 dB").unwrap();
+        ape_tag.set_text("Replaygain_Track_Peak", "0.5").unwrap();
+        ape_tag.set_text("REPLAYGAIN_ALBUM_GAIN", "not a gain").unwrap();
+        let metadata = ape_tag.get_metadata();
+        assert_eq!(metadata.m_replay_gain.m_track, Some(ReplayGain { m_gain: 2.3, m_peak: Some(0.5) }));
+        assert_eq!(metadata.m_replay_gain.m_album, None);
+    }
+
+    #[test]
+    fn choose_gain_by_mode()
+    {
+        let replay_gain_info = create_replay_gain_info();
+        let mut settings = ReplayGainSettings::new();
+        settings.m_prevent_clipping = false;
+        let check_gain = |settings: &ReplayGainSettings, is_album_playback: bool, expected_gain: f64|
+        {
+            let scale = settings.compute_scale(&replay_gain_info, is_album_playback);
+            assert!((20.0 * scale.log10() - expected_gain).abs() < 1e-6, "{} instead of {}", 20.0 * scale.log10(), expected_gain);
+        };
+
+        check_gain(&settings, true, 6.0);
+        settings.m_mode = ReplayGainMode::Album;
+        check_gain(&settings, false, -3.0);
+        settings.m_mode = ReplayGainMode::Auto;
+        check_gain(&settings, true, -3.0);
+        check_gain(&settings, false, 6.0);
+        settings.m_preamp = 2.5;
+        check_gain(&settings, false, 8.5);
+        settings.m_mode = ReplayGainMode::Off;
+        check_gain(&settings, false, 0.0);
+
+        //
+        // The other gain is used when the gain of the mode is unknown, no gain is applied without any gain
+        settings.m_mode = ReplayGainMode::Album;
+        settings.m_preamp = 0.0;
+        let track_only = ReplayGainInfo { m_track: replay_gain_info.m_track, m_album: None };
+        assert_eq!(settings.choose_gain(&track_only, true), replay_gain_info.m_track);
+        assert_eq!(settings.compute_scale(&ReplayGainInfo::new(), true), 1.0);
+    }
+
+    #[test]
+    fn prevent_clipping_with_peak()
+    {
+        let replay_gain_info = create_replay_gain_info();
+        let mut settings = ReplayGainSettings::new();
+
+        //
+        // +6 dB would raise the peak of 0.25 to about 0.5, which does not clip
+        assert!((settings.compute_scale(&replay_gain_info, false) - 10f64.powf(0.3)).abs() < 1e-9);
+
+        //
+        // +18 dB would raise it to about 2, the scale is limited to 4 to reach the full scale
+        settings.m_preamp = 12.0;
+        assert_eq!(settings.compute_scale(&replay_gain_info, false), 4.0);
+        settings.m_prevent_clipping = false;
+        assert!(settings.compute_scale(&replay_gain_info, false) > 7.9);
+    }
+
+    #[test]
+    fn apply_gain_to_blocks()
+    {
+        let mut settings = ReplayGainSettings::new();
+        settings.m_prevent_clipping = false;
+        let replay_gain_info = ReplayGainInfo { m_track: Some(ReplayGain { m_gain: 20.0 * 2f32.log10(), m_peak: None }), m_album: None };
+        let processor = ReplayGainProcessor::new(&settings, &replay_gain_info, false);
+
+        //
+        // The samples doubled above the range of 16 bits are limited to it
+        let mut block = DecodedBlock { m_first_sample: 0, m_rate: 44100, m_channel_count: 2, m_bits_per_sample: 16, m_samples: vec![0, 100, -1000, 20000, -20000, 32767] };
+        processor.process(&mut block);
+        assert_eq!(block.m_samples, vec![0, 200, -2000, 32767, -32768, 32767]);
+
+        let quiet_processor = ReplayGainProcessor::new(&settings, &ReplayGainInfo { m_track: Some(ReplayGain { m_gain: -20.0, m_peak: None }), m_album: None }, false);
+        let mut block = DecodedBlock { m_first_sample: 0, m_rate: 44100, m_channel_count: 1, m_bits_per_sample: 24, m_samples: vec![1000, -1005, 3] };
+        quiet_processor.process(&mut block);
+        assert_eq!(block.m_samples, vec![100, -101, 0]);
+    }
+
+    #[test]
+    fn apply_gain_in_decoders()
+    {
+        let samples: Vec<i32> = (0..6000).map(|index| ((index as f64 * 0.03).sin() * 20000.0) as i32).collect();
+        let flac_path = std::env::temp_dir().join("quadrium_decoder_gain.flac");
+        let str_flac_path = flac_path.to_string_lossy().to_string();
+        let mut encoder_settings = FlacEncoderSettings::new(44100, 2, 16);
+        encoder_settings.m_vorbis_comments = vec![format!("REPLAYGAIN_TRACK_GAIN={:.6} dB", 20.0 * 0.5f64.log10())];
+        let mut encoder = FlacEncoder::create(str_flac_path.clone(), encoder_settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        //
+        // The same samples stored without compression
+        let wav_path = std::env::temp_dir().join("quadrium_decoder_gain.wav");
+        let str_wav_path = wav_path.to_string_lossy().to_string();
+        let mut wav_data = b"RIFF".to_vec();
+        wav_data.extend_from_slice(&(36 + samples.len() as u32 * 2).to_le_bytes());
+        wav_data.extend_from_slice(b"WAVEfmt \x10\0\0\0\x01\0\x02\0");
+        wav_data.extend_from_slice(&44100u32.to_le_bytes());
+        wav_data.extend_from_slice(&(44100u32 * 4).to_le_bytes());
+        wav_data.extend_from_slice(b"\x04\0\x10\0data");
+        wav_data.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+        wav_data.extend(samples.iter().flat_map(|&sample| (sample as i16).to_le_bytes().to_vec()));
+        std::fs::write(&wav_path, wav_data).unwrap();
+
+        let registry = AudioReaderRegistry::with_default_readers();
+        let replay_gain_info = registry.read_information(str_flac_path.clone()).unwrap().m_metadata.m_replay_gain;
+        let processor = ReplayGainProcessor::new(&ReplayGainSettings::new(), &replay_gain_info, false);
+        assert!((processor.get_scale() - 0.5).abs() < 1e-6);
+        let halved_samples: Vec<i32> = samples.iter().map(|&sample| (sample as f64 * processor.get_scale()).round() as i32).collect();
+        let read_all_samples = |decoder: &mut FileDecoder|
+        {
+            let mut decoded_samples: Vec<i32> = Vec::new();
+            while let Some(decoded_block) = decoder.read_next_block().unwrap()
+            {
+                decoded_samples.extend_from_slice(&decoded_block.m_samples);
+            }
+            return decoded_samples;
+        };
+        let mut decoders = [
+            registry.open_decoder(str_flac_path.clone()).unwrap(),
+            registry.open_decoder(str_wav_path.clone()).unwrap(),
+            FileDecoder::Symphonia(SymphoniaDecoder::open(str_flac_path.clone()).unwrap()),
+        ];
+        assert!(matches!(decoders[1], FileDecoder::Pcm(_)));
+        for decoder in decoders.iter_mut()
+        {
+            decoder.set_replay_gain(Some(processor));
+            assert_eq!(read_all_samples(decoder), halved_samples);
+
+            //
+            // Without gain, the samples of the file are decoded unchanged
+            decoder.set_replay_gain(None);
+            decoder.seek(0).unwrap();
+            assert_eq!(read_all_samples(decoder), samples);
+        }
+
+        std::fs::remove_file(&flac_path).unwrap();
+        std::fs::remove_file(&wav_path).unwrap();
+    }
+
+    #[test]
+    fn change_settings_from_event_argument()
+    {
+        let key_map = |values: &[(&str, &str)]| values.iter()
+            .map(|(key, value)| (key.to_string(), QuAvailableTypeInEvent::String, value.to_string()))
+            .collect::<Vec<(String, QuAvailableTypeInEvent, String)>>();
+        let settings = ReplayGainSettings::new().update_from_key_map(&key_map(&[("replay_gain_mode", "Album"), ("replay_gain_preamp", "+3.5 dB"), ("prevent_clipping", "false")]));
+        assert_eq!(settings, ReplayGainSettings { m_mode: ReplayGainMode::Album, m_preamp: 3.5, m_prevent_clipping: false });
+
+        //
+        // The missing and invalid values are kept
+        let kept_settings = settings.update_from_key_map(&key_map(&[("replay_gain_mode", "loud"), ("replay_gain_preamp", "high"), ("path_file", "a.flac")]));
+        assert_eq!(kept_settings, settings);
+        assert_eq!(settings.update_from_key_map(&key_map(&[("replay_gain_mode", "auto")])).m_mode, ReplayGainMode::Auto);
+    }
+
+    #[test]
+    fn change_gain_of_played_music()
+    {
+        let samples: Vec<i32> = (0..40000).map(|index| ((index as f64 * 0.03).sin() * 20000.0) as i32).collect();
+        let path = std::env::temp_dir().join("quadrium_player_gain.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut encoder_settings = FlacEncoderSettings::new(44100, 2, 16);
+        encoder_settings.m_vorbis_comments = vec![format!("REPLAYGAIN_TRACK_GAIN={:.6} dB", 20.0 * 0.5f64.log10())];
+        let mut encoder = FlacEncoder::create(str_path.clone(), encoder_settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        let mut music_player = MusicPlayer::new();
+        assert!(music_player.read_next_block().unwrap().is_none());
+        let played_music_state = music_player.play(&str_path, None, false).unwrap();
+        assert!((played_music_state.m_replay_gain_scale - 0.5).abs() < 1e-6);
+        let first_block = music_player.read_next_block().unwrap().unwrap();
+        let block_length = first_block.m_samples.len();
+        let expected: Vec<i32> = samples[..block_length].iter().map(|&sample| (sample as f64 * played_music_state.m_replay_gain_scale).round() as i32).collect();
+        assert_eq!(first_block.m_samples, expected);
+
+        //
+        // The new settings apply to the next blocks of the music being played
+        let mut settings = music_player.get_replay_gain_settings();
+        settings.m_mode = ReplayGainMode::Off;
+        music_player.set_replay_gain_settings(settings);
+        assert_eq!(music_player.get_state().unwrap().m_replay_gain_scale, 1.0);
+        let mut decoded_samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = music_player.read_next_block().unwrap()
+        {
+            decoded_samples.extend(decoded_block.m_samples);
+        }
+        assert_eq!(decoded_samples, samples[block_length..]);
+        assert!(music_player.get_state().unwrap().m_is_finished);
+        assert!(music_player.read_next_block().unwrap().is_none());
+
+        //
+        // A music which cannot be opened stops the previous one
+        assert!(music_player.play("quadrium_missing_file.flac", None, false).is_err());
+        assert!(music_player.get_state().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,735 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Reader of the formats without a native reader, like MP3, AAC, Vorbis and ALAC, through the Symphonia library
+
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecParameters, CodecType, Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::codecs::{CODEC_TYPE_ADPCM_G722, CODEC_TYPE_ADPCM_G726, CODEC_TYPE_ADPCM_G726LE, CODEC_TYPE_ADPCM_IMA_QT, CODEC_TYPE_ADPCM_IMA_WAV, CODEC_TYPE_ADPCM_MS};
+use symphonia::core::codecs::{CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MONKEYS_AUDIO, CODEC_TYPE_PCM_ALAW, CODEC_TYPE_PCM_MULAW, CODEC_TYPE_TTA, CODEC_TYPE_WAVPACK};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{ColorMode, Metadata, MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::TimeBase;
+use crate::audio_reader::{AudioInformation, AudioPicture, AudioReader, AudioReaderError, DecodedBlock, PictureType, TagDate, TagFormat, TrackMetadata};
+use crate::audio_reader::ape_tag::ApeTag;
+use crate::audio_reader::replay_gain::{ReplayGainInfo, ReplayGainProcessor};
+use crate::audio_reader::id3_tag::Id3FileTags;
+
+/// Compressed codecs which keep all the samples of the stream
+const LOSSLESS_CODECS: [CodecType; 5] = [CODEC_TYPE_FLAC, CODEC_TYPE_ALAC, CODEC_TYPE_WAVPACK, CODEC_TYPE_MONKEYS_AUDIO, CODEC_TYPE_TTA];
+
+/// PCM codecs which give the resolution of their samples but lose a part of it
+const LOSSY_PCM_CODECS: [CodecType; 8] = [
+    CODEC_TYPE_PCM_ALAW, CODEC_TYPE_PCM_MULAW, CODEC_TYPE_ADPCM_G722, CODEC_TYPE_ADPCM_G726, CODEC_TYPE_ADPCM_G726LE,
+    CODEC_TYPE_ADPCM_MS, CODEC_TYPE_ADPCM_IMA_WAV, CODEC_TYPE_ADPCM_IMA_QT,
+];
+
+/// Namespace of the free-form atoms of the MP4 files written by iTunes and most taggers, Symphonia prefixes their name with it
+const ITUNES_NAMESPACE_PREFIX: &str = "com.apple.iTunes:";
+
+pub struct SymphoniaReader
+{
+    /// Order in which the tags of a file are preferred, the tags missing from the list are ignored
+    pub m_tag_priority: Vec<TagFormat>,
+}
+
+/// Decoder of the default audio track of a file read by Symphonia, used to play it
+pub struct SymphoniaDecoder
+{
+    m_format_reader: Box<dyn FormatReader>,
+    m_decoder: Box<dyn Decoder>,
+    m_track_id: u32,
+
+    /// Unit of the timestamps of the packets, None if they count samples
+    m_time_base: Option<TimeBase>,
+    m_rate: u32,
+    m_channel_count: u8,
+
+    /// Significant bits of the samples of a lossless codec, 32 for the lossy codecs
+    m_bits_per_sample: u8,
+    m_total_samples: u64,
+
+    /// Buffer reused to convert the decoded packets to interleaved samples
+    m_sample_buffer: Option<SampleBuffer<i32>>,
+
+    /// Sample asked by the last seek, the samples before it in the next packets are dropped
+    m_seek_target: Option<u64>,
+
+    /// Number of corrupted packets skipped since the file was opened
+    m_decode_error_count: u32,
+
+    /// Gain of the title applied to the decoded samples, None to keep the samples of the file
+    m_replay_gain: Option<ReplayGainProcessor>,
+}
+
+/// Convert the errors of Symphonia to the errors of the readers
+fn convert_symphonia_error(error: Error) -> AudioReaderError
+{
+    return match error
+    {
+        Error::IoError(io_error) => AudioReaderError::Io(io_error),
+        Error::DecodeError(field) => AudioReaderError::InvalidValue(field),
+        Error::SeekError(_) => AudioReaderError::InvalidSeekPosition,
+        Error::Unsupported(feature) => AudioReaderError::UnsupportedFeature(feature),
+        Error::LimitError(limit) => AudioReaderError::UnsupportedFeature(limit),
+        Error::ResetRequired => AudioReaderError::UnsupportedFeature("stream whose tracks change"),
+    };
+}
+
+/// Find the format of a file from its content and its extension, and read its header
+fn probe_file(str_path_to_music: &str) -> Result<ProbeResult, AudioReaderError>
+{
+    let file = File::open(str_path_to_music)?;
+    let mut hint = Hint::new();
+    if let Some(extension) = Path::new(str_path_to_music).extension().and_then(|extension| extension.to_str())
+    {
+        hint.with_extension(extension);
+    }
+
+    //
+    // The gapless option gives the encoder delay and padding to remove from the decoded packets
+    let format_options = FormatOptions { enable_gapless: true, ..Default::default() };
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+    return symphonia::default::get_probe().format(&hint, source, &format_options, &MetadataOptions::default())
+        .map_err(|error| match error
+        {
+            Error::Unsupported(_) => AudioReaderError::InvalidMagicNumber,
+            error => convert_symphonia_error(error),
+        });
+}
+
+/// Check whether a codec keeps all the samples of the stream.
+/// The lossy codecs do not give the resolution of their samples, except the PCM codecs which compress them.
+fn is_lossless_codec(codec_parameters: &CodecParameters) -> bool
+{
+    if LOSSLESS_CODECS.contains(&codec_parameters.codec)
+    {
+        return true;
+    }
+    return codec_parameters.bits_per_sample.is_some() && !LOSSY_PCM_CODECS.contains(&codec_parameters.codec);
+}
+
+/// Find the audio track played by default: the default track of the format if it is audio, otherwise the first audio track
+fn find_audio_track(format_reader: &dyn FormatReader) -> Result<(u32, CodecParameters), AudioReaderError>
+{
+    let is_audio_track = |codec_parameters: &CodecParameters| codec_parameters.codec != CODEC_TYPE_NULL && codec_parameters.sample_rate.is_some();
+    let track = format_reader.default_track()
+        .filter(|track| is_audio_track(&track.codec_params))
+        .or_else(|| format_reader.tracks().iter().find(|track| is_audio_track(&track.codec_params)))
+        .ok_or(AudioReaderError::MissingBlock("audio track"))?;
+    return Ok((track.id, track.codec_params.clone()));
+}
+
+/// Convert the usage of a picture to its ID3v2 picture type
+fn convert_visual_key(visual_key: Option<StandardVisualKey>) -> PictureType
+{
+    return match visual_key
+    {
+        None => PictureType::Other,
+        Some(StandardVisualKey::FileIcon) => PictureType::FileIcon,
+        Some(StandardVisualKey::OtherIcon) => PictureType::OtherFileIcon,
+        Some(StandardVisualKey::FrontCover) => PictureType::FrontCover,
+        Some(StandardVisualKey::BackCover) => PictureType::BackCover,
+        Some(StandardVisualKey::Leaflet) => PictureType::LeafletPage,
+        Some(StandardVisualKey::Media) => PictureType::Media,
+        Some(StandardVisualKey::LeadArtistPerformerSoloist) => PictureType::LeadArtist,
+        Some(StandardVisualKey::ArtistPerformer) => PictureType::Artist,
+        Some(StandardVisualKey::Conductor) => PictureType::Conductor,
+        Some(StandardVisualKey::BandOrchestra) => PictureType::Band,
+        Some(StandardVisualKey::Composer) => PictureType::Composer,
+        Some(StandardVisualKey::Lyricist) => PictureType::Lyricist,
+        Some(StandardVisualKey::RecordingLocation) => PictureType::RecordingLocation,
+        Some(StandardVisualKey::RecordingSession) => PictureType::DuringRecording,
+        Some(StandardVisualKey::Performance) => PictureType::DuringPerformance,
+        Some(StandardVisualKey::ScreenCapture) => PictureType::MovieScreenCapture,
+        Some(StandardVisualKey::Illustration) => PictureType::Illustration,
+        Some(StandardVisualKey::BandArtistLogo) => PictureType::BandLogo,
+        Some(StandardVisualKey::PublisherStudioLogo) => PictureType::PublisherLogo,
+    };
+}
+
+/// Copy the tags and the pictures of a metadata revision to the information of a title.
+/// The fields not stored in the revision are kept, the tags without a field of their own are the extra fields.
+fn apply_metadata_revision(revision: &MetadataRevision, audio_information: &mut AudioInformation)
+{
+    let mut metadata = TrackMetadata::new();
+    for tag in revision.tags()
+    {
+        let value = tag.value.to_string();
+        if value.is_empty()
+        {
+            continue;
+        }
+        match tag.std_key
+        {
+            Some(StandardTagKey::TrackTitle) => metadata.m_title = value,
+            Some(StandardTagKey::Artist) => metadata.m_artists.push(value),
+            Some(StandardTagKey::Album) => metadata.m_album = value,
+            Some(StandardTagKey::AlbumArtist) => metadata.m_album_artists.push(value),
+            Some(StandardTagKey::Composer) => metadata.m_composers.push(value),
+            Some(StandardTagKey::Genre) => metadata.m_genres.push(value),
+            Some(StandardTagKey::TrackNumber) => metadata.set_track_text(&value),
+            Some(StandardTagKey::TrackTotal) => metadata.m_track_total = value.trim().parse().ok(),
+            Some(StandardTagKey::DiscNumber) => metadata.set_disc_text(&value),
+            Some(StandardTagKey::DiscTotal) => metadata.m_disc_total = value.trim().parse().ok(),
+            Some(StandardTagKey::Date) | Some(StandardTagKey::ReleaseDate) => metadata.m_date = TagDate::parse(&value).or(metadata.m_date),
+            Some(StandardTagKey::Comment) => audio_information.m_comments.push(value),
+
+            //
+            // The keys of the gains depend on the format of the tag, they are stored with the keys of the Vorbis comments.
+            // The gains of the MP4 files are free-form atoms, whose key is found without its namespace.
+            Some(StandardTagKey::ReplayGainTrackGain) => metadata.add_extra("REPLAYGAIN_TRACK_GAIN", value),
+            Some(StandardTagKey::ReplayGainTrackPeak) => metadata.add_extra("REPLAYGAIN_TRACK_PEAK", value),
+            Some(StandardTagKey::ReplayGainAlbumGain) => metadata.add_extra("REPLAYGAIN_ALBUM_GAIN", value),
+            Some(StandardTagKey::ReplayGainAlbumPeak) => metadata.add_extra("REPLAYGAIN_ALBUM_PEAK", value),
+            _ => metadata.add_extra(tag.key.strip_prefix(ITUNES_NAMESPACE_PREFIX).unwrap_or(&tag.key), value),
+        }
+    }
+    metadata.m_replay_gain = ReplayGainInfo::take_from_extra(&mut metadata.m_extra);
+    audio_information.m_metadata.apply(metadata);
+
+    for visual in revision.visuals()
+    {
+        audio_information.m_pictures.push(AudioPicture
+        {
+            m_picture_type: convert_visual_key(visual.usage),
+            m_mime_type: visual.media_type.clone(),
+            m_description: String::new(),
+            m_width: visual.dimensions.map_or(0, |dimensions| dimensions.width),
+            m_height: visual.dimensions.map_or(0, |dimensions| dimensions.height),
+            m_color_depth: visual.bits_per_pixel.map_or(0, |bits_per_pixel| bits_per_pixel.get()),
+            m_indexed_color_count: match visual.color_mode
+            {
+                Some(ColorMode::Indexed(color_count)) => color_count.get(),
+                _ => 0,
+            },
+            m_data: visual.data.to_vec(),
+        });
+    }
+}
+
+/// Apply all the revisions of a metadata log, from the oldest to the newest
+fn apply_metadata(mut metadata: Metadata, audio_information: &mut AudioInformation)
+{
+    while let Some(revision) = metadata.pop()
+    {
+        apply_metadata_revision(&revision, audio_information);
+    }
+    if let Some(revision) = metadata.current()
+    {
+        apply_metadata_revision(revision, audio_information);
+    }
+}
+
+impl AudioReader for SymphoniaReader
+{
+    fn read_information(&self, str_path_to_music: String) -> Result<AudioInformation, AudioReaderError>
+    {
+        let mut probe_result = probe_file(&str_path_to_music)?;
+        let (_track_id, codec_parameters) = find_audio_track(probe_result.format.as_ref())?;
+        let mut audio_information = AudioInformation::new();
+        audio_information.m_rate = codec_parameters.sample_rate.unwrap_or(0);
+        audio_information.m_channel_count = codec_parameters.channels.map_or(0, |channels| channels.count() as u8);
+        audio_information.m_bits_per_sample = codec_parameters.bits_per_sample.unwrap_or(0) as u8;
+        audio_information.m_total_samples = codec_parameters.n_frames.unwrap_or(0);
+        audio_information.m_codec = symphonia::default::get_codecs().get_codec(codec_parameters.codec)
+            .map_or(String::new(), |codec_descriptor| codec_descriptor.short_name.to_ascii_uppercase());
+        audio_information.m_is_lossless = is_lossless_codec(&codec_parameters);
+
+        //
+        // The ID3 tags are read by Quadrium, which decodes more frames than Symphonia.
+        // A tag which cannot be read is ignored, the other tags are still used.
+        let mut file = File::open(&str_path_to_music)?;
+        let id3_tags = Id3FileTags::read(&mut file).unwrap_or(Id3FileTags { m_id3v2_tag: None, m_id3v1_tag: None, m_tag_byte_count: 0 });
+        let ape_tag = ApeTag::read(&mut file).unwrap_or(None);
+        let ape_tag_byte_count = ape_tag.as_ref().map_or(0, |ape_tag| ape_tag.to_bytes().len() as u64);
+        let file_length = file.metadata()?.len();
+        audio_information.set_average_bitrate(file_length.saturating_sub(id3_tags.m_tag_byte_count + ape_tag_byte_count));
+
+        //
+        // Each tag fills the fields left empty by the tags preferred to it
+        for tag_format in self.m_tag_priority.iter()
+        {
+            let mut tag_information = AudioInformation::new();
+            match tag_format
+            {
+                TagFormat::Native => apply_metadata(probe_result.format.metadata(), &mut tag_information),
+                TagFormat::Id3v2 => match &id3_tags.m_id3v2_tag
+                {
+                    Some(id3v2_tag) => id3v2_tag.apply_to_information(&mut tag_information),
+
+                    //
+                    // The metadata found by Symphonia before the container is an ID3v2 tag
+                    None => if let Some(metadata) = probe_result.metadata.get()
+                    {
+                        apply_metadata(metadata, &mut tag_information);
+                    },
+                },
+                TagFormat::Ape => if let Some(ape_tag) = &ape_tag
+                {
+                    ape_tag.apply_to_information(&mut tag_information);
+                },
+                TagFormat::Id3v1 => if let Some(id3v1_tag) = &id3_tags.m_id3v1_tag
+                {
+                    id3v1_tag.apply_to_information(&mut tag_information);
+                },
+            }
+            audio_information.fill_missing_tags(tag_information);
+        }
+
+        return Ok(audio_information);
+    }
+}
+
+impl SymphoniaDecoder
+{
+    /// Open a file in any format supported by Symphonia, and create the decoder of its default audio track
+    ///
+    /// # Params
+    /// str_path_to_music: the path of the file
+    pub fn open(str_path_to_music: String) -> Result<SymphoniaDecoder, AudioReaderError>
+    {
+        let probe_result = probe_file(&str_path_to_music)?;
+        let format_reader = probe_result.format;
+        let (track_id, codec_parameters) = find_audio_track(format_reader.as_ref())?;
+        let decoder = symphonia::default::get_codecs().make(&codec_parameters, &DecoderOptions::default()).map_err(convert_symphonia_error)?;
+
+        let rate = codec_parameters.sample_rate.unwrap_or(0);
+        let time_base = codec_parameters.time_base.filter(|time_base| !(time_base.numer == 1 && time_base.denom == rate));
+        return Ok(SymphoniaDecoder
+        {
+            m_format_reader: format_reader,
+            m_track_id: track_id,
+            m_time_base: time_base,
+            m_rate: rate,
+            m_channel_count: decoder.codec_params().channels.map_or(0, |channels| channels.count() as u8),
+            m_bits_per_sample: codec_parameters.bits_per_sample.map_or(32, |bits_per_sample| bits_per_sample.min(32) as u8),
+            m_total_samples: codec_parameters.n_frames.unwrap_or(0),
+            m_decoder: decoder,
+            m_sample_buffer: None,
+            m_seek_target: None,
+            m_decode_error_count: 0,
+            m_replay_gain: None,
+        });
+    }
+
+    /// Convert a timestamp of a packet to the index of a sample
+    fn convert_timestamp_to_sample(&self, timestamp: u64) -> u64
+    {
+        return match self.m_time_base
+        {
+            None => timestamp,
+            Some(time_base) => (timestamp as u128 * time_base.numer as u128 * self.m_rate as u128 / time_base.denom as u128) as u64,
+        };
+    }
+
+    /// Convert the index of a sample to a timestamp of the packets
+    fn convert_sample_to_timestamp(&self, sample_index: u64) -> u64
+    {
+        return match self.m_time_base
+        {
+            None => sample_index,
+            Some(time_base) => (sample_index as u128 * time_base.denom as u128 / (time_base.numer as u128 * self.m_rate as u128)) as u64,
+        };
+    }
+
+    /// Decode the next packet of the audio track.
+    /// A corrupted packet is skipped and counted, like the corrupted frames of the flac files.
+    ///
+    /// # Return
+    /// The samples of the packet, or None at the end of the track
+    pub fn read_next_block(&mut self) -> Result<Option<DecodedBlock>, AudioReaderError>
+    {
+        loop
+        {
+            let packet = match self.m_format_reader.next_packet()
+            {
+                Ok(packet) => packet,
+                Err(Error::IoError(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(error) => return Err(convert_symphonia_error(error)),
+            };
+            if packet.track_id() != self.m_track_id
+            {
+                continue;
+            }
+
+            let mut first_sample = self.convert_timestamp_to_sample(packet.ts());
+            let decoded = match self.m_decoder.decode(&packet)
+            {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) =>
+                {
+                    self.m_decode_error_count += 1;
+                    continue;
+                }
+                Err(error) => return Err(convert_symphonia_error(error)),
+            };
+            let channel_count = decoded.spec().channels.count();
+            let frame_count = decoded.frames();
+            if self.m_sample_buffer.as_ref().map_or(true, |sample_buffer| sample_buffer.capacity() < decoded.capacity() * channel_count)
+            {
+                self.m_sample_buffer = Some(SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+            }
+            let sample_buffer = self.m_sample_buffer.as_mut().unwrap();
+            sample_buffer.copy_interleaved_ref(decoded);
+
+            //
+            // Remove the encoder delay and padding, then the samples before the target of a seek
+            let mut first_frame = (packet.trim_start as usize).min(frame_count);
+            let last_frame = frame_count.saturating_sub(packet.trim_end as usize).max(first_frame);
+            if let Some(seek_target) = self.m_seek_target
+            {
+                let skipped_frame_count = (seek_target.saturating_sub(first_sample) as usize).min(last_frame - first_frame);
+                first_frame += skipped_frame_count;
+                first_sample += skipped_frame_count as u64;
+            }
+            if first_frame == last_frame
+            {
+                continue;
+            }
+            self.m_seek_target = None;
+
+            //
+            // The samples are converted on the 32 bits of an i32, the lossless codecs get back their bits per sample
+            let shift = 32 - self.m_bits_per_sample as u32;
+            let samples: Vec<i32> = sample_buffer.samples()[first_frame * channel_count..last_frame * channel_count].iter()
+                .map(|sample| sample >> shift)
+                .collect();
+            let mut decoded_block = DecodedBlock
+            {
+                m_first_sample: first_sample,
+                m_rate: self.m_rate,
+                m_channel_count: channel_count as u8,
+                m_bits_per_sample: self.m_bits_per_sample,
+                m_samples: samples,
+            };
+            if let Some(replay_gain) = &self.m_replay_gain
+            {
+                replay_gain.process(&mut decoded_block);
+            }
+            return Ok(Some(decoded_block));
+        }
+    }
+
+    /// Apply the gain of the title to the samples decoded from now on
+    ///
+    /// # Params
+    /// replay_gain: the gain chosen by the settings of the player, None to decode the samples unchanged
+    pub fn set_replay_gain(&mut self, replay_gain: Option<ReplayGainProcessor>)
+    {
+        self.m_replay_gain = replay_gain;
+    }
+
+    /// Decode all the remaining packets of the audio track
+    ///
+    /// # Return
+    /// The interleaved samples of all the packets
+    pub fn read_all_samples(&mut self) -> Result<Vec<i32>, AudioReaderError>
+    {
+        let mut samples: Vec<i32> = Vec::new();
+        while let Some(decoded_block) = self.read_next_block()?
+        {
+            samples.extend_from_slice(&decoded_block.m_samples);
+        }
+
+        return Ok(samples);
+    }
+
+    /// Move the decoder to a sample of the track, the next read block starts with this sample
+    ///
+    /// # Params
+    /// sample_index: index of the sample in the track
+    pub fn seek(&mut self, sample_index: u64) -> Result<(), AudioReaderError>
+    {
+        if self.m_total_samples != 0 && sample_index >= self.m_total_samples
+        {
+            return Err(AudioReaderError::InvalidSeekPosition);
+        }
+        let timestamp = self.convert_sample_to_timestamp(sample_index);
+        self.m_format_reader.seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: timestamp, track_id: self.m_track_id })
+            .map_err(convert_symphonia_error)?;
+        self.m_decoder.reset();
+        self.m_seek_target = Some(sample_index);
+        return Ok(());
+    }
+
+    pub fn get_rate(&self) -> u32
+    {
+        return self.m_rate;
+    }
+
+    pub fn get_channel_count(&self) -> u8
+    {
+        return self.m_channel_count;
+    }
+
+    /// Get the number of significant bits of the decoded samples, 32 for the lossy codecs
+    pub fn get_bits_per_sample(&self) -> u8
+    {
+        return self.m_bits_per_sample;
+    }
+
+    /// Get the number of samples per channel of the track, 0 if unknown
+    pub fn get_total_samples(&self) -> u64
+    {
+        return self.m_total_samples;
+    }
+
+    /// Get the number of corrupted packets skipped since the file was opened
+    pub fn get_decode_error_count(&self) -> u32
+    {
+        return self.m_decode_error_count;
+    }
+}
+
+#[cfg(test)]
+mod test_symphonia_reader
+{
+    use crate::audio_reader::{AudioReader, TagDate, DEFAULT_TAG_PRIORITY};
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::flac_reader::FlacDecoder;
+    use crate::audio_reader::symphonia_reader::{SymphoniaDecoder, SymphoniaReader};
+
+    /// Write an atom of a MP4 file
+    fn write_atom(mp4_data: &mut Vec<u8>, atom_type: &[u8; 4], data: &[u8])
+    {
+        mp4_data.extend_from_slice(&(8 + data.len() as u32).to_be_bytes());
+        mp4_data.extend_from_slice(atom_type);
+        mp4_data.extend_from_slice(data);
+    }
+
+    /// Write an atom of the iTunes metadata list, holding one value
+    ///
+    /// # Params
+    /// data_type: 1 for a text, 0 for the binary pairs of the track and disc numbers
+    fn write_metadata_atom(ilst_data: &mut Vec<u8>, atom_type: &[u8; 4], data_type: u32, value: &[u8])
+    {
+        let mut data_atom: Vec<u8> = Vec::new();
+        data_atom.extend_from_slice(&data_type.to_be_bytes());
+        data_atom.extend_from_slice(&0u32.to_be_bytes());
+        data_atom.extend_from_slice(value);
+        let mut tag_atom: Vec<u8> = Vec::new();
+        write_atom(&mut tag_atom, b"data", &data_atom);
+        write_atom(ilst_data, atom_type, &tag_atom);
+    }
+
+    /// Create a MP4 file holding a single chunk of 16 bits little endian PCM samples, like a WAV file in a MP4 container
+    fn create_mp4_file(rate: u32, channel_count: u16, samples: &[i16], ilst_data: &[u8]) -> Vec<u8>
+    {
+        let frame_count = (samples.len() / channel_count as usize) as u32;
+        let identity_matrix: Vec<u8> = [0x10000u32, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000].iter().flat_map(|value| value.to_be_bytes().to_vec()).collect();
+
+        let mut mvhd = vec![0u8; 12];
+        mvhd.extend_from_slice(&rate.to_be_bytes());
+        mvhd.extend_from_slice(&frame_count.to_be_bytes());
+        mvhd.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x01, 0x00]);
+        mvhd.extend_from_slice(&[0u8; 10]);
+        mvhd.extend_from_slice(&identity_matrix);
+        mvhd.extend_from_slice(&[0u8; 24]);
+        mvhd.extend_from_slice(&2u32.to_be_bytes());
+
+        let mut tkhd = vec![0, 0, 0, 3];
+        tkhd.extend_from_slice(&[0u8; 8]);
+        tkhd.extend_from_slice(&1u32.to_be_bytes());
+        tkhd.extend_from_slice(&[0u8; 4]);
+        tkhd.extend_from_slice(&frame_count.to_be_bytes());
+        tkhd.extend_from_slice(&[0u8; 12]);
+        tkhd.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        tkhd.extend_from_slice(&identity_matrix);
+        tkhd.extend_from_slice(&[0u8; 8]);
+
+        let mut mdhd = vec![0u8; 12];
+        mdhd.extend_from_slice(&rate.to_be_bytes());
+        mdhd.extend_from_slice(&frame_count.to_be_bytes());
+        mdhd.extend_from_slice(&[0x55, 0xC4, 0x00, 0x00]);
+
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(b"soun");
+        hdlr.extend_from_slice(&[0u8; 13]);
+
+        //
+        // Version 0 sample entry, whose type gives the format of the samples
+        let mut sowt = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        sowt.extend_from_slice(&[0u8; 8]);
+        sowt.extend_from_slice(&channel_count.to_be_bytes());
+        sowt.extend_from_slice(&16u16.to_be_bytes());
+        sowt.extend_from_slice(&[0u8; 4]);
+        sowt.extend_from_slice(&(rate << 16).to_be_bytes());
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        write_atom(&mut stsd, b"sowt", &sowt);
+
+        let mut stts = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stts.extend_from_slice(&frame_count.to_be_bytes());
+        stts.extend_from_slice(&1u32.to_be_bytes());
+        let mut stsc = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1];
+        stsc.extend_from_slice(&frame_count.to_be_bytes());
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+        let mut stsz = vec![0u8; 4];
+        stsz.extend_from_slice(&(2 * channel_count as u32).to_be_bytes());
+        stsz.extend_from_slice(&frame_count.to_be_bytes());
+
+        //
+        // The offset of the samples depends on the size of the moov atom, it is written once the size is known
+        let create_moov = |chunk_offset: u32| -> Vec<u8>
+        {
+            let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            stco.extend_from_slice(&chunk_offset.to_be_bytes());
+            let mut stbl: Vec<u8> = Vec::new();
+            write_atom(&mut stbl, b"stsd", &stsd);
+            write_atom(&mut stbl, b"stts", &stts);
+            write_atom(&mut stbl, b"stsc", &stsc);
+            write_atom(&mut stbl, b"stsz", &stsz);
+            write_atom(&mut stbl, b"stco", &stco);
+            let mut minf: Vec<u8> = Vec::new();
+            write_atom(&mut minf, b"smhd", &[0u8; 8]);
+            write_atom(&mut minf, b"stbl", &stbl);
+            let mut mdia: Vec<u8> = Vec::new();
+            write_atom(&mut mdia, b"mdhd", &mdhd);
+            write_atom(&mut mdia, b"hdlr", &hdlr);
+            write_atom(&mut mdia, b"minf", &minf);
+            let mut trak: Vec<u8> = Vec::new();
+            write_atom(&mut trak, b"tkhd", &tkhd);
+            write_atom(&mut trak, b"mdia", &mdia);
+
+            let mut meta = vec![0u8; 4];
+            write_atom(&mut meta, b"ilst", ilst_data);
+            let mut udta: Vec<u8> = Vec::new();
+            write_atom(&mut udta, b"meta", &meta);
+
+            let mut moov: Vec<u8> = Vec::new();
+            write_atom(&mut moov, b"mvhd", &mvhd);
+            write_atom(&mut moov, b"trak", &trak);
+            write_atom(&mut moov, b"udta", &udta);
+            return moov;
+        };
+
+        let mut mp4_data: Vec<u8> = Vec::new();
+        write_atom(&mut mp4_data, b"ftyp", b"M4A \0\0\0\0M4A isom");
+        let moov_length = 8 + create_moov(0).len();
+        let moov = create_moov((mp4_data.len() + moov_length + 8) as u32);
+        write_atom(&mut mp4_data, b"moov", &moov);
+        let audio_data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes().to_vec()).collect();
+        write_atom(&mut mp4_data, b"mdat", &audio_data);
+        return mp4_data;
+    }
+
+    #[test]
+    fn decode_with_symphonia()
+    {
+        //
+        // A flac file is also read by Symphonia, the native decoder gives the expected samples
+        let sample_count = 10000;
+        let samples: Vec<i32> = (0..sample_count * 2).map(|index| ((index as f64 * 0.01).sin() * 20000.0) as i32 + (index % 2) as i32 * 7).collect();
+        let path = std::env::temp_dir().join("quadrium_symphonia.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(48000, 2, 16);
+        settings.m_total_samples = sample_count as u64;
+        settings.m_vorbis_comments = vec!["TITLE=Symphonia title".to_string(), "ARTIST=Symphonia artist".to_string()];
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap();
+
+        let audio_information = SymphoniaReader { m_tag_priority: DEFAULT_TAG_PRIORITY.to_vec() }.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Symphonia title");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Symphonia artist"]);
+        assert_eq!(audio_information.m_rate, 48000);
+        assert_eq!(audio_information.m_channel_count, 2);
+        assert_eq!(audio_information.m_bits_per_sample, 16);
+
+        let mut decoder = SymphoniaDecoder::open(str_path.clone()).unwrap();
+        assert_eq!(decoder.get_rate(), 48000);
+        assert_eq!(decoder.get_channel_count(), 2);
+        assert_eq!(decoder.get_bits_per_sample(), 16);
+        assert_eq!(decoder.get_total_samples(), sample_count as u64);
+        assert_eq!(decoder.read_all_samples().unwrap(), FlacDecoder::open(str_path.clone()).unwrap().read_all_samples().unwrap());
+        assert_eq!(decoder.get_decode_error_count(), 0);
+
+        decoder.seek(5000).unwrap();
+        let decoded_block = decoder.read_next_block().unwrap().unwrap();
+        assert_eq!(decoded_block.m_first_sample, 5000);
+        assert_eq!(decoded_block.m_samples[..20], samples[10000..10020]);
+        assert!(decoder.seek(sample_count as u64).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn decode_pcm_in_mp4_with_symphonia()
+    {
+        let path = std::env::temp_dir().join("quadrium_symphonia.m4a");
+        let str_path = path.to_string_lossy().to_string();
+        let samples: Vec<i16> = (0..4000).map(|index| ((index as f64 * 0.02).sin() * 30000.0) as i16 - (index % 2) as i16 * 5).collect();
+
+        let mut ilst: Vec<u8> = Vec::new();
+        write_metadata_atom(&mut ilst, b"\xA9nam", 1, b"Mp4 title");
+        write_metadata_atom(&mut ilst, b"\xA9ART", 1, b"Mp4 artist");
+        write_metadata_atom(&mut ilst, b"\xA9alb", 1, b"Mp4 album");
+        write_metadata_atom(&mut ilst, b"trkn", 0, &[0, 0, 0, 3, 0, 12, 0, 0]);
+        write_metadata_atom(&mut ilst, b"disk", 0, &[0, 0, 0, 1, 0, 2, 0, 0]);
+        write_metadata_atom(&mut ilst, b"\xA9day", 1, b"2010-11-12");
+        write_metadata_atom(&mut ilst, b"\xA9cmt", 1, b"Mp4 comment");
+
+        //
+        // Free-form atoms, named in the namespace of iTunes
+        for (name, value) in [("replaygain_track_gain", "-6.50 dB"), ("replaygain_track_peak", "0.912"), ("Rating", "Good")].iter()
+        {
+            let mut free_form: Vec<u8> = Vec::new();
+            write_atom(&mut free_form, b"mean", &[&b"\0\0\0\0"[..], &b"com.apple.iTunes"[..]].concat());
+            write_atom(&mut free_form, b"name", &[&b"\0\0\0\0"[..], name.as_bytes()].concat());
+            let mut data_atom = vec![0, 0, 0, 1, 0, 0, 0, 0];
+            data_atom.extend_from_slice(value.as_bytes());
+            write_atom(&mut free_form, b"data", &data_atom);
+            write_atom(&mut ilst, b"----", &free_form);
+        }
+        std::fs::write(&path, create_mp4_file(44100, 2, &samples, &ilst)).unwrap();
+
+        let audio_information = SymphoniaReader { m_tag_priority: DEFAULT_TAG_PRIORITY.to_vec() }.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_rate, 44100);
+        assert_eq!(audio_information.m_channel_count, 2);
+        assert_eq!(audio_information.m_bits_per_sample, 16);
+        assert_eq!(audio_information.m_total_samples, 2000);
+        assert!(audio_information.m_is_lossless);
+        assert_eq!(audio_information.m_codec, "PCM_S16LE");
+        assert_eq!(audio_information.m_metadata.m_title, "Mp4 title");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Mp4 artist"]);
+        assert_eq!(audio_information.m_metadata.m_album, "Mp4 album");
+        assert_eq!((audio_information.m_metadata.m_track_number, audio_information.m_metadata.m_track_total), (Some(3), Some(12)));
+        assert_eq!((audio_information.m_metadata.m_disc_number, audio_information.m_metadata.m_disc_total), (Some(1), Some(2)));
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 2010, m_month: Some(11), m_day: Some(12) }));
+        assert_eq!(audio_information.m_comments, vec!["Mp4 comment"]);
+        let track_gain = audio_information.m_metadata.m_replay_gain.m_track.unwrap();
+        assert_eq!((track_gain.m_gain, track_gain.m_peak), (-6.5, Some(0.912)));
+        assert_eq!(audio_information.m_metadata.m_extra.get("RATING"), Some(&vec!["Good".to_string()]));
+
+        let mut decoder = SymphoniaDecoder::open(str_path.clone()).unwrap();
+        assert_eq!(decoder.get_rate(), 44100);
+        assert_eq!(decoder.get_channel_count(), 2);
+        assert_eq!(decoder.get_bits_per_sample(), 16);
+        assert_eq!(decoder.get_total_samples(), 2000);
+        assert_eq!(decoder.read_all_samples().unwrap(), samples.iter().map(|&sample| sample as i32).collect::<Vec<i32>>());
+        assert_eq!(decoder.get_decode_error_count(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
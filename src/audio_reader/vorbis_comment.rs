@@ -0,0 +1,225 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on https://www.xiph.org/vorbis/doc/v-comment.html
+
+use crate::audio_reader::{AudioInformation, AudioReaderError, TagDate, TrackMetadata};
+use crate::audio_reader::replay_gain::ReplayGainInfo;
+use crate::audio_reader::flac_reader::parse_vorbis_comment_block;
+
+/// Keys of the fields stored in the fields of their own of the metadata, or in the comments
+const VORBIS_METADATA_KEYS: [&str; 17] = [
+    "TITLE", "ARTIST", "ALBUM", "ALBUMARTIST", "ALBUM ARTIST", "COMPOSER", "GENRE", "TRACKNUMBER", "TRACKTOTAL", "TOTALTRACKS",
+    "DISCNUMBER", "DISCTOTAL", "TOTALDISCS", "DATE", "YEAR", "COMMENT", "DESCRIPTION",
+];
+
+/// Field of a Vorbis comment, written "KEY=value"
+#[derive(Clone, Debug, PartialEq)]
+pub struct VorbisCommentField
+{
+    /// Key of the field as written in the file, compared without case
+    pub m_key: String,
+    pub m_value: String,
+}
+
+/// Vorbis comment, the tags of the flac, Ogg Vorbis and Opus files
+#[derive(Clone, Debug, PartialEq)]
+pub struct VorbisComment
+{
+    /// Name of the encoder which wrote the file
+    pub m_vendor_string: String,
+
+    /// The fields in the order of the file, a key can be repeated to give several values
+    pub m_fields: Vec<VorbisCommentField>,
+}
+
+/// Check whether a key only uses the characters allowed by the specification: the printable ASCII characters except '='
+pub(crate) fn is_valid_key(key: &str) -> bool
+{
+    return !key.is_empty() && key.bytes().all(|byte| (0x20..=0x7D).contains(&byte) && byte != b'=');
+}
+
+impl VorbisComment
+{
+    /// Split the comments written "KEY=value" into fields.
+    /// The key ends at the first '=', the value can contain other '='. The comments without a valid key are ignored.
+    ///
+    /// # Params
+    /// vendor_string: the name of the encoder which wrote the file
+    /// comments: the comments, decoded from UTF-8
+    pub fn from_comments(vendor_string: String, comments: &[String]) -> VorbisComment
+    {
+        let fields: Vec<VorbisCommentField> = comments.iter()
+            .filter_map(|comment| comment.split_once('='))
+            .filter(|(key, _value)| is_valid_key(key))
+            .map(|(key, value)| VorbisCommentField { m_key: key.to_string(), m_value: value.to_string() })
+            .collect();
+        return VorbisComment
+        {
+            m_vendor_string: vendor_string,
+            m_fields: fields,
+        };
+    }
+
+    /// Decode the content of a VORBIS_COMMENT block, which is also the comment header of Ogg Vorbis and Opus without its signature
+    pub fn parse(data: &[u8]) -> Result<VorbisComment, AudioReaderError>
+    {
+        let vorbis_comment_block = parse_vorbis_comment_block(data)?;
+        return Ok(VorbisComment::from_comments(vorbis_comment_block.m_vendor_string, &vorbis_comment_block.m_user_comment_list));
+    }
+
+    /// Get all the values of a field, in the order of the file
+    ///
+    /// # Params
+    /// key: the key of the field, compared without case
+    pub fn get_values(&self, key: &str) -> Vec<&str>
+    {
+        return self.m_fields.iter()
+            .filter(|field| field.m_key.eq_ignore_ascii_case(key))
+            .map(|field| field.m_value.as_str())
+            .collect();
+    }
+
+    /// Get the first value of a field, None if the field is missing
+    pub fn get_value(&self, key: &str) -> Option<&str>
+    {
+        return self.m_fields.iter().find(|field| field.m_key.eq_ignore_ascii_case(key)).map(|field| field.m_value.as_str());
+    }
+
+    /// Get all the non empty values of the first key given by the comment
+    fn get_non_empty_values(&self, keys: &[&str]) -> Vec<String>
+    {
+        return keys.iter()
+            .map(|key| self.get_values(key).into_iter().filter(|value| !value.is_empty()).map(|value| value.to_string()).collect::<Vec<String>>())
+            .find(|values| !values.is_empty())
+            .unwrap_or_default();
+    }
+
+    /// Get the tags stored in the fields. The fields without a field of their own are the extra fields.
+    pub fn get_metadata(&self) -> TrackMetadata
+    {
+        let mut metadata = TrackMetadata::new();
+        metadata.m_title = self.get_non_empty_values(&["TITLE"]).join("; ");
+        metadata.m_artists = self.get_non_empty_values(&["ARTIST"]);
+        metadata.m_album = self.get_value("ALBUM").unwrap_or_default().to_string();
+        metadata.m_album_artists = self.get_non_empty_values(&["ALBUMARTIST", "ALBUM ARTIST"]);
+        metadata.m_composers = self.get_non_empty_values(&["COMPOSER"]);
+        metadata.m_genres = self.get_non_empty_values(&["GENRE"]);
+
+        //
+        // The total is either a field of its own or written after the number, like "3/12"
+        metadata.set_track_text(self.get_value("TRACKNUMBER").unwrap_or_default());
+        metadata.m_track_total = metadata.m_track_total.or_else(|| self.get_non_empty_values(&["TRACKTOTAL", "TOTALTRACKS"]).first().and_then(|total| total.trim().parse().ok()));
+        metadata.set_disc_text(self.get_value("DISCNUMBER").unwrap_or_default());
+        metadata.m_disc_total = metadata.m_disc_total.or_else(|| self.get_non_empty_values(&["DISCTOTAL", "TOTALDISCS"]).first().and_then(|total| total.trim().parse().ok()));
+
+        //
+        // YEAR is not standard but is written by some taggers instead of DATE
+        metadata.m_date = self.get_non_empty_values(&["DATE", "YEAR"]).first().and_then(|date| TagDate::parse(date));
+        for field in self.m_fields.iter()
+        {
+            if !VORBIS_METADATA_KEYS.iter().any(|key| key.eq_ignore_ascii_case(&field.m_key))
+            {
+                metadata.add_extra(&field.m_key, field.m_value.clone());
+            }
+        }
+        metadata.m_replay_gain = ReplayGainInfo::take_from_extra(&mut metadata.m_extra);
+        return metadata;
+    }
+
+    /// Copy the fields to the information of a title, the fields not stored in the comment are kept
+    pub fn apply_to_information(&self, audio_information: &mut AudioInformation)
+    {
+        audio_information.m_metadata.apply(self.get_metadata());
+        audio_information.m_comments.extend(self.get_values("COMMENT").into_iter().chain(self.get_values("DESCRIPTION")).map(|comment| comment.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test_vorbis_comment
+{
+    use crate::audio_reader::{AudioReader, TagDate};
+    use crate::audio_reader::flac_encoder::{FlacEncoder, FlacEncoderSettings};
+    use crate::audio_reader::flac_reader::FlacReader;
+    use crate::audio_reader::vorbis_comment::{VorbisComment, VorbisCommentField};
+
+    #[test]
+    fn split_comments()
+    {
+        let comments: Vec<String> = ["TITLE=a=b", "=no key", "NO SEPARATOR", "K\u{E9}Y=invalid key", "Artist=One", "ARTIST=Two", "EMPTY="].iter()
+            .map(|comment| comment.to_string())
+            .collect();
+        let vorbis_comment = VorbisComment::from_comments("vendor".to_string(), &comments);
+        assert_eq!(vorbis_comment.m_fields, vec![
+            VorbisCommentField { m_key: "TITLE".to_string(), m_value: "a=b".to_string() },
+            VorbisCommentField { m_key: "Artist".to_string(), m_value: "One".to_string() },
+            VorbisCommentField { m_key: "ARTIST".to_string(), m_value: "Two".to_string() },
+            VorbisCommentField { m_key: "EMPTY".to_string(), m_value: String::new() },
+        ]);
+        assert_eq!(vorbis_comment.get_values("artist"), vec!["One", "Two"]);
+        assert_eq!(vorbis_comment.get_value("Title"), Some("a=b"));
+        assert_eq!(vorbis_comment.get_value("ALBUM"), None);
+    }
+
+    #[test]
+    fn read_vorbis_comments_of_flac_file()
+    {
+        //
+        // Fields whose key contains the key of another field, and which used to replace it
+        let path = std::env::temp_dir().join("quadrium_vorbis_comment.flac");
+        let str_path = path.to_string_lossy().to_string();
+        let mut settings = FlacEncoderSettings::new(44100, 1, 16);
+        settings.m_total_samples = 100;
+        settings.m_vorbis_comments = ["ARTIST=First artist", "ALBUMARTIST=Album artist", "artist=Second artist", "TITLE=Caf\u{E9} = bar",
+            "UPDATE_DATE=2023-01-01", "date=1999-12-31", "ALBUM=\u{41F}\u{440}\u{438}\u{432}\u{435}\u{442}", "TITLESORT=Cafe", "GENRE=Jazz",
+            "GENRE=Blues", "COMMENT=Live recording", "MY_FIELD=Custom value", "TRACKNUMBER=3", "TRACKTOTAL=12", "DISCNUMBER=1/2",
+            "COMPOSER=Composer"].iter()
+            .map(|comment| comment.to_string())
+            .collect();
+        let mut encoder = FlacEncoder::create(str_path.clone(), settings).unwrap();
+        encoder.write_samples(&[0; 100]).unwrap();
+        encoder.finish().unwrap();
+
+        let audio_information = FlacReader {}.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["First artist", "Second artist"]);
+        assert_eq!(audio_information.m_metadata.m_title, "Caf\u{E9} = bar");
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 1999, m_month: Some(12), m_day: Some(31) }));
+        assert_eq!(audio_information.m_metadata.m_album, "\u{41F}\u{440}\u{438}\u{432}\u{435}\u{442}");
+        assert_eq!(audio_information.m_metadata.m_genres, vec!["Jazz", "Blues"]);
+        assert_eq!(audio_information.m_comments, vec!["Live recording"]);
+        assert_eq!(audio_information.m_metadata.m_album_artists, vec!["Album artist"]);
+        assert_eq!(audio_information.m_metadata.m_composers, vec!["Composer"]);
+        assert_eq!((audio_information.m_metadata.m_track_number, audio_information.m_metadata.m_track_total), (Some(3), Some(12)));
+        assert_eq!((audio_information.m_metadata.m_disc_number, audio_information.m_metadata.m_disc_total), (Some(1), Some(2)));
+
+        //
+        // Only the fields without a field of their own are extra fields
+        assert_eq!(audio_information.m_metadata.m_extra["UPDATE_DATE"], vec!["2023-01-01"]);
+        assert_eq!(audio_information.m_metadata.m_extra["TITLESORT"], vec!["Cafe"]);
+        assert_eq!(audio_information.m_metadata.m_extra["MY_FIELD"], vec!["Custom value"]);
+        assert_eq!(audio_information.m_metadata.m_extra.len(), 3);
+
+        assert_eq!(audio_information.m_codec, "FLAC");
+        assert!(audio_information.m_is_lossless);
+        assert_eq!(audio_information.m_total_samples, 100);
+        assert!(audio_information.m_bitrate > 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,651 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// Based on the RIFF/WAVE specification of Microsoft, EBU Tech 3285 (bext) and EBU Tech 3306 (RF64)
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use crate::audio_reader::{AudioInformation, AudioReader, AudioReaderError, TagDate, TrackMetadata};
+use crate::audio_reader::id3_tag::Id3Tag;
+use crate::audio_reader::pcm_decoder::{get_default_channel_mask, PcmDecoder, PcmFormat, PcmSampleFormat};
+
+/// Format tags of the fmt chunk
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// End of the GUID of the sub formats of WAVE_FORMAT_EXTENSIBLE, which start with the format tag
+const EXTENSIBLE_GUID_SUFFIX: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+/// Size of the fixed part of the bext chunk, before the coding history
+const BEXT_FIXED_SIZE: usize = 602;
+
+/// Size written in the 32 bits fields of a RF64 file when the real size is stored in the ds64 chunk
+const RF64_SIZE_IN_DS64: u32 = 0xFFFFFFFF;
+
+/// Text fields of the bext chunk of the Broadcast Wave Format
+struct BextChunk
+{
+    m_description: String,
+    m_originator: String,
+    m_origination_date: String,
+}
+
+/// Position of the audio and tags of a WAV stream, found by reading its chunks
+struct WavStream
+{
+    m_format: PcmFormat,
+
+    /// Position of the first sample from the beginning of the stream
+    m_data_offset: u64,
+
+    /// Number of bytes of samples
+    m_data_length: u64,
+
+    /// Text fields of the LIST/INFO chunk, with their ids like "INAM"
+    m_info_fields: Vec<([u8; 4], String)>,
+    m_bext: Option<BextChunk>,
+    m_id3_tag: Option<Id3Tag>,
+}
+
+pub struct WavReader
+{
+}
+
+/// Decode a text of a chunk: UTF-8 when it is valid, ISO-8859-1 otherwise, without the null characters at its end
+pub(crate) fn decode_chunk_text(data: &[u8]) -> String
+{
+    let text_length = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+    let text = &data[..text_length];
+    return match std::str::from_utf8(text)
+    {
+        Ok(text) => text.trim_end().to_string(),
+        Err(_) => text.iter().map(|byte| *byte as char).collect::<String>().trim_end().to_string(),
+    };
+}
+
+/// Read the content of a chunk, shared by the readers of the RIFF and IFF files
+///
+/// # Params
+/// source: the stream, placed at the content of the chunk
+/// length: the size of the content
+/// chunk_name: the name of the chunk, used by the error
+/// chunk_offset: the position of the chunk, used by the error
+pub(crate) fn read_chunk_data<R: Read>(source: &mut R, length: u64, chunk_name: &'static str, chunk_offset: u64) -> Result<Vec<u8>, AudioReaderError>
+{
+    let mut data: Vec<u8> = Vec::new();
+    source.take(length).read_to_end(&mut data)?;
+    if data.len() as u64 != length
+    {
+        return Err(AudioReaderError::TruncatedBlock { m_block_name: chunk_name, m_offset: chunk_offset });
+    }
+    return Ok(data);
+}
+
+/// Write a chunk of a RIFF or IFF file, with its padding byte when its size is odd, to build the files of the tests
+///
+/// # Params
+/// file_data: the content of the file, the chunk is added at its end
+/// chunk_id: the id of the chunk
+/// data: the content of the chunk
+/// is_big_endian: true for the IFF files like AIFF, false for the RIFF files like WAV
+#[cfg(test)]
+pub(crate) fn write_chunk(file_data: &mut Vec<u8>, chunk_id: &[u8; 4], data: &[u8], is_big_endian: bool)
+{
+    let size = data.len() as u32;
+    file_data.extend_from_slice(chunk_id);
+    file_data.extend_from_slice(&if is_big_endian { size.to_be_bytes() } else { size.to_le_bytes() });
+    file_data.extend_from_slice(data);
+    if data.len() % 2 == 1
+    {
+        file_data.push(0);
+    }
+}
+
+/// Decode the content of the fmt chunk
+fn parse_format_chunk(data: &[u8]) -> Result<PcmFormat, AudioReaderError>
+{
+    if data.len() < 16
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "fmt", m_length: data.len() as u32 });
+    }
+    let mut format_tag = u16::from_le_bytes(data[0..2].try_into().unwrap());
+    let channel_count = u16::from_le_bytes(data[2..4].try_into().unwrap());
+    let rate = u32::from_le_bytes(data[4..8].try_into().unwrap());
+    let block_align = u16::from_le_bytes(data[12..14].try_into().unwrap());
+    let container_bits = u16::from_le_bytes(data[14..16].try_into().unwrap());
+    let mut valid_bits = container_bits;
+    let mut channel_mask: Option<u32> = None;
+
+    //
+    // The extensible format gives the significant bits, the speaker positions and the real format tag
+    if format_tag == WAVE_FORMAT_EXTENSIBLE
+    {
+        if data.len() < 40
+        {
+            return Err(AudioReaderError::InvalidBlockLength { m_block_name: "fmt", m_length: data.len() as u32 });
+        }
+        valid_bits = u16::from_le_bytes(data[18..20].try_into().unwrap());
+        channel_mask = Some(u32::from_le_bytes(data[20..24].try_into().unwrap()));
+        if data[26..40] != EXTENSIBLE_GUID_SUFFIX
+        {
+            return Err(AudioReaderError::UnsupportedFeature("wav sub format"));
+        }
+        format_tag = u16::from_le_bytes(data[24..26].try_into().unwrap());
+        if valid_bits == 0
+        {
+            valid_bits = container_bits;
+        }
+    }
+
+    //
+    // The samples on 8 bits are unsigned
+    let sample_format = match format_tag
+    {
+        WAVE_FORMAT_PCM if container_bits == 8 => PcmSampleFormat::UnsignedInteger,
+        WAVE_FORMAT_PCM => PcmSampleFormat::SignedInteger,
+        WAVE_FORMAT_IEEE_FLOAT => PcmSampleFormat::Float,
+        _ => return Err(AudioReaderError::UnsupportedFeature("wav compression format")),
+    };
+    let is_valid_container = match sample_format
+    {
+        PcmSampleFormat::Float => container_bits == 32 || container_bits == 64,
+        _ => container_bits == 8 || container_bits == 16 || container_bits == 24 || container_bits == 32,
+    };
+    if !is_valid_container || valid_bits > container_bits || (sample_format == PcmSampleFormat::Float && valid_bits != container_bits)
+    {
+        return Err(AudioReaderError::InvalidValue("bits per sample"));
+    }
+    if channel_count == 0 || channel_count > u8::MAX as u16
+    {
+        return Err(AudioReaderError::UnsupportedFeature("wav channel count"));
+    }
+    if rate == 0
+    {
+        return Err(AudioReaderError::InvalidValue("sample rate"));
+    }
+    if block_align as u32 != channel_count as u32 * container_bits as u32 / 8
+    {
+        return Err(AudioReaderError::InvalidValue("block align"));
+    }
+
+    return Ok(PcmFormat
+    {
+        m_sample_format: sample_format,
+        m_is_big_endian: false,
+        m_channel_count: channel_count as u8,
+        m_rate: rate,
+        m_container_bits: container_bits,
+        m_valid_bits: valid_bits,
+        m_channel_mask: channel_mask.unwrap_or_else(|| get_default_channel_mask(channel_count as u8)),
+    });
+}
+
+/// Decode the text fields of a LIST chunk of type INFO
+fn parse_info_list(data: &[u8]) -> Vec<([u8; 4], String)>
+{
+    let mut info_fields: Vec<([u8; 4], String)> = Vec::new();
+    let mut position = 4;
+    while position + 8 <= data.len()
+    {
+        let field_id: [u8; 4] = data[position..position + 4].try_into().unwrap();
+        let field_size = u32::from_le_bytes(data[position + 4..position + 8].try_into().unwrap()) as usize;
+        let field_end = (position + 8 + field_size).min(data.len());
+        info_fields.push((field_id, decode_chunk_text(&data[position + 8..field_end])));
+        position = field_end + field_size % 2;
+    }
+    return info_fields;
+}
+
+/// Decode the text fields of a bext chunk
+fn parse_bext_chunk(data: &[u8]) -> Result<BextChunk, AudioReaderError>
+{
+    if data.len() < BEXT_FIXED_SIZE
+    {
+        return Err(AudioReaderError::InvalidBlockLength { m_block_name: "bext", m_length: data.len() as u32 });
+    }
+    return Ok(BextChunk
+    {
+        m_description: decode_chunk_text(&data[0..256]),
+        m_originator: decode_chunk_text(&data[256..288]),
+        m_origination_date: decode_chunk_text(&data[320..330]),
+    });
+}
+
+/// Read the chunks of a WAV stream to find its format, its samples and its tags
+///
+/// # Params
+/// source: the stream, placed at its first byte
+/// is_reading_tags: false to stop at the data chunk, when only the samples are needed
+fn read_wav_stream<R: Read + Seek>(source: &mut R, is_reading_tags: bool) -> Result<WavStream, AudioReaderError>
+{
+    let start_offset = source.seek(SeekFrom::Current(0))?;
+    let stream_length = source.seek(SeekFrom::End(0))? - start_offset;
+    source.seek(SeekFrom::Start(start_offset))?;
+
+    //
+    // RF64 and BW64 are RIFF files whose sizes over 4 GB are stored in the ds64 chunk
+    let mut header = [0u8; 12];
+    source.read_exact(&mut header).map_err(|_| AudioReaderError::InvalidMagicNumber)?;
+    let is_rf64 = &header[0..4] == b"RF64" || &header[0..4] == b"BW64";
+    if (&header[0..4] != b"RIFF" && !is_rf64) || &header[8..12] != b"WAVE"
+    {
+        return Err(AudioReaderError::InvalidMagicNumber);
+    }
+
+    let mut format: Option<PcmFormat> = None;
+    let mut data_position: Option<(u64, u64)> = None;
+    let mut ds64_data_size: Option<u64> = None;
+    let mut ds64_chunk_sizes: Vec<([u8; 4], u64)> = Vec::new();
+    let mut info_fields: Vec<([u8; 4], String)> = Vec::new();
+    let mut bext: Option<BextChunk> = None;
+    let mut id3_tag: Option<Id3Tag> = None;
+    let mut chunk_offset: u64 = 12;
+    while chunk_offset + 8 <= stream_length
+    {
+        let mut chunk_header = [0u8; 8];
+        source.read_exact(&mut chunk_header)?;
+        let chunk_id: [u8; 4] = chunk_header[0..4].try_into().unwrap();
+        let mut chunk_size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+        if is_rf64 && chunk_size == RF64_SIZE_IN_DS64 as u64
+        {
+            if &chunk_id == b"data"
+            {
+                chunk_size = ds64_data_size.ok_or(AudioReaderError::MissingBlock("ds64"))?;
+            }
+            else if let Some((_chunk_id, size)) = ds64_chunk_sizes.iter().find(|(id, _size)| *id == chunk_id)
+            {
+                chunk_size = *size;
+            }
+        }
+        let content_offset = chunk_offset + 8;
+
+        match &chunk_id
+        {
+            b"ds64" =>
+            {
+                let data = read_chunk_data(source, chunk_size, "ds64", chunk_offset)?;
+                if data.len() < 28
+                {
+                    return Err(AudioReaderError::InvalidBlockLength { m_block_name: "ds64", m_length: data.len() as u32 });
+                }
+                ds64_data_size = Some(u64::from_le_bytes(data[8..16].try_into().unwrap()));
+                let table_length = u32::from_le_bytes(data[24..28].try_into().unwrap()) as usize;
+                ds64_chunk_sizes = data[28..].chunks_exact(12).take(table_length)
+                    .map(|entry| (entry[0..4].try_into().unwrap(), u64::from_le_bytes(entry[4..12].try_into().unwrap())))
+                    .collect();
+            }
+            b"fmt " =>
+            {
+                format = Some(parse_format_chunk(&read_chunk_data(source, chunk_size, "fmt", chunk_offset)?)?);
+            }
+            b"data" =>
+            {
+                //
+                // A recording stopped before its header was updated can have a data chunk longer than the file
+                let data_length = chunk_size.min(stream_length - content_offset);
+                data_position = Some((content_offset, data_length));
+                if !is_reading_tags && format.is_some()
+                {
+                    break;
+                }
+            }
+            b"LIST" if is_reading_tags =>
+            {
+                let data = read_chunk_data(source, chunk_size, "LIST", chunk_offset)?;
+                if data.starts_with(b"INFO")
+                {
+                    info_fields = parse_info_list(&data);
+                }
+            }
+            b"bext" if is_reading_tags =>
+            {
+                bext = Some(parse_bext_chunk(&read_chunk_data(source, chunk_size, "bext", chunk_offset)?)?);
+            }
+            b"id3 " | b"ID3 " if is_reading_tags =>
+            {
+                //
+                // A damaged ID3 chunk only loses the tags, the audio is still readable
+                id3_tag = Id3Tag::parse(&read_chunk_data(source, chunk_size, "id3", chunk_offset)?).ok();
+            }
+            _ => {}
+        }
+
+        //
+        // The chunks are aligned on 2 bytes
+        chunk_offset = content_offset + chunk_size + chunk_size % 2;
+        source.seek(SeekFrom::Start(start_offset + chunk_offset))?;
+    }
+
+    let format = format.ok_or(AudioReaderError::MissingBlock("fmt"))?;
+    let (data_offset, data_length) = data_position.ok_or(AudioReaderError::MissingBlock("data"))?;
+    return Ok(WavStream
+    {
+        m_format: format,
+        m_data_offset: data_offset,
+        m_data_length: data_length,
+        m_info_fields: info_fields,
+        m_bext: bext,
+        m_id3_tag: id3_tag,
+    });
+}
+
+/// Check if a file is a WAV file, RIFF, RF64 or BW64
+///
+/// # Params
+/// file: the file, placed at its first byte
+pub fn is_wav_file(file: &File) -> bool
+{
+    let mut header = [0u8; 12];
+    if (&*file).read_exact(&mut header).is_err()
+    {
+        return false;
+    }
+    return (&header[0..4] == b"RIFF" || &header[0..4] == b"RF64" || &header[0..4] == b"BW64") && &header[8..12] == b"WAVE";
+}
+
+impl AudioReader for WavReader
+{
+    fn read_information(&self, str_path_to_music: String) -> Result<AudioInformation, AudioReaderError>
+    {
+        let wav_stream = read_wav_stream(&mut File::open(str_path_to_music)?, true)?;
+        let block_align = wav_stream.m_format.get_block_align();
+        let mut audio_information = AudioInformation
+        {
+            m_metadata: TrackMetadata::new(),
+            m_rate: wav_stream.m_format.m_rate,
+            m_channel_count: wav_stream.m_format.m_channel_count,
+            m_bits_per_sample: wav_stream.m_format.m_valid_bits as u8,
+            m_total_samples: if block_align != 0 { wav_stream.m_data_length / block_align } else { 0 },
+            m_bitrate: wav_stream.m_format.get_bitrate(),
+            m_codec: wav_stream.m_format.get_codec_name().to_string(),
+            m_is_lossless: true,
+            m_pictures: Vec::new(),
+            m_tracks: Vec::new(),
+            m_comments: Vec::new(),
+            m_cue_points: Vec::new(),
+            m_loops: Vec::new(),
+        };
+
+        //
+        // The tags are applied from the least to the most precise, each one replacing the fields it has
+        if let Some(bext) = wav_stream.m_bext
+        {
+            let mut metadata = TrackMetadata::new();
+            metadata.m_title = bext.m_description;
+            metadata.m_artists = Some(bext.m_originator).filter(|originator| !originator.is_empty()).into_iter().collect();
+            metadata.m_date = TagDate::parse(&bext.m_origination_date);
+            audio_information.m_metadata.apply(metadata);
+        }
+        let mut info_metadata = TrackMetadata::new();
+        for (field_id, value) in wav_stream.m_info_fields
+        {
+            if value.is_empty()
+            {
+                continue;
+            }
+            match &field_id
+            {
+                b"INAM" => info_metadata.m_title = value,
+                b"IART" => info_metadata.m_artists.push(value),
+                b"IPRD" => info_metadata.m_album = value,
+                b"ICRD" => info_metadata.m_date = TagDate::parse(&value),
+                b"IGNR" => info_metadata.m_genres.push(value),
+                b"ITRK" | b"IPRT" => info_metadata.set_track_text(&value),
+                _ => info_metadata.add_extra(&String::from_utf8_lossy(&field_id), value),
+            }
+        }
+        audio_information.m_metadata.apply(info_metadata);
+        if let Some(id3_tag) = wav_stream.m_id3_tag
+        {
+            id3_tag.apply_to_information(&mut audio_information);
+        }
+
+        return Ok(audio_information);
+    }
+}
+
+/// Open a WAV file and find its samples
+///
+/// # Params
+/// str_path_to_music: the path of the WAV file
+pub fn open_wav_decoder(str_path_to_music: String) -> Result<PcmDecoder<File>, AudioReaderError>
+{
+    return create_wav_decoder(File::open(str_path_to_music)?);
+}
+
+/// Create a decoder reading a WAV stream from a source, ready to decode the first sample
+///
+/// # Params
+/// source: the source of the WAV stream, placed at the beginning of the stream
+pub fn create_wav_decoder<R: Read + Seek>(mut source: R) -> Result<PcmDecoder<R>, AudioReaderError>
+{
+    let start_offset = source.seek(SeekFrom::Current(0))?;
+    let wav_stream = read_wav_stream(&mut source, false)?;
+    return PcmDecoder::new(source, wav_stream.m_format, start_offset + wav_stream.m_data_offset, wav_stream.m_data_length);
+}
+
+#[cfg(test)]
+mod test_wav_reader
+{
+    use crate::audio_reader::{AudioReader, TagDate};
+    use crate::audio_reader::pcm_decoder::PcmSampleFormat;
+    use crate::audio_reader::wav_reader::{open_wav_decoder, write_chunk, WavReader};
+
+    /// Create the content of a fmt chunk
+    ///
+    /// # Params
+    /// format_tag: 1 for PCM, 3 for float
+    /// valid_bits: the significant bits of the samples, written in a WAVE_FORMAT_EXTENSIBLE chunk when set
+    fn create_format_chunk(format_tag: u16, channel_count: u16, rate: u32, container_bits: u16, valid_bits: Option<(u16, u32)>) -> Vec<u8>
+    {
+        let block_align = channel_count * container_bits / 8;
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&(if valid_bits.is_some() { 0xFFFE } else { format_tag }).to_le_bytes());
+        data.extend_from_slice(&channel_count.to_le_bytes());
+        data.extend_from_slice(&rate.to_le_bytes());
+        data.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+        data.extend_from_slice(&block_align.to_le_bytes());
+        data.extend_from_slice(&container_bits.to_le_bytes());
+        if let Some((valid_bits, channel_mask)) = valid_bits
+        {
+            data.extend_from_slice(&22u16.to_le_bytes());
+            data.extend_from_slice(&valid_bits.to_le_bytes());
+            data.extend_from_slice(&channel_mask.to_le_bytes());
+            data.extend_from_slice(&format_tag.to_le_bytes());
+            data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+        }
+        return data;
+    }
+
+    /// Create a RIFF file from its chunks, or a RF64 file with a ds64 chunk giving the sizes
+    fn create_wav_file(chunks: &[u8], is_rf64: bool, data_size: u64) -> Vec<u8>
+    {
+        let mut wav_data: Vec<u8> = Vec::new();
+        if is_rf64
+        {
+            wav_data.extend_from_slice(b"RF64");
+            wav_data.extend_from_slice(&0xFFFFFFFFu32.to_le_bytes());
+            wav_data.extend_from_slice(b"WAVE");
+            let mut ds64: Vec<u8> = Vec::new();
+            ds64.extend_from_slice(&(4 + 36 + chunks.len() as u64).to_le_bytes());
+            ds64.extend_from_slice(&data_size.to_le_bytes());
+            ds64.extend_from_slice(&0u64.to_le_bytes());
+            ds64.extend_from_slice(&0u32.to_le_bytes());
+            write_chunk(&mut wav_data, b"ds64", &ds64, false);
+        }
+        else
+        {
+            wav_data.extend_from_slice(b"RIFF");
+            wav_data.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+            wav_data.extend_from_slice(b"WAVE");
+        }
+        wav_data.extend_from_slice(chunks);
+        return wav_data;
+    }
+
+    #[test]
+    fn decode_wav_files()
+    {
+        let values: Vec<f64> = (0..1001).map(|i| (i as f64 * 0.05).sin() * 0.99).collect();
+        let path = std::env::temp_dir().join("quadrium_decode.wav");
+        let str_path = path.to_string_lossy().to_string();
+
+        //
+        // Each format: the tag, the container bits, the valid bits and channel mask of WAVE_FORMAT_EXTENSIBLE, RF64
+        for &(format_tag, container_bits, extensible, is_rf64) in [(1u16, 8u16, None, false), (1, 16, None, false), (1, 24, None, true),
+                                                                  (1, 32, None, false), (1, 24, Some((20u16, 0x60Fu32)), false),
+                                                                  (3, 32, None, false), (3, 64, Some((64, 0x3)), true)].iter()
+        {
+            let channel_count: u16 = 2;
+            let significant_bits = extensible.map_or(container_bits, |(valid_bits, _channel_mask)| valid_bits);
+            let mut audio_data: Vec<u8> = Vec::new();
+            let mut expected_samples: Vec<i32> = Vec::new();
+            for value in values.iter().flat_map(|value| vec![*value, -*value])
+            {
+                if format_tag == 3
+                {
+                    if container_bits == 32
+                    {
+                        audio_data.extend_from_slice(&(value as f32).to_le_bytes());
+                        expected_samples.push((value as f32 as f64 * 2147483648.0).round() as i32);
+                    }
+                    else
+                    {
+                        audio_data.extend_from_slice(&value.to_le_bytes());
+                        expected_samples.push((value * 2147483648.0).round() as i32);
+                    }
+                    continue;
+                }
+
+                let sample = (value * ((1i64 << (significant_bits - 1)) - 1) as f64).round() as i64;
+                expected_samples.push(sample as i32);
+                let stored_value = (sample << (container_bits - significant_bits)) as u64;
+                let stored_value = if container_bits == 8 { stored_value.wrapping_add(128) } else { stored_value };
+                audio_data.extend_from_slice(&stored_value.to_le_bytes()[..container_bits as usize / 8]);
+            }
+
+            let mut chunks: Vec<u8> = Vec::new();
+            write_chunk(&mut chunks, b"fmt ", &create_format_chunk(format_tag, channel_count, 48000, container_bits, extensible), false);
+            if is_rf64
+            {
+                chunks.extend_from_slice(b"data");
+                chunks.extend_from_slice(&0xFFFFFFFFu32.to_le_bytes());
+                chunks.extend_from_slice(&audio_data);
+            }
+            else
+            {
+                write_chunk(&mut chunks, b"data", &audio_data, false);
+            }
+            std::fs::write(&path, create_wav_file(&chunks, is_rf64, audio_data.len() as u64)).unwrap();
+
+            let mut decoder = open_wav_decoder(str_path.clone()).unwrap();
+            assert_eq!(decoder.get_total_samples(), values.len() as u64);
+            let expected_sample_format = match (format_tag, container_bits)
+            {
+                (3, _) => PcmSampleFormat::Float,
+                (_, 8) => PcmSampleFormat::UnsignedInteger,
+                _ => PcmSampleFormat::SignedInteger,
+            };
+            assert_eq!(decoder.get_sample_format(), expected_sample_format);
+            assert_eq!(decoder.get_channel_mask(), extensible.map_or(0x3, |(_valid_bits, channel_mask)| channel_mask));
+            assert_eq!(decoder.get_bits_per_sample() as u16, if format_tag == 3 { 32 } else { significant_bits });
+            assert!(decoder.read_all_samples().unwrap() == expected_samples, "format {0} on {1} bits: the samples are different", format_tag, container_bits);
+
+            decoder.seek(999).unwrap();
+            let decoded_block = decoder.read_next_block().unwrap().unwrap();
+            assert_eq!(decoded_block.m_first_sample, 999);
+            assert_eq!(decoded_block.m_samples, expected_samples[1998..]);
+            assert!(decoder.read_next_block().unwrap().is_none());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_wav_tags()
+    {
+        let path = std::env::temp_dir().join("quadrium_tags.wav");
+        let str_path = path.to_string_lossy().to_string();
+
+        let mut bext = vec![0u8; 602];
+        bext[..11].copy_from_slice(b"Description");
+        bext[256..266].copy_from_slice(b"Originator");
+        bext[320..330].copy_from_slice(b"2001-02-03");
+        let mut info = b"INFO".to_vec();
+        write_chunk(&mut info, b"INAM", b"Title\0", false);
+        write_chunk(&mut info, b"IART", "Artiste \u{E9}\0".as_bytes(), false);
+        write_chunk(&mut info, b"IPRD", b"Album\0", false);
+
+        //
+        // ID3v2.3 tag with an UTF-16 album, which replaces the one of the INFO list
+        let album_text: Vec<u8> = [1u8, 0xFF, 0xFE].iter().cloned().chain("Id3 album".encode_utf16().flat_map(|unit| unit.to_le_bytes().to_vec())).collect();
+        let mut id3_frames: Vec<u8> = Vec::new();
+        for (frame_id, frame_data) in [(b"TALB", album_text), (b"TCON", b"\0Jazz".to_vec())].iter()
+        {
+            id3_frames.extend_from_slice(*frame_id);
+            id3_frames.extend_from_slice(&(frame_data.len() as u32).to_be_bytes());
+            id3_frames.extend_from_slice(&[0, 0]);
+            id3_frames.extend_from_slice(frame_data);
+        }
+        let mut id3 = b"ID3\x03\x00\x00".to_vec();
+        id3.extend_from_slice(&[0, 0, (id3_frames.len() >> 7) as u8, (id3_frames.len() & 0x7F) as u8]);
+        id3.extend_from_slice(&id3_frames);
+
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"fmt ", &create_format_chunk(1, 1, 44100, 16, None), false);
+        write_chunk(&mut chunks, b"bext", &bext, false);
+        write_chunk(&mut chunks, b"data", &[1, 0, 2, 0, 3], false);
+        write_chunk(&mut chunks, b"LIST", &info, false);
+        write_chunk(&mut chunks, b"id3 ", &id3, false);
+        std::fs::write(&path, create_wav_file(&chunks, false, 0)).unwrap();
+
+        let audio_information = WavReader {}.read_information(str_path.clone()).unwrap();
+        assert_eq!(audio_information.m_rate, 44100);
+        assert_eq!(audio_information.m_channel_count, 1);
+        assert_eq!(audio_information.m_bits_per_sample, 16);
+        assert_eq!(audio_information.m_metadata.m_title, "Title");
+        assert_eq!(audio_information.m_metadata.m_artists, vec!["Artiste \u{E9}"]);
+        assert_eq!(audio_information.m_metadata.m_album, "Id3 album");
+        assert_eq!(audio_information.m_metadata.m_genres, vec!["Jazz"]);
+        assert_eq!(audio_information.m_metadata.m_date, Some(TagDate { m_year: 2001, m_month: Some(2), m_day: Some(3) }));
+        assert_eq!(audio_information.m_codec, "PCM");
+        assert_eq!(audio_information.m_bitrate, 705600);
+
+        //
+        // The odd byte of the data chunk is not a whole sample
+        assert_eq!(audio_information.m_total_samples, 2);
+        assert_eq!(open_wav_decoder(str_path.clone()).unwrap().read_all_samples().unwrap(), vec![1, 2]);
+
+        //
+        // A damaged ID3 chunk is ignored, the INFO list is still read
+        let mut chunks: Vec<u8> = Vec::new();
+        write_chunk(&mut chunks, b"fmt ", &create_format_chunk(1, 1, 44100, 16, None), false);
+        write_chunk(&mut chunks, b"data", &[1, 0, 2, 0], false);
+        write_chunk(&mut chunks, b"LIST", &info, false);
+        write_chunk(&mut chunks, b"id3 ", b"ID3\x09\x00\x00\x00\x00\x00\x10", false);
+        std::fs::write(&path, create_wav_file(&chunks, false, 0)).unwrap();
+        let audio_information = WavReader {}.read_information(str_path).unwrap();
+        assert_eq!(audio_information.m_metadata.m_title, "Title");
+        assert_eq!(audio_information.m_metadata.m_album, "Album");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -21,9 +21,99 @@ mod utils;
 mod GUI;
 mod Controller;
 
-use crate::audio_reader::flac_reader::FlacReader;
+use crate::audio_reader::cue_sheet::read_cue_tracks;
+use crate::audio_reader::flac_reader::{FlacMd5Status, FlacReader};
+use crate::audio_reader::loudness_scanner::{scan_library, LoudnessResult};
+use crate::audio_reader::reader_registry::AudioReaderRegistry;
 use crate::audio_reader::AudioReader;
 use std::env;
+use std::sync::atomic::AtomicBool;
+
+/// Verify the MD5 signature of each flac file given and print the result of each file
+///
+/// # Params
+/// file_paths: the paths of the flac files to verify
+fn verify_files(file_paths: &[String])
+{
+    let reader: FlacReader = FlacReader {};
+    for file_path in file_paths
+    {
+        match reader.verify(file_path.clone())
+        {
+            Err(error) => println!("{0}: ERROR {1:?}", file_path, error),
+            Ok(verification) =>
+            {
+                let status = match verification.m_md5_status
+                {
+                    FlacMd5Status::Match => "OK",
+                    FlacMd5Status::Mismatch => "MD5 MISMATCH",
+                    FlacMd5Status::NoSignature => "NO MD5 STORED",
+                };
+                println!("{0}: {1}", file_path, status);
+                for frame_error in verification.m_frame_errors
+                {
+                    println!("    corrupted frame at sample {0} (byte {1}): {2:?}", frame_error.m_sample_position, frame_error.m_byte_position, frame_error.m_error);
+                }
+            }
+        }
+    }
+}
+
+/// Format the loudness of a title or an album with its ReplayGain 2.0 gain
+fn format_loudness(loudness_result: &LoudnessResult) -> String
+{
+    let gain = loudness_result.get_replay_gain().map_or(String::from("none"), |replay_gain| format!("{:+.2} dB", replay_gain.m_gain));
+    return format!("{0:.2} LUFS, range {1:.2} LU, true peak {2:.2} dBTP, gain {3}", loudness_result.m_integrated_loudness, loudness_result.m_loudness_range,
+        20.0 * loudness_result.m_true_peak.log10(), gain);
+}
+
+/// Measure the loudness of the files given, grouped by album, print it and write the gains in their tags
+///
+/// # Params
+/// paths: the paths of the files and directories to scan, starting with "--dry-run" to not write the tags
+fn scan_loudness(paths: &[String])
+{
+    let should_write_tags = paths.first().map_or(true, |path| path != "--dry-run");
+    let paths = if should_write_tags { paths } else { &paths[1..] };
+    let registry = AudioReaderRegistry::with_default_readers();
+    let mut last_album_result: Option<LoudnessResult> = None;
+    scan_library(&registry, paths, should_write_tags, &AtomicBool::new(false), |scan_result| match scan_result
+    {
+        Err(error) => println!("{0}: ERROR {1}", error.m_str_path_to_music, error.m_str_error),
+        Ok(scan_result) =>
+        {
+            if scan_result.m_album_result.is_some() && scan_result.m_album_result != last_album_result
+            {
+                println!("album: {0}", format_loudness(scan_result.m_album_result.as_ref().unwrap()));
+            }
+            last_album_result = scan_result.m_album_result;
+            println!("{0}: {1}", scan_result.m_str_path_to_music, format_loudness(&scan_result.m_track_result));
+        }
+    });
+}
+
+/// Print the tracks of a cue sheet with the part of the file each one covers
+///
+/// # Params
+/// registry: the registry reading the files of the sheet
+/// cue_sheet_path: the path of the cue sheet
+fn print_cue_tracks(registry: &AudioReaderRegistry, cue_sheet_path: &str)
+{
+    match read_cue_tracks(registry, cue_sheet_path)
+    {
+        Err(error) => println!("\nCannot read the tracks of the cue sheet: {0}", error),
+        Ok(cue_tracks) =>
+        {
+            for cue_track in cue_tracks
+            {
+                let metadata = &cue_track.m_information.m_metadata;
+                let duration = cue_track.m_information.get_duration().map_or(String::from("unknown"), |duration| format!("{:.3} s", duration.as_secs_f64()));
+                println!("\nTrack {0:02}: {1} - {2}\nfile: {3}\nsamples: {4} to {5}\nduration: {6}", cue_track.m_track.m_track_number, metadata.m_artists.join(", "),
+                    metadata.m_title, cue_track.m_str_path_to_music, cue_track.m_track.m_start_sample, cue_track.m_track.m_end_sample, duration);
+            }
+        }
+    }
+}
 
 fn main() -> std::io::Result<()>
 {
@@ -32,32 +122,71 @@ fn main() -> std::io::Result<()>
     let args: Vec<String> = env::args().collect();
     if args.len() == 0
     {
-        panic!("Not enough arguments. Usage: \nQuadrium path/file/to/flac.flac");
+        panic!("Not enough arguments. Usage: \nQuadrium path/file/to/music\nQuadrium path/file/to/sheet.cue\nQuadrium --verify path/file/to/flac.flac...\nQuadrium --scan-loudness [--dry-run] path/to/music...");
     }
 
     if args.get(1).unwrap().is_empty()
     {
         panic!("No file given");
     }
+
+    //
+    // Check the integrity of the files without launching the GUI
+    if args[1] == "--verify"
+    {
+        verify_files(&args[2..]);
+        return Ok(());
+    }
+
+    //
+    // Measure the loudness and write the gains without launching the GUI
+    if args[1] == "--scan-loudness"
+    {
+        scan_loudness(&args[2..]);
+        return Ok(());
+    }
     let file_path = &args[1].clone();
     println!("file_path: {0}", file_path);
 
-    let file = std::fs::File::open(file_path.clone())?;
-    let b_is_flac_file = audio_reader::flac_reader::is_flac_file(&file);
-    if b_is_flac_file
+    let registry = AudioReaderRegistry::with_default_readers();
+
+    //
+    // A cue sheet describes the tracks of other files
+    if file_path.to_ascii_lowercase().ends_with(".cue")
     {
-        println!("It's a flac file");
+        print_cue_tracks(&registry, file_path);
+        GUI::launch_gui();
+        return Ok(());
     }
-    else
+
+    match registry.find_reader(file_path)
     {
-        println!("It's not a flac file");
+        Ok(registered_reader) => println!("It's a {0} file", registered_reader.m_format.get_name()),
+        Err(error) => println!("Cannot find the format of the file: {0}", error),
     }
 
-    let reader: FlacReader = FlacReader {};
-    let file_path = file_path;
-    let audio_information = reader.read_information(file_path.clone());
-    println!("\nAudio Information\nrate: {0}\nbits per sample: {1}\nchannel count: {2}", audio_information.m_rate, audio_information.m_bits_per_sample, audio_information.m_channel_count);
-    println!("\nTrack information:\nTrackname: {0}\nArtist: {1}\nAlbum: {2}\nDate: {3}\nGenre: {4}", audio_information.m_str_music_name, audio_information.m_str_artist_name, audio_information.m_str_album, audio_information.m_str_date, audio_information.m_str_music_type);
+    match registry.read_information(file_path.clone())
+    {
+        Ok(audio_information) =>
+        {
+            let metadata = &audio_information.m_metadata;
+            let duration = audio_information.get_duration().map_or(String::from("unknown"), |duration| format!("{:.3} s", duration.as_secs_f64()));
+            let date = metadata.m_date.map_or(String::new(), |date| date.to_string());
+            let track_number = metadata.m_track_number.map_or(String::new(), |number| number.to_string());
+            let track_total = metadata.m_track_total.map_or(String::new(), |total| format!("/{0}", total));
+            println!("\nAudio Information\ncodec: {0}\nlossless: {1}\nrate: {2}\nbits per sample: {3}\nchannel count: {4}\nduration: {5}\nbitrate: {6} kbit/s",
+                audio_information.m_codec, audio_information.m_is_lossless, audio_information.m_rate, audio_information.m_bits_per_sample,
+                audio_information.m_channel_count, duration, audio_information.m_bitrate / 1000);
+            println!("\nTrack information:\nTrackname: {0}\nArtist: {1}\nAlbum: {2}\nAlbum artist: {3}\nComposer: {4}\nTrack: {5}{6}\nDate: {7}\nGenre: {8}",
+                metadata.m_title, metadata.m_artists.join(", "), metadata.m_album, metadata.m_album_artists.join(", "), metadata.m_composers.join(", "),
+                track_number, track_total, date, metadata.m_genres.join(", "));
+            for (key, values) in metadata.m_extra.iter()
+            {
+                println!("{0}: {1}", key, values.join(", "));
+            }
+        }
+        Err(error) => println!("\nCannot read the information of the file: {0}", error),
+    }
 
     GUI::launch_gui();
 
@@ -0,0 +1,186 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/// Reader of a big endian bit stream stored inside a slice of bytes.
+/// Used to decode the audio frames, where the fields are not aligned on bytes.
+/// All the read functions return None when there is not enough data left inside the slice.
+pub struct BitReader<'a>
+{
+    m_data: &'a [u8],
+    m_byte_position: usize,
+    m_bit_position: u32,
+}
+
+impl<'a> BitReader<'a>
+{
+    /// Create a bit reader starting at the first bit of the slice
+    pub fn new(data: &'a [u8]) -> BitReader<'a>
+    {
+        return BitReader
+        {
+            m_data: data,
+            m_byte_position: 0,
+            m_bit_position: 0,
+        };
+    }
+
+    /// Get the position of the current byte in the slice
+    pub fn get_byte_position(&self) -> usize
+    {
+        return self.m_byte_position;
+    }
+
+    /// Return true if the next bit to read is the first bit of a byte
+    pub fn is_byte_aligned(&self) -> bool
+    {
+        return self.m_bit_position == 0;
+    }
+
+    /// Skip the remaining bits of the current byte
+    pub fn align_to_byte(&mut self)
+    {
+        if self.m_bit_position != 0
+        {
+            self.m_bit_position = 0;
+            self.m_byte_position += 1;
+        }
+    }
+
+    /// Read bytes, the reader must be aligned on a byte
+    pub fn read_bytes(&mut self, byte_count: usize) -> Option<&'a [u8]>
+    {
+        debug_assert!(self.is_byte_aligned());
+        if byte_count > self.m_data.len() - self.m_byte_position
+        {
+            return None;
+        }
+
+        let bytes = &self.m_data[self.m_byte_position..self.m_byte_position + byte_count];
+        self.m_byte_position += byte_count;
+        return Some(bytes);
+    }
+
+    /// Read one bit
+    pub fn read_bit(&mut self) -> Option<bool>
+    {
+        if self.m_byte_position >= self.m_data.len()
+        {
+            return None;
+        }
+
+        let bit = (self.m_data[self.m_byte_position] >> (7 - self.m_bit_position)) & 1;
+        self.m_bit_position += 1;
+        if self.m_bit_position == 8
+        {
+            self.m_bit_position = 0;
+            self.m_byte_position += 1;
+        }
+
+        return Some(bit == 1);
+    }
+
+    /// Read an unsigned value written on bit_count bits (64 bits max)
+    pub fn read_bits_u64(&mut self, bit_count: u32) -> Option<u64>
+    {
+        debug_assert!(bit_count <= 64);
+        let mut value: u64 = 0;
+        let mut bits_left = bit_count;
+        while bits_left > 0
+        {
+            if self.m_byte_position >= self.m_data.len()
+            {
+                return None;
+            }
+
+            //
+            // Take as many bits as possible from the current byte
+            let available_bits = 8 - self.m_bit_position;
+            let bits_to_take = available_bits.min(bits_left);
+            let current_byte = self.m_data[self.m_byte_position] as u64;
+            let shift = available_bits - bits_to_take;
+            let bits = (current_byte >> shift) & ((1 << bits_to_take) - 1);
+            value = (value << bits_to_take) | bits;
+
+            bits_left -= bits_to_take;
+            self.m_bit_position += bits_to_take;
+            if self.m_bit_position == 8
+            {
+                self.m_bit_position = 0;
+                self.m_byte_position += 1;
+            }
+        }
+
+        return Some(value);
+    }
+
+    /// Read an unsigned value written on bit_count bits (32 bits max)
+    pub fn read_bits(&mut self, bit_count: u32) -> Option<u32>
+    {
+        debug_assert!(bit_count <= 32);
+        return self.read_bits_u64(bit_count).map(|value| value as u32);
+    }
+
+    /// Read a two's complement signed value written on bit_count bits (64 bits max)
+    pub fn read_signed_bits(&mut self, bit_count: u32) -> Option<i64>
+    {
+        if bit_count == 0
+        {
+            return Some(0);
+        }
+
+        let value = self.read_bits_u64(bit_count)?;
+        let shift = 64 - bit_count;
+        return Some(((value << shift) as i64) >> shift);
+    }
+
+    /// Read an unary coded value: the number of 0 before the next 1
+    pub fn read_unary(&mut self) -> Option<u32>
+    {
+        let mut value: u32 = 0;
+        loop
+        {
+            if self.m_byte_position >= self.m_data.len()
+            {
+                return None;
+            }
+
+            //
+            // Count the leading zeros of the remaining bits of the current byte in one step
+            let remaining_bits = (self.m_data[self.m_byte_position] << self.m_bit_position) as u32;
+            let available_bits = 8 - self.m_bit_position;
+            if remaining_bits == 0
+            {
+                value += available_bits;
+                self.m_bit_position = 0;
+                self.m_byte_position += 1;
+                continue;
+            }
+
+            let zero_count = (remaining_bits as u8).leading_zeros();
+            value += zero_count;
+            self.m_bit_position += zero_count + 1;
+            if self.m_bit_position == 8
+            {
+                self.m_bit_position = 0;
+                self.m_byte_position += 1;
+            }
+
+            return Some(value);
+        }
+    }
+}
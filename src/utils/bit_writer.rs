@@ -0,0 +1,140 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/// Writer of a big endian bit stream inside a vector of bytes.
+/// Used to encode the audio frames, where the fields are not aligned on bytes.
+pub struct BitWriter
+{
+    m_data: Vec<u8>,
+
+    /// Bits not written in the data yet, aligned on the right
+    m_pending_bits: u64,
+    m_pending_bit_count: u32,
+}
+
+impl BitWriter
+{
+    pub fn new() -> BitWriter
+    {
+        return BitWriter
+        {
+            m_data: Vec::new(),
+            m_pending_bits: 0,
+            m_pending_bit_count: 0,
+        };
+    }
+
+    /// Get the number of bits written
+    pub fn get_bit_count(&self) -> usize
+    {
+        return self.m_data.len() * 8 + self.m_pending_bit_count as usize;
+    }
+
+    /// Return true if the next bit written is the first bit of a byte
+    pub fn is_byte_aligned(&self) -> bool
+    {
+        return self.m_pending_bit_count == 0;
+    }
+
+    /// Write an unsigned value on bit_count bits (64 bits max)
+    pub fn write_bits_u64(&mut self, value: u64, bit_count: u32)
+    {
+        debug_assert!(bit_count <= 64);
+        let mut bits_left = bit_count;
+        while bits_left > 0
+        {
+            //
+            // Add the bits by chunks to never overflow the pending bits
+            let bits_to_take = bits_left.min(32);
+            bits_left -= bits_to_take;
+            let bits = (value >> bits_left) & ((1u64 << bits_to_take) - 1);
+            self.m_pending_bits = (self.m_pending_bits << bits_to_take) | bits;
+            self.m_pending_bit_count += bits_to_take;
+
+            while self.m_pending_bit_count >= 8
+            {
+                self.m_pending_bit_count -= 8;
+                self.m_data.push((self.m_pending_bits >> self.m_pending_bit_count) as u8);
+            }
+        }
+    }
+
+    /// Write an unsigned value on bit_count bits (32 bits max)
+    pub fn write_bits(&mut self, value: u32, bit_count: u32)
+    {
+        debug_assert!(bit_count <= 32);
+        self.write_bits_u64(value as u64, bit_count);
+    }
+
+    /// Write a two's complement signed value on bit_count bits (64 bits max)
+    pub fn write_signed_bits(&mut self, value: i64, bit_count: u32)
+    {
+        self.write_bits_u64(value as u64, bit_count);
+    }
+
+    /// Write an unary coded value: value bits at 0 followed by a bit at 1
+    pub fn write_unary(&mut self, value: u32)
+    {
+        let mut zero_count = value;
+        while zero_count >= 32
+        {
+            self.write_bits(0, 32);
+            zero_count -= 32;
+        }
+        self.write_bits(1, zero_count + 1);
+    }
+
+    /// Write all the bits written by another writer
+    pub fn append(&mut self, other: &BitWriter)
+    {
+        if self.is_byte_aligned()
+        {
+            self.m_data.extend_from_slice(&other.m_data);
+        }
+        else
+        {
+            for byte in other.m_data.iter()
+            {
+                self.write_bits(*byte as u32, 8);
+            }
+        }
+        self.write_bits_u64(other.m_pending_bits, other.m_pending_bit_count);
+    }
+
+    /// Complete the current byte with bits at 0
+    pub fn align_to_byte(&mut self)
+    {
+        if self.m_pending_bit_count != 0
+        {
+            self.write_bits(0, 8 - self.m_pending_bit_count);
+        }
+    }
+
+    /// Get the complete bytes written
+    pub fn get_bytes(&self) -> &[u8]
+    {
+        return &self.m_data;
+    }
+
+    /// Complete the current byte and get all the bytes written
+    pub fn into_bytes(mut self) -> Vec<u8>
+    {
+        self.align_to_byte();
+        return self.m_data;
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ *     Quadrium - Music Player in Rust
+ *     Copyright (C) 2023  SIL3nCe beta-ray70
+ *
+ *     This program is free software: you can redistribute it and/or modify
+ *     it under the terms of the GNU General Public License as published by
+ *     the Free Software Foundation, either version 3 of the License, or
+ *     any later version.
+ *
+ *     This program is distributed in the hope that it will be useful,
+ *     but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *     GNU General Public License for more details.
+ *
+ *     You should have received a copy of the GNU General Public License
+ *     along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//
+// CRCs used by the flac frames and the ogg pages, without reflection and with an initial value of 0
+
+/// Table of the CRC-8 with the polynomial x^8 + x^2 + x^1 + x^0
+const CRC8_TABLE: [u8; 256] = create_crc8_table();
+
+/// Table of the CRC-16 with the polynomial x^16 + x^15 + x^2 + x^0
+const CRC16_TABLE: [u16; 256] = create_crc16_table();
+
+/// Table of the CRC-32 with the polynomial 0x04C11DB7
+const CRC32_TABLE: [u32; 256] = create_crc32_table();
+
+const fn create_crc8_table() -> [u8; 256]
+{
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256
+    {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8
+        {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    return table;
+}
+
+const fn create_crc16_table() -> [u16; 256]
+{
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256
+    {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8
+        {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    return table;
+}
+
+const fn create_crc32_table() -> [u32; 256]
+{
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256
+    {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8
+        {
+            crc = if crc & 0x80000000 != 0 { (crc << 1) ^ 0x04C11DB7 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    return table;
+}
+
+/// Compute the CRC-8 of the data, used by the flac frame headers
+pub fn compute_crc8(data: &[u8]) -> u8
+{
+    let mut crc: u8 = 0;
+    for byte in data
+    {
+        crc = CRC8_TABLE[(crc ^ byte) as usize];
+    }
+    return crc;
+}
+
+/// Compute the CRC-16 of the data, used by the flac frame footers
+pub fn compute_crc16(data: &[u8]) -> u16
+{
+    let mut crc: u16 = 0;
+    for byte in data
+    {
+        crc = (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize];
+    }
+    return crc;
+}
+
+/// Continue the CRC-32 of data split in several parts, used by the ogg pages
+///
+/// # Params
+/// crc: the CRC of the previous parts, 0 for the first one
+/// data: the next part of the data
+pub fn update_crc32(mut crc: u32, data: &[u8]) -> u32
+{
+    for byte in data
+    {
+        crc = (crc << 8) ^ CRC32_TABLE[((crc >> 24) as u8 ^ byte) as usize];
+    }
+    return crc;
+}
@@ -18,39 +18,47 @@
 
 use std::io::Read;
 
-pub fn read_u8_from_file(mut file: &std::fs::File) -> u8
+pub fn read_u8_from_file(mut file: &std::fs::File) -> std::io::Result<u8>
 {
     let u8_value : u8;
     let mut au8_buffer_magic = [0; 1];
-    let _result = file.read_exact(&mut au8_buffer_magic);
+    file.read_exact(&mut au8_buffer_magic)?;
     u8_value = au8_buffer_magic[0];
     if cfg!(target_endian = "big")
     {
-        return u8_value;
+        return Ok(u8_value);
     }
     else
     {
-        return u8_value.swap_bytes();
+        return Ok(u8_value.swap_bytes());
     }
 }
 
-pub fn read_u16_from_file(mut file: &std::fs::File) -> u16
+/// Read a given number of bytes from the file.
+/// Returns an error of kind UnexpectedEof if the end of the file is reached before.
+pub fn read_bytes_from_file(file: &std::fs::File, byte_count: usize) -> std::io::Result<Vec<u8>>
 {
-    let u16_value : u16;
-    let mut au8_buffer = [0; 2];
-    let _result = file.read_exact(&mut au8_buffer);
-    u16_value = au8_buffer[0] as u16 + (au8_buffer[1] as u16) << 8;
-    if cfg!(target_endian = "big")
-    {
-        return u16_value;
-    }
-    else
+    let mut au8_buffer: Vec<u8> = Vec::new();
+    file.take(byte_count as u64).read_to_end(&mut au8_buffer)?;
+    if au8_buffer.len() != byte_count
     {
-        return u16_value.swap_bytes();
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
     }
+    return Ok(au8_buffer);
+}
+
+pub fn read_u16_from_file(mut file: &std::fs::File) -> std::io::Result<u16>
+{
+    let mut au8_buffer = [0; 2];
+    file.read_exact(&mut au8_buffer)?;
+
+    //
+    // Like the other functions, the first byte read is the least significant one.
+    // The caller swaps the bytes to get the big endian value.
+    return Ok(au8_buffer[0] as u16 | ((au8_buffer[1] as u16) << 8));
 }
 
-pub fn read_u32_from_file(mut file: &std::fs::File) -> u32
+pub fn read_u32_from_file(mut file: &std::fs::File) -> std::io::Result<u32>
 {
     let mut u32_value: u32 = 0;
     let mut au8_buffer = [0; 4];
@@ -59,7 +67,7 @@ pub fn read_u32_from_file(mut file: &std::fs::File) -> u32
     // We read exactly 32 bits of data in the file.
     // Depending of the architecture of the processor, the data can be in little endian or in big endian.
     // So if the data in the file are in little endian, you must swap the data before to make bitwise operation (TO VERIFY).
-    let _result = file.read_exact(&mut au8_buffer);
+    file.read_exact(&mut au8_buffer)?;
 
     let mut u32_valuetmp : u32;
 
@@ -74,7 +82,7 @@ pub fn read_u32_from_file(mut file: &std::fs::File) -> u32
             u32_value += u32_valuetmp;
         }
 
-        return u32_value;
+        return Ok(u32_value);
     }
     else
     {
@@ -87,11 +95,11 @@ pub fn read_u32_from_file(mut file: &std::fs::File) -> u32
             u32_value += u32_valuetmp;
         }
 
-        return u32_value;
+        return Ok(u32_value);
     }
 }
 
-pub fn read_u64_from_file(mut file: &std::fs::File) -> u64
+pub fn read_u64_from_file(mut file: &std::fs::File) -> std::io::Result<u64>
 {
     let mut u64_value: u64 = 0;
     let mut au8_buffer = [0; 8];
@@ -100,7 +108,7 @@ pub fn read_u64_from_file(mut file: &std::fs::File) -> u64
     // We read exactly 64 bits of data in the file.
     // Depending of the architecture of the processor, the data can be in little endian or in big endian.
     // So if the data in the file are in little endian, you must swap the data before to make bitwise operation (TO VERIFY).
-    let _result = file.read_exact(&mut au8_buffer);
+    file.read_exact(&mut au8_buffer)?;
 
     let mut u64_valuetmp : u64;
 
@@ -115,7 +123,7 @@ pub fn read_u64_from_file(mut file: &std::fs::File) -> u64
             u64_value += u64_valuetmp;
         }
 
-        return u64_value;
+        return Ok(u64_value);
     }
     else
     {
@@ -128,11 +136,11 @@ pub fn read_u64_from_file(mut file: &std::fs::File) -> u64
             u64_value += u64_valuetmp;
         }
 
-        return u64_value;
+        return Ok(u64_value);
     }
 }
 
-pub fn read_u128_from_file(mut file: &std::fs::File) -> u128
+pub fn read_u128_from_file(mut file: &std::fs::File) -> std::io::Result<u128>
 {
     let mut u128_value: u128 = 0;
     let mut au128_buffer = [0; 16];
@@ -141,7 +149,7 @@ pub fn read_u128_from_file(mut file: &std::fs::File) -> u128
     // We read exactly 128 bits of data in the file.
     // Depending of the architecture of the processor, the data can be in little endian or in big endian.
     // So if the data in the file are in little endian, you must swap the data before to make bitwise operation (TO VERIFY).
-    let _result = file.read_exact(&mut au128_buffer);
+    file.read_exact(&mut au128_buffer)?;
 
     let mut u128_valuetmp : u128;
 
@@ -156,7 +164,7 @@ pub fn read_u128_from_file(mut file: &std::fs::File) -> u128
             u128_value += u128_valuetmp;
         }
 
-        return u128_value;
+        return Ok(u128_value);
     }
     else
     {
@@ -169,6 +177,6 @@ pub fn read_u128_from_file(mut file: &std::fs::File) -> u128
             u128_value += u128_valuetmp;
         }
 
-        return u128_value;
+        return Ok(u128_value);
     }
 }
\ No newline at end of file
@@ -17,6 +17,9 @@
  */
 
 pub mod file_reader;
+pub mod bit_reader;
+pub mod bit_writer;
+pub mod crc;
 
 #[cfg(test)]
 mod test_file_reader
@@ -32,7 +35,7 @@ mod test_file_reader
             Err(why) => panic!("Could not open the file !"),
             Ok(file) => file,
         };
-        let mut result = read_u128_from_file(&file);
+        let mut result = read_u128_from_file(&file).unwrap();
         let string_result = "AZERTYUIOPQSDFGF";
         let stringInVecU8 = string_result.as_bytes();
         let mut is_ok : bool = true;
@@ -50,4 +53,63 @@ mod test_file_reader
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test_bit_reader
+{
+    use crate::utils::bit_reader::BitReader;
+
+    #[test]
+    fn read_unaligned_values()
+    {
+        let data: [u8; 4] = [0b1011_0010, 0b0000_0001, 0b1111_1111, 0b1000_0000];
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(reader.read_bits(3), Some(0b101));
+        assert_eq!(reader.read_signed_bits(4), Some(-7));
+        assert_eq!(reader.read_unary(), Some(8));
+        assert!(reader.is_byte_aligned());
+        assert_eq!(reader.read_bits(9), Some(0x1FF));
+        assert_eq!(reader.read_bits(8), None);
+    }
+}
+
+#[cfg(test)]
+mod test_bit_writer
+{
+    use crate::utils::bit_reader::BitReader;
+    use crate::utils::bit_writer::BitWriter;
+
+    #[test]
+    fn write_unaligned_values()
+    {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b101, 3);
+        writer.write_signed_bits(-7, 4);
+        writer.write_unary(40);
+        writer.write_bits_u64(0x1_2345_6789, 33);
+        let data = writer.into_bytes();
+
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits(3), Some(0b101));
+        assert_eq!(reader.read_signed_bits(4), Some(-7));
+        assert_eq!(reader.read_unary(), Some(40));
+        assert_eq!(reader.read_bits_u64(33), Some(0x1_2345_6789));
+    }
+}
+
+#[cfg(test)]
+mod test_crc
+{
+    use crate::utils::crc::{compute_crc16, compute_crc8, update_crc32};
+
+    #[test]
+    fn check_values()
+    {
+        assert_eq!(compute_crc8(b"123456789"), 0xF4);
+        assert_eq!(compute_crc16(b"123456789"), 0xFEE8);
+        assert_eq!(update_crc32(0, b"123456789"), 0x89A1897F);
+        assert_eq!(update_crc32(update_crc32(0, b"1234"), b"56789"), 0x89A1897F);
+    }
+}